@@ -128,6 +128,12 @@ where
     ) -> Result<Self::StateType, DynamicsError> {
         Ok(next_state)
     }
+
+    /// Describes these dynamics, and is recorded in the provenance of the exported files.
+    /// Defaults to the type name, so implementors should override it to list all of the models used.
+    fn description(&self) -> String {
+        std::any::type_name::<Self>().to_string()
+    }
 }
 
 /// The `ForceModel` trait handles immutable dynamics which return a force. Those will be divided by the mass of the spacecraft to compute the acceleration (F = ma).
@@ -150,6 +150,10 @@ impl Dynamics for SpacecraftDynamics {
     type HyperdualSize = Const<9>;
     type StateType = Spacecraft;
 
+    fn description(&self) -> String {
+        self.to_string()
+    }
+
     fn finally(
         &self,
         next_state: Self::StateType,
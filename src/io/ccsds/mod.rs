@@ -0,0 +1,574 @@
+/*
+    Nyx, blazing fast astrodynamics
+    Copyright (C) 2018-onwards Christopher Rabotin <christopher.rabotin@gmail.com>
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published
+    by the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use anise::constants::orientations::J2000;
+use anise::prelude::Frame;
+
+use crate::cosmic::Spacecraft;
+use crate::dynamics::guidance::{LocalFrame, Maneuver};
+use crate::errors::NyxError;
+use crate::io::watermark::prj_name_ver;
+use crate::io::Provenance;
+use crate::linalg::{Matrix3, Matrix6, Vector3};
+use crate::md::trajectory::OemCovariance;
+use crate::time::{Duration, Epoch, Format, Formatter, Unit};
+use crate::utils::{r1, r2, r3};
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+mod ocm;
+mod omm;
+mod opm;
+
+pub use ocm::OrbitComprehensiveMessage;
+pub use omm::{OrbitMeanElementsMessage, TleParameters};
+pub use opm::OrbitParameterMessage;
+
+/// Names of the components of the state vector, in the order of the CCSDS covariance matrix.
+pub(crate) const COMPONENTS: [&str; 6] = ["X", "Y", "Z", "X_DOT", "Y_DOT", "Z_DOT"];
+
+/// Returns the row, the column and the keyword of each element of the lower triangular part of the covariance, row by row.
+pub(crate) fn covar_elements() -> impl Iterator<Item = (usize, usize, String)> {
+    COMPONENTS.iter().enumerate().flat_map(|(i, row)| {
+        COMPONENTS[..=i]
+            .iter()
+            .enumerate()
+            .map(move |(j, col)| (i, j, format!("C{row}_{col}")))
+    })
+}
+
+/// Epoch format of CCSDS messages, the time system is specified in the metadata.
+pub(crate) fn ccsds_epoch_fmt() -> Format {
+    Format::from_str("%Y-%m-%dT%H:%M:%S.%f").unwrap()
+}
+
+/// Returns the CENTER_NAME and REF_FRAME of the provided frame.
+pub(crate) fn ccsds_frame_names(frame: Frame) -> (String, String) {
+    let frame_str = format!(
+        "{frame:e} {}",
+        match frame.orientation_id {
+            J2000 => "ICRF".to_string(),
+            _ => format!("{frame:o}"),
+        }
+    );
+    let splt: Vec<&str> = frame_str.split(' ').collect();
+    let center = splt[0];
+    let ref_frame = frame_str.replace(center, " ");
+    let ref_frame = match ref_frame.trim() {
+        "J2000" => "ICRF",
+        ref_frame => ref_frame,
+    };
+    (center.to_string(), ref_frame.to_string())
+}
+
+/// Builds the frame from the CENTER_NAME and REF_FRAME of a CCSDS message, accepting upper case center names.
+///
+/// The EME2000 and GCRF frames are treated as ICRF. TEME is rejected: its states must be rotated with [teme_to_j2000].
+pub(crate) fn ccsds_frame(center_name: &str, ref_frame: &str) -> Result<Frame, NyxError> {
+    let ref_frame = match ref_frame {
+        "EME2000" | "GCRF" => "ICRF",
+        "TEME" => {
+            return Err(NyxError::CCSDS {
+                msg: format!("TEME states of `{center_name}` must be rotated to J2000"),
+            })
+        }
+        ref_frame => ref_frame,
+    };
+    Frame::from_name(center_name, ref_frame)
+        .or_else(|_| {
+            // CCSDS names are usually in upper case, e.g. EARTH
+            let mut chars = center_name.chars();
+            let title_case = match chars.next() {
+                Some(first) => first
+                    .to_uppercase()
+                    .chain(chars.flat_map(|c| c.to_lowercase())),
+                None => return Frame::from_name(center_name, ref_frame),
+            }
+            .collect::<String>();
+            Frame::from_name(&title_case, ref_frame)
+        })
+        .map_err(|e| NyxError::CCSDS {
+            msg: format!("frame error `{center_name} {ref_frame}`: {e}"),
+        })
+}
+
+/// Leading terms of the IAU 1980 nutation series: multipliers of the fundamental arguments (l, l', F, D, Ω), and the
+/// coefficients of the nutation in longitude and in obliquity, in 0.0001 arcsec and 0.0001 arcsec per Julian century.
+/// The omitted terms are all below 5 mas.
+const IAU1980_NUTATION: [([f64; 5], f64, f64, f64, f64); 18] = [
+    ([0.0, 0.0, 0.0, 0.0, 1.0], -171_996.0, -174.2, 92_025.0, 8.9),
+    ([0.0, 0.0, 2.0, -2.0, 2.0], -13_187.0, -1.6, 5_736.0, -3.1),
+    ([0.0, 0.0, 2.0, 0.0, 2.0], -2_274.0, -0.2, 977.0, -0.5),
+    ([0.0, 0.0, 0.0, 0.0, 2.0], 2_062.0, 0.2, -895.0, 0.5),
+    ([0.0, 1.0, 0.0, 0.0, 0.0], 1_426.0, -3.4, 54.0, -0.1),
+    ([1.0, 0.0, 0.0, 0.0, 0.0], 712.0, 0.1, -7.0, 0.0),
+    ([0.0, 1.0, 2.0, -2.0, 2.0], -517.0, 1.2, 224.0, -0.6),
+    ([0.0, 0.0, 2.0, 0.0, 1.0], -386.0, -0.4, 200.0, 0.0),
+    ([1.0, 0.0, 2.0, 0.0, 2.0], -301.0, 0.0, 129.0, -0.1),
+    ([0.0, -1.0, 2.0, -2.0, 2.0], 217.0, -0.5, -95.0, 0.3),
+    ([1.0, 0.0, 0.0, -2.0, 0.0], -158.0, 0.0, 0.0, 0.0),
+    ([0.0, 0.0, 2.0, -2.0, 1.0], 129.0, 0.1, -70.0, 0.0),
+    ([-1.0, 0.0, 2.0, 0.0, 2.0], 123.0, 0.0, -53.0, 0.0),
+    ([1.0, 0.0, 0.0, 0.0, 1.0], 63.0, 0.1, -33.0, 0.0),
+    ([0.0, 0.0, 0.0, 2.0, 0.0], 63.0, 0.0, 0.0, 0.0),
+    ([-1.0, 0.0, 2.0, 2.0, 2.0], -59.0, 0.0, 26.0, 0.0),
+    ([-1.0, 0.0, 0.0, 0.0, 1.0], -58.0, -0.1, 32.0, 0.0),
+    ([1.0, 0.0, 2.0, 0.0, 1.0], -51.0, 0.0, 27.0, 0.0),
+];
+
+/// Returns the rotation from the True Equator Mean Equinox (TEME) frame of SGP4 at the provided epoch to the J2000 frame.
+///
+/// This uses the IAU 1976 precession and the leading terms of the IAU 1980 nutation without EOP corrections, i.e. about ten meters
+/// of error at GEO, which is well below the accuracy of SGP4. The rotation rate of the TEME frame is negligible and is ignored.
+pub(crate) fn teme_to_j2000(epoch: Epoch) -> Matrix3<f64> {
+    let arcsec = |value: f64| (value / 3600.0).to_radians();
+    let t = epoch.to_tt_centuries_j2k();
+
+    // Fundamental arguments of the nutation, in degrees
+    let l = 134.962_981_39 + (1325.0 * 360.0 + 198.867_398_1) * t + 0.008_697_2 * t.powi(2);
+    let l_prime = 357.527_723_33 + (99.0 * 360.0 + 359.050_340_0) * t - 0.000_160_3 * t.powi(2);
+    let f = 93.271_910_28 + (1342.0 * 360.0 + 82.017_538_1) * t - 0.003_682_5 * t.powi(2);
+    let d = 297.850_363_06 + (1236.0 * 360.0 + 307.111_480_0) * t - 0.001_914_2 * t.powi(2);
+    let omega = 125.044_522_22 - (5.0 * 360.0 + 134.136_260_8) * t + 0.002_070_8 * t.powi(2);
+    let args = [l, l_prime, f, d, omega];
+
+    let (mut dpsi, mut deps) = (0.0, 0.0);
+    for (multipliers, psi, psi_t, eps, eps_t) in IAU1980_NUTATION {
+        let arg = multipliers
+            .iter()
+            .zip(args)
+            .map(|(k, arg)| k * arg)
+            .sum::<f64>()
+            .to_radians();
+        dpsi += (psi + psi_t * t) * arg.sin();
+        deps += (eps + eps_t * t) * arg.cos();
+    }
+    let dpsi = arcsec(dpsi * 1e-4);
+    let deps = arcsec(deps * 1e-4);
+
+    let mean_eps = arcsec(84_381.448 - 46.815_0 * t - 0.000_59 * t.powi(2) + 0.001_813 * t.powi(3));
+    let eps = mean_eps + deps;
+    let eq_equinoxes = dpsi * mean_eps.cos();
+
+    let zeta = arcsec(2_306.218_1 * t + 0.301_88 * t.powi(2) + 0.017_998 * t.powi(3));
+    let theta = arcsec(2_004.310_9 * t - 0.426_65 * t.powi(2) - 0.041_833 * t.powi(3));
+    let z = arcsec(2_306.218_1 * t + 1.094_68 * t.powi(2) + 0.018_203 * t.powi(3));
+
+    let precession = r3(zeta) * r2(-theta) * r3(z);
+    let nutation = r1(-mean_eps) * r3(dpsi) * r1(eps);
+    precession * nutation * r3(-eq_equinoxes)
+}
+
+/// Parses a CCSDS epoch in the provided time system, either in the calendar format or in the day of year format (e.g. 2007-064T10:34:41).
+pub(crate) fn ccsds_epoch(epoch: &str, time_system: &str) -> Result<Epoch, NyxError> {
+    let epoch = epoch.trim();
+    let is_day_of_year = epoch
+        .split('T')
+        .next()
+        .and_then(|date| date.split('-').nth(1))
+        .is_some_and(|day| day.len() == 3);
+    let epoch_str = format!("{epoch} {time_system}");
+    if is_day_of_year {
+        let fmt = if epoch.contains('.') {
+            "%Y-%jT%H:%M:%S.%f %T"
+        } else {
+            "%Y-%jT%H:%M:%S %T"
+        };
+        Epoch::from_format_str(epoch_str.trim(), fmt)
+    } else {
+        Epoch::from_str(epoch_str.trim())
+    }
+    .map_err(|e| NyxError::CCSDS {
+        msg: format!("Parsing epoch error: {e}"),
+    })
+}
+
+/// Keyword = value entries of a KVN message, in the order of the file, with the line number of each entry.
+///
+/// Comments are skipped and the units in square brackets are removed from the values.
+pub(crate) fn parse_kvn(contents: &str) -> Vec<(usize, String, String)> {
+    contents
+        .lines()
+        .enumerate()
+        .filter_map(|(lno, line)| {
+            let (keyword, value) = kvn_line(line.trim())?;
+            Some((lno + 1, keyword, value))
+        })
+        .collect()
+}
+
+/// Keyword and value of a single KVN line, or None if it's a comment or a data line.
+pub(crate) fn kvn_line(line: &str) -> Option<(String, String)> {
+    if line.starts_with("COMMENT") {
+        return None;
+    }
+    let (keyword, value) = line.split_once('=')?;
+    let value = match value.find('[') {
+        Some(idx) => &value[..idx],
+        None => value,
+    };
+    Some((keyword.trim().to_string(), value.trim().to_string()))
+}
+
+/// Single valued keywords of a KVN message.
+pub(crate) struct KvnValues {
+    values: HashMap<String, (usize, String)>,
+}
+
+impl KvnValues {
+    pub(crate) fn new() -> Self {
+        Self {
+            values: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn insert(&mut self, line: usize, keyword: String, value: String) {
+        self.values.insert(keyword, (line, value));
+    }
+
+    pub(crate) fn get(&self, keyword: &str) -> Option<&str> {
+        self.values.get(keyword).map(|(_, value)| value.as_str())
+    }
+
+    pub(crate) fn required(&self, keyword: &str) -> Result<&str, NyxError> {
+        self.get(keyword).ok_or(NyxError::CCSDS {
+            msg: format!("missing mandatory keyword `{keyword}`"),
+        })
+    }
+
+    pub(crate) fn f64(&self, keyword: &str) -> Result<Option<f64>, NyxError> {
+        match self.values.get(keyword) {
+            Some((line, value)) => value.parse::<f64>().map(Some).map_err(|e| NyxError::CCSDS {
+                msg: format!("[line: {line}] could not parse `{keyword}`: {e}"),
+            }),
+            None => Ok(None),
+        }
+    }
+
+    pub(crate) fn required_f64(&self, keyword: &str) -> Result<f64, NyxError> {
+        self.f64(keyword)?.ok_or(NyxError::CCSDS {
+            msg: format!("missing mandatory keyword `{keyword}`"),
+        })
+    }
+
+    pub(crate) fn epoch(&self, keyword: &str) -> Result<Epoch, NyxError> {
+        ccsds_epoch(
+            self.required(keyword)?,
+            self.get("TIME_SYSTEM").unwrap_or_default(),
+        )
+    }
+
+    pub(crate) fn frame(&self) -> Result<Frame, NyxError> {
+        ccsds_frame(self.required("CENTER_NAME")?, self.required("REF_FRAME")?)
+    }
+
+    /// Applies the spacecraft parameters (mass, SRP and drag) of the message to the spacecraft.
+    ///
+    /// The mass of the message is the total mass, so the dry mass is set such that the propellant and extra masses are kept.
+    pub(crate) fn apply_spacecraft_parameters(&self, sc: &mut Spacecraft) -> Result<(), NyxError> {
+        if let Some(mass_kg) = self.f64("MASS")? {
+            sc.mass.dry_mass_kg = mass_kg - sc.mass.prop_mass_kg - sc.mass.extra_mass_kg;
+        }
+        if let Some(area_m2) = self.f64("SOLAR_RAD_AREA")? {
+            sc.srp.area_m2 = area_m2;
+        }
+        if let Some(coeff_reflectivity) = self.f64("SOLAR_RAD_COEFF")? {
+            sc.srp.coeff_reflectivity = coeff_reflectivity;
+        }
+        if let Some(area_m2) = self.f64("DRAG_AREA")? {
+            sc.drag.area_m2 = area_m2;
+        }
+        if let Some(coeff_drag) = self.f64("DRAG_COEFF")? {
+            sc.drag.coeff_drag = coeff_drag;
+        }
+        Ok(())
+    }
+
+    /// Returns the covariance of the message, if any, and the frame it is expressed in.
+    pub(crate) fn covariance(&self) -> Result<Option<(Matrix6<f64>, LocalFrame)>, NyxError> {
+        if self.get("CX_X").is_none() {
+            return Ok(None);
+        }
+        let mut covar = Matrix6::zeros();
+        for (i, j, keyword) in covar_elements() {
+            covar[(i, j)] = self.required_f64(&keyword)?;
+            covar[(j, i)] = covar[(i, j)];
+        }
+        // Covariances are not rotated, so an inertial covariance must be in the reference frame of the state.
+        let frame = match self.get("COV_REF_FRAME") {
+            None => LocalFrame::Inertial,
+            Some("RTN" | "RSW" | "RIC") => LocalFrame::RIC,
+            Some(name) if Some(name) == self.get("REF_FRAME") => LocalFrame::Inertial,
+            Some(name) => {
+                return Err(NyxError::CCSDS {
+                    msg: format!(
+                        "unsupported covariance frame `{name}`, expected RTN or the reference frame `{}`",
+                        self.get("REF_FRAME").unwrap_or_default()
+                    ),
+                })
+            }
+        };
+        Ok(Some((covar, frame)))
+    }
+}
+
+/// A maneuver as described in CCSDS messages, whose delta-v is expressed in the maneuver frame of the message.
+///
+/// When converted to a Nyx [`Maneuver`], a maneuver of zero duration is impulsive, and any other is a finite burn along the delta-v
+/// whose thrust level provides this delta-v with the thruster of the spacecraft. The delta mass is not used.
+pub(crate) struct CcsdsManeuver {
+    pub start: Epoch,
+    pub duration: Duration,
+    /// Mass change during the maneuver, negative when propellant is used
+    pub delta_mass_kg: f64,
+    /// Name of the maneuver frame, e.g. RTN or TNW
+    pub frame: String,
+    pub dv_km_s: Vector3<f64>,
+}
+
+impl CcsdsManeuver {
+    /// Builds the CCSDS maneuver of a maneuver of the provided spacecraft, `ref_frame` is the name of the inertial frame of the spacecraft.
+    ///
+    /// The delta-v of finite burns and the delta mass are computed from the thruster of the spacecraft, which is required for finite burns.
+    pub(crate) fn from_maneuver(
+        mnvr: &Maneuver,
+        sc: &Spacecraft,
+        ref_frame: &str,
+    ) -> Result<Self, NyxError> {
+        let mass_kg = sc.mass_kg();
+        let (duration, dv_km_s, delta_mass_kg) = if mnvr.duration() <= Unit::Millisecond * 1 {
+            let dv_km_s = mnvr.vector(mnvr.start);
+            let delta_mass_kg = match sc.thruster {
+                Some(thruster) => {
+                    // Rocket equation, the exhaust velocity is in m/s
+                    -mass_kg
+                        * (1.0 - (-dv_km_s.norm() * 1e3 / thruster.exhaust_velocity_m_s()).exp())
+                }
+                None => 0.0,
+            };
+            (Duration::ZERO, dv_km_s, delta_mass_kg)
+        } else {
+            let thruster = sc.thruster.ok_or(NyxError::CCSDS {
+                msg: format!("finite burn at {} requires a thruster", mnvr.start),
+            })?;
+            let thrust_n = thruster.thrust_N * mnvr.thrust_prct;
+            let duration_s = mnvr.duration().to_seconds();
+            let dv_km_s = mnvr.direction() * thrust_n * duration_s / mass_kg * 1e-3;
+            let delta_mass_kg = -thrust_n * duration_s / thruster.exhaust_velocity_m_s();
+            (mnvr.duration(), dv_km_s, delta_mass_kg)
+        };
+
+        let (frame, dv_km_s) = match mnvr.frame {
+            LocalFrame::RIC => ("RTN", dv_km_s),
+            // T is along the velocity and W along the orbit momentum, so N is the opposite of the cross direction of VNC
+            LocalFrame::VNC => ("TNW", Vector3::new(dv_km_s.x, -dv_km_s.z, dv_km_s.y)),
+            LocalFrame::Inertial => (ref_frame, dv_km_s),
+            LocalFrame::RCN => {
+                return Err(NyxError::CCSDS {
+                    msg: format!("RCN maneuver at {} is not supported", mnvr.start),
+                })
+            }
+        };
+
+        Ok(Self {
+            start: mnvr.start,
+            duration,
+            delta_mass_kg,
+            frame: frame.to_string(),
+            dv_km_s,
+        })
+    }
+
+    /// Converts this maneuver to a Nyx maneuver, `ref_frame` is the inertial frame of the message if any.
+    ///
+    /// The thrust level of finite burns is computed from the delta-v and the thruster of the spacecraft, as when writing the maneuver.
+    pub(crate) fn to_maneuver(
+        &self,
+        sc: &Spacecraft,
+        ref_frame: Option<&str>,
+    ) -> Result<Maneuver, NyxError> {
+        let dv_km_s = self.dv_km_s;
+        let (frame, dv_km_s) = match self.frame.as_str() {
+            "RTN" | "RSW" | "RIC" => (LocalFrame::RIC, dv_km_s),
+            "TNW" => (
+                LocalFrame::VNC,
+                Vector3::new(dv_km_s.x, dv_km_s.z, -dv_km_s.y),
+            ),
+            "ICRF" | "EME2000" | "GCRF" => (LocalFrame::Inertial, dv_km_s),
+            name if Some(name) == ref_frame => (LocalFrame::Inertial, dv_km_s),
+            name => {
+                return Err(NyxError::CCSDS {
+                    msg: format!("unsupported maneuver frame `{name}`"),
+                })
+            }
+        };
+
+        if self.duration <= Duration::ZERO {
+            Ok(Maneuver::from_impulsive(self.start, dv_km_s, frame))
+        } else {
+            let direction = dv_km_s.try_normalize(f64::EPSILON).ok_or(NyxError::CCSDS {
+                msg: format!("finite burn at {} without delta-v", self.start),
+            })?;
+            let thrust_prct = match sc.thruster {
+                Some(thruster) => {
+                    let thrust_n = dv_km_s.norm() * 1e3 * sc.mass_kg() / self.duration.to_seconds();
+                    let thrust_prct = thrust_n / thruster.thrust_N;
+                    if thrust_prct > 1.0 + 1e-9 {
+                        return Err(NyxError::CCSDS {
+                            msg: format!(
+                                "finite burn at {} requires {thrust_n} N but the thruster provides {} N",
+                                self.start, thruster.thrust_N
+                            ),
+                        });
+                    }
+                    thrust_prct.min(1.0)
+                }
+                None => 1.0,
+            };
+            Ok(Maneuver::from_time_invariant(
+                self.start,
+                self.start + self.duration,
+                thrust_prct,
+                direction,
+                frame,
+            ))
+        }
+    }
+}
+
+/// Writes the header of a CCSDS message, e.g. `OPM` or `OMM`.
+pub(crate) fn write_kvn_header<W: Write>(
+    writer: &mut W,
+    message: &str,
+    metadata: &HashMap<String, String>,
+    provenance: &Provenance,
+) -> io::Result<()> {
+    // The OCM was introduced in version 3 of the ODM standard
+    let version = if message == "OCM" { "3.0" } else { "2.0" };
+    writeln!(writer, "CCSDS_{message}_VERS = {version}")?;
+    writeln!(
+        writer,
+        "COMMENT Built by {} -- https://nyxspace.com/",
+        prj_name_ver()
+    )?;
+    writeln!(
+        writer,
+        "COMMENT Nyx Space provided under the AGPL v3 open source license -- https://nyxspace.com/pricing"
+    )?;
+    for line in provenance.comment_lines().map_err(io::Error::other)? {
+        writeln!(writer, "COMMENT {line}")?;
+    }
+    writeln!(
+        writer,
+        "CREATION_DATE = {}",
+        Formatter::new(Epoch::now().unwrap(), ccsds_epoch_fmt())
+    )?;
+    writeln!(
+        writer,
+        "ORIGINATOR = {}\n",
+        metadata
+            .get("originator")
+            .map(String::as_str)
+            .unwrap_or("Nyx Space")
+    )
+}
+
+/// Writes the spacecraft parameters of a CCSDS message.
+pub(crate) fn write_spacecraft_parameters<W: Write>(
+    writer: &mut W,
+    sc: &Spacecraft,
+) -> io::Result<()> {
+    writeln!(writer, "MASS = {} [kg]", sc.mass_kg())?;
+    writeln!(writer, "SOLAR_RAD_AREA = {} [m**2]", sc.srp.area_m2)?;
+    writeln!(writer, "SOLAR_RAD_COEFF = {}", sc.srp.coeff_reflectivity)?;
+    writeln!(writer, "DRAG_AREA = {} [m**2]", sc.drag.area_m2)?;
+    writeln!(writer, "DRAG_COEFF = {}\n", sc.drag.coeff_drag)
+}
+
+/// Writes the covariance of a CCSDS message with one keyword per element.
+pub(crate) fn write_covariance_kvn<W: Write>(
+    writer: &mut W,
+    covariance: &OemCovariance,
+    ref_frame: &str,
+) -> io::Result<()> {
+    writeln!(
+        writer,
+        "COV_REF_FRAME = {}",
+        match covariance.frame {
+            LocalFrame::RIC => "RTN",
+            _ => ref_frame,
+        }
+    )?;
+    for (i, j, keyword) in covar_elements() {
+        writeln!(writer, "{keyword} = {:E}", covariance.covar[(i, j)])?;
+    }
+    writeln!(writer)
+}
+
+#[cfg(test)]
+mod ut_ccsds {
+    use super::{ccsds_epoch, ccsds_frame, parse_kvn, teme_to_j2000};
+    use crate::linalg::Vector3;
+    use crate::time::{Epoch, Unit};
+    use anise::constants::frames::{EARTH_J2000, MOON_J2000};
+
+    #[test]
+    fn kvn_entries() {
+        let entries = parse_kvn(
+            "CCSDS_OPM_VERS = 2.0\nCOMMENT X = 1\n\nX = 6503.514 [km]\nOBJECT_NAME = GOES 9\n",
+        );
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[1], (4, "X".to_string(), "6503.514".to_string()));
+        assert_eq!(entries[2].2, "GOES 9");
+    }
+
+    #[test]
+    fn epochs() {
+        let epoch = Epoch::from_gregorian_utc(2007, 3, 5, 10, 34, 41, 426_400_000);
+        assert_eq!(ccsds_epoch("2007-064T10:34:41.4264", "UTC").unwrap(), epoch);
+        assert_eq!(
+            ccsds_epoch("2007-03-05T10:34:41.4264", "UTC").unwrap(),
+            epoch
+        );
+        assert_eq!(
+            ccsds_epoch("2007-064T10:34:41", "UTC").unwrap(),
+            epoch - Unit::Microsecond * 426_400
+        );
+    }
+
+    #[test]
+    fn frames() {
+        assert_eq!(ccsds_frame("EARTH", "EME2000").unwrap(), EARTH_J2000);
+        assert_eq!(ccsds_frame("Earth", "ICRF").unwrap(), EARTH_J2000);
+        assert_eq!(ccsds_frame("MOON", "ICRF").unwrap(), MOON_J2000);
+        assert!(ccsds_frame("EARTH", "ITRF2000").is_err());
+        assert!(ccsds_frame("EARTH", "TEME").is_err());
+    }
+
+    #[test]
+    fn teme_rotation() {
+        // Vallado et al., Revisiting Spacetrack Report #3, AIAA 2006-6753, example of the TEME to J2000 conversion
+        let epoch = Epoch::from_gregorian_utc(2004, 4, 6, 7, 51, 28, 386_009_000);
+        let r_teme_km = Vector3::new(5_094.180_162_10, 6_127.644_659_50, 6_380.344_532_70);
+        let r_j2000_km = Vector3::new(5_102.508_957_90, 6_123.011_400_70, 6_378.136_928_20);
+
+        let err_km = (teme_to_j2000(epoch) * r_teme_km - r_j2000_km).norm();
+        assert!(err_km < 5e-3, "{err_km} km");
+    }
+}
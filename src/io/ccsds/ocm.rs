@@ -0,0 +1,565 @@
+/*
+    Nyx, blazing fast astrodynamics
+    Copyright (C) 2018-onwards Christopher Rabotin <christopher.rabotin@gmail.com>
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published
+    by the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use anise::prelude::{Frame, Orbit};
+use log::{debug, info};
+
+use super::{
+    ccsds_epoch, ccsds_epoch_fmt, ccsds_frame, ccsds_frame_names, kvn_line, write_kvn_header,
+    CcsdsManeuver, KvnValues,
+};
+use crate::cosmic::Spacecraft;
+use crate::dynamics::guidance::{LocalFrame, Maneuver};
+use crate::errors::NyxError;
+use crate::io::{ExportCfg, Provenance};
+use crate::linalg::{SMatrix, Vector3};
+use crate::md::prelude::Traj;
+use crate::md::trajectory::{lower_triangle_to_matrix, OemCovariance};
+use crate::od::estimate::{Estimate, KfEstimate};
+use crate::time::{Epoch, Formatter, Unit};
+use crate::State;
+use std::fs::{read_to_string, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// Composition of the maneuvers written by Nyx, one maneuver per line.
+const MAN_COMPOSITION: &str = "TIME_ABSOLUTE,MAN_DURA,DELTA_MASS,DV_X,DV_Y,DV_Z";
+
+/// A CCSDS Orbit Comprehensive Message (OCM): the trajectory of a spacecraft, its covariance history, its maneuvers and its physical properties.
+///
+/// Only the Cartesian trajectories and covariances, and the maneuvers described by their delta-v are supported.
+/// The maneuvers are converted as in the OPM, cf. [`OrbitParameterMessage`](super::OrbitParameterMessage).
+#[derive(Clone, Debug, PartialEq)]
+pub struct OrbitComprehensiveMessage {
+    pub object_name: String,
+    /// Trajectory of the spacecraft, whose first state provides the physical properties of the message
+    pub traj: Traj<Spacecraft>,
+    /// Covariance history, e.g. the estimates of an orbit determination
+    pub estimates: Vec<KfEstimate<Spacecraft>>,
+    /// Frame in which the covariance history is written to the message, either Inertial or RIC
+    pub covar_frame: LocalFrame,
+    pub maneuvers: Vec<Maneuver>,
+    /// Provenance recorded in the message if the export configuration does not provide one, e.g. that of an orbit determination
+    pub provenance: Option<Provenance>,
+}
+
+impl OrbitComprehensiveMessage {
+    /// Initializes a new OCM of this trajectory without any covariance or maneuver.
+    pub fn new(object_name: String, traj: Traj<Spacecraft>) -> Self {
+        Self {
+            object_name,
+            traj,
+            estimates: Vec::new(),
+            covar_frame: LocalFrame::Inertial,
+            maneuvers: Vec::new(),
+            provenance: None,
+        }
+    }
+
+    /// Reads an OCM in the KVN format: its trajectory, covariance, maneuver and physical properties sections.
+    ///
+    /// The spacecraft template provides the thruster and the parameters which are not in the physical properties of the OCM.
+    /// The nominal state of each covariance is interpolated from the trajectory.
+    pub fn from_file<P: AsRef<Path>>(
+        path: P,
+        tpl_option: Option<Spacecraft>,
+    ) -> Result<Self, NyxError> {
+        let contents = read_to_string(path).map_err(|e| NyxError::CCSDS {
+            msg: format!("File opening error: {e}"),
+        })?;
+
+        let mut header = KvnValues::new();
+        // Each section is stored with its keywords and its data lines, and processed once the metadata is known.
+        let mut sections = Vec::new();
+        let mut current: Option<(String, KvnValues, Vec<(usize, Vec<String>)>)> = None;
+
+        for (lno, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with("COMMENT") {
+                continue;
+            }
+            if let Some(name) = line.strip_suffix("_START") {
+                current = Some((name.to_string(), KvnValues::new(), Vec::new()));
+            } else if line.ends_with("_STOP") {
+                if let Some(section) = current.take() {
+                    sections.push(section);
+                }
+            } else if let Some((keyword, value)) = kvn_line(line) {
+                match current.as_mut() {
+                    Some((_, values, _)) => values.insert(lno + 1, keyword, value),
+                    None => header.insert(lno + 1, keyword, value),
+                }
+            } else if let Some((_, _, rows)) = current.as_mut() {
+                rows.push((
+                    lno + 1,
+                    line.split_whitespace().map(str::to_string).collect(),
+                ));
+            }
+        }
+
+        header.required("CCSDS_OCM_VERS")?;
+
+        let meta = sections
+            .iter()
+            .find(|(name, _, _)| name == "META")
+            .map(|(_, values, _)| values)
+            .ok_or(NyxError::CCSDS {
+                msg: "OCM without metadata".to_string(),
+            })?;
+        let time_system = meta.get("TIME_SYSTEM").unwrap_or("UTC");
+        let tzero = meta.epoch("EPOCH_TZERO")?;
+        let row_epoch = |token: &str| -> Result<Epoch, NyxError> {
+            // Epochs are either absolute or relative to EPOCH_TZERO
+            match token.parse::<f64>() {
+                Ok(seconds) => Ok(tzero + Unit::Second * seconds),
+                Err(_) => ccsds_epoch(token, time_system),
+            }
+        };
+        let row_values = |lno: usize, tokens: &[String]| -> Result<Vec<f64>, NyxError> {
+            tokens
+                .iter()
+                .map(|token| token.parse::<f64>())
+                .collect::<Result<Vec<f64>, _>>()
+                .map_err(|e| NyxError::CCSDS {
+                    msg: format!("[line: {lno}] could not parse data: {e}"),
+                })
+        };
+
+        let mut template = tpl_option.unwrap_or_default();
+        let mut orbits = Vec::new();
+        let mut ref_frame = None;
+        let mut covariances = Vec::new();
+        let mut covar_frame = LocalFrame::Inertial;
+        let mut maneuvers = Vec::new();
+
+        for (name, values, rows) in &sections {
+            match name.as_str() {
+                "TRAJ" => {
+                    let traj_type = values.get("TRAJ_TYPE").unwrap_or("CARTPV");
+                    if !traj_type.starts_with("CARTPV") {
+                        return Err(NyxError::CCSDS {
+                            msg: format!("unsupported trajectory type `{traj_type}`"),
+                        });
+                    }
+                    let frame_name = values.required("TRAJ_REF_FRAME")?;
+                    let frame: Frame = ccsds_frame(
+                        values
+                            .get("CENTER_NAME")
+                            .or(meta.get("CENTER_NAME"))
+                            .unwrap_or("EARTH"),
+                        frame_name,
+                    )?;
+                    ref_frame = Some(frame_name.to_string());
+                    for (lno, tokens) in rows {
+                        if tokens.len() < 7 {
+                            return Err(NyxError::CCSDS {
+                                msg: format!("[line: {lno}] expected an epoch and six values"),
+                            });
+                        }
+                        let state = row_values(*lno, &tokens[1..7])?;
+                        orbits.push(Orbit::new(
+                            state[0],
+                            state[1],
+                            state[2],
+                            state[3],
+                            state[4],
+                            state[5],
+                            row_epoch(&tokens[0])?,
+                            frame,
+                        ));
+                    }
+                }
+                "PHYS" => {
+                    if let Some(dry_mass_kg) = values.f64("DRY_MASS")? {
+                        template.mass.dry_mass_kg = dry_mass_kg;
+                    }
+                    if let Some(wet_mass_kg) = values.f64("WET_MASS")? {
+                        template.mass.prop_mass_kg =
+                            wet_mass_kg - template.mass.dry_mass_kg - template.mass.extra_mass_kg;
+                    }
+                    if let Some(area_m2) = values.f64("DRAG_CONST_AREA")? {
+                        template.drag.area_m2 = area_m2;
+                    }
+                    if let Some(coeff_drag) = values.f64("DRAG_COEFF_NOM")? {
+                        template.drag.coeff_drag = coeff_drag;
+                    }
+                    if let Some(area_m2) = values.f64("SRP_CONST_AREA")? {
+                        template.srp.area_m2 = area_m2;
+                    }
+                    if let Some(coeff_reflectivity) = values.f64("SOLAR_RAD_COEFF")? {
+                        template.srp.coeff_reflectivity = coeff_reflectivity;
+                    }
+                }
+                "COV" => {
+                    let cov_type = values.get("COV_TYPE").unwrap_or("CARTPV");
+                    let ordering = values.get("COV_ORDERING").unwrap_or("LTM");
+                    if cov_type != "CARTPV" || ordering != "LTM" {
+                        return Err(NyxError::CCSDS {
+                            msg: format!("unsupported covariance `{cov_type}` in `{ordering}`"),
+                        });
+                    }
+                    // Covariances are not rotated, so an inertial covariance must be in the frame of the trajectory.
+                    let frame = match values.required("COV_REF_FRAME")? {
+                        "RTN" | "RSW" | "RIC" => LocalFrame::RIC,
+                        name if Some(name) == ref_frame.as_deref() => LocalFrame::Inertial,
+                        name => {
+                            return Err(NyxError::CCSDS {
+                                msg: format!(
+                                    "unsupported covariance frame `{name}`, expected RTN or the trajectory frame `{}`",
+                                    ref_frame.as_deref().unwrap_or_default()
+                                ),
+                            })
+                        }
+                    };
+                    covar_frame = frame;
+                    for (lno, tokens) in rows {
+                        if tokens.len() != 22 {
+                            return Err(NyxError::CCSDS {
+                                msg: format!("[line: {lno}] expected an epoch and 21 values"),
+                            });
+                        }
+                        covariances.push(OemCovariance {
+                            epoch: row_epoch(&tokens[0])?,
+                            frame,
+                            covar: lower_triangle_to_matrix(&row_values(*lno, &tokens[1..])?),
+                        });
+                    }
+                }
+                "MAN" => {
+                    let composition: Vec<&str> = values
+                        .required("MAN_COMPOSITION")?
+                        .split(',')
+                        .map(str::trim)
+                        .collect();
+                    let column = |name: &str| composition.iter().position(|col| *col == name);
+                    let epoch_col = column("TIME_ABSOLUTE").or(column("TIME_RELATIVE")).ok_or(
+                        NyxError::CCSDS {
+                            msg: "maneuver composition without time".to_string(),
+                        },
+                    )?;
+                    let dv_cols = [column("DV_X"), column("DV_Y"), column("DV_Z")];
+                    if dv_cols.iter().any(Option::is_none) {
+                        return Err(NyxError::CCSDS {
+                            msg: format!(
+                                "unsupported maneuver composition `{}`",
+                                composition.join(",")
+                            ),
+                        });
+                    }
+                    for (lno, tokens) in rows {
+                        if tokens.len() != composition.len() {
+                            return Err(NyxError::CCSDS {
+                                msg: format!("[line: {lno}] expected {} values", composition.len()),
+                            });
+                        }
+                        let value = |col: usize| row_values(*lno, &tokens[col..=col]).map(|v| v[0]);
+                        let ccsds_mnvr = CcsdsManeuver {
+                            start: row_epoch(&tokens[epoch_col])?,
+                            duration: match column("MAN_DURA") {
+                                Some(col) => Unit::Second * value(col)?,
+                                None => Unit::Second * 0.0,
+                            },
+                            delta_mass_kg: match column("DELTA_MASS") {
+                                Some(col) => value(col)?,
+                                None => 0.0,
+                            },
+                            frame: values.required("MAN_REF_FRAME")?.to_string(),
+                            dv_km_s: Vector3::new(
+                                value(dv_cols[0].unwrap())?,
+                                value(dv_cols[1].unwrap())?,
+                                value(dv_cols[2].unwrap())?,
+                            ),
+                        };
+                        maneuvers.push(ccsds_mnvr.to_maneuver(&template, ref_frame.as_deref())?);
+                    }
+                }
+                name => debug!("skipping {name} section of OCM"),
+            }
+        }
+
+        let mut traj = Traj::new();
+        traj.name = meta.get("OBJECT_NAME").map(str::to_string);
+        traj.states = orbits
+            .into_iter()
+            .map(|orbit| template.with_orbit(orbit))
+            .collect();
+        traj.finalize();
+
+        let mut estimates = Vec::with_capacity(covariances.len());
+        for covariance in covariances {
+            let nominal_state = traj.at(covariance.epoch).map_err(|e| NyxError::CCSDS {
+                msg: format!("no state for covariance at {}: {e}", covariance.epoch),
+            })?;
+            let mut covar = SMatrix::<f64, 9, 9>::zeros();
+            covar
+                .fixed_view_mut::<6, 6>(0, 0)
+                .copy_from(&covariance.inertial_covar(&nominal_state.orbit)?);
+            estimates.push(KfEstimate::from_covar(nominal_state, covar));
+        }
+        Ok(Self {
+            object_name: meta.get("OBJECT_NAME").unwrap_or("UNKNOWN").to_string(),
+            traj,
+            estimates,
+            covar_frame,
+            maneuvers,
+            provenance: None,
+        })
+    }
+
+    /// Writes this OCM in the KVN format.
+    ///
+    /// The maneuvers are computed with the state of the trajectory at their start, cf. [`OrbitParameterMessage`](super::OrbitParameterMessage).
+    pub fn to_file<P: AsRef<Path>>(&self, path: P, cfg: ExportCfg) -> Result<PathBuf, NyxError> {
+        if self.traj.states.is_empty() {
+            return Err(NyxError::CCSDS {
+                msg: "Cannot export an empty trajectory to OCM".to_string(),
+            });
+        }
+        info!("Exporting {} to CCSDS OCM file...", self.object_name);
+        let path_buf = cfg.actual_path(path);
+        let provenance = cfg.provenance_or(self.provenance.clone(), None);
+        let metadata = cfg.metadata.unwrap_or_default();
+
+        let file = File::create(&path_buf).map_err(|e| NyxError::CCSDS {
+            msg: format!("File creation error: {e}"),
+        })?;
+        let mut writer = BufWriter::new(file);
+
+        let err_hdlr = |e| NyxError::CCSDS {
+            msg: format!("Could not write: {e}"),
+        };
+
+        let first = self.traj.first();
+        let last = self.traj.last();
+        let (center, ref_frame) = ccsds_frame_names(first.orbit.frame);
+        let fmt = ccsds_epoch_fmt();
+
+        write_kvn_header(&mut writer, "OCM", &metadata, &provenance).map_err(err_hdlr)?;
+
+        writeln!(writer, "META_START").map_err(err_hdlr)?;
+        writeln!(writer, "OBJECT_NAME = {}", self.object_name).map_err(err_hdlr)?;
+        writeln!(writer, "TIME_SYSTEM = {}", first.epoch().time_scale).map_err(err_hdlr)?;
+        writeln!(
+            writer,
+            "EPOCH_TZERO = {}",
+            Formatter::new(first.epoch(), fmt)
+        )
+        .map_err(err_hdlr)?;
+        writeln!(
+            writer,
+            "START_TIME = {}",
+            Formatter::new(first.epoch(), fmt)
+        )
+        .map_err(err_hdlr)?;
+        writeln!(writer, "STOP_TIME = {}", Formatter::new(last.epoch(), fmt)).map_err(err_hdlr)?;
+        writeln!(writer, "META_STOP\n").map_err(err_hdlr)?;
+
+        writeln!(writer, "TRAJ_START").map_err(err_hdlr)?;
+        writeln!(writer, "CENTER_NAME = {center}").map_err(err_hdlr)?;
+        writeln!(writer, "TRAJ_REF_FRAME = {ref_frame}").map_err(err_hdlr)?;
+        writeln!(writer, "TRAJ_TYPE = CARTPV").map_err(err_hdlr)?;
+        writeln!(writer, "TRAJ_UNITS = [km,km,km,km/s,km/s,km/s]").map_err(err_hdlr)?;
+        for state in &self.traj.states {
+            let orbit = state.orbit;
+            writeln!(
+                writer,
+                "{} {:E} {:E} {:E} {:E} {:E} {:E}",
+                Formatter::new(orbit.epoch, fmt),
+                orbit.radius_km.x,
+                orbit.radius_km.y,
+                orbit.radius_km.z,
+                orbit.velocity_km_s.x,
+                orbit.velocity_km_s.y,
+                orbit.velocity_km_s.z
+            )
+            .map_err(err_hdlr)?;
+        }
+        writeln!(writer, "TRAJ_STOP\n").map_err(err_hdlr)?;
+
+        writeln!(writer, "PHYS_START").map_err(err_hdlr)?;
+        writeln!(writer, "WET_MASS = {} [kg]", first.mass_kg()).map_err(err_hdlr)?;
+        writeln!(writer, "DRY_MASS = {} [kg]", first.mass.dry_mass_kg).map_err(err_hdlr)?;
+        writeln!(writer, "DRAG_CONST_AREA = {} [m**2]", first.drag.area_m2).map_err(err_hdlr)?;
+        writeln!(writer, "DRAG_COEFF_NOM = {}", first.drag.coeff_drag).map_err(err_hdlr)?;
+        writeln!(writer, "SRP_CONST_AREA = {} [m**2]", first.srp.area_m2).map_err(err_hdlr)?;
+        writeln!(writer, "SOLAR_RAD_COEFF = {}", first.srp.coeff_reflectivity).map_err(err_hdlr)?;
+        writeln!(writer, "PHYS_STOP\n").map_err(err_hdlr)?;
+
+        if !self.estimates.is_empty() {
+            writeln!(writer, "COV_START").map_err(err_hdlr)?;
+            writeln!(
+                writer,
+                "COV_REF_FRAME = {}",
+                match self.covar_frame {
+                    LocalFrame::RIC => "RTN",
+                    _ => &ref_frame,
+                }
+            )
+            .map_err(err_hdlr)?;
+            writeln!(writer, "COV_TYPE = CARTPV").map_err(err_hdlr)?;
+            writeln!(writer, "COV_ORDERING = LTM").map_err(err_hdlr)?;
+            for estimate in &self.estimates {
+                let covariance = OemCovariance::from_inertial(
+                    &estimate.state().orbit,
+                    estimate.covar().fixed_view::<6, 6>(0, 0).into_owned(),
+                    self.covar_frame,
+                )?;
+                let mut row = vec![Formatter::new(covariance.epoch, fmt).to_string()];
+                for i in 0..6 {
+                    for j in 0..=i {
+                        row.push(format!("{:E}", covariance.covar[(i, j)]));
+                    }
+                }
+                writeln!(writer, "{}", row.join(" ")).map_err(err_hdlr)?;
+            }
+            writeln!(writer, "COV_STOP\n").map_err(err_hdlr)?;
+        }
+
+        for (i, mnvr) in self.maneuvers.iter().enumerate() {
+            let sc = self.traj.at(mnvr.start).unwrap_or(*first);
+            let ccsds_mnvr = CcsdsManeuver::from_maneuver(mnvr, &sc, &ref_frame)?;
+            writeln!(writer, "MAN_START").map_err(err_hdlr)?;
+            writeln!(writer, "MAN_ID = MAN_{}", i + 1).map_err(err_hdlr)?;
+            writeln!(writer, "MAN_DEVICE_ID = THR_1").map_err(err_hdlr)?;
+            writeln!(writer, "MAN_REF_FRAME = {}", ccsds_mnvr.frame).map_err(err_hdlr)?;
+            writeln!(writer, "MAN_COMPOSITION = {MAN_COMPOSITION}").map_err(err_hdlr)?;
+            writeln!(writer, "MAN_UNITS = [s,kg,km/s,km/s,km/s]").map_err(err_hdlr)?;
+            writeln!(
+                writer,
+                "{} {} {} {:E} {:E} {:E}",
+                Formatter::new(ccsds_mnvr.start, fmt),
+                ccsds_mnvr.duration.to_seconds(),
+                ccsds_mnvr.delta_mass_kg,
+                ccsds_mnvr.dv_km_s.x,
+                ccsds_mnvr.dv_km_s.y,
+                ccsds_mnvr.dv_km_s.z
+            )
+            .map_err(err_hdlr)?;
+            writeln!(writer, "MAN_STOP\n").map_err(err_hdlr)?;
+        }
+
+        info!("OCM written to {}", path_buf.display());
+        Ok(path_buf)
+    }
+}
+
+#[cfg(test)]
+mod ut_ocm {
+    use super::OrbitComprehensiveMessage;
+    use crate::dynamics::guidance::{LocalFrame, Maneuver, Thruster};
+    use crate::io::{ExportCfg, Provenance};
+    use crate::linalg::{SMatrix, Vector3};
+    use crate::md::prelude::Traj;
+    use crate::od::estimate::{Estimate, KfEstimate};
+    use crate::time::Unit;
+    use crate::{Spacecraft, State};
+    use std::path::PathBuf;
+
+    #[test]
+    fn ocm_round_trip() {
+        let path: PathBuf = [
+            env!("CARGO_MANIFEST_DIR"),
+            "data",
+            "03_tests",
+            "ccsds",
+            "oem",
+            "LEO_10s.oem",
+        ]
+        .iter()
+        .collect();
+
+        let mut template = Spacecraft::default()
+            .with_dry_mass(500.0)
+            .with_prop_mass(50.0)
+            .with_srp(2.0, 1.5)
+            .with_drag(3.0, 2.1);
+        template.thruster = Some(Thruster {
+            thrust_N: 1.0,
+            isp_s: 220.0,
+        });
+        let traj: Traj<Spacecraft> = Traj::from_oem_file(path, Some(template)).unwrap();
+
+        let mut ocm = OrbitComprehensiveMessage::new("LEO".to_string(), traj.clone());
+        ocm.covar_frame = LocalFrame::RIC;
+        ocm.estimates = [3, 50, 100]
+            .iter()
+            .map(|idx| {
+                let mut covar = SMatrix::<f64, 9, 9>::identity() * 1e-4;
+                covar[(1, 0)] = 5e-5;
+                covar[(0, 1)] = 5e-5;
+                KfEstimate::from_covar(traj.states[*idx], covar)
+            })
+            .collect();
+        let start = traj.first().epoch();
+        ocm.maneuvers = vec![
+            Maneuver::from_impulsive(
+                start + Unit::Minute * 10,
+                Vector3::new(1e-3, 2e-3, -3e-3),
+                LocalFrame::RIC,
+            ),
+            Maneuver::from_time_invariant(
+                start + Unit::Minute * 20,
+                start + Unit::Minute * 25,
+                1.0,
+                Vector3::new(0.0, 0.0, 1.0),
+                LocalFrame::VNC,
+            ),
+        ];
+        ocm.provenance = Some(Provenance::new().with_seed("OCM", 42));
+
+        let out: PathBuf = [env!("CARGO_MANIFEST_DIR"), "data", "04_output", "ocm.txt"]
+            .iter()
+            .collect();
+        let out = ocm.to_file(out, ExportCfg::default()).unwrap();
+
+        let mut tpl = Spacecraft::default().with_prop_mass(50.0);
+        tpl.thruster = template.thruster;
+        let reloaded = OrbitComprehensiveMessage::from_file(&out, Some(tpl)).unwrap();
+
+        assert_eq!(reloaded.object_name, "LEO");
+        assert_eq!(Provenance::from_file(&out).ok(), ocm.provenance);
+        assert_eq!(reloaded.traj.states, traj.states);
+        assert_eq!(reloaded.covar_frame, LocalFrame::RIC);
+        assert_eq!(reloaded.estimates.len(), 3);
+        for (estimate, expected) in reloaded.estimates.iter().zip(&ocm.estimates) {
+            assert_eq!(estimate.epoch(), expected.epoch());
+            let err = (estimate.covar - expected.covar)
+                .fixed_view::<6, 6>(0, 0)
+                .abs()
+                .max();
+            assert!(err < 1e-15, "{err}");
+        }
+
+        assert_eq!(reloaded.maneuvers.len(), 2);
+        assert_eq!(reloaded.maneuvers[0], ocm.maneuvers[0]);
+        assert_eq!(reloaded.maneuvers[1].frame, LocalFrame::VNC);
+        assert_eq!(reloaded.maneuvers[1].start, ocm.maneuvers[1].start);
+        assert_eq!(reloaded.maneuvers[1].end, ocm.maneuvers[1].end);
+        assert!((reloaded.maneuvers[1].direction() - Vector3::new(0.0, 0.0, 1.0)).norm() < 1e-12);
+
+        // Covariances in an unsupported frame are rejected instead of being read as inertial.
+        let contents = std::fs::read_to_string(&out).unwrap();
+        assert!(contents.contains("COV_REF_FRAME = RTN"));
+        std::fs::write(
+            &out,
+            contents.replace("COV_REF_FRAME = RTN", "COV_REF_FRAME = TOD"),
+        )
+        .unwrap();
+        assert!(OrbitComprehensiveMessage::from_file(&out, None).is_err());
+    }
+}
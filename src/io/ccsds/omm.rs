@@ -0,0 +1,506 @@
+/*
+    Nyx, blazing fast astrodynamics
+    Copyright (C) 2018-onwards Christopher Rabotin <christopher.rabotin@gmail.com>
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published
+    by the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use anise::constants::celestial_objects::EARTH;
+use anise::constants::orientations::J2000;
+use anise::prelude::Orbit;
+use log::{info, warn};
+
+use super::{
+    ccsds_epoch_fmt, ccsds_frame, ccsds_frame_names, parse_kvn, teme_to_j2000,
+    write_covariance_kvn, write_kvn_header, write_spacecraft_parameters, KvnValues,
+};
+use crate::cosmic::Spacecraft;
+use crate::dynamics::guidance::LocalFrame;
+use crate::errors::NyxError;
+use crate::io::ExportCfg;
+use crate::linalg::{Matrix6, SMatrix};
+use crate::md::trajectory::OemCovariance;
+use crate::od::estimate::KfEstimate;
+use crate::time::{Epoch, Formatter, Unit};
+use std::f64::consts::TAU;
+use std::fs::{read_to_string, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// Gravitational parameter of the WGS-72 model used by SGP4, used when the OMM does not specify its GM.
+const SGP4_GM_KM3_S2: f64 = 398_600.8;
+
+/// Rotation of the position and velocity from TEME to J2000, cf. [teme_to_j2000].
+fn teme_to_j2000_state(epoch: Epoch) -> Matrix6<f64> {
+    let teme_dcm = teme_to_j2000(epoch);
+    let mut dcm = Matrix6::zeros();
+    dcm.fixed_view_mut::<3, 3>(0, 0).copy_from(&teme_dcm);
+    dcm.fixed_view_mut::<3, 3>(3, 3).copy_from(&teme_dcm);
+    dcm
+}
+
+/// Parameters of a two-line element set, which are specific to the SGP4 mean element theory.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TleParameters {
+    pub ephemeris_type: u8,
+    pub classification_type: String,
+    pub norad_cat_id: u32,
+    pub element_set_no: u32,
+    pub rev_at_epoch: u32,
+    /// Drag term, in inverse Earth radii
+    pub bstar: f64,
+    /// First time derivative of the mean motion, in rev/day**2
+    pub mean_motion_dot: f64,
+    /// Second time derivative of the mean motion, in rev/day**3
+    pub mean_motion_ddot: f64,
+}
+
+/// A CCSDS Orbit Mean-Elements Message (OMM), e.g. a catalog state of a space object.
+///
+/// Nyx does not implement the mean element theories (e.g. SGP4), so the mean Keplerian elements are used as osculating elements:
+/// the orbit of the spacecraft is only an approximation of the state of the object.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OrbitMeanElementsMessage {
+    pub object_name: String,
+    /// International designator of the object, e.g. 2000-053A
+    pub object_id: String,
+    /// Mean element theory of the elements, e.g. SGP4 or DSST
+    pub mean_element_theory: String,
+    /// Spacecraft, including its mass and its SRP and drag parameters
+    pub spacecraft: Spacecraft,
+    /// Covariance of the orbit, in the inertial frame of the spacecraft, in km and km/s
+    pub covar: Option<Matrix6<f64>>,
+    /// Frame in which the covariance is written to the message, either Inertial or RIC
+    pub covar_frame: LocalFrame,
+    /// TLE parameters, if the mean element theory is SGP4
+    pub tle: Option<TleParameters>,
+}
+
+impl OrbitMeanElementsMessage {
+    /// Initializes a new OMM of this spacecraft without any covariance or TLE parameters.
+    pub fn new(object_name: String, mean_element_theory: String, spacecraft: Spacecraft) -> Self {
+        Self {
+            object_name,
+            object_id: "UNKNOWN".to_string(),
+            mean_element_theory,
+            spacecraft,
+            covar: None,
+            covar_frame: LocalFrame::Inertial,
+            tle: None,
+        }
+    }
+
+    /// Returns the estimate of this OMM if it has a covariance.
+    ///
+    /// Only the orbit part of the covariance is set, the covariance of the other spacecraft parameters is zero.
+    pub fn estimate(&self) -> Option<KfEstimate<Spacecraft>> {
+        let orbit_covar = self.covar?;
+        let mut covar = SMatrix::<f64, 9, 9>::zeros();
+        covar.fixed_view_mut::<6, 6>(0, 0).copy_from(&orbit_covar);
+        Some(KfEstimate::from_covar(self.spacecraft, covar))
+    }
+
+    /// Reads an OMM in the KVN format.
+    ///
+    /// The spacecraft template provides the thruster and the propellant mass, which the OMM does not specify.
+    /// If the OMM does not specify the GM and the theory is SGP4, then the GM of WGS-72 is used.
+    /// States and covariances in the TEME frame are rotated to J2000.
+    pub fn from_file<P: AsRef<Path>>(
+        path: P,
+        tpl_option: Option<Spacecraft>,
+    ) -> Result<Self, NyxError> {
+        let contents = read_to_string(path).map_err(|e| NyxError::CCSDS {
+            msg: format!("File opening error: {e}"),
+        })?;
+
+        let mut values = KvnValues::new();
+        for (line, keyword, value) in parse_kvn(&contents) {
+            values.insert(line, keyword, value);
+        }
+
+        values.required("CCSDS_OMM_VERS")?;
+        let mean_element_theory = values.required("MEAN_ELEMENT_THEORY")?.to_string();
+        let is_sgp4 = mean_element_theory.starts_with("SGP4");
+        if is_sgp4 {
+            warn!("{mean_element_theory} mean elements are used as osculating elements");
+        }
+
+        let gm = match values.f64("GM")? {
+            Some(gm) => gm,
+            None if is_sgp4 => SGP4_GM_KM3_S2,
+            None => {
+                return Err(NyxError::CCSDS {
+                    msg: format!("GM is required for the {mean_element_theory} theory"),
+                })
+            }
+        };
+        // SGP4 elements are usually in TEME, which is rotated to J2000 once the state is computed.
+        let is_teme = values.required("REF_FRAME")? == "TEME";
+        let frame = if is_teme {
+            ccsds_frame(values.required("CENTER_NAME")?, "ICRF")?
+        } else {
+            values.frame()?
+        }
+        .with_mu_km3_s2(gm);
+
+        let sma_km = match values.f64("SEMI_MAJOR_AXIS")? {
+            Some(sma_km) => sma_km,
+            None => {
+                let mean_motion_rad_s =
+                    values.required_f64("MEAN_MOTION")? * TAU / Unit::Day.in_seconds();
+                (gm / mean_motion_rad_s.powi(2)).cbrt()
+            }
+        };
+
+        let mut orbit = Orbit::try_keplerian_mean_anomaly(
+            sma_km,
+            values.required_f64("ECCENTRICITY")?,
+            values.required_f64("INCLINATION")?,
+            values.required_f64("RA_OF_ASC_NODE")?,
+            values.required_f64("ARG_OF_PERICENTER")?,
+            values.required_f64("MEAN_ANOMALY")?,
+            values.epoch("EPOCH")?,
+            frame,
+        )
+        .map_err(|e| NyxError::CCSDS {
+            msg: format!("invalid mean elements: {e}"),
+        })?;
+
+        let teme_dcm = teme_to_j2000(orbit.epoch);
+        if is_teme {
+            orbit.radius_km = teme_dcm * orbit.radius_km;
+            orbit.velocity_km_s = teme_dcm * orbit.velocity_km_s;
+        }
+
+        let mut spacecraft = tpl_option.unwrap_or_default().with_orbit(orbit);
+        values.apply_spacecraft_parameters(&mut spacecraft)?;
+
+        let (covar, covar_frame) = match values.covariance()? {
+            Some((covar, covar_frame)) => {
+                let covariance = OemCovariance {
+                    epoch: orbit.epoch,
+                    frame: covar_frame,
+                    covar,
+                };
+                let mut covar = covariance.inertial_covar(&orbit)?;
+                if is_teme && covar_frame == LocalFrame::Inertial {
+                    let dcm = teme_to_j2000_state(orbit.epoch);
+                    covar = dcm * covar * dcm.transpose();
+                }
+                (Some(covar), covar_frame)
+            }
+            None => (None, LocalFrame::Inertial),
+        };
+
+        let tle = match values.get("NORAD_CAT_ID") {
+            Some(norad_cat_id) => {
+                let integer = |keyword: &str, value: &str| -> Result<u32, NyxError> {
+                    value.parse::<u32>().map_err(|e| NyxError::CCSDS {
+                        msg: format!("could not parse `{keyword}`: {e}"),
+                    })
+                };
+                Some(TleParameters {
+                    ephemeris_type: integer(
+                        "EPHEMERIS_TYPE",
+                        values.get("EPHEMERIS_TYPE").unwrap_or("0"),
+                    )? as u8,
+                    classification_type: values
+                        .get("CLASSIFICATION_TYPE")
+                        .unwrap_or("U")
+                        .to_string(),
+                    norad_cat_id: integer("NORAD_CAT_ID", norad_cat_id)?,
+                    element_set_no: integer(
+                        "ELEMENT_SET_NO",
+                        values.get("ELEMENT_SET_NO").unwrap_or("0"),
+                    )?,
+                    rev_at_epoch: integer(
+                        "REV_AT_EPOCH",
+                        values.get("REV_AT_EPOCH").unwrap_or("0"),
+                    )?,
+                    bstar: values.f64("BSTAR")?.unwrap_or_default(),
+                    mean_motion_dot: values.f64("MEAN_MOTION_DOT")?.unwrap_or_default(),
+                    mean_motion_ddot: values.f64("MEAN_MOTION_DDOT")?.unwrap_or_default(),
+                })
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            object_name: values.required("OBJECT_NAME")?.to_string(),
+            object_id: values.get("OBJECT_ID").unwrap_or("UNKNOWN").to_string(),
+            mean_element_theory,
+            spacecraft,
+            covar,
+            covar_frame,
+            tle,
+        })
+    }
+
+    /// Writes this OMM in the KVN format, the orbit is written as mean elements.
+    ///
+    /// The mean motion is written instead of the semi-major axis if the OMM has TLE parameters, as required by SGP4.
+    /// SGP4 elements are rotated to TEME, so the orbit must be in the Earth J2000 frame.
+    pub fn to_file<P: AsRef<Path>>(&self, path: P, cfg: ExportCfg) -> Result<PathBuf, NyxError> {
+        info!("Exporting {} to CCSDS OMM file...", self.object_name);
+
+        let mut orbit = self.spacecraft.orbit;
+        let mut covar = self.covar;
+        let (center, mut ref_frame) = ccsds_frame_names(orbit.frame);
+        if self.mean_element_theory.starts_with("SGP4") {
+            if orbit.frame.ephemeris_id != EARTH || orbit.frame.orientation_id != J2000 {
+                return Err(NyxError::CCSDS {
+                    msg: format!(
+                        "{} elements must be written in TEME, which requires an Earth J2000 orbit, got {}",
+                        self.mean_element_theory, orbit.frame
+                    ),
+                });
+            }
+            let teme_dcm = teme_to_j2000(orbit.epoch).transpose();
+            orbit.radius_km = teme_dcm * orbit.radius_km;
+            orbit.velocity_km_s = teme_dcm * orbit.velocity_km_s;
+            let dcm = teme_to_j2000_state(orbit.epoch).transpose();
+            covar = covar.map(|covar| dcm * covar * dcm.transpose());
+            ref_frame = "TEME".to_string();
+        }
+
+        let path_buf = cfg.actual_path(path);
+        let provenance = cfg.provenance_or(None, None);
+        let metadata = cfg.metadata.unwrap_or_default();
+
+        let physics_err = |e| NyxError::CCSDS {
+            msg: format!("could not compute the elements: {e}"),
+        };
+        let gm = orbit.frame.mu_km3_s2().map_err(physics_err)?;
+        let sma_km = orbit.sma_km().map_err(physics_err)?;
+        let elements = [
+            ("ECCENTRICITY", orbit.ecc().map_err(physics_err)?, ""),
+            (
+                "INCLINATION",
+                orbit.inc_deg().map_err(physics_err)?,
+                " [deg]",
+            ),
+            (
+                "RA_OF_ASC_NODE",
+                orbit.raan_deg().map_err(physics_err)?,
+                " [deg]",
+            ),
+            (
+                "ARG_OF_PERICENTER",
+                orbit.aop_deg().map_err(physics_err)?,
+                " [deg]",
+            ),
+            (
+                "MEAN_ANOMALY",
+                orbit.ma_deg().map_err(physics_err)?,
+                " [deg]",
+            ),
+        ];
+
+        let file = File::create(&path_buf).map_err(|e| NyxError::CCSDS {
+            msg: format!("File creation error: {e}"),
+        })?;
+        let mut writer = BufWriter::new(file);
+
+        let err_hdlr = |e| NyxError::CCSDS {
+            msg: format!("Could not write: {e}"),
+        };
+
+        write_kvn_header(&mut writer, "OMM", &metadata, &provenance).map_err(err_hdlr)?;
+
+        writeln!(writer, "OBJECT_NAME = {}", self.object_name).map_err(err_hdlr)?;
+        writeln!(writer, "OBJECT_ID = {}", self.object_id).map_err(err_hdlr)?;
+        writeln!(writer, "CENTER_NAME = {center}").map_err(err_hdlr)?;
+        writeln!(writer, "REF_FRAME = {ref_frame}").map_err(err_hdlr)?;
+        writeln!(writer, "TIME_SYSTEM = {}", orbit.epoch.time_scale).map_err(err_hdlr)?;
+        writeln!(
+            writer,
+            "MEAN_ELEMENT_THEORY = {}\n",
+            self.mean_element_theory
+        )
+        .map_err(err_hdlr)?;
+
+        writeln!(
+            writer,
+            "EPOCH = {}",
+            Formatter::new(orbit.epoch, ccsds_epoch_fmt())
+        )
+        .map_err(err_hdlr)?;
+        if self.tle.is_some() {
+            let mean_motion_rev_day = (gm / sma_km.powi(3)).sqrt() * Unit::Day.in_seconds() / TAU;
+            writeln!(writer, "MEAN_MOTION = {mean_motion_rev_day:E} [rev/day]")
+                .map_err(err_hdlr)?;
+        } else {
+            writeln!(writer, "SEMI_MAJOR_AXIS = {sma_km:E} [km]").map_err(err_hdlr)?;
+        }
+        for (name, value, unit) in elements {
+            writeln!(writer, "{name} = {value:E}{unit}").map_err(err_hdlr)?;
+        }
+        writeln!(writer, "GM = {gm} [km**3/s**2]\n").map_err(err_hdlr)?;
+
+        write_spacecraft_parameters(&mut writer, &self.spacecraft).map_err(err_hdlr)?;
+
+        if let Some(tle) = &self.tle {
+            writeln!(writer, "EPHEMERIS_TYPE = {}", tle.ephemeris_type).map_err(err_hdlr)?;
+            writeln!(writer, "CLASSIFICATION_TYPE = {}", tle.classification_type)
+                .map_err(err_hdlr)?;
+            writeln!(writer, "NORAD_CAT_ID = {}", tle.norad_cat_id).map_err(err_hdlr)?;
+            writeln!(writer, "ELEMENT_SET_NO = {}", tle.element_set_no).map_err(err_hdlr)?;
+            writeln!(writer, "REV_AT_EPOCH = {}", tle.rev_at_epoch).map_err(err_hdlr)?;
+            writeln!(writer, "BSTAR = {:E} [1/ER]", tle.bstar).map_err(err_hdlr)?;
+            writeln!(
+                writer,
+                "MEAN_MOTION_DOT = {:E} [rev/day**2]",
+                tle.mean_motion_dot
+            )
+            .map_err(err_hdlr)?;
+            writeln!(
+                writer,
+                "MEAN_MOTION_DDOT = {:E} [rev/day**3]\n",
+                tle.mean_motion_ddot
+            )
+            .map_err(err_hdlr)?;
+        }
+
+        if let Some(covar) = covar {
+            let covariance = OemCovariance::from_inertial(&orbit, covar, self.covar_frame)?;
+            write_covariance_kvn(&mut writer, &covariance, &ref_frame).map_err(err_hdlr)?;
+        }
+
+        info!("OMM written to {}", path_buf.display());
+        Ok(path_buf)
+    }
+}
+
+#[cfg(test)]
+mod ut_omm {
+    use super::{parse_kvn, teme_to_j2000, OrbitMeanElementsMessage, SGP4_GM_KM3_S2};
+    use crate::dynamics::guidance::LocalFrame;
+    use crate::io::ExportCfg;
+    use crate::linalg::Matrix6;
+    use anise::constants::frames::{EARTH_J2000, MOON_J2000};
+    use std::path::PathBuf;
+
+    #[test]
+    fn omm_round_trip() {
+        let path: PathBuf = [
+            env!("CARGO_MANIFEST_DIR"),
+            "data",
+            "03_tests",
+            "ccsds",
+            "omm",
+            "omm_example.txt",
+        ]
+        .iter()
+        .collect();
+
+        let omm = OrbitMeanElementsMessage::from_file(path, None).unwrap();
+        assert_eq!(omm.object_name, "GOES 9");
+        assert_eq!(omm.object_id, "1995-025A");
+        assert_eq!(omm.mean_element_theory, "SGP4");
+        let orbit = omm.spacecraft.orbit;
+        assert_eq!(orbit.frame, EARTH_J2000.with_mu_km3_s2(SGP4_GM_KM3_S2));
+        assert!((orbit.ecc().unwrap() - 0.0005013).abs() < 1e-12);
+        assert!((orbit.ma_deg().unwrap() - 25.1173).abs() < 1e-8);
+        // The TEME elements are rotated to J2000.
+        let teme_dcm = teme_to_j2000(orbit.epoch);
+        let mut teme_orbit = orbit;
+        teme_orbit.radius_km = teme_dcm.transpose() * orbit.radius_km;
+        teme_orbit.velocity_km_s = teme_dcm.transpose() * orbit.velocity_km_s;
+        assert!((teme_orbit.inc_deg().unwrap() - 3.0539).abs() < 1e-10);
+        assert!((teme_orbit.raan_deg().unwrap() - 81.7939).abs() < 1e-8);
+        assert!((orbit.inc_deg().unwrap() - 3.0539).abs() > 1e-3);
+        // GEO, 1.00273272 rev/day
+        assert!((orbit.sma_km().unwrap() - 42_164.8).abs() < 1.0);
+        assert_eq!(omm.spacecraft.mass_kg(), 300.0);
+        assert_eq!(omm.spacecraft.drag.coeff_drag, 2.2);
+        let tle = omm.tle.clone().unwrap();
+        assert_eq!(tle.norad_cat_id, 23581);
+        assert_eq!(tle.bstar, 0.0001);
+        assert_eq!(omm.covar_frame, LocalFrame::Inertial);
+        let covar = omm.covar.unwrap();
+        let mut dcm = Matrix6::zeros();
+        dcm.fixed_view_mut::<3, 3>(0, 0).copy_from(&teme_dcm);
+        dcm.fixed_view_mut::<3, 3>(3, 3).copy_from(&teme_dcm);
+        let teme_covar = dcm.transpose() * covar * dcm;
+        assert!((teme_covar[(0, 0)] - 3.331349476038534e-04).abs() < 1e-15);
+        assert_eq!(covar[(5, 4)], covar[(4, 5)]);
+
+        let out: PathBuf = [env!("CARGO_MANIFEST_DIR"), "data", "04_output", "omm.txt"]
+            .iter()
+            .collect();
+        let out = omm.to_file(out, ExportCfg::default()).unwrap();
+
+        // The SGP4 elements are written back in TEME, as in the original message.
+        let contents = std::fs::read_to_string(&out).unwrap();
+        let written = parse_kvn(&contents);
+        let value = |keyword: &str| -> String {
+            written
+                .iter()
+                .find(|(_, kw, _)| kw == keyword)
+                .map(|(_, _, value)| value.clone())
+                .unwrap()
+        };
+        assert_eq!(value("REF_FRAME"), "TEME");
+        assert_eq!(value("COV_REF_FRAME"), "TEME");
+        assert!((value("INCLINATION").parse::<f64>().unwrap() - 3.0539).abs() < 1e-10);
+        assert!((value("RA_OF_ASC_NODE").parse::<f64>().unwrap() - 81.7939).abs() < 1e-8);
+        assert!((value("MEAN_MOTION").parse::<f64>().unwrap() - 1.00273272).abs() < 1e-12);
+        assert!((value("CX_X").parse::<f64>().unwrap() - 3.331349476038534e-04).abs() < 1e-15);
+
+        let reloaded = OrbitMeanElementsMessage::from_file(out, None).unwrap();
+
+        assert_eq!(reloaded.tle, omm.tle);
+        let err = (reloaded.covar.unwrap() - covar).abs().max();
+        assert!(err < 1e-15, "{err}");
+        assert!(
+            (reloaded.spacecraft.orbit.radius_km - orbit.radius_km).norm() < 1e-6,
+            "{}",
+            reloaded.spacecraft.orbit
+        );
+        assert!((reloaded.spacecraft.orbit.velocity_km_s - orbit.velocity_km_s).norm() < 1e-9);
+
+        // The semi-major axis is written instead of the mean motion when there are no TLE parameters
+        let mut not_sgp4 = reloaded.clone();
+        not_sgp4.mean_element_theory = "DSST".to_string();
+        not_sgp4.tle = None;
+        not_sgp4.covar = Some(Matrix6::identity());
+        not_sgp4.covar_frame = LocalFrame::RIC;
+        let out: PathBuf = [
+            env!("CARGO_MANIFEST_DIR"),
+            "data",
+            "04_output",
+            "omm_dsst.txt",
+        ]
+        .iter()
+        .collect();
+        let out = not_sgp4.to_file(out, ExportCfg::default()).unwrap();
+        let reloaded = OrbitMeanElementsMessage::from_file(out, None).unwrap();
+        assert_eq!(reloaded.covar_frame, LocalFrame::RIC);
+        assert!((reloaded.covar.unwrap() - Matrix6::identity()).abs().max() < 1e-12);
+        assert!((reloaded.spacecraft.orbit.radius_km - orbit.radius_km).norm() < 1e-6);
+
+        // SGP4 elements cannot be written in TEME if the orbit is not an Earth J2000 orbit.
+        let mut lunar = omm.clone();
+        lunar.spacecraft.orbit.frame = MOON_J2000.with_mu_km3_s2(4_902.8);
+        let out: PathBuf = [
+            env!("CARGO_MANIFEST_DIR"),
+            "data",
+            "04_output",
+            "omm_lunar.txt",
+        ]
+        .iter()
+        .collect();
+        assert!(lunar.to_file(out, ExportCfg::default()).is_err());
+    }
+}
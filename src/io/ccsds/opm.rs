@@ -0,0 +1,482 @@
+/*
+    Nyx, blazing fast astrodynamics
+    Copyright (C) 2018-onwards Christopher Rabotin <christopher.rabotin@gmail.com>
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published
+    by the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use anise::prelude::Orbit;
+use log::info;
+
+use super::{
+    ccsds_epoch, ccsds_epoch_fmt, ccsds_frame_names, parse_kvn, write_covariance_kvn,
+    write_kvn_header, write_spacecraft_parameters, CcsdsManeuver, KvnValues,
+};
+use crate::cosmic::Spacecraft;
+use crate::dynamics::guidance::{LocalFrame, Maneuver};
+use crate::errors::NyxError;
+use crate::io::ExportCfg;
+use crate::linalg::{Matrix6, SMatrix, Vector3};
+use crate::md::trajectory::OemCovariance;
+use crate::od::estimate::{Estimate, KfEstimate};
+use crate::time::{Formatter, Unit};
+use std::fs::{read_to_string, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// A CCSDS Orbit Parameter Message (OPM): the state of a spacecraft, and optionally its covariance and its planned maneuvers.
+///
+/// The maneuvers of the message are converted to impulsive maneuvers if their duration is zero, and to finite burns at full thrust otherwise,
+/// cf. [`CcsdsManeuver`] for the conversions.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OrbitParameterMessage {
+    pub object_name: String,
+    /// International designator of the object, e.g. 2000-053A
+    pub object_id: String,
+    /// Spacecraft, including its mass and its SRP and drag parameters
+    pub spacecraft: Spacecraft,
+    /// Covariance of the orbit, in the inertial frame of the spacecraft, in km and km/s
+    pub covar: Option<Matrix6<f64>>,
+    /// Frame in which the covariance is written to the message, either Inertial or RIC
+    pub covar_frame: LocalFrame,
+    pub maneuvers: Vec<Maneuver>,
+}
+
+impl OrbitParameterMessage {
+    /// Initializes a new OPM of this spacecraft without any covariance or maneuver.
+    pub fn new(object_name: String, spacecraft: Spacecraft) -> Self {
+        Self {
+            object_name,
+            object_id: "UNKNOWN".to_string(),
+            spacecraft,
+            covar: None,
+            covar_frame: LocalFrame::Inertial,
+            maneuvers: Vec::new(),
+        }
+    }
+
+    /// Initializes a new OPM from an estimate, whose orbit covariance will be written in the provided frame.
+    pub fn from_estimate(
+        object_name: String,
+        estimate: &KfEstimate<Spacecraft>,
+        covar_frame: LocalFrame,
+    ) -> Self {
+        Self {
+            covar: Some(estimate.covar().fixed_view::<6, 6>(0, 0).into_owned()),
+            covar_frame,
+            ..Self::new(object_name, estimate.state())
+        }
+    }
+
+    /// Returns the estimate of this OPM if it has a covariance.
+    ///
+    /// Only the orbit part of the covariance is set, the covariance of the other spacecraft parameters is zero.
+    pub fn estimate(&self) -> Option<KfEstimate<Spacecraft>> {
+        let orbit_covar = self.covar?;
+        let mut covar = SMatrix::<f64, 9, 9>::zeros();
+        covar.fixed_view_mut::<6, 6>(0, 0).copy_from(&orbit_covar);
+        Some(KfEstimate::from_covar(self.spacecraft, covar))
+    }
+
+    /// Reads an OPM in the KVN format.
+    ///
+    /// The spacecraft template provides the thruster and the propellant mass, which the OPM does not specify.
+    /// The dry mass is set such that the total mass is the MASS of the OPM.
+    pub fn from_file<P: AsRef<Path>>(
+        path: P,
+        tpl_option: Option<Spacecraft>,
+    ) -> Result<Self, NyxError> {
+        let contents = read_to_string(path).map_err(|e| NyxError::CCSDS {
+            msg: format!("File opening error: {e}"),
+        })?;
+
+        // Maneuvers repeat the same keywords, so each one is stored separately.
+        let mut values = KvnValues::new();
+        let mut maneuver_values = Vec::new();
+        for (line, keyword, value) in parse_kvn(&contents) {
+            if keyword == "MAN_EPOCH_IGNITION" {
+                maneuver_values.push(KvnValues::new());
+            }
+            match maneuver_values.last_mut() {
+                Some(man) if keyword.starts_with("MAN_") => man.insert(line, keyword, value),
+                _ => values.insert(line, keyword, value),
+            }
+        }
+
+        values.required("CCSDS_OPM_VERS")?;
+
+        let mut frame = values.frame()?;
+        if let Some(gm) = values.f64("GM")? {
+            frame = frame.with_mu_km3_s2(gm);
+        }
+        let orbit = Orbit::new(
+            values.required_f64("X")?,
+            values.required_f64("Y")?,
+            values.required_f64("Z")?,
+            values.required_f64("X_DOT")?,
+            values.required_f64("Y_DOT")?,
+            values.required_f64("Z_DOT")?,
+            values.epoch("EPOCH")?,
+            frame,
+        );
+
+        let mut spacecraft = tpl_option.unwrap_or_default().with_orbit(orbit);
+        values.apply_spacecraft_parameters(&mut spacecraft)?;
+
+        let (covar, covar_frame) = match values.covariance()? {
+            Some((covar, covar_frame)) => {
+                let covariance = OemCovariance {
+                    epoch: orbit.epoch,
+                    frame: covar_frame,
+                    covar,
+                };
+                (Some(covariance.inertial_covar(&orbit)?), covar_frame)
+            }
+            None => (None, LocalFrame::Inertial),
+        };
+
+        let mut maneuvers = Vec::with_capacity(maneuver_values.len());
+        for man in maneuver_values {
+            // The time system is specified in the metadata.
+            let start = ccsds_epoch(
+                man.required("MAN_EPOCH_IGNITION")?,
+                values.get("TIME_SYSTEM").unwrap_or_default(),
+            )?;
+            let ccsds_mnvr = CcsdsManeuver {
+                start,
+                duration: Unit::Second * man.required_f64("MAN_DURATION")?,
+                delta_mass_kg: man.f64("MAN_DELTA_MASS")?.unwrap_or_default(),
+                frame: man.required("MAN_REF_FRAME")?.to_string(),
+                dv_km_s: Vector3::new(
+                    man.required_f64("MAN_DV_1")?,
+                    man.required_f64("MAN_DV_2")?,
+                    man.required_f64("MAN_DV_3")?,
+                ),
+            };
+            maneuvers.push(ccsds_mnvr.to_maneuver(&spacecraft, values.get("REF_FRAME"))?);
+        }
+
+        Ok(Self {
+            object_name: values.required("OBJECT_NAME")?.to_string(),
+            object_id: values.get("OBJECT_ID").unwrap_or("UNKNOWN").to_string(),
+            spacecraft,
+            covar,
+            covar_frame,
+            maneuvers,
+        })
+    }
+
+    /// Writes this OPM in the KVN format.
+    pub fn to_file<P: AsRef<Path>>(&self, path: P, cfg: ExportCfg) -> Result<PathBuf, NyxError> {
+        info!("Exporting {} to CCSDS OPM file...", self.object_name);
+        let path_buf = cfg.actual_path(path);
+        let provenance = cfg.provenance_or(None, None);
+        let metadata = cfg.metadata.unwrap_or_default();
+
+        let file = File::create(&path_buf).map_err(|e| NyxError::CCSDS {
+            msg: format!("File creation error: {e}"),
+        })?;
+        let mut writer = BufWriter::new(file);
+
+        let err_hdlr = |e| NyxError::CCSDS {
+            msg: format!("Could not write: {e}"),
+        };
+
+        let orbit = self.spacecraft.orbit;
+        let (center, ref_frame) = ccsds_frame_names(orbit.frame);
+        let fmt = ccsds_epoch_fmt();
+
+        write_kvn_header(&mut writer, "OPM", &metadata, &provenance).map_err(err_hdlr)?;
+
+        writeln!(writer, "OBJECT_NAME = {}", self.object_name).map_err(err_hdlr)?;
+        writeln!(writer, "OBJECT_ID = {}", self.object_id).map_err(err_hdlr)?;
+        writeln!(writer, "CENTER_NAME = {center}").map_err(err_hdlr)?;
+        writeln!(writer, "REF_FRAME = {ref_frame}").map_err(err_hdlr)?;
+        writeln!(writer, "TIME_SYSTEM = {}\n", orbit.epoch.time_scale).map_err(err_hdlr)?;
+
+        writeln!(writer, "EPOCH = {}", Formatter::new(orbit.epoch, fmt)).map_err(err_hdlr)?;
+        for (name, value, unit) in [
+            ("X", orbit.radius_km.x, "km"),
+            ("Y", orbit.radius_km.y, "km"),
+            ("Z", orbit.radius_km.z, "km"),
+            ("X_DOT", orbit.velocity_km_s.x, "km/s"),
+            ("Y_DOT", orbit.velocity_km_s.y, "km/s"),
+            ("Z_DOT", orbit.velocity_km_s.z, "km/s"),
+        ] {
+            writeln!(writer, "{name} = {value:E} [{unit}]").map_err(err_hdlr)?;
+        }
+        if let Ok(gm) = orbit.frame.mu_km3_s2() {
+            writeln!(writer, "GM = {gm} [km**3/s**2]").map_err(err_hdlr)?;
+        }
+        writeln!(writer).map_err(err_hdlr)?;
+
+        write_spacecraft_parameters(&mut writer, &self.spacecraft).map_err(err_hdlr)?;
+
+        if let Some(covar) = self.covar {
+            let covariance = OemCovariance::from_inertial(&orbit, covar, self.covar_frame)?;
+            write_covariance_kvn(&mut writer, &covariance, &ref_frame).map_err(err_hdlr)?;
+        }
+
+        for mnvr in &self.maneuvers {
+            let ccsds_mnvr = CcsdsManeuver::from_maneuver(mnvr, &self.spacecraft, &ref_frame)?;
+            writeln!(
+                writer,
+                "MAN_EPOCH_IGNITION = {}",
+                Formatter::new(ccsds_mnvr.start, fmt)
+            )
+            .map_err(err_hdlr)?;
+            writeln!(
+                writer,
+                "MAN_DURATION = {} [s]",
+                ccsds_mnvr.duration.to_seconds()
+            )
+            .map_err(err_hdlr)?;
+            writeln!(writer, "MAN_DELTA_MASS = {} [kg]", ccsds_mnvr.delta_mass_kg)
+                .map_err(err_hdlr)?;
+            writeln!(writer, "MAN_REF_FRAME = {}", ccsds_mnvr.frame).map_err(err_hdlr)?;
+            for (i, dv) in ccsds_mnvr.dv_km_s.iter().enumerate() {
+                writeln!(writer, "MAN_DV_{} = {dv:E} [km/s]", i + 1).map_err(err_hdlr)?;
+            }
+            writeln!(writer).map_err(err_hdlr)?;
+        }
+
+        info!("OPM written to {}", path_buf.display());
+        Ok(path_buf)
+    }
+}
+
+#[cfg(test)]
+mod ut_opm {
+    use super::OrbitParameterMessage;
+    use crate::cosmic::GuidanceMode;
+    use crate::cosmic::{Orbit, Spacecraft};
+    use crate::dynamics::guidance::{FiniteBurns, LocalFrame, Maneuver, Thruster};
+    use crate::dynamics::{OrbitalDynamics, SpacecraftDynamics};
+    use crate::io::ExportCfg;
+    use crate::linalg::{Matrix6, Vector3, Vector6};
+    use crate::od::estimate::Estimate;
+    use crate::propagators::Propagator;
+    use crate::time::{Epoch, Unit};
+    use anise::constants::frames::EARTH_J2000;
+    use anise::prelude::Almanac;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    #[test]
+    fn opm_round_trip() {
+        let epoch = Epoch::from_gregorian_utc_at_midnight(2024, 2, 29);
+        let orbit = Orbit::new(
+            6_655.994_2,
+            -40_218.575_1,
+            -82.917_7,
+            3.115_484,
+            0.470_42,
+            -0.001_01,
+            epoch,
+            EARTH_J2000.with_mu_km3_s2(398_600.441_5),
+        );
+        let spacecraft = Spacecraft::builder()
+            .orbit(orbit)
+            .thruster(Thruster {
+                thrust_N: 10.0,
+                isp_s: 300.0,
+            })
+            .build()
+            .with_dry_mass(1_913.0)
+            .with_prop_mass(87.0)
+            .with_srp(10.0, 1.3)
+            .with_drag(12.0, 2.2);
+
+        let mut covar = Matrix6::from_diagonal(&Vector6::new(1e-2, 2e-2, 3e-2, 1e-8, 1e-8, 1e-8));
+        covar[(1, 0)] = 1e-3;
+        covar[(0, 1)] = 1e-3;
+
+        let mut opm = OrbitParameterMessage::new("GEO 1".to_string(), spacecraft);
+        opm.object_id = "2024-001A".to_string();
+        opm.covar = Some(covar);
+        opm.covar_frame = LocalFrame::RIC;
+        opm.maneuvers = vec![
+            Maneuver::from_impulsive(
+                epoch + Unit::Hour * 2,
+                Vector3::new(1e-3, -2e-3, 3e-4),
+                LocalFrame::VNC,
+            ),
+            Maneuver::from_time_invariant(
+                epoch + Unit::Hour * 5,
+                epoch + Unit::Hour * 5 + Unit::Minute * 2,
+                1.0,
+                Vector3::new(0.0, 1.0, 0.0),
+                LocalFrame::RIC,
+            ),
+        ];
+
+        let path: PathBuf = [env!("CARGO_MANIFEST_DIR"), "data", "04_output", "opm.txt"]
+            .iter()
+            .collect();
+        let path = opm.to_file(path, ExportCfg::default()).unwrap();
+
+        // The propellant mass and the thruster come from the template.
+        let template = Spacecraft::builder()
+            .orbit(orbit)
+            .thruster(spacecraft.thruster.unwrap())
+            .build()
+            .with_prop_mass(87.0);
+        let reloaded = OrbitParameterMessage::from_file(&path, Some(template)).unwrap();
+
+        assert_eq!(reloaded.object_name, opm.object_name);
+        assert_eq!(reloaded.object_id, opm.object_id);
+        assert_eq!(reloaded.spacecraft, opm.spacecraft);
+        assert_eq!(reloaded.covar_frame, LocalFrame::RIC);
+        let err = (reloaded.covar.unwrap() - covar).abs().max();
+        assert!(err < 1e-15, "{err}");
+        assert_eq!(reloaded.estimate().unwrap().state(), spacecraft);
+
+        assert_eq!(reloaded.maneuvers.len(), 2);
+        assert_eq!(reloaded.maneuvers[0].frame, LocalFrame::VNC);
+        assert_eq!(reloaded.maneuvers[0], opm.maneuvers[0]);
+        assert!(
+            (reloaded.maneuvers[0].vector(epoch) - Vector3::new(1e-3, -2e-3, 3e-4)).norm() < 1e-15
+        );
+        assert_eq!(reloaded.maneuvers[1].start, opm.maneuvers[1].start);
+        assert_eq!(reloaded.maneuvers[1].end, opm.maneuvers[1].end);
+        assert!((reloaded.maneuvers[1].direction() - Vector3::new(0.0, 1.0, 0.0)).norm() < 1e-12);
+
+        // Inertial covariances must be in the frame of the state, since they are not rotated.
+        opm.covar_frame = LocalFrame::Inertial;
+        let path = opm.to_file(path, ExportCfg::default()).unwrap();
+        let reloaded = OrbitParameterMessage::from_file(&path, None).unwrap();
+        assert_eq!(reloaded.covar_frame, LocalFrame::Inertial);
+        assert!((reloaded.covar.unwrap() - covar).abs().max() < 1e-15);
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert!(contents.contains("COV_REF_FRAME = ICRF"));
+        std::fs::write(
+            &path,
+            contents.replace("COV_REF_FRAME = ICRF", "COV_REF_FRAME = EME2000"),
+        )
+        .unwrap();
+        assert!(OrbitParameterMessage::from_file(&path, None).is_err());
+    }
+
+    #[test]
+    fn opm_finite_burn_propagation() {
+        let epoch = Epoch::from_gregorian_utc_at_midnight(2024, 2, 29);
+        let orbit = Orbit::keplerian(
+            7_000.0,
+            0.01,
+            30.0,
+            45.0,
+            60.0,
+            0.0,
+            epoch,
+            EARTH_J2000.with_mu_km3_s2(398_600.441_5),
+        );
+        let thruster = Thruster {
+            thrust_N: 10.0,
+            isp_s: 300.0,
+        };
+        let spacecraft = Spacecraft::builder()
+            .orbit(orbit)
+            .thruster(thruster)
+            .build()
+            .with_dry_mass(1_900.0)
+            .with_prop_mass(100.0);
+
+        let mut opm = OrbitParameterMessage::new("LEO 1".to_string(), spacecraft);
+        opm.maneuvers = vec![Maneuver::from_time_invariant(
+            epoch + Unit::Minute * 10,
+            epoch + Unit::Minute * 12,
+            0.5,
+            Vector3::new(0.0, 1.0, 0.0),
+            LocalFrame::RIC,
+        )];
+
+        let path: PathBuf = [
+            env!("CARGO_MANIFEST_DIR"),
+            "data",
+            "04_output",
+            "opm_finite_burn.txt",
+        ]
+        .iter()
+        .collect();
+        let path = opm.to_file(path, ExportCfg::default()).unwrap();
+
+        let template = Spacecraft::builder()
+            .orbit(orbit)
+            .thruster(thruster)
+            .build()
+            .with_prop_mass(100.0);
+        let reloaded = OrbitParameterMessage::from_file(&path, Some(template)).unwrap();
+
+        let mnvr = reloaded.maneuvers[0];
+        assert!((mnvr.thrust_prct - 0.5).abs() < 1e-12);
+        assert!((mnvr.vector(mnvr.start).norm() - 1.0).abs() < 1e-12);
+
+        // Propagate through the burn read from the OPM.
+        let dynamics = SpacecraftDynamics::from_guidance_law(
+            OrbitalDynamics::two_body(),
+            FiniteBurns::from_mnvrs(reloaded.maneuvers.clone()),
+        );
+        let mut sc = reloaded.spacecraft;
+        sc.mut_mode(GuidanceMode::Thrust);
+        let final_sc = Propagator::default(dynamics)
+            .with(sc, Arc::new(Almanac::default()))
+            .until_epoch(mnvr.end)
+            .unwrap();
+
+        // Rocket equation at half thrust over two minutes.
+        let expected_kg = 5.0 * 120.0 / thruster.exhaust_velocity_m_s();
+        let consumed_kg = sc.mass.prop_mass_kg - final_sc.mass.prop_mass_kg;
+        assert!(
+            (consumed_kg - expected_kg).abs() < 1e-3 * expected_kg,
+            "consumed {consumed_kg} kg instead of {expected_kg} kg"
+        );
+    }
+
+    #[test]
+    fn opm_blue_book() {
+        let path: PathBuf = [
+            env!("CARGO_MANIFEST_DIR"),
+            "data",
+            "03_tests",
+            "ccsds",
+            "opm",
+            "opm_example.txt",
+        ]
+        .iter()
+        .collect();
+
+        let opm = OrbitParameterMessage::from_file(path, None).unwrap();
+        assert_eq!(opm.object_name, "EUTELSAT W4");
+        assert_eq!(opm.object_id, "2000-028A");
+        assert_eq!(
+            opm.spacecraft.orbit.frame,
+            EARTH_J2000.with_mu_km3_s2(398_600.441_5)
+        );
+        assert_eq!(opm.spacecraft.orbit.radius_km.x, 6_655.994_2);
+        assert_eq!(opm.spacecraft.mass_kg(), 1_913.0);
+        assert_eq!(opm.spacecraft.srp.area_m2, 10.0);
+        assert_eq!(opm.spacecraft.srp.coeff_reflectivity, 1.3);
+        assert!(opm.covar.is_none());
+
+        assert_eq!(opm.maneuvers.len(), 2);
+        assert_eq!(opm.maneuvers[0].frame, LocalFrame::Inertial);
+        assert_eq!(opm.maneuvers[0].duration(), Unit::Second * 132.6);
+        assert_eq!(opm.maneuvers[1].frame, LocalFrame::RIC);
+        assert_eq!(
+            opm.maneuvers[1].vector(opm.maneuvers[1].start),
+            Vector3::new(0.0, -0.02, 0.0)
+        );
+        assert_eq!(opm.maneuvers[1].duration(), Unit::Millisecond * 1);
+    }
+}
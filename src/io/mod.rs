@@ -19,9 +19,11 @@
 use crate::errors::NyxError;
 use crate::md::StateParameter;
 use crate::time::Epoch;
+use anise::almanac::Almanac;
 use arrow::error::ArrowError;
 use log::debug;
 use parquet::errors::ParquetError;
+use provenance::PROVENANCE_KEY;
 use snafu::prelude::*;
 pub(crate) mod watermark;
 use hifitime::prelude::{Format, Formatter};
@@ -40,6 +42,16 @@ use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use typed_builder::TypedBuilder;
 
+/// Reads and writes the CCSDS messages of single states (OPM and OMM) and of comprehensive orbit data (OCM).
+pub mod ccsds;
+
+/// Reads and writes the SP3 precise orbit files of GNSS and laser ranging satellites.
+pub mod sp3;
+
+/// Records the exact inputs used to generate the exported files.
+pub mod provenance;
+pub use provenance::Provenance;
+
 /// Handles loading of gravity models using files of NASA PDS and GMAT COF. Several gunzipped files are provided with nyx.
 pub mod gravity;
 
@@ -67,6 +79,9 @@ pub struct ExportCfg {
     /// Set to true to append the timestamp to the filename
     #[builder(default)]
     pub timestamp: bool,
+    /// Provenance to embed in the exported file, defaults to the one recorded by the exporter itself
+    #[builder(default, setter(strip_option))]
+    pub provenance: Option<Provenance>,
 }
 
 impl ExportCfg {
@@ -98,6 +113,38 @@ impl ExportCfg {
         }
     }
 
+    /// Returns the provenance of this configuration, or the default one recorded by the exporter.
+    /// The kernels of the almanac are recorded if the provenance does not already list them.
+    pub(crate) fn provenance_or(
+        &self,
+        default_provenance: Option<Provenance>,
+        almanac: Option<&Almanac>,
+    ) -> Provenance {
+        let provenance = self
+            .provenance
+            .clone()
+            .or(default_provenance)
+            .unwrap_or_default();
+        match almanac {
+            Some(almanac) if provenance.almanac.is_empty() => provenance.with_almanac(almanac),
+            _ => provenance,
+        }
+    }
+
+    /// Returns the additional metadata along with the provenance, cf. [`ExportCfg::provenance_or`].
+    pub(crate) fn all_metadata(
+        &self,
+        default_provenance: Option<Provenance>,
+        almanac: Option<&Almanac>,
+    ) -> Result<HashMap<String, String>, InputOutputError> {
+        let mut metadata = self.metadata.clone().unwrap_or_default();
+        metadata.insert(
+            PROVENANCE_KEY.to_string(),
+            self.provenance_or(default_provenance, almanac).to_yaml()?,
+        );
+        Ok(metadata)
+    }
+
     /// Modifies the provided path to include the timestamp if required.
     pub(crate) fn actual_path<P: AsRef<Path>>(&self, path: P) -> PathBuf {
         let mut path_buf = path.as_ref().to_path_buf();
@@ -169,6 +216,8 @@ pub enum InputOutputError {
     ParseDhall { data: String, err: String },
     #[snafu(display("error serializing {what} to Dhall: {err}"))]
     SerializeDhall { what: String, err: String },
+    #[snafu(display("error serializing {what} to YAML: {err}"))]
+    SerializeYaml { what: String, err: String },
     #[snafu(display("empty dataset error when (de)serializing {action}"))]
     EmptyDataset { action: &'static str },
 }
@@ -0,0 +1,345 @@
+/*
+    Nyx, blazing fast astrodynamics
+    Copyright (C) 2018-onwards Christopher Rabotin <christopher.rabotin@gmail.com>
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published
+    by the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use anise::almanac::Almanac;
+use parquet::file::reader::{FileReader, SerializedFileReader};
+use quick_xml::escape::unescape;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs::{read, read_to_string, File};
+use std::io::Read;
+use std::path::Path;
+
+use super::watermark::prj_name_ver;
+use super::InputOutputError;
+use crate::dynamics::Dynamics;
+use crate::errors::NyxError;
+use crate::linalg::allocator::Allocator;
+use crate::linalg::DefaultAllocator;
+use crate::propagators::Propagator;
+use crate::State;
+
+/// Key of the provenance in the Parquet metadata, and prefix of the provenance comments in the CCSDS files.
+pub(crate) const PROVENANCE_KEY: &str = "Provenance";
+
+/// Exact inputs used to generate an exported file, for audits and reproducibility.
+///
+/// The provenance is serialized to YAML and embedded in the Parquet metadata or in the comments of the CCSDS files.
+/// Exporters always record the Nyx version and, when available, the almanac, the propagator and the seed used.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Provenance {
+    /// Name and version of Nyx which generated the data
+    pub nyx_version: String,
+    /// CRC32 of each kernel loaded in the almanac, indexed by the kernel name
+    #[serde(default)]
+    pub almanac: BTreeMap<String, String>,
+    /// Description of the dynamics
+    #[serde(default)]
+    pub dynamics: Option<String>,
+    /// Integration method and its options
+    #[serde(default)]
+    pub integrator: Option<String>,
+    /// Seeds of the random number generators, indexed by their purpose
+    #[serde(default)]
+    pub seeds: BTreeMap<String, u128>,
+    /// CRC32 of each configuration file, indexed by its path
+    #[serde(default)]
+    pub config_files: BTreeMap<String, String>,
+}
+
+impl Provenance {
+    /// Initializes a new provenance which only records the current version of Nyx.
+    pub fn new() -> Self {
+        Self {
+            nyx_version: prj_name_ver(),
+            almanac: BTreeMap::new(),
+            dynamics: None,
+            integrator: None,
+            seeds: BTreeMap::new(),
+            config_files: BTreeMap::new(),
+        }
+    }
+
+    /// Records the CRC32 of all of the kernels loaded in this almanac.
+    pub fn with_almanac(mut self, almanac: &Almanac) -> Self {
+        for (name, spk) in &almanac.spk_data {
+            self.almanac
+                .insert(format!("SPK {name}"), format!("{:08x}", spk.crc32()));
+        }
+        for (name, bpc) in &almanac.bpc_data {
+            self.almanac
+                .insert(format!("BPC {name}"), format!("{:08x}", bpc.crc32()));
+        }
+        for (name, is_empty, crc32) in [
+            (
+                "planetary data",
+                almanac.planetary_data.is_empty(),
+                almanac.planetary_data.crc32(),
+            ),
+            (
+                "spacecraft data",
+                almanac.spacecraft_data.is_empty(),
+                almanac.spacecraft_data.crc32(),
+            ),
+            (
+                "Euler parameter data",
+                almanac.euler_param_data.is_empty(),
+                almanac.euler_param_data.crc32(),
+            ),
+            (
+                "location data",
+                almanac.location_data.is_empty(),
+                almanac.location_data.crc32(),
+            ),
+        ] {
+            if !is_empty {
+                self.almanac
+                    .insert(name.to_string(), format!("{crc32:08x}"));
+            }
+        }
+        self
+    }
+
+    /// Records the description of the dynamics, cf. [`Dynamics::description`].
+    pub fn with_dynamics<D: Dynamics>(mut self, dynamics: &D) -> Self
+    where
+        DefaultAllocator: Allocator<<D::StateType as State>::Size>
+            + Allocator<<D::StateType as State>::Size, <D::StateType as State>::Size>
+            + Allocator<<D::StateType as State>::VecLength>,
+    {
+        self.dynamics = Some(dynamics.description());
+        self
+    }
+
+    /// Records the dynamics, integration method and integrator options of this propagator.
+    pub fn with_propagator<D: Dynamics>(mut self, prop: &Propagator<D>) -> Self
+    where
+        DefaultAllocator: Allocator<<D::StateType as State>::Size>
+            + Allocator<<D::StateType as State>::Size, <D::StateType as State>::Size>
+            + Allocator<<D::StateType as State>::VecLength>,
+    {
+        self.integrator = Some(format!("{:?} ({})", prop.method, prop.opts));
+        self.with_dynamics(&prop.dynamics)
+    }
+
+    /// Records the seed of a random number generator.
+    pub fn with_seed<N: Into<String>>(mut self, name: N, seed: u128) -> Self {
+        self.seeds.insert(name.into(), seed);
+        self
+    }
+
+    /// Records the CRC32 of the provided configuration file.
+    pub fn with_config_file<P: AsRef<Path>>(mut self, path: P) -> Result<Self, NyxError> {
+        let bytes = read(&path).map_err(|e| NyxError::FileUnreadable {
+            msg: format!("{}: {e}", path.as_ref().display()),
+        })?;
+        self.config_files.insert(
+            path.as_ref().display().to_string(),
+            format!("{:08x}", crc32fast::hash(&bytes)),
+        );
+        Ok(self)
+    }
+
+    /// Extracts the provenance from the metadata of a Parquet file, if any.
+    pub fn from_metadata(metadata: &HashMap<String, String>) -> Option<Self> {
+        metadata
+            .get(PROVENANCE_KEY)
+            .and_then(|yaml| serde_yml::from_str(yaml).ok())
+    }
+
+    /// Extracts the provenance embedded in a file exported by Nyx, either a Parquet file or a CCSDS file in KVN or XML.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, NyxError> {
+        let unreadable = |msg: String| NyxError::FileUnreadable {
+            msg: format!("{}: {msg}", path.as_ref().display()),
+        };
+
+        let mut file = File::open(&path).map_err(|e| unreadable(e.to_string()))?;
+        let mut magic = [0; 4];
+        let is_parquet = file.read_exact(&mut magic).is_ok() && &magic == b"PAR1";
+
+        let yaml = if is_parquet {
+            let reader = SerializedFileReader::new(file).map_err(|e| unreadable(e.to_string()))?;
+            reader
+                .metadata()
+                .file_metadata()
+                .key_value_metadata()
+                .and_then(|kvs| {
+                    kvs.iter()
+                        .find(|kv| kv.key == PROVENANCE_KEY)
+                        .and_then(|kv| kv.value.clone())
+                })
+                .unwrap_or_default()
+        } else {
+            let contents = read_to_string(&path).map_err(|e| unreadable(e.to_string()))?;
+            let kvn_prefix = format!("COMMENT {} ", PROVENANCE_KEY.to_uppercase());
+            let xml_prefix = format!("<COMMENT>{} ", PROVENANCE_KEY.to_uppercase());
+            let mut yaml = String::new();
+            for line in contents.lines() {
+                let line = line.trim_start();
+                if let Some(yaml_line) = line.strip_prefix(&kvn_prefix) {
+                    yaml.push_str(yaml_line);
+                } else if let Some(yaml_line) = line
+                    .strip_prefix(&xml_prefix)
+                    .and_then(|line| line.strip_suffix("</COMMENT>"))
+                {
+                    let yaml_line = unescape(yaml_line).map_err(|e| unreadable(e.to_string()))?;
+                    yaml.push_str(&yaml_line);
+                } else {
+                    continue;
+                }
+                yaml.push('\n');
+            }
+            yaml
+        };
+
+        if yaml.is_empty() {
+            return Err(unreadable("no provenance found".to_string()));
+        }
+
+        serde_yml::from_str(&yaml).map_err(|e| unreadable(format!("invalid provenance: {e}")))
+    }
+
+    /// Serializes this provenance to YAML.
+    pub(crate) fn to_yaml(&self) -> Result<String, InputOutputError> {
+        serde_yml::to_string(self).map_err(|e| InputOutputError::SerializeYaml {
+            what: "provenance".to_string(),
+            err: e.to_string(),
+        })
+    }
+
+    /// Returns the lines to be written in the comments of a KVN or XML file.
+    pub(crate) fn comment_lines(&self) -> Result<Vec<String>, InputOutputError> {
+        Ok(self
+            .to_yaml()?
+            .lines()
+            .map(|line| format!("{} {line}", PROVENANCE_KEY.to_uppercase()))
+            .collect())
+    }
+}
+
+impl Default for Provenance {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Display for Provenance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_yaml().map_err(|_| fmt::Error)?)
+    }
+}
+
+#[cfg(test)]
+mod ut_provenance {
+    use super::Provenance;
+    use crate::dynamics::{Dynamics, OrbitalDynamics, SpacecraftDynamics};
+    use crate::io::ccsds::OrbitParameterMessage;
+    use crate::io::ExportCfg;
+    use crate::md::prelude::{Orbit, Traj};
+    use crate::time::Epoch;
+    use crate::Spacecraft;
+    use anise::constants::frames::EARTH_J2000;
+    use std::path::PathBuf;
+
+    #[test]
+    fn provenance_in_kvn() {
+        let dynamics = SpacecraftDynamics::new(OrbitalDynamics::two_body());
+        let provenance = Provenance::new()
+            .with_dynamics(&dynamics)
+            .with_seed("Monte Carlo", u128::MAX)
+            .with_config_file(
+                [env!("CARGO_MANIFEST_DIR"), "Cargo.toml"]
+                    .iter()
+                    .collect::<PathBuf>(),
+            )
+            .unwrap();
+
+        let orbit = Orbit::new(
+            6_655.994_2,
+            -40_218.575_1,
+            -82.917_7,
+            3.115_484,
+            0.470_42,
+            -0.001_01,
+            Epoch::from_gregorian_utc_at_midnight(2024, 2, 29),
+            EARTH_J2000.with_mu_km3_s2(398_600.441_5),
+        );
+        let opm = OrbitParameterMessage::new(
+            "PROV".to_string(),
+            Spacecraft::builder().orbit(orbit).build(),
+        );
+
+        let path: PathBuf = [
+            env!("CARGO_MANIFEST_DIR"),
+            "data",
+            "04_output",
+            "provenance.opm",
+        ]
+        .iter()
+        .collect();
+        opm.to_file(
+            &path,
+            ExportCfg::builder().provenance(provenance.clone()).build(),
+        )
+        .unwrap();
+
+        // The provenance is transparent to the reader of the message
+        let read = OrbitParameterMessage::from_file(&path, None).unwrap();
+        assert_eq!(read.object_name, opm.object_name);
+        assert_eq!(read.spacecraft.orbit.epoch, orbit.epoch);
+
+        let read_provenance = Provenance::from_file(&path).unwrap();
+        assert_eq!(read_provenance, provenance);
+        assert_eq!(read_provenance.dynamics, Some(dynamics.to_string()));
+        assert_eq!(dynamics.description(), dynamics.to_string());
+    }
+
+    #[test]
+    fn provenance_in_oem_xml() {
+        let path: PathBuf = [
+            env!("CARGO_MANIFEST_DIR"),
+            "data",
+            "03_tests",
+            "ccsds",
+            "oem",
+            "LEO_10s.oem",
+        ]
+        .iter()
+        .collect();
+
+        // Files which were not exported by Nyx do not have any provenance
+        assert!(Provenance::from_file(&path).is_err());
+
+        let traj = Traj::<Spacecraft>::from_oem_file(path, None).unwrap();
+        let out: PathBuf = [
+            env!("CARGO_MANIFEST_DIR"),
+            "data",
+            "04_output",
+            "provenance_oem.xml",
+        ]
+        .iter()
+        .collect();
+        traj.to_oem_xml_file(&out, ExportCfg::default()).unwrap();
+
+        // The exporter always records its provenance, and the reader ignores it
+        assert_eq!(Provenance::from_file(&out).unwrap(), Provenance::new());
+        let read = Traj::<Spacecraft>::from_oem_file(&out, None).unwrap();
+        assert_eq!(read.states.len(), traj.states.len());
+    }
+}
@@ -0,0 +1,719 @@
+/*
+    Nyx, blazing fast astrodynamics
+    Copyright (C) 2018-onwards Christopher Rabotin <christopher.rabotin@gmail.com>
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published
+    by the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use anise::constants::frames::EARTH_ITRF93;
+use anise::math::interpolation::lagrange_eval;
+use anise::prelude::Orbit;
+use log::{info, warn};
+
+use super::watermark::prj_name_ver;
+use super::ExportCfg;
+use crate::cosmic::Spacecraft;
+use crate::errors::NyxError;
+use crate::md::prelude::Traj;
+use crate::time::{Duration, Epoch, TimeScale, Unit};
+use crate::State;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{read_to_string, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// Clock values of 999999.999999 microseconds or more are flagged as bad or absent.
+const BAD_CLOCK_US: f64 = 999_999.999_999;
+/// Number of positions used to compute the velocities when the file does not provide them.
+const VELOCITY_SAMPLES: usize = 9;
+/// Number of satellites listed on each of the `+` lines of the header.
+const SATS_PER_LINE: usize = 17;
+/// GLONASS time is UTC(SU) plus three hours.
+const GLONASS_UTC_OFFSET_H: i64 = 3;
+
+/// Version of the SP3 format.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Sp3Version {
+    C,
+    #[default]
+    D,
+}
+
+/// Clock record of a satellite in an SP3 file.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Sp3Clock {
+    pub epoch: Epoch,
+    /// Clock offset in seconds
+    pub offset_s: f64,
+    /// Clock rate in seconds per second, only available in the files with velocities
+    pub rate_s_s: Option<f64>,
+}
+
+/// An SP3-c or SP3-d precise orbit file, as distributed by the IGS and the ILRS.
+///
+/// Each satellite is stored in its own trajectory, and its clock is stored separately.
+/// The positions of SP3 files are expressed in an ITRF realization (e.g. IGS14), which are all mapped onto the EARTH_ITRF93 frame of ANISE:
+/// use [`Traj::to_frame`] to convert the trajectories to an inertial frame.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sp3 {
+    pub version: Sp3Version,
+    /// Time scale of the epochs of the file, e.g. GPST for IGS products
+    pub time_scale: TimeScale,
+    /// Whether the epochs of the file are in GLONASS time, i.e. three hours ahead of UTC, in which case the time scale is UTC
+    pub glonass_time: bool,
+    /// Coordinate system of the file, e.g. `IGS14`
+    pub coord_system: String,
+    /// Orbit type, e.g. `FIT` or `HLM`
+    pub orbit_type: String,
+    pub agency: String,
+    /// Trajectory of each satellite ID, e.g. `G01` or `L51`
+    pub trajectories: BTreeMap<String, Traj<Spacecraft>>,
+    /// Clock records of each satellite ID, bad or absent clock values are skipped
+    pub clocks: BTreeMap<String, Vec<Sp3Clock>>,
+}
+
+impl Sp3 {
+    /// Initializes an empty SP3-d file in the provided time scale.
+    pub fn new(time_scale: TimeScale) -> Self {
+        Self {
+            version: Sp3Version::D,
+            time_scale,
+            glonass_time: false,
+            coord_system: "IGS20".to_string(),
+            orbit_type: "FIT".to_string(),
+            agency: "NYX".to_string(),
+            trajectories: BTreeMap::new(),
+            clocks: BTreeMap::new(),
+        }
+    }
+
+    /// Reads an SP3-c or SP3-d file, building one trajectory per satellite from the spacecraft template (or the default spacecraft).
+    ///
+    /// If the file only contains positions, the velocities are computed by differentiating a Lagrange interpolation of the neighboring positions.
+    pub fn from_file<P: AsRef<Path>>(
+        path: P,
+        tpl_option: Option<Spacecraft>,
+    ) -> Result<Self, NyxError> {
+        let contents = read_to_string(path).map_err(|e| NyxError::FileUnreadable {
+            msg: format!("SP3 file opening error: {e}"),
+        })?;
+        let template = tpl_option.unwrap_or_default();
+
+        let mut lines = contents.lines().enumerate();
+        let (_, first_line) = lines.next().ok_or(NyxError::FileUnreadable {
+            msg: "empty SP3 file".to_string(),
+        })?;
+        let version = match first_line.get(..2) {
+            Some("#c") => Sp3Version::C,
+            Some("#d") => Sp3Version::D,
+            _ => {
+                return Err(NyxError::FileUnreadable {
+                    msg: format!("unsupported SP3 version in `{first_line}`"),
+                })
+            }
+        };
+        let column = |line: &str, start: usize, end: usize| -> String {
+            line.get(start..end.min(line.len()))
+                .unwrap_or_default()
+                .trim()
+                .to_string()
+        };
+
+        let mut me = Self::new(TimeScale::GPST);
+        me.version = version;
+        me.coord_system = column(first_line, 46, 51);
+        me.orbit_type = column(first_line, 52, 55);
+        me.agency = column(first_line, 56, 60);
+
+        let mut positions: BTreeMap<String, Vec<(Epoch, [f64; 3])>> = BTreeMap::new();
+        let mut velocities: BTreeMap<String, BTreeMap<Epoch, [f64; 3]>> = BTreeMap::new();
+        let mut time_system_read = false;
+        let mut epoch = None;
+
+        for (lno, line) in lines {
+            let parse_err = |what: &str| NyxError::FileUnreadable {
+                msg: format!("[line: {}] could not parse SP3 {what}: `{line}`", lno + 1),
+            };
+            if line.starts_with("%c") && !time_system_read {
+                // The time system is in the first %c line only
+                let time_system = column(line, 9, 12);
+                me.time_scale = sp3_time_scale(&time_system).ok_or(parse_err("time system"))?;
+                me.glonass_time = time_system == "GLO";
+                time_system_read = true;
+            } else if line.starts_with("EOF") {
+                break;
+            } else if let Some(epoch_str) = line.strip_prefix('*') {
+                epoch = Some(
+                    sp3_epoch(epoch_str, me.time_scale).ok_or(parse_err("epoch"))?
+                        - me.glonass_offset(),
+                );
+            } else if line.starts_with('P') || line.starts_with('V') {
+                let epoch = epoch.ok_or(parse_err("record before any epoch"))?;
+                let sat_id = column(line, 1, 4).replace(' ', "0");
+                let values = [4, 18, 32, 46]
+                    .iter()
+                    .map(|start| column(line, *start, start + 14).parse::<f64>())
+                    .collect::<Result<Vec<f64>, _>>()
+                    .map_err(|_| parse_err("record"))?;
+                let clock_us = values[3];
+                if line.starts_with('P') {
+                    if values[..3].iter().any(|val| *val != 0.0) {
+                        positions
+                            .entry(sat_id.clone())
+                            .or_default()
+                            .push((epoch, [values[0], values[1], values[2]]));
+                    }
+                    if clock_us.abs() < BAD_CLOCK_US {
+                        me.clocks.entry(sat_id).or_default().push(Sp3Clock {
+                            epoch,
+                            offset_s: clock_us * 1e-6,
+                            rate_s_s: None,
+                        });
+                    }
+                } else {
+                    // Velocities are in decimeters per second and clock rates in 1e-4 microseconds per second
+                    velocities.entry(sat_id.clone()).or_default().insert(
+                        epoch,
+                        [values[0] * 1e-4, values[1] * 1e-4, values[2] * 1e-4],
+                    );
+                    if clock_us.abs() < BAD_CLOCK_US {
+                        if let Some(clock) = me
+                            .clocks
+                            .get_mut(&sat_id)
+                            .and_then(|clocks| clocks.last_mut())
+                            .filter(|clock| clock.epoch == epoch)
+                        {
+                            clock.rate_s_s = Some(clock_us * 1e-10);
+                        }
+                    }
+                }
+            }
+        }
+
+        for (sat_id, samples) in positions {
+            let sat_velocities = velocities.remove(&sat_id).unwrap_or_default();
+            if sat_velocities.is_empty() && samples.len() < 2 {
+                warn!("{sat_id} has a single position and no velocity: skipped");
+                continue;
+            }
+            let mut traj = Traj::new();
+            for (i, (epoch, pos_km)) in samples.iter().enumerate() {
+                let vel_km_s = match sat_velocities.get(epoch) {
+                    Some(vel_km_s) => *vel_km_s,
+                    None => lagrange_velocity(&samples, i)?,
+                };
+                traj.states.push(template.with_orbit(Orbit::new(
+                    pos_km[0],
+                    pos_km[1],
+                    pos_km[2],
+                    vel_km_s[0],
+                    vel_km_s[1],
+                    vel_km_s[2],
+                    *epoch,
+                    EARTH_ITRF93,
+                )));
+            }
+            traj.name = Some(sat_id.clone());
+            traj.finalize();
+            me.trajectories.insert(sat_id, traj);
+        }
+
+        Ok(me)
+    }
+
+    /// Writes this SP3 file with positions, velocities and clocks, in the version and time scale of this structure.
+    ///
+    /// The epochs of the file are either all of the epochs of the trajectories, or sampled with the step of the configuration.
+    /// The satellites which are not available at an epoch are written with the bad position and clock flags.
+    /// All of the trajectories must be in the EARTH_ITRF93 frame, cf. [`Traj::to_frame`].
+    pub fn to_file<P: AsRef<Path>>(&self, path: P, cfg: ExportCfg) -> Result<PathBuf, NyxError> {
+        let write_err = |msg: String| NyxError::CustomError { msg };
+        if self
+            .trajectories
+            .values()
+            .all(|traj| traj.states.is_empty())
+        {
+            return Err(write_err(
+                "Cannot export empty trajectories to SP3".to_string(),
+            ));
+        }
+        for (sat_id, traj) in &self.trajectories {
+            if sat_id.len() != 3 {
+                return Err(write_err(format!(
+                    "SP3 satellite IDs must have three characters, got `{sat_id}`"
+                )));
+            }
+            if let Some(state) = traj.states.first() {
+                if state.orbit.frame.ephemeris_id != EARTH_ITRF93.ephemeris_id
+                    || state.orbit.frame.orientation_id != EARTH_ITRF93.orientation_id
+                {
+                    return Err(write_err(format!(
+                        "{sat_id} is in {} but SP3 requires {EARTH_ITRF93}",
+                        state.orbit.frame
+                    )));
+                }
+            }
+        }
+        let time_system = match self.glonass_time {
+            true if self.time_scale == TimeScale::UTC => Some("GLO".to_string()),
+            true => None,
+            false => sp3_time_system(self.time_scale),
+        }
+        .ok_or(write_err(format!(
+            "{} is not an SP3 time system",
+            self.time_scale
+        )))?;
+
+        info!("Exporting trajectories to SP3 file...");
+        let path_buf = cfg.actual_path(path);
+
+        // Build the epochs of the file
+        let trajs = self
+            .trajectories
+            .values()
+            .filter(|traj| !traj.states.is_empty());
+        let start = cfg.start_epoch.unwrap_or_else(|| {
+            trajs
+                .clone()
+                .map(|traj| traj.first().epoch())
+                .min()
+                .unwrap()
+        });
+        let end = cfg
+            .end_epoch
+            .unwrap_or_else(|| trajs.clone().map(|traj| traj.last().epoch()).max().unwrap());
+        let epochs: Vec<Epoch> = match cfg.step {
+            Some(step) => {
+                let mut epochs = Vec::new();
+                let mut epoch = start;
+                while epoch <= end {
+                    epochs.push(epoch);
+                    epoch += step;
+                }
+                epochs
+            }
+            None => trajs
+                .flat_map(|traj| traj.states.iter().map(|state| state.epoch()))
+                .filter(|epoch| (start..=end).contains(epoch))
+                .collect::<BTreeSet<Epoch>>()
+                .into_iter()
+                .collect(),
+        };
+        if epochs.is_empty() {
+            return Err(write_err("No epoch to export to SP3".to_string()));
+        }
+        let interval = if epochs.len() > 1 {
+            epochs[1] - epochs[0]
+        } else {
+            Duration::ZERO
+        };
+
+        let file =
+            File::create(&path_buf).map_err(|e| write_err(format!("SP3 file error: {e}")))?;
+        let mut writer = BufWriter::new(file);
+
+        self.write_sp3(&mut writer, &epochs, interval, &time_system)
+            .map_err(|e| write_err(format!("Could not write SP3: {e}")))?;
+
+        info!("SP3 written to {}", path_buf.display());
+        Ok(path_buf)
+    }
+
+    fn write_sp3<W: Write>(
+        &self,
+        writer: &mut W,
+        epochs: &[Epoch],
+        interval: Duration,
+        time_system: &str,
+    ) -> std::io::Result<()> {
+        let version = match self.version {
+            Sp3Version::C => 'c',
+            Sp3Version::D => 'd',
+        };
+        let sat_ids: Vec<&String> = self.trajectories.keys().collect();
+        let offset = self.glonass_offset();
+
+        let (gps_week, gps_ns) = epochs[0].to_time_scale(TimeScale::GPST).to_time_of_week();
+        let (y, m, d, hh, mm, ss, ns) = (epochs[0] + offset).to_gregorian(self.time_scale);
+        let mjd = Epoch::from_gregorian_at_midnight(y, m, d, TimeScale::TAI).to_mjd_tai_days();
+        let day_frac =
+            (f64::from(hh) * 3600.0 + f64::from(mm) * 60.0 + f64::from(ss) + f64::from(ns) * 1e-9)
+                / 86_400.0;
+
+        writeln!(
+            writer,
+            "#{version}V{} {:7} ORBIT {:>5} {:>3} {:>4}",
+            sp3_epoch_str(epochs[0] + offset, self.time_scale),
+            epochs.len(),
+            self.coord_system,
+            self.orbit_type,
+            self.agency
+        )?;
+        writeln!(
+            writer,
+            "## {gps_week:4} {:15.8} {:14.8} {:5} {day_frac:15.13}",
+            gps_ns as f64 * 1e-9,
+            interval.to_seconds(),
+            mjd.round() as i64,
+        )?;
+
+        // Satellite list and accuracy lines, at least five of each
+        let num_lines = sat_ids.len().div_ceil(SATS_PER_LINE).max(5);
+        for line in 0..num_lines {
+            let ids: String = (0..SATS_PER_LINE)
+                .map(|i| match sat_ids.get(line * SATS_PER_LINE + i) {
+                    Some(sat_id) => sat_id.to_string(),
+                    None => "  0".to_string(),
+                })
+                .collect();
+            if line == 0 {
+                writeln!(writer, "+  {:3}   {ids}", sat_ids.len())?;
+            } else {
+                writeln!(writer, "+        {ids}")?;
+            }
+        }
+        for _ in 0..num_lines {
+            writeln!(writer, "++       {}", "  0".repeat(SATS_PER_LINE))?;
+        }
+
+        let constellations: BTreeSet<char> = sat_ids
+            .iter()
+            .filter_map(|sat_id| sat_id.chars().next())
+            .collect();
+        let file_type = match constellations.len() {
+            1 => *constellations.first().unwrap(),
+            _ => 'M',
+        };
+        writeln!(
+            writer,
+            "%c {file_type}  cc {time_system:3} ccc cccc cccc cccc cccc ccccc ccccc ccccc ccccc"
+        )?;
+        writeln!(
+            writer,
+            "%c cc cc ccc ccc cccc cccc cccc cccc ccccc ccccc ccccc ccccc"
+        )?;
+        writeln!(
+            writer,
+            "%f  1.2500000  1.025000000  0.00000000000  0.000000000000000"
+        )?;
+        writeln!(
+            writer,
+            "%f  0.0000000  0.000000000  0.00000000000  0.000000000000000"
+        )?;
+        for _ in 0..2 {
+            writeln!(
+                writer,
+                "%i    0    0    0    0      0      0      0      0         0"
+            )?;
+        }
+        writeln!(
+            writer,
+            "/* Built by {} -- https://nyxspace.com/",
+            prj_name_ver()
+        )?;
+        writeln!(
+            writer,
+            "/* Nyx Space provided under the AGPL v3 open source license"
+        )?;
+        writeln!(writer, "/*")?;
+        writeln!(writer, "/*")?;
+
+        for epoch in epochs {
+            writeln!(
+                writer,
+                "*  {}",
+                sp3_epoch_str(*epoch + offset, self.time_scale)
+            )?;
+            for (sat_id, traj) in &self.trajectories {
+                let clock = self.clocks.get(sat_id).and_then(|clocks| {
+                    clocks
+                        .binary_search_by(|clock| clock.epoch.cmp(epoch))
+                        .ok()
+                        .map(|idx| clocks[idx])
+                });
+                let (clock_us, rate) = match clock {
+                    Some(clock) => (
+                        clock.offset_s * 1e6,
+                        clock.rate_s_s.map_or(BAD_CLOCK_US, |rate| rate * 1e10),
+                    ),
+                    None => (BAD_CLOCK_US, BAD_CLOCK_US),
+                };
+                match traj.at(*epoch) {
+                    Ok(state) => {
+                        let orbit = state.orbit;
+                        writeln!(
+                            writer,
+                            "P{sat_id}{:14.6}{:14.6}{:14.6}{clock_us:14.6}",
+                            orbit.radius_km.x, orbit.radius_km.y, orbit.radius_km.z
+                        )?;
+                        // Each velocity record directly follows the position record of the same satellite.
+                        writeln!(
+                            writer,
+                            "V{sat_id}{:14.6}{:14.6}{:14.6}{rate:14.6}",
+                            orbit.velocity_km_s.x * 1e4,
+                            orbit.velocity_km_s.y * 1e4,
+                            orbit.velocity_km_s.z * 1e4
+                        )?;
+                    }
+                    Err(_) => {
+                        writeln!(
+                            writer,
+                            "P{sat_id}{:14.6}{:14.6}{:14.6}{BAD_CLOCK_US:14.6}",
+                            0.0, 0.0, 0.0
+                        )?;
+                        writeln!(
+                            writer,
+                            "V{sat_id}{:14.6}{:14.6}{:14.6}{BAD_CLOCK_US:14.6}",
+                            0.0, 0.0, 0.0
+                        )?;
+                    }
+                }
+            }
+        }
+        writeln!(writer, "EOF")
+    }
+
+    /// Offset between the epochs written in the file and the time scale of the file.
+    fn glonass_offset(&self) -> Duration {
+        if self.glonass_time {
+            Unit::Hour * GLONASS_UTC_OFFSET_H
+        } else {
+            Duration::ZERO
+        }
+    }
+}
+
+/// Time scale of an SP3 time system, where GLONASS time is UTC: its three hours offset is applied separately.
+fn sp3_time_scale(time_system: &str) -> Option<TimeScale> {
+    match time_system {
+        "GPS" | "ccc" => Some(TimeScale::GPST),
+        "GLO" | "UTC" => Some(TimeScale::UTC),
+        "GAL" => Some(TimeScale::GST),
+        "BDT" => Some(TimeScale::BDT),
+        "QZS" => Some(TimeScale::QZSST),
+        "TAI" => Some(TimeScale::TAI),
+        _ => None,
+    }
+}
+
+/// SP3 time system of a time scale, if supported.
+fn sp3_time_system(time_scale: TimeScale) -> Option<String> {
+    match time_scale {
+        TimeScale::GPST => Some("GPS"),
+        TimeScale::UTC => Some("UTC"),
+        TimeScale::GST => Some("GAL"),
+        TimeScale::BDT => Some("BDT"),
+        TimeScale::QZSST => Some("QZS"),
+        TimeScale::TAI => Some("TAI"),
+        _ => None,
+    }
+    .map(str::to_string)
+}
+
+/// Parses the `yyyy mm dd hh mm ss.ssssssss` epoch of an SP3 file.
+fn sp3_epoch(epoch_str: &str, time_scale: TimeScale) -> Option<Epoch> {
+    let tokens: Vec<&str> = epoch_str.split_whitespace().collect();
+    if tokens.len() != 6 {
+        return None;
+    }
+    let (seconds, fraction) = tokens[5].split_once('.').unwrap_or((tokens[5], "0"));
+    // Keep the nanosecond digits of the fraction
+    let nanos = format!("{:0<9}", &fraction[..fraction.len().min(9)]);
+    Epoch::maybe_from_gregorian(
+        tokens[0].parse().ok()?,
+        tokens[1].parse().ok()?,
+        tokens[2].parse().ok()?,
+        tokens[3].parse().ok()?,
+        tokens[4].parse().ok()?,
+        seconds.parse().ok()?,
+        nanos.parse().ok()?,
+        time_scale,
+    )
+    .ok()
+}
+
+/// Formats an epoch as `yyyy mm dd hh mm ss.ssssssss` in the time scale of the SP3 file.
+fn sp3_epoch_str(epoch: Epoch, time_scale: TimeScale) -> String {
+    let (y, m, d, hh, mm, ss, ns) = epoch.to_gregorian(time_scale);
+    format!("{y:4} {m:2} {d:2} {hh:2} {mm:2} {ss:2}.{:08}", ns / 10)
+}
+
+/// Computes the velocity at the i-th position by differentiating the Lagrange interpolation of the neighboring positions.
+fn lagrange_velocity(samples: &[(Epoch, [f64; 3])], i: usize) -> Result<[f64; 3], NyxError> {
+    let first = i
+        .saturating_sub(VELOCITY_SAMPLES / 2)
+        .min(samples.len().saturating_sub(VELOCITY_SAMPLES));
+    let window = &samples[first..(first + VELOCITY_SAMPLES).min(samples.len())];
+    let ref_epoch = samples[i].0;
+    let times: Vec<f64> = window
+        .iter()
+        .map(|(epoch, _)| (*epoch - ref_epoch).to_unit(Unit::Second))
+        .collect();
+    let mut vel_km_s = [0.0; 3];
+    for (axis, vel) in vel_km_s.iter_mut().enumerate() {
+        let values: Vec<f64> = window.iter().map(|(_, pos_km)| pos_km[axis]).collect();
+        *vel = lagrange_eval(&times, &values, 0.0)
+            .map_err(|e| NyxError::InvalidInterpolationData {
+                msg: format!("SP3 velocity at {ref_epoch}: {e}"),
+            })?
+            .1;
+    }
+    Ok(vel_km_s)
+}
+
+#[cfg(test)]
+mod ut_sp3 {
+    use super::{Sp3, Sp3Clock, Sp3Version};
+    use crate::io::ExportCfg;
+    use crate::time::{Epoch, TimeScale, Unit};
+    use crate::State;
+    use std::fs::read_to_string;
+    use std::path::PathBuf;
+
+    #[test]
+    fn sp3c_positions() {
+        let path: PathBuf = [
+            env!("CARGO_MANIFEST_DIR"),
+            "data",
+            "03_tests",
+            "sp3",
+            "circular.sp3",
+        ]
+        .iter()
+        .collect();
+
+        let sp3 = Sp3::from_file(path, None).unwrap();
+        assert_eq!(sp3.version, Sp3Version::C);
+        assert_eq!(sp3.time_scale, TimeScale::GPST);
+        assert_eq!(sp3.coord_system, "IGS14");
+        assert_eq!(sp3.orbit_type, "FIT");
+        assert_eq!(sp3.agency, "NYX");
+        assert_eq!(sp3.trajectories.len(), 2);
+
+        let start = Epoch::from_gregorian_at_midnight(2023, 1, 1, TimeScale::GPST);
+        let g01 = &sp3.trajectories["G01"];
+        assert_eq!(g01.states.len(), 3);
+        assert_eq!(g01.first().epoch(), start);
+        assert_eq!(g01.last().epoch(), start + Unit::Minute * 30);
+        assert_eq!(g01.name.as_deref(), Some("G01"));
+        assert!((g01.first().orbit.radius_km.x - 26_560.0).abs() < 1e-6);
+        // The velocities are computed from the positions: circular orbits of 3.874 km/s, only approximated by a quadratic with three positions
+        for state in &g01.states {
+            assert!((state.orbit.vmag_km_s() - 3.874).abs() < 3e-2);
+        }
+
+        // The bad clock of G02 is skipped
+        assert_eq!(sp3.clocks["G01"].len(), 3);
+        assert_eq!(sp3.clocks["G02"].len(), 2);
+        let clock: Sp3Clock = sp3.clocks["G01"][1];
+        assert_eq!(clock.epoch, start + Unit::Minute * 15);
+        assert!((clock.offset_s - 12.500125e-6).abs() < 1e-18);
+        assert_eq!(clock.rate_s_s, None);
+    }
+
+    #[test]
+    fn sp3d_round_trip() {
+        let path: PathBuf = [
+            env!("CARGO_MANIFEST_DIR"),
+            "data",
+            "03_tests",
+            "sp3",
+            "circular.sp3",
+        ]
+        .iter()
+        .collect();
+
+        let mut sp3 = Sp3::from_file(path, None).unwrap();
+        sp3.version = Sp3Version::D;
+        sp3.clocks.get_mut("G01").unwrap()[0].rate_s_s = Some(1.5e-10);
+
+        let out: PathBuf = [env!("CARGO_MANIFEST_DIR"), "data", "04_output", "sp3d.sp3"]
+            .iter()
+            .collect();
+        let out = sp3.to_file(out, ExportCfg::default()).unwrap();
+
+        let reloaded = Sp3::from_file(out, None).unwrap();
+        assert_eq!(reloaded.version, Sp3Version::D);
+        assert_eq!(reloaded.time_scale, TimeScale::GPST);
+        for (sat_id, clocks) in &sp3.clocks {
+            for (clock, other) in clocks.iter().zip(&reloaded.clocks[sat_id]) {
+                assert_eq!(clock.epoch, other.epoch);
+                assert!((clock.offset_s - other.offset_s).abs() < 1e-18);
+            }
+        }
+        let rate = reloaded.clocks["G01"][0].rate_s_s.unwrap();
+        assert!((rate - 1.5e-10).abs() < 1e-20);
+        assert_eq!(reloaded.clocks["G01"][1].rate_s_s, None);
+        for (sat_id, traj) in &sp3.trajectories {
+            let other = &reloaded.trajectories[sat_id];
+            assert_eq!(other.states.len(), traj.states.len());
+            for (state, other) in traj.states.iter().zip(&other.states) {
+                assert_eq!(state.epoch(), other.epoch());
+                assert!((state.orbit.radius_km - other.orbit.radius_km).norm() < 1e-6);
+                assert!((state.orbit.velocity_km_s - other.orbit.velocity_km_s).norm() < 1e-9);
+            }
+        }
+
+        // Export in UTC, which shifts the epochs by the GPS leap seconds
+        sp3.time_scale = TimeScale::UTC;
+        let out: PathBuf = [
+            env!("CARGO_MANIFEST_DIR"),
+            "data",
+            "04_output",
+            "sp3d_utc.sp3",
+        ]
+        .iter()
+        .collect();
+        let out = sp3.to_file(out, ExportCfg::default()).unwrap();
+        let reloaded = Sp3::from_file(&out, None).unwrap();
+        assert_eq!(reloaded.time_scale, TimeScale::UTC);
+        assert_eq!(
+            reloaded.trajectories["G01"].first().epoch(),
+            sp3.trajectories["G01"].first().epoch()
+        );
+
+        // Each velocity record follows the position record of its satellite.
+        let contents = read_to_string(&out).unwrap();
+        let records: Vec<&str> = contents
+            .lines()
+            .filter(|line| line.starts_with('P') || line.starts_with('V'))
+            .collect();
+        for pair in records.chunks(2) {
+            assert!(pair[0].starts_with('P') && pair[1].starts_with('V'));
+            assert_eq!(pair[0][1..4], pair[1][1..4]);
+        }
+
+        // GLONASS time is three hours ahead of UTC
+        sp3.glonass_time = true;
+        let out: PathBuf = [
+            env!("CARGO_MANIFEST_DIR"),
+            "data",
+            "04_output",
+            "sp3d_glo.sp3",
+        ]
+        .iter()
+        .collect();
+        let out = sp3.to_file(out, ExportCfg::default()).unwrap();
+        let contents = read_to_string(&out).unwrap();
+        assert!(contents.contains("%c G  cc GLO"));
+        assert!(contents.contains("*  2023  1  1  2 59 42.00000000"));
+        let reloaded = Sp3::from_file(out, None).unwrap();
+        assert!(reloaded.glonass_time);
+        assert_eq!(reloaded.time_scale, TimeScale::UTC);
+        assert_eq!(
+            reloaded.trajectories["G01"].first().epoch(),
+            sp3.trajectories["G01"].first().epoch()
+        );
+    }
+}
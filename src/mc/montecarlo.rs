@@ -18,6 +18,7 @@
 
 use super::Pcg64Mcg;
 use crate::dynamics::Dynamics;
+use crate::io::Provenance;
 use crate::linalg::allocator::Allocator;
 use crate::linalg::DefaultAllocator;
 use crate::mc::results::{PropResult, Results, Run};
@@ -133,7 +134,13 @@ where
         <DefaultAllocator as Allocator<<D::StateType as State>::VecLength>>::Buffer<f64>: Send,
     {
         // Generate the initial states
-        let init_states = self.generate_states(skip, num_runs, self.seed);
+        // Draw the seed if unset, such that it is recorded in the provenance of the results
+        let seed = self.seed.unwrap_or_else(rand::random);
+        let init_states = self.generate_states(skip, num_runs, Some(seed));
+        let provenance = Provenance::new()
+            .with_almanac(&almanac)
+            .with_propagator(&prop)
+            .with_seed("Monte Carlo", seed);
         // Setup the progress bar
         let pb = self.progress_bar(num_runs);
         // Setup the thread friendly communication
@@ -182,6 +189,7 @@ where
         Results {
             runs,
             scenario: self.scenario.clone(),
+            provenance: Some(provenance),
         }
     }
 
@@ -226,7 +234,13 @@ where
         <DefaultAllocator as Allocator<<D::StateType as State>::VecLength>>::Buffer<f64>: Send,
     {
         // Generate the initial states
-        let init_states = self.generate_states(skip, num_runs, self.seed);
+        // Draw the seed if unset, such that it is recorded in the provenance of the results
+        let seed = self.seed.unwrap_or_else(rand::random);
+        let init_states = self.generate_states(skip, num_runs, Some(seed));
+        let provenance = Provenance::new()
+            .with_almanac(&almanac)
+            .with_propagator(&prop)
+            .with_seed("Monte Carlo", seed);
         // Setup the progress bar
         let pb = self.progress_bar(num_runs);
         // Setup the thread friendly communication
@@ -274,6 +288,7 @@ where
         Results {
             runs,
             scenario: self.scenario.clone(),
+            provenance: Some(provenance),
         }
     }
 
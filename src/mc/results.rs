@@ -25,6 +25,7 @@ use std::sync::Arc;
 
 use crate::errors::{MonteCarloError, NoSuccessfulRunsSnafu, StateError};
 use crate::io::watermark::pq_writer;
+use crate::io::Provenance;
 use crate::io::{ExportCfg, InputOutputError};
 use crate::linalg::allocator::Allocator;
 use crate::linalg::DefaultAllocator;
@@ -69,6 +70,8 @@ where
     pub runs: Vec<Run<S, R>>,
     /// Name of this scenario
     pub scenario: String,
+    /// Inputs of this Monte Carlo, embedded in the exported files
+    pub provenance: Option<Provenance>,
 }
 
 /// A structure that stores the result of a propagation segment of a Monte Carlo.
@@ -261,7 +264,7 @@ where
 
         // Use the first successful run to build up some data shared for all
         let mut frame = EARTH_J2000;
-        let mut fields = match cfg.fields {
+        let mut fields = match cfg.fields.clone() {
             Some(fields) => fields,
             None => S::export_params(),
         };
@@ -401,11 +404,7 @@ where
             "Purpose".to_string(),
             "Monte Carlo Trajectory data".to_string(),
         );
-        if let Some(add_meta) = cfg.metadata {
-            for (k, v) in add_meta {
-                metadata.insert(k, v);
-            }
-        }
+        metadata.extend(cfg.all_metadata(self.provenance.clone(), Some(almanac.as_ref()))?);
 
         let props = pq_writer(Some(metadata));
 
@@ -0,0 +1,399 @@
+/*
+    Nyx, blazing fast astrodynamics
+    Copyright (C) 2018-onwards Christopher Rabotin <christopher.rabotin@gmail.com>
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published
+    by the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use super::details::{EventArc, EventDetails, EventEdge};
+use super::EventEvaluator;
+use crate::errors::EventError;
+use crate::linalg::allocator::Allocator;
+use crate::linalg::DefaultAllocator;
+use crate::md::prelude::{Interpolatable, Traj};
+use crate::time::{Duration, Epoch};
+use anise::almanac::Almanac;
+use log::info;
+use std::fmt;
+use std::sync::Arc;
+
+/// A boolean combination of events, used to search for the arcs where the combination holds.
+///
+/// An event "holds" when its evaluation is positive, i.e. between the rising and falling edges found by [`Traj::find_arcs`].
+/// For example, "in view of DSS-65 and not in umbra" is `EventCombination::event(Arc::new(dss65)).and(EventCombination::event(Arc::new(umbra)).not())`.
+///
+/// # Note
+/// All of the events are evaluated on the same trajectory, so they must all support the frame of that trajectory.
+/// For example, a ground station elevation event requires the trajectory to be in the body fixed frame of that station.
+#[derive(Clone)]
+pub enum EventCombination<S: Interpolatable>
+where
+    DefaultAllocator: Allocator<S::VecLength> + Allocator<S::Size> + Allocator<S::Size, S::Size>,
+{
+    /// A single event
+    Event(Arc<dyn EventEvaluator<S>>),
+    /// Holds when all of the combinations hold
+    And(Vec<EventCombination<S>>),
+    /// Holds when any of the combinations holds
+    Or(Vec<EventCombination<S>>),
+    /// Holds when the combination does not hold
+    Not(Box<EventCombination<S>>),
+}
+
+impl<S: Interpolatable> EventCombination<S>
+where
+    DefaultAllocator: Allocator<S::VecLength> + Allocator<S::Size> + Allocator<S::Size, S::Size>,
+{
+    /// Initializes a combination from a single event
+    pub fn event(event: Arc<dyn EventEvaluator<S>>) -> Self {
+        Self::Event(event)
+    }
+
+    /// Returns the combination which holds when both this and the other combination hold
+    pub fn and(self, other: Self) -> Self {
+        match self {
+            Self::And(mut combinations) => {
+                combinations.push(other);
+                Self::And(combinations)
+            }
+            _ => Self::And(vec![self, other]),
+        }
+    }
+
+    /// Returns the combination which holds when either this or the other combination holds
+    pub fn or(self, other: Self) -> Self {
+        match self {
+            Self::Or(mut combinations) => {
+                combinations.push(other);
+                Self::Or(combinations)
+            }
+            _ => Self::Or(vec![self, other]),
+        }
+    }
+
+    /// Returns the negation of this combination
+    #[allow(clippy::should_implement_trait)]
+    pub fn not(self) -> Self {
+        match self {
+            Self::Not(combination) => *combination,
+            _ => Self::Not(Box::new(self)),
+        }
+    }
+
+    /// Finds the arcs of the provided trajectory where this combination holds.
+    ///
+    /// The arcs of each event are found with [`Traj::find_arcs`], and then intersected (AND), merged (OR) or complemented
+    /// over the span of the trajectory (NOT). The rise and fall details of each resulting arc are those of the event which bounds it.
+    /// The details of a bound created by a negation are those of the negated event with its edge flipped and its values negated.
+    pub fn find_arcs(
+        &self,
+        traj: &Traj<S>,
+        heuristic: Option<Duration>,
+        almanac: Arc<Almanac>,
+    ) -> Result<Vec<EventArc<S>>, EventError> {
+        match self {
+            Self::Event(event) => match traj.find_arcs(event.as_ref(), heuristic, almanac) {
+                Ok(arcs) => Ok(arcs),
+                // The event never holds
+                Err(EventError::NotFound { .. }) => Ok(Vec::new()),
+                Err(e) => Err(e),
+            },
+            Self::And(combinations) => {
+                let mut arcs: Option<Vec<EventArc<S>>> = None;
+                for combination in combinations {
+                    let these_arcs = combination.find_arcs(traj, heuristic, almanac.clone())?;
+                    arcs = Some(match arcs {
+                        None => merge(these_arcs),
+                        Some(prev_arcs) => intersect(&prev_arcs, &merge(these_arcs)),
+                    });
+                }
+                Ok(arcs.unwrap_or_default())
+            }
+            Self::Or(combinations) => {
+                let mut arcs = Vec::new();
+                for combination in combinations {
+                    arcs.extend(combination.find_arcs(traj, heuristic, almanac.clone())?);
+                }
+                Ok(merge(arcs))
+            }
+            Self::Not(combination) => {
+                let arcs = merge(combination.find_arcs(traj, heuristic, almanac)?);
+                Ok(complement(&arcs, traj, format!("{self}")))
+            }
+        }
+    }
+}
+
+impl<S: Interpolatable> Traj<S>
+where
+    DefaultAllocator: Allocator<S::VecLength> + Allocator<S::Size> + Allocator<S::Size, S::Size>,
+{
+    /// Finds the arcs where the provided combination of events holds, cf. [`EventCombination::find_arcs`].
+    pub fn find_combined_arcs(
+        &self,
+        combination: &EventCombination<S>,
+        heuristic: Option<Duration>,
+        almanac: Arc<Almanac>,
+    ) -> Result<Vec<EventArc<S>>, EventError> {
+        let arcs = combination.find_arcs(self, heuristic, almanac)?;
+        info!("Found {} arcs of {combination}", arcs.len());
+        Ok(arcs)
+    }
+}
+
+impl<S: Interpolatable> From<Arc<dyn EventEvaluator<S>>> for EventCombination<S>
+where
+    DefaultAllocator: Allocator<S::VecLength> + Allocator<S::Size> + Allocator<S::Size, S::Size>,
+{
+    fn from(event: Arc<dyn EventEvaluator<S>>) -> Self {
+        Self::Event(event)
+    }
+}
+
+impl<S: Interpolatable> fmt::Display for EventCombination<S>
+where
+    DefaultAllocator: Allocator<S::VecLength> + Allocator<S::Size> + Allocator<S::Size, S::Size>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let join = |combinations: &[EventCombination<S>], op: &str| {
+            combinations
+                .iter()
+                .map(|c| format!("{c}"))
+                .collect::<Vec<String>>()
+                .join(op)
+        };
+        match self {
+            Self::Event(event) => write!(f, "{event}"),
+            Self::And(combinations) => write!(f, "({})", join(combinations, " AND ")),
+            Self::Or(combinations) => write!(f, "({})", join(combinations, " OR ")),
+            Self::Not(combination) => write!(f, "NOT {combination}"),
+        }
+    }
+}
+
+fn rise_epoch<S: Interpolatable>(arc: &EventArc<S>) -> Epoch
+where
+    DefaultAllocator: Allocator<S::VecLength> + Allocator<S::Size> + Allocator<S::Size, S::Size>,
+{
+    arc.rise.state.epoch()
+}
+
+fn fall_epoch<S: Interpolatable>(arc: &EventArc<S>) -> Epoch
+where
+    DefaultAllocator: Allocator<S::VecLength> + Allocator<S::Size> + Allocator<S::Size, S::Size>,
+{
+    arc.fall.state.epoch()
+}
+
+/// Sorts the arcs and merges those which overlap or touch.
+pub(crate) fn merge<S: Interpolatable>(mut arcs: Vec<EventArc<S>>) -> Vec<EventArc<S>>
+where
+    DefaultAllocator: Allocator<S::VecLength> + Allocator<S::Size> + Allocator<S::Size, S::Size>,
+{
+    arcs.sort_by_key(|arc| rise_epoch(arc));
+
+    let mut merged: Vec<EventArc<S>> = Vec::with_capacity(arcs.len());
+    for arc in arcs {
+        match merged.last_mut() {
+            Some(prev) if rise_epoch(&arc) <= fall_epoch(prev) => {
+                if fall_epoch(&arc) > fall_epoch(prev) {
+                    prev.fall = arc.fall;
+                }
+            }
+            _ => merged.push(arc),
+        }
+    }
+    merged
+}
+
+/// Intersects two sets of sorted and disjoint arcs.
+pub(crate) fn intersect<S: Interpolatable>(
+    arcs_a: &[EventArc<S>],
+    arcs_b: &[EventArc<S>],
+) -> Vec<EventArc<S>>
+where
+    DefaultAllocator: Allocator<S::VecLength> + Allocator<S::Size> + Allocator<S::Size, S::Size>,
+{
+    let mut intersection = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < arcs_a.len() && j < arcs_b.len() {
+        let (a, b) = (&arcs_a[i], &arcs_b[j]);
+        let rise = if rise_epoch(a) >= rise_epoch(b) {
+            &a.rise
+        } else {
+            &b.rise
+        };
+        let fall = if fall_epoch(a) <= fall_epoch(b) {
+            &a.fall
+        } else {
+            &b.fall
+        };
+
+        if rise.state.epoch() < fall.state.epoch() {
+            intersection.push(EventArc {
+                rise: rise.clone(),
+                fall: fall.clone(),
+            });
+        }
+
+        // Move past whichever arc ends first
+        if fall_epoch(a) <= fall_epoch(b) {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    intersection
+}
+
+/// Complements a set of sorted and disjoint arcs over the span of the trajectory.
+pub(crate) fn complement<S: Interpolatable>(
+    arcs: &[EventArc<S>],
+    traj: &Traj<S>,
+    repr: String,
+) -> Vec<EventArc<S>>
+where
+    DefaultAllocator: Allocator<S::VecLength> + Allocator<S::Size> + Allocator<S::Size, S::Size>,
+{
+    let mut complement = Vec::with_capacity(arcs.len() + 1);
+
+    let mut rise = boundary(*traj.first(), EventEdge::Rising, &repr);
+    for arc in arcs {
+        if rise.state.epoch() < rise_epoch(arc) {
+            complement.push(EventArc {
+                rise,
+                fall: negate(&arc.rise),
+            });
+        }
+        rise = negate(&arc.fall);
+    }
+
+    if rise.state.epoch() < traj.last().epoch() {
+        complement.push(EventArc {
+            rise,
+            fall: boundary(*traj.last(), EventEdge::Falling, &repr),
+        });
+    }
+
+    complement
+}
+
+/// Returns the details of the negation of an event: the edge is flipped and the values are negated.
+fn negate<S: Interpolatable>(details: &EventDetails<S>) -> EventDetails<S>
+where
+    DefaultAllocator: Allocator<S::VecLength> + Allocator<S::Size> + Allocator<S::Size, S::Size>,
+{
+    EventDetails {
+        state: details.state,
+        edge: match details.edge {
+            EventEdge::Rising => EventEdge::Falling,
+            EventEdge::Falling => EventEdge::Rising,
+            EventEdge::Unclear => EventEdge::Unclear,
+        },
+        value: -details.value,
+        prev_value: details.prev_value.map(|v| -v),
+        next_value: details.next_value.map(|v| -v),
+        pm_duration: details.pm_duration,
+        repr: format!("NOT {}", details.repr),
+    }
+}
+
+/// Returns the details of the bound of an arc set by the start or end of the trajectory, where the combination is not evaluated.
+fn boundary<S: Interpolatable>(state: S, edge: EventEdge, repr: &str) -> EventDetails<S>
+where
+    DefaultAllocator: Allocator<S::VecLength> + Allocator<S::Size> + Allocator<S::Size, S::Size>,
+{
+    EventDetails {
+        state,
+        edge,
+        value: f64::NAN,
+        prev_value: None,
+        next_value: None,
+        pm_duration: Duration::ZERO,
+        repr: format!("{repr} at trajectory bound"),
+    }
+}
+
+#[cfg(test)]
+mod ut_combination {
+    use super::{complement, intersect, merge};
+    use crate::md::prelude::Traj;
+    use crate::md::{EventArc, EventDetails, EventEdge};
+    use crate::time::{Epoch, Unit};
+    use crate::{Orbit, Spacecraft, State};
+    use anise::constants::frames::EARTH_J2000;
+
+    fn details(epoch: Epoch, edge: EventEdge) -> EventDetails<Spacecraft> {
+        let orbit = Orbit::cartesian(7000.0, 0.0, 0.0, 0.0, 7.5, 0.0, epoch, EARTH_J2000);
+        EventDetails {
+            state: Spacecraft::from(orbit),
+            edge,
+            value: 0.0,
+            prev_value: None,
+            next_value: None,
+            pm_duration: Unit::Second * 1,
+            repr: format!("{edge:?}"),
+        }
+    }
+
+    fn arc(e0: Epoch, rise_h: i64, fall_h: i64) -> EventArc<Spacecraft> {
+        EventArc {
+            rise: details(e0 + Unit::Hour * rise_h, EventEdge::Rising),
+            fall: details(e0 + Unit::Hour * fall_h, EventEdge::Falling),
+        }
+    }
+
+    fn hours(e0: Epoch, arcs: &[EventArc<Spacecraft>]) -> Vec<(f64, f64)> {
+        arcs.iter()
+            .map(|arc| {
+                (
+                    (arc.rise.state.epoch() - e0).to_unit(Unit::Hour),
+                    (arc.fall.state.epoch() - e0).to_unit(Unit::Hour),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn arc_algebra() {
+        let e0 = Epoch::from_gregorian_utc_at_midnight(2024, 1, 1);
+
+        let merged = merge(vec![arc(e0, 5, 8), arc(e0, 1, 3), arc(e0, 2, 4)]);
+        assert_eq!(hours(e0, &merged), vec![(1.0, 4.0), (5.0, 8.0)]);
+
+        let other = merge(vec![arc(e0, 0, 2), arc(e0, 3, 6), arc(e0, 7, 9)]);
+        let inter = intersect(&merged, &other);
+        assert_eq!(
+            hours(e0, &inter),
+            vec![(1.0, 2.0), (3.0, 4.0), (5.0, 6.0), (7.0, 8.0)]
+        );
+
+        let mut traj = Traj::new();
+        traj.states = vec![
+            details(e0, EventEdge::Rising).state,
+            details(e0 + Unit::Hour * 10, EventEdge::Rising).state,
+        ];
+        traj.finalize();
+
+        let not = complement(&merged, &traj, "test".to_string());
+        assert_eq!(hours(e0, &not), vec![(0.0, 1.0), (4.0, 5.0), (8.0, 10.0)]);
+        // The rise of a negated arc is the fall of the original one
+        assert_eq!(not[1].rise.edge, EventEdge::Rising);
+        assert_eq!(not[1].fall.edge, EventEdge::Falling);
+
+        // The complement of nothing is the whole trajectory
+        let not = complement(&[], &traj, "test".to_string());
+        assert_eq!(hours(e0, &not), vec![(0.0, 10.0)]);
+    }
+}
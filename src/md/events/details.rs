@@ -84,7 +84,7 @@ where
     /// - `Ok(EventDetails<S>)` if the state at the given epoch can be determined and the event details are successfully evaluated.
     /// - `Err(EventError)` if there is an error in retrieving the state at the specified epoch.
     ///
-    pub fn new<E: EventEvaluator<S>>(
+    pub fn new<E: EventEvaluator<S> + ?Sized>(
         state: S,
         value: f64,
         event: &E,
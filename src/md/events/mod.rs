@@ -16,10 +16,13 @@
     along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
+pub mod combination;
 pub mod details;
 use log::warn;
 pub mod evaluators;
+pub mod relative;
 pub mod search;
+pub mod stop_cond;
 use super::StateParameter;
 use crate::errors::EventError;
 use crate::linalg::allocator::Allocator;
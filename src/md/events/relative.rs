@@ -0,0 +1,359 @@
+/*
+    Nyx, blazing fast astrodynamics
+    Copyright (C) 2018-onwards Christopher Rabotin <christopher.rabotin@gmail.com>
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published
+    by the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use anise::prelude::{Almanac, Frame};
+use hifitime::{Duration, Epoch, Unit};
+use snafu::ResultExt;
+use std::fmt;
+use std::sync::Arc;
+
+use super::EventEvaluator;
+use crate::errors::{EventAlmanacSnafu, EventError, EventPhysicsSnafu, EventTrajSnafu};
+use crate::linalg::Vector3;
+use crate::md::prelude::Traj;
+use crate::{Spacecraft, State};
+
+/// The second object of a relative event: either an ephemeris body or another spacecraft trajectory.
+#[derive(Clone)]
+pub enum EventTarget {
+    /// An ephemeris body (e.g. the Sun, the Earth or the Moon), whose position is computed by the Almanac
+    Body(Frame),
+    /// Another spacecraft, whose position is interpolated from its trajectory
+    Spacecraft(Box<Traj<Spacecraft>>),
+}
+
+impl EventTarget {
+    /// Returns the position of this target at the provided epoch, in the provided frame.
+    pub fn position_km(
+        &self,
+        epoch: Epoch,
+        frame: Frame,
+        almanac: Arc<Almanac>,
+    ) -> Result<Vector3<f64>, EventError> {
+        match self {
+            Self::Body(body) => Ok(almanac
+                .transform(*body, frame, epoch, None)
+                .context(EventAlmanacSnafu)?
+                .radius_km),
+            Self::Spacecraft(traj) => {
+                let state = traj.at(epoch).context(EventTrajSnafu)?;
+                if state.orbit.frame.ephem_origin_match(frame)
+                    && state.orbit.frame.orient_origin_match(frame)
+                {
+                    Ok(state.orbit.radius_km)
+                } else {
+                    Ok(almanac
+                        .transform_to(state.orbit, frame, None)
+                        .context(EventAlmanacSnafu)?
+                        .radius_km)
+                }
+            }
+        }
+    }
+}
+
+impl From<Frame> for EventTarget {
+    fn from(body: Frame) -> Self {
+        Self::Body(body)
+    }
+}
+
+impl From<Traj<Spacecraft>> for EventTarget {
+    fn from(traj: Traj<Spacecraft>) -> Self {
+        Self::Spacecraft(Box::new(traj))
+    }
+}
+
+impl fmt::Display for EventTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Body(body) => write!(f, "{body:x}"),
+            Self::Spacecraft(traj) => match &traj.name {
+                Some(name) => write!(f, "{name}"),
+                None => write!(f, "spacecraft trajectory"),
+            },
+        }
+    }
+}
+
+/// An event on the range between the spacecraft and a target, e.g. another spacecraft crossing a distance threshold.
+///
+/// The evaluation is positive when the target is farther than the desired range.
+#[derive(Clone)]
+pub struct RangeEvent {
+    pub target: EventTarget,
+    /// Range threshold, in kilometers
+    pub desired_range_km: f64,
+    pub epoch_precision: Duration,
+    /// Precision on the range, in kilometers
+    pub value_precision: f64,
+}
+
+impl RangeEvent {
+    /// Range event with a precision of one meter and one millisecond
+    pub fn new<T: Into<EventTarget>>(target: T, desired_range_km: f64) -> Self {
+        Self {
+            target: target.into(),
+            desired_range_km,
+            epoch_precision: Unit::Millisecond * 1,
+            value_precision: 1e-3,
+        }
+    }
+
+    /// Returns the range to the target in km
+    pub fn range_km(&self, sc: &Spacecraft, almanac: Arc<Almanac>) -> Result<f64, EventError> {
+        let target_km = self
+            .target
+            .position_km(sc.epoch(), sc.orbit.frame, almanac)?;
+        Ok((target_km - sc.orbit.radius_km).norm())
+    }
+}
+
+impl fmt::Display for RangeEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "range to {} = {} km (± {} km)",
+            self.target, self.desired_range_km, self.value_precision
+        )
+    }
+}
+
+impl EventEvaluator<Spacecraft> for RangeEvent {
+    fn eval(&self, state: &Spacecraft, almanac: Arc<Almanac>) -> Result<f64, EventError> {
+        Ok(self.range_km(state, almanac)? - self.desired_range_km)
+    }
+
+    fn eval_string(&self, state: &Spacecraft, almanac: Arc<Almanac>) -> Result<String, EventError> {
+        Ok(format!(
+            "range to {} = {:.3} km",
+            self.target,
+            self.range_km(state, almanac)?
+        ))
+    }
+
+    fn epoch_precision(&self) -> Duration {
+        self.epoch_precision
+    }
+
+    fn value_precision(&self) -> f64 {
+        self.value_precision
+    }
+}
+
+/// An event on the angular separation between two targets, as seen from the spacecraft, e.g. the Sun-spacecraft-Earth angle.
+///
+/// The evaluation is positive when the separation is larger than the desired angle.
+#[derive(Clone)]
+pub struct AngularSeparationEvent {
+    pub first: EventTarget,
+    pub second: EventTarget,
+    /// Angular separation threshold, in degrees
+    pub desired_angle_deg: f64,
+    pub epoch_precision: Duration,
+    /// Precision on the angle, in degrees
+    pub value_precision: f64,
+}
+
+impl AngularSeparationEvent {
+    /// Angular separation event with a precision of one millidegree and one millisecond
+    pub fn new<T: Into<EventTarget>, U: Into<EventTarget>>(
+        first: T,
+        second: U,
+        desired_angle_deg: f64,
+    ) -> Self {
+        Self {
+            first: first.into(),
+            second: second.into(),
+            desired_angle_deg,
+            epoch_precision: Unit::Millisecond * 1,
+            value_precision: 1e-3,
+        }
+    }
+
+    /// Returns the angular separation between both targets as seen from the spacecraft, in degrees
+    pub fn separation_deg(
+        &self,
+        sc: &Spacecraft,
+        almanac: Arc<Almanac>,
+    ) -> Result<f64, EventError> {
+        let first_km = self
+            .first
+            .position_km(sc.epoch(), sc.orbit.frame, almanac.clone())?
+            - sc.orbit.radius_km;
+        let second_km = self
+            .second
+            .position_km(sc.epoch(), sc.orbit.frame, almanac)?
+            - sc.orbit.radius_km;
+        Ok(first_km.angle(&second_km).to_degrees())
+    }
+}
+
+impl fmt::Display for AngularSeparationEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "separation of {} and {} = {} deg (± {} deg)",
+            self.first, self.second, self.desired_angle_deg, self.value_precision
+        )
+    }
+}
+
+impl EventEvaluator<Spacecraft> for AngularSeparationEvent {
+    fn eval(&self, state: &Spacecraft, almanac: Arc<Almanac>) -> Result<f64, EventError> {
+        Ok(self.separation_deg(state, almanac)? - self.desired_angle_deg)
+    }
+
+    fn eval_string(&self, state: &Spacecraft, almanac: Arc<Almanac>) -> Result<String, EventError> {
+        Ok(format!(
+            "separation of {} and {} = {:.3} deg",
+            self.first,
+            self.second,
+            self.separation_deg(state, almanac)?
+        ))
+    }
+
+    fn epoch_precision(&self) -> Duration {
+        self.epoch_precision
+    }
+
+    fn value_precision(&self) -> f64 {
+        self.value_precision
+    }
+}
+
+/// An event on the line of sight between the spacecraft and a target, e.g. an inter-satellite link blocked by the Moon.
+///
+/// The evaluation is the distance between the line of sight and the surface of the obstructing body (using its mean equatorial radius),
+/// so it is positive when the target is visible and negative when the line of sight is blocked.
+#[derive(Clone)]
+pub struct LineOfSightEvent {
+    pub target: EventTarget,
+    /// The body which may obstruct the line of sight, must have its shape defined in the Almanac
+    pub obstructing_body: Frame,
+    pub epoch_precision: Duration,
+    /// Precision on the distance of the line of sight to the obstructing body, in kilometers
+    pub value_precision: f64,
+}
+
+impl LineOfSightEvent {
+    /// Line of sight event with a precision of one meter and one millisecond
+    pub fn new<T: Into<EventTarget>>(target: T, obstructing_body: Frame) -> Self {
+        Self {
+            target: target.into(),
+            obstructing_body,
+            epoch_precision: Unit::Millisecond * 1,
+            value_precision: 1e-3,
+        }
+    }
+
+    /// Returns the altitude of the line of sight above the obstructing body, in km
+    pub fn clearance_km(&self, sc: &Spacecraft, almanac: Arc<Almanac>) -> Result<f64, EventError> {
+        let body = if self.obstructing_body.mean_equatorial_radius_km().is_ok() {
+            self.obstructing_body
+        } else {
+            // Fetch the shape from the Almanac, and report the missing shape if it isn't there either.
+            almanac
+                .frame_info(self.obstructing_body)
+                .unwrap_or(self.obstructing_body)
+        };
+        let radius_km = body
+            .mean_equatorial_radius_km()
+            .context(EventPhysicsSnafu)?;
+
+        let target_km = self
+            .target
+            .position_km(sc.epoch(), sc.orbit.frame, almanac.clone())?;
+        let body_km = almanac
+            .transform(body, sc.orbit.frame, sc.epoch(), None)
+            .context(EventAlmanacSnafu)?
+            .radius_km;
+
+        Ok(segment_distance_km(&sc.orbit.radius_km, &target_km, &body_km) - radius_km)
+    }
+}
+
+impl fmt::Display for LineOfSightEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line of sight to {} obstructed by {:x}",
+            self.target, self.obstructing_body
+        )
+    }
+}
+
+impl EventEvaluator<Spacecraft> for LineOfSightEvent {
+    fn eval(&self, state: &Spacecraft, almanac: Arc<Almanac>) -> Result<f64, EventError> {
+        self.clearance_km(state, almanac)
+    }
+
+    fn eval_string(&self, state: &Spacecraft, almanac: Arc<Almanac>) -> Result<String, EventError> {
+        let clearance_km = self.clearance_km(state, almanac)?;
+        Ok(format!(
+            "line of sight to {} {} by {:x} (clearance = {:.3} km)",
+            self.target,
+            if clearance_km > 0.0 {
+                "not obstructed"
+            } else {
+                "obstructed"
+            },
+            self.obstructing_body,
+            clearance_km
+        ))
+    }
+
+    fn epoch_precision(&self) -> Duration {
+        self.epoch_precision
+    }
+
+    fn value_precision(&self) -> f64 {
+        self.value_precision
+    }
+}
+
+/// Returns the shortest distance between the point `p` and the segment from `a` to `b`.
+fn segment_distance_km(a: &Vector3<f64>, b: &Vector3<f64>, p: &Vector3<f64>) -> f64 {
+    let ab = b - a;
+    let len_sq = ab.norm_squared();
+    let t = if len_sq > 0.0 {
+        ((p - a).dot(&ab) / len_sq).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    (a + t * ab - p).norm()
+}
+
+#[cfg(test)]
+mod ut_relative {
+    use super::segment_distance_km;
+    use crate::linalg::Vector3;
+
+    #[test]
+    fn segment_distance() {
+        let a = Vector3::new(-10.0, 5.0, 0.0);
+        let b = Vector3::new(10.0, 5.0, 0.0);
+        // Closest point is in the middle of the segment
+        assert!((segment_distance_km(&a, &b, &Vector3::zeros()) - 5.0).abs() < 1e-12);
+        // Closest point is an end of the segment
+        let p = Vector3::new(13.0, 1.0, 0.0);
+        assert!((segment_distance_km(&a, &b, &p) - 5.0).abs() < 1e-12);
+        // Degenerate segment
+        assert!((segment_distance_km(&a, &a, &Vector3::zeros()) - 125.0_f64.sqrt()).abs() < 1e-12);
+    }
+}
@@ -45,7 +45,7 @@ where
         almanac: Arc<Almanac>,
     ) -> Result<EventDetails<S>, EventError>
     where
-        E: EventEvaluator<S>,
+        E: EventEvaluator<S> + ?Sized,
     {
         let max_iter = 50;
 
@@ -204,7 +204,7 @@ where
         almanac: Arc<Almanac>,
     ) -> Result<Vec<EventDetails<S>>, EventError>
     where
-        E: EventEvaluator<S>,
+        E: EventEvaluator<S> + ?Sized,
     {
         let start_epoch = self.first().epoch();
         let end_epoch = self.last().epoch();
@@ -332,7 +332,7 @@ where
         almanac: Arc<Almanac>,
     ) -> Result<(S, S), EventError>
     where
-        E: EventEvaluator<S>,
+        E: EventEvaluator<S> + ?Sized,
     {
         let step: Duration = 1 * precision;
         let mut min_val = f64::INFINITY;
@@ -399,7 +399,7 @@ where
         almanac: Arc<Almanac>,
     ) -> Result<Vec<EventArc<S>>, EventError>
     where
-        E: EventEvaluator<S>,
+        E: EventEvaluator<S> + ?Sized,
     {
         let mut events = match self.find(event, heuristic, almanac.clone()) {
             Ok(events) => events,
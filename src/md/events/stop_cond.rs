@@ -0,0 +1,289 @@
+/*
+    Nyx, blazing fast astrodynamics
+    Copyright (C) 2018-onwards Christopher Rabotin <christopher.rabotin@gmail.com>
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published
+    by the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use super::details::{EventDetails, EventEdge};
+use super::EventEvaluator;
+use crate::errors::EventError;
+use crate::linalg::allocator::Allocator;
+use crate::linalg::DefaultAllocator;
+use crate::md::prelude::{Interpolatable, Traj};
+use crate::time::{Duration, Epoch};
+use anise::almanac::Almanac;
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::Arc;
+
+/// A single stopping condition: the `nth` occurrence (counting from zero) of an event, optionally only on a given edge.
+///
+/// For example, the third exit of an umbra is `StopCondition::new(umbra).nth(2).on_edge(EventEdge::Rising)`.
+#[derive(Clone)]
+pub struct StopCondition<S: Interpolatable>
+where
+    DefaultAllocator: Allocator<S::VecLength> + Allocator<S::Size> + Allocator<S::Size, S::Size>,
+{
+    /// The event to search for
+    pub event: Arc<dyn EventEvaluator<S>>,
+    /// Occurrence of this event which triggers the condition, starting at zero for the first occurrence
+    pub nth: usize,
+    /// If set, only the occurrences of the event on this edge are counted
+    pub edge: Option<EventEdge>,
+}
+
+impl<S: Interpolatable> StopCondition<S>
+where
+    DefaultAllocator: Allocator<S::VecLength> + Allocator<S::Size> + Allocator<S::Size, S::Size>,
+{
+    /// Initializes a condition triggered on the first occurrence of the event, on any edge.
+    pub fn new(event: Arc<dyn EventEvaluator<S>>) -> Self {
+        Self {
+            event,
+            nth: 0,
+            edge: None,
+        }
+    }
+
+    /// Sets the occurrence of the event which triggers this condition (zero being the first occurrence)
+    pub fn nth(mut self, nth: usize) -> Self {
+        self.nth = nth;
+        self
+    }
+
+    /// Only count the occurrences of the event on the provided edge
+    pub fn on_edge(mut self, edge: EventEdge) -> Self {
+        self.edge = Some(edge);
+        self
+    }
+
+    /// Returns the occurrence of the event which triggers this condition in the provided trajectory, if any.
+    pub fn find_in(
+        &self,
+        traj: &Traj<S>,
+        heuristic: Option<Duration>,
+        almanac: Arc<Almanac>,
+    ) -> Result<Option<EventDetails<S>>, EventError> {
+        let events = match traj.find(self.event.as_ref(), heuristic, almanac) {
+            Ok(events) => events,
+            Err(EventError::NotFound { .. }) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        Ok(events
+            .into_iter()
+            .filter(|details| match self.edge {
+                Some(edge) => details.edge == edge,
+                None => true,
+            })
+            .nth(self.nth))
+    }
+}
+
+impl<S: Interpolatable> fmt::Display for StopCondition<S>
+where
+    DefaultAllocator: Allocator<S::VecLength> + Allocator<S::Size> + Allocator<S::Size, S::Size>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{} of {}", self.nth, self.event)?;
+        if let Some(edge) = self.edge {
+            write!(f, " ({edge:?} edge)")?;
+        }
+        Ok(())
+    }
+}
+
+/// Defines how the conditions of a set are combined to stop a propagation.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StopLogic {
+    /// Stop when the first of the conditions is met
+    Any,
+    /// Stop when all of the conditions have been met, i.e. when the last of them is met
+    All,
+    /// Stop when the n-th condition is met (counting from zero), regardless of which one it is
+    Nth(usize),
+}
+
+impl fmt::Display for StopLogic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Any => write!(f, "any of"),
+            Self::All => write!(f, "all of"),
+            Self::Nth(n) => write!(f, "#{n} of"),
+        }
+    }
+}
+
+/// A set of stopping conditions, combined with some logic, cf. [`PropInstance::until_stop_conditions`](crate::propagators::PropInstance::until_stop_conditions).
+#[derive(Clone)]
+pub struct StopConditions<S: Interpolatable>
+where
+    DefaultAllocator: Allocator<S::VecLength> + Allocator<S::Size> + Allocator<S::Size, S::Size>,
+{
+    pub conditions: Vec<StopCondition<S>>,
+    pub logic: StopLogic,
+}
+
+/// The stopping condition which was met, and the details of its event.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StoppingEvent<S: Interpolatable>
+where
+    DefaultAllocator: Allocator<S::VecLength> + Allocator<S::Size> + Allocator<S::Size, S::Size>,
+{
+    /// Index of the condition which was met in the set of stopping conditions
+    pub condition: usize,
+    /// Details of the event of that condition
+    pub details: EventDetails<S>,
+}
+
+impl<S: Interpolatable> fmt::Display for StoppingEvent<S>
+where
+    DefaultAllocator: Allocator<S::VecLength> + Allocator<S::Size> + Allocator<S::Size, S::Size>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "condition #{}: {}", self.condition, self.details)
+    }
+}
+
+impl<S: Interpolatable> StopConditions<S>
+where
+    DefaultAllocator: Allocator<S::VecLength> + Allocator<S::Size> + Allocator<S::Size, S::Size>,
+{
+    /// Stop at the first of these conditions
+    pub fn any(conditions: Vec<StopCondition<S>>) -> Self {
+        Self {
+            conditions,
+            logic: StopLogic::Any,
+        }
+    }
+
+    /// Stop once all of these conditions have been met
+    pub fn all(conditions: Vec<StopCondition<S>>) -> Self {
+        Self {
+            conditions,
+            logic: StopLogic::All,
+        }
+    }
+
+    /// Stop at the n-th condition met (counting from zero)
+    pub fn nth(conditions: Vec<StopCondition<S>>, nth: usize) -> Self {
+        Self {
+            conditions,
+            logic: StopLogic::Nth(nth),
+        }
+    }
+
+    /// Searches the trajectory for each of the conditions and returns the one which stops the propagation given the logic of this set.
+    ///
+    /// Each event is found with the same bracketing precision as [`Traj::find_bracketed`].
+    pub fn find_in(
+        &self,
+        traj: &Traj<S>,
+        heuristic: Option<Duration>,
+        almanac: Arc<Almanac>,
+    ) -> Result<StoppingEvent<S>, EventError> {
+        let mut triggers = Vec::with_capacity(self.conditions.len());
+        for condition in &self.conditions {
+            let maybe_details = condition.find_in(traj, heuristic, almanac.clone())?;
+            match &maybe_details {
+                Some(details) => debug!("{condition} met on {}", details.state.epoch()),
+                None => debug!("{condition} not met"),
+            }
+            triggers.push(maybe_details);
+        }
+
+        let epochs: Vec<Option<Epoch>> = triggers
+            .iter()
+            .map(|maybe| maybe.as_ref().map(|details| details.state.epoch()))
+            .collect();
+
+        match self.logic.select(&epochs) {
+            Some(condition) => {
+                let details = triggers[condition].take().unwrap();
+                info!(
+                    "Stopping on {} ({}) on {}",
+                    self.conditions[condition],
+                    self.logic,
+                    details.state.epoch()
+                );
+                Ok(StoppingEvent { condition, details })
+            }
+            None => Err(EventError::NotFound {
+                start: traj.first().epoch(),
+                end: traj.last().epoch(),
+                event: format!("{self}"),
+            }),
+        }
+    }
+}
+
+impl StopLogic {
+    /// Given the epoch at which each condition is met (if at all), returns the index of the condition which stops the propagation.
+    pub(crate) fn select(&self, epochs: &[Option<Epoch>]) -> Option<usize> {
+        let mut met: Vec<(usize, Epoch)> = epochs
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, maybe_epoch)| maybe_epoch.map(|epoch| (idx, epoch)))
+            .collect();
+        // Stable sort, so the first condition in the set wins if two are met at the same time.
+        met.sort_by_key(|(_, epoch)| *epoch);
+
+        match self {
+            Self::Any => met.first().map(|(idx, _)| *idx),
+            Self::All => {
+                if met.len() == epochs.len() {
+                    met.last().map(|(idx, _)| *idx)
+                } else {
+                    None
+                }
+            }
+            Self::Nth(n) => met.get(*n).map(|(idx, _)| *idx),
+        }
+    }
+}
+
+impl<S: Interpolatable> fmt::Display for StopConditions<S>
+where
+    DefaultAllocator: Allocator<S::VecLength> + Allocator<S::Size> + Allocator<S::Size, S::Size>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let conditions: Vec<String> = self.conditions.iter().map(|c| format!("{c}")).collect();
+        write!(f, "{} [{}]", self.logic, conditions.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod ut_stop_cond {
+    use super::StopLogic;
+    use crate::time::{Epoch, Unit};
+
+    #[test]
+    fn stop_logic() {
+        let e0 = Epoch::from_gregorian_utc_at_midnight(2024, 1, 1);
+        let epochs = [Some(e0 + Unit::Hour * 3), None, Some(e0 + Unit::Hour * 1)];
+
+        assert_eq!(StopLogic::Any.select(&epochs), Some(2));
+        // Second condition is never met
+        assert_eq!(StopLogic::All.select(&epochs), None);
+        assert_eq!(StopLogic::Nth(0).select(&epochs), Some(2));
+        assert_eq!(StopLogic::Nth(1).select(&epochs), Some(0));
+        assert_eq!(StopLogic::Nth(2).select(&epochs), None);
+
+        let epochs = [Some(e0 + Unit::Hour * 3), Some(e0 + Unit::Hour * 5)];
+        assert_eq!(StopLogic::All.select(&epochs), Some(1));
+        assert_eq!(StopLogic::Any.select(&[None, None]), None);
+    }
+}
@@ -26,7 +26,10 @@ use snafu::prelude::*;
 pub mod prelude {
     pub use super::{
         targeter::*,
-        trajectory::{ExportCfg, Interpolatable, Traj},
+        trajectory::{
+            BspDataType, BspExportCfg, ChebyshevTraj, CompressionCfg, ExportCfg, Interpolatable,
+            OemFormat, Traj,
+        },
         Event, StateParameter, Trajectory,
     };
     pub use crate::cosmic::{try_achieve_b_plane, BPlane, BPlaneTarget, GuidanceMode, OrbitDual};
@@ -48,6 +51,10 @@ pub mod prelude {
 pub mod trajectory;
 
 pub(crate) mod events;
+pub use events::combination::EventCombination;
+pub use events::details::{EventArc, EventDetails, EventEdge};
+pub use events::relative::{AngularSeparationEvent, EventTarget, LineOfSightEvent, RangeEvent};
+pub use events::stop_cond::{StopCondition, StopConditions, StopLogic, StoppingEvent};
 pub use events::{Event, EventEvaluator};
 
 pub mod objective;
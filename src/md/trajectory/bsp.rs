@@ -0,0 +1,524 @@
+/*
+    Nyx, blazing fast astrodynamics
+    Copyright (C) 2018-onwards Christopher Rabotin <christopher.rabotin@gmail.com>
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published
+    by the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use super::Traj;
+use crate::io::watermark::prj_name_ver;
+use crate::io::{InconsistencySnafu, InputOutputError, StdIOSnafu};
+use crate::time::{Duration, Epoch};
+use crate::{Spacecraft, State};
+use log::info;
+use serde_derive::{Deserialize, Serialize};
+use snafu::{ensure, ResultExt};
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use typed_builder::TypedBuilder;
+
+/// Number of doubles in a DAF record
+const RCRD_DBL: usize = 128;
+/// Number of bytes in a DAF record
+const RCRD_LEN: usize = RCRD_DBL * 8;
+/// SPK summaries are made of two doubles (ND) and six integers (NI), i.e. five doubles.
+const SUMMARY_DBL: usize = 5;
+/// Number of summaries which fit in each summary record, after its three control doubles
+const SUMMARIES_PER_RCRD: usize = (RCRD_DBL - 3) / SUMMARY_DBL;
+/// Length of the name of each segment
+const NAME_LEN: usize = SUMMARY_DBL * 8;
+/// SPICE stores every 100th epoch in the epoch directory of unequal time step types
+const EPOCH_DIR_STEP: usize = 100;
+/// FTP validation string of the DAF file record
+const FTP_STR: &[u8; 28] = b"FTPSTR:\r:\n:\r\n:\r\x00:\x81:\x10\xce:ENDFTP";
+
+/// Interpolation data type of the segments of an exported SPK.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BspDataType {
+    /// Hermite interpolation of the position and velocity with unequal time steps (SPK Type 13)
+    #[default]
+    Hermite,
+    /// Lagrange interpolation of the position and velocity with unequal time steps (SPK Type 9)
+    Lagrange,
+}
+
+impl BspDataType {
+    /// Returns the SPK type number
+    pub fn spk_type(&self) -> i32 {
+        match self {
+            Self::Hermite => 13,
+            Self::Lagrange => 9,
+        }
+    }
+
+    /// Returns the largest window size supported by SPICE for this data type
+    pub fn max_window_size(&self) -> usize {
+        match self {
+            // Maximum degree of 27 and each sample provides both the value and its derivative.
+            Self::Hermite => 14,
+            Self::Lagrange => 28,
+        }
+    }
+
+    /// Returns the value stored in the segment trailer for this window size
+    fn window_trailer(&self, window_size: usize) -> f64 {
+        match self {
+            // Type 13 stores the window size minus one, and Type 9 stores the polynomial degree, which are the same number.
+            Self::Hermite | Self::Lagrange => (window_size - 1) as f64,
+        }
+    }
+}
+
+impl fmt::Display for BspDataType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Hermite => write!(f, "Hermite Type 13"),
+            Self::Lagrange => write!(f, "Lagrange Type 9"),
+        }
+    }
+}
+
+/// Configuration of the SPK (BSP) export of a spacecraft trajectory, cf. [`Traj::to_bsp`].
+#[derive(Clone, Debug, TypedBuilder)]
+#[builder(doc)]
+pub struct BspExportCfg {
+    /// NAIF ID of the spacecraft, which should be negative per the NAIF convention (e.g. -10000001)
+    pub naif_id: i32,
+    #[builder(default)]
+    pub data_type: BspDataType,
+    /// Number of states used for each interpolation, must be even.
+    #[builder(default = 8)]
+    pub window_size: usize,
+    /// If set, the trajectory is split into segments of this duration, each stored as a separate SPK segment.
+    #[builder(default, setter(strip_option))]
+    pub segment_duration: Option<Duration>,
+    /// Name of the segments, defaults to the name of the trajectory. Truncated to 40 characters.
+    #[builder(default, setter(strip_option, into))]
+    pub segment_name: Option<String>,
+}
+
+impl Traj<Spacecraft> {
+    /// Exports this trajectory as a SPICE SPK (BSP) file, which can be loaded in an Almanac or in any SPICE toolkit.
+    ///
+    /// The states are exported in the frame of the first state of the trajectory: its ephemeris ID is the center of the segments
+    /// and its orientation ID is the reference frame. The stored states are written as is, such that loading the file back
+    /// reproduces them exactly and interpolates between them with the selected data type.
+    /// Each segment of the trajectory, i.e. between its boundaries, is written to its own SPK segments.
+    /// If the trajectory is split into segments and a segment boundary is not a stored state, the state at that boundary is
+    /// interpolated and written to both adjacent segments.
+    ///
+    /// Every summary record holds up to 25 segments, and the summary records are chained as per the DAF format when more are needed.
+    /// Note that ANISE only reads the first summary record of a DAF file, whereas the SPICE toolkit reads all of them.
+    pub fn to_bsp<P: AsRef<Path>>(
+        &self,
+        path: P,
+        cfg: BspExportCfg,
+    ) -> Result<PathBuf, InputOutputError> {
+        ensure!(
+            self.states.len() >= 2,
+            InconsistencySnafu {
+                msg: "cannot export a trajectory with fewer than two states to BSP".to_string()
+            }
+        );
+        ensure!(
+            cfg.window_size >= 2
+                && cfg.window_size.is_multiple_of(2)
+                && cfg.window_size <= cfg.data_type.max_window_size(),
+            InconsistencySnafu {
+                msg: format!(
+                    "{} window size must be even and between 2 and {}, got {}",
+                    cfg.data_type,
+                    cfg.data_type.max_window_size(),
+                    cfg.window_size
+                )
+            }
+        );
+
+        let tick = Epoch::now().unwrap();
+        let frame = self.first().orbit.frame;
+
+        if let Some(segment_duration) = cfg.segment_duration {
+            ensure!(
+                segment_duration.to_seconds() > 0.0,
+                InconsistencySnafu {
+                    msg: format!("BSP segment duration must be positive, got {segment_duration}")
+                }
+            );
+        }
+
+        // Split the states into segments: the SPK segments never span a boundary of the trajectory
+        let mut segments = Vec::new();
+        for traj in self.segments() {
+            let start = traj.first().epoch();
+            let end = traj.last().epoch();
+            let mut boundaries = vec![start];
+            if let Some(segment_duration) = cfg.segment_duration {
+                let mut boundary = start + segment_duration;
+                while boundary < end {
+                    boundaries.push(boundary);
+                    boundary += segment_duration;
+                }
+            }
+            boundaries.push(end);
+
+            for pair in boundaries.windows(2) {
+                let mut states: Vec<Spacecraft> = traj
+                    .states
+                    .iter()
+                    .filter(|state| state.epoch() > pair[0] && state.epoch() < pair[1])
+                    .copied()
+                    .collect();
+                for (boundary, idx) in [(pair[0], 0), (pair[1], states.len() + 1)] {
+                    let state = traj
+                        .at(boundary)
+                        .map_err(|e| InputOutputError::Inconsistency {
+                            msg: format!("could not build BSP segment boundary at {boundary}: {e}"),
+                        })?;
+                    states.insert(idx, state);
+                }
+
+                ensure!(
+                    states.len() >= cfg.window_size,
+                    InconsistencySnafu {
+                        msg: format!(
+                            "BSP segment from {} to {} has {} states, fewer than the window size of {}",
+                            pair[0],
+                            pair[1],
+                            states.len(),
+                            cfg.window_size
+                        )
+                    }
+                );
+
+                for state in &states {
+                    ensure!(
+                        state.orbit.frame == frame,
+                        InconsistencySnafu {
+                            msg: format!(
+                                "all states must be in {frame:x} for BSP export, but state at {} is in {:x}",
+                                state.epoch(),
+                                state.orbit.frame
+                            )
+                        }
+                    );
+                }
+
+                segments.push(states);
+            }
+        }
+
+        // Build the DAF file: file record, pairs of summary and name records, and then the data.
+        let name = cfg
+            .segment_name
+            .clone()
+            .or_else(|| self.name.clone())
+            .unwrap_or_else(|| format!("NYX SC {}", cfg.naif_id));
+
+        let num_summary_rcrds = segments.len().div_ceil(SUMMARIES_PER_RCRD);
+        let mut summaries = Vec::with_capacity(segments.len());
+        let mut data: Vec<f64> = Vec::new();
+        // Data starts right after the last name record, and DAF addresses start at one.
+        let first_addr = (1 + 2 * num_summary_rcrds) * RCRD_DBL + 1;
+        for states in &segments {
+            let start_addr = first_addr + data.len();
+            data.extend(segment_data(states, &cfg));
+            let end_addr = first_addr + data.len() - 1;
+
+            let mut summary = Vec::with_capacity(SUMMARY_DBL * 8);
+            summary.extend(states[0].epoch().to_et_seconds().to_ne_bytes());
+            summary.extend(
+                states[states.len() - 1]
+                    .epoch()
+                    .to_et_seconds()
+                    .to_ne_bytes(),
+            );
+            for int in [
+                cfg.naif_id,
+                frame.ephemeris_id,
+                frame.orientation_id,
+                cfg.data_type.spk_type(),
+                start_addr as i32,
+                end_addr as i32,
+            ] {
+                summary.extend(int.to_ne_bytes());
+            }
+            summaries.push(summary);
+        }
+        let free_addr = first_addr + data.len();
+
+        let mut bytes =
+            Vec::with_capacity((1 + 2 * num_summary_rcrds) * RCRD_LEN + data.len() * 8 + RCRD_LEN);
+
+        // File record
+        bytes.extend(b"DAF/SPK ");
+        bytes.extend(2_u32.to_ne_bytes());
+        bytes.extend(6_u32.to_ne_bytes());
+        bytes.extend(padded(&prj_name_ver(), 60));
+        // Forward and backward pointers to the first and last summary records, and the first free address
+        bytes.extend(2_u32.to_ne_bytes());
+        bytes.extend((2 * num_summary_rcrds as u32).to_ne_bytes());
+        bytes.extend((free_addr as u32).to_ne_bytes());
+        bytes.extend(if cfg!(target_endian = "little") {
+            b"LTL-IEEE"
+        } else {
+            b"BIG-IEEE"
+        });
+        bytes.extend([0_u8; 603]);
+        bytes.extend(FTP_STR);
+        bytes.resize(RCRD_LEN, 0);
+
+        for (idx, chunk) in summaries.chunks(SUMMARIES_PER_RCRD).enumerate() {
+            // Summary record, i.e. record number 2 + 2 * idx: next and previous summary records, and number of summaries
+            let next = if idx + 1 < num_summary_rcrds {
+                4 + 2 * idx
+            } else {
+                0
+            };
+            let prev = if idx > 0 { 2 * idx } else { 0 };
+            bytes.extend((next as f64).to_ne_bytes());
+            bytes.extend((prev as f64).to_ne_bytes());
+            bytes.extend((chunk.len() as f64).to_ne_bytes());
+            for summary in chunk {
+                bytes.extend(summary);
+            }
+            bytes.resize((2 + 2 * idx) * RCRD_LEN, 0);
+
+            // Name record
+            for _ in chunk {
+                bytes.extend(padded(&name, NAME_LEN));
+            }
+            bytes.resize((3 + 2 * idx) * RCRD_LEN, 0);
+        }
+
+        for value in &data {
+            bytes.extend(value.to_ne_bytes());
+        }
+        bytes.resize(bytes.len().div_ceil(RCRD_LEN) * RCRD_LEN, 0);
+
+        let path_buf = path.as_ref().to_path_buf();
+        let file = File::create(&path_buf).context(StdIOSnafu {
+            action: "creating BSP file",
+        })?;
+        let mut writer = BufWriter::new(file);
+        writer.write_all(&bytes).context(StdIOSnafu {
+            action: "writing BSP file",
+        })?;
+        writer.flush().context(StdIOSnafu {
+            action: "writing BSP file",
+        })?;
+
+        let tock_time = Epoch::now().unwrap() - tick;
+        info!(
+            "Trajectory written to {} as {} segment(s) of {} in {tock_time}",
+            path_buf.display(),
+            segments.len(),
+            cfg.data_type
+        );
+
+        Ok(path_buf)
+    }
+}
+
+/// Builds the data of an SPK Type 9 or 13 segment: the states, their epochs, the epoch directory, and the trailer.
+fn segment_data(states: &[Spacecraft], cfg: &BspExportCfg) -> Vec<f64> {
+    let num_records = states.len();
+    let mut data = Vec::with_capacity(7 * num_records + num_records / EPOCH_DIR_STEP + 2);
+    for state in states {
+        data.extend(state.orbit.radius_km.iter());
+        data.extend(state.orbit.velocity_km_s.iter());
+    }
+    data.extend(states.iter().map(|state| state.epoch().to_et_seconds()));
+    data.extend(
+        states
+            .iter()
+            .skip(EPOCH_DIR_STEP - 1)
+            .step_by(EPOCH_DIR_STEP)
+            .take((num_records - 1) / EPOCH_DIR_STEP)
+            .map(|state| state.epoch().to_et_seconds()),
+    );
+    data.push(cfg.data_type.window_trailer(cfg.window_size));
+    data.push(num_records as f64);
+    data
+}
+
+/// Returns the ASCII bytes of the provided string, truncated or padded with spaces to the provided length.
+fn padded(s: &str, len: usize) -> Vec<u8> {
+    let mut bytes: Vec<u8> = s.bytes().filter(u8::is_ascii).take(len).collect();
+    bytes.resize(len, b' ');
+    bytes
+}
+
+#[cfg(test)]
+mod ut_bsp {
+    use super::super::fixtures::{self, circular_state};
+    use super::{BspDataType, BspExportCfg, RCRD_DBL, RCRD_LEN, SUMMARIES_PER_RCRD, SUMMARY_DBL};
+    use crate::md::prelude::Traj;
+    use crate::time::{Epoch, Unit};
+    use crate::{Spacecraft, State};
+    use anise::constants::frames::EARTH_J2000;
+    use anise::prelude::{Almanac, Frame};
+    use std::path::PathBuf;
+
+    fn start() -> Epoch {
+        Epoch::from_gregorian_utc_at_midnight(2024, 1, 1)
+    }
+
+    /// Builds a trajectory of the circular orbit over twelve hours, sampled every 45 seconds.
+    fn circular_traj() -> Traj<Spacecraft> {
+        fixtures::circular_traj(start(), Unit::Hour * 12, Unit::Second * 45)
+    }
+
+    #[test]
+    fn bsp_round_trip() {
+        let traj = circular_traj();
+        let sc_frame = Frame::from_ephem_j2000(-10000001);
+
+        for (data_type, segment_duration) in [
+            (BspDataType::Hermite, None),
+            (BspDataType::Lagrange, Some(Unit::Hour * 5)),
+        ] {
+            let path: PathBuf = [
+                env!("CARGO_MANIFEST_DIR"),
+                "data",
+                "04_output",
+                &format!("ut_bsp_{data_type:?}.bsp"),
+            ]
+            .iter()
+            .collect();
+
+            let cfg = match segment_duration {
+                Some(duration) => BspExportCfg::builder()
+                    .naif_id(-10000001)
+                    .data_type(data_type)
+                    .segment_duration(duration)
+                    .build(),
+                None => BspExportCfg::builder()
+                    .naif_id(-10000001)
+                    .data_type(data_type)
+                    .build(),
+            };
+            traj.to_bsp(&path, cfg).unwrap();
+
+            let almanac = Almanac::default().load(path.to_str().unwrap()).unwrap();
+            let spk = almanac.spk_summaries(-10000001).unwrap();
+            assert_eq!(spk.len(), segment_duration.map_or(1, |_| 3));
+
+            // The stored states are reproduced exactly, and the interpolation between them is within a centimeter and a millimeter per second.
+            for (i, state) in traj.states.iter().enumerate() {
+                let epoch = if i % 2 == 0 {
+                    state.epoch()
+                } else {
+                    state.epoch() + Unit::Second * 20
+                };
+                if epoch > traj.last().epoch() {
+                    continue;
+                }
+                let expected = circular_state(start(), epoch).orbit;
+                let loaded = almanac
+                    .transform(sc_frame, EARTH_J2000, epoch, None)
+                    .unwrap();
+                let err_km = (loaded.radius_km - expected.radius_km).norm();
+                let err_km_s = (loaded.velocity_km_s - expected.velocity_km_s).norm();
+                assert!(err_km < 1e-5, "{data_type:?} {err_km} km error at {epoch}");
+                assert!(
+                    err_km_s < 1e-6,
+                    "{data_type:?} {err_km_s} km/s error at {epoch}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn bsp_invalid_cfg() {
+        let traj = circular_traj();
+        let path: PathBuf = [
+            env!("CARGO_MANIFEST_DIR"),
+            "data",
+            "04_output",
+            "ut_bsp_bad.bsp",
+        ]
+        .iter()
+        .collect();
+
+        let odd_window = BspExportCfg::builder()
+            .naif_id(-10000001)
+            .window_size(7)
+            .build();
+        assert!(traj.to_bsp(&path, odd_window).is_err());
+    }
+
+    #[test]
+    fn bsp_chained_summaries() {
+        let traj = circular_traj();
+        let path: PathBuf = [
+            env!("CARGO_MANIFEST_DIR"),
+            "data",
+            "04_output",
+            "ut_bsp_chained.bsp",
+        ]
+        .iter()
+        .collect();
+
+        // Twelve hours split in ten minute segments need three summary records.
+        let cfg = BspExportCfg::builder()
+            .naif_id(-10000001)
+            .segment_duration(Unit::Minute * 10)
+            .build();
+        traj.to_bsp(&path, cfg).unwrap();
+
+        let bytes = std::fs::read(&path).unwrap();
+        let int_at =
+            |offset: usize| i32::from_ne_bytes(bytes[offset..offset + 4].try_into().unwrap());
+        let dbl_at =
+            |offset: usize| f64::from_ne_bytes(bytes[offset..offset + 8].try_into().unwrap());
+
+        // Walk the summary records from the first one, as pointed to by the file record.
+        let mut rcrd = int_at(76) as usize;
+        let mut prev = 0;
+        let mut summaries = Vec::new();
+        while rcrd != 0 {
+            let offset = (rcrd - 1) * RCRD_LEN;
+            assert_eq!(dbl_at(offset + 8) as usize, prev);
+            let count = dbl_at(offset + 16) as usize;
+            assert!(count <= SUMMARIES_PER_RCRD);
+            for idx in 0..count {
+                let summary = offset + 8 * (3 + idx * SUMMARY_DBL);
+                // Start and end epochs, and then the start and end addresses of the data
+                summaries.push((
+                    dbl_at(summary),
+                    dbl_at(summary + 8),
+                    int_at(summary + 32),
+                    int_at(summary + 36),
+                ));
+            }
+            prev = rcrd;
+            rcrd = dbl_at(offset) as usize;
+        }
+        assert_eq!(prev, int_at(80) as usize, "last summary record");
+        assert_eq!(prev, 6);
+
+        assert_eq!(summaries.len(), 72);
+        assert_eq!(summaries[0].0, traj.first().epoch().to_et_seconds());
+        assert_eq!(summaries[71].1, traj.last().epoch().to_et_seconds());
+        assert_eq!(summaries[0].2 as usize, 7 * RCRD_DBL + 1);
+        for pair in summaries.windows(2) {
+            assert_eq!(pair[0].1, pair[1].0);
+            assert_eq!(pair[0].3 + 1, pair[1].2);
+        }
+        // The first free address follows the data of the last segment.
+        assert_eq!(int_at(84), summaries[71].3 + 1);
+    }
+}
@@ -0,0 +1,374 @@
+/*
+    Nyx, blazing fast astrodynamics
+    Copyright (C) 2018-onwards Christopher Rabotin <christopher.rabotin@gmail.com>
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published
+    by the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use anise::almanac::Almanac;
+use anise::prelude::Frame;
+use snafu::ResultExt;
+
+use super::{Interpolatable, Traj, TrajError};
+use crate::errors::{FromAlmanacSnafu, NyxError};
+use crate::linalg::allocator::Allocator;
+use crate::linalg::{DefaultAllocator, Vector3};
+use crate::time::{Duration, Epoch, TimeSeries};
+use std::fmt;
+use std::sync::Arc;
+
+/// RIC difference between two trajectories at a single epoch, computed in the RIC frame of the first trajectory.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RicDifference {
+    pub epoch: Epoch,
+    /// Radial, in-track and cross-track position difference
+    pub pos_km: Vector3<f64>,
+    /// Radial, in-track and cross-track velocity difference, accounting for the transport theorem
+    pub vel_km_s: Vector3<f64>,
+}
+
+/// Summary statistics of one component of the differences between two trajectories.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DiffStatistics {
+    pub rms: f64,
+    /// Largest absolute value of the difference
+    pub max_abs: f64,
+    pub epoch_of_max: Epoch,
+    /// Slope of the least squares line fitted through the difference, per second
+    pub drift_rate_per_s: f64,
+}
+
+impl DiffStatistics {
+    fn new(epochs: &[Epoch], values: &[f64]) -> Self {
+        let n = values.len() as f64;
+        let rms = (values.iter().map(|val| val.powi(2)).sum::<f64>() / n).sqrt();
+
+        let (idx_of_max, max_abs) = values.iter().map(|val| val.abs()).enumerate().fold(
+            (0, 0.0),
+            |(best_idx, best), (idx, val)| {
+                if val > best {
+                    (idx, val)
+                } else {
+                    (best_idx, best)
+                }
+            },
+        );
+
+        // Least squares slope with respect to the elapsed time since the first epoch
+        let times: Vec<f64> = epochs
+            .iter()
+            .map(|epoch| (*epoch - epochs[0]).to_seconds())
+            .collect();
+        let mean_t = times.iter().sum::<f64>() / n;
+        let mean_v = values.iter().sum::<f64>() / n;
+        let (cov_tv, var_t) = times
+            .iter()
+            .zip(values)
+            .fold((0.0, 0.0), |(cov, var), (t, v)| {
+                (
+                    cov + (t - mean_t) * (v - mean_v),
+                    var + (t - mean_t).powi(2),
+                )
+            });
+        let drift_rate_per_s = if var_t > 0.0 { cov_tv / var_t } else { 0.0 };
+
+        Self {
+            rms,
+            max_abs,
+            epoch_of_max: epochs[idx_of_max],
+            drift_rate_per_s,
+        }
+    }
+}
+
+/// Comparison of two trajectories on a common time grid, cf. [`Traj::compare`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct TrajComparison {
+    /// Frame in which the trajectories were compared, i.e. the frame of the first trajectory
+    pub frame: Frame,
+    pub differences: Vec<RicDifference>,
+    pub radial_km: DiffStatistics,
+    pub in_track_km: DiffStatistics,
+    pub cross_track_km: DiffStatistics,
+    /// Statistics of the norm of the position difference
+    pub position_km: DiffStatistics,
+    pub radial_km_s: DiffStatistics,
+    pub in_track_km_s: DiffStatistics,
+    pub cross_track_km_s: DiffStatistics,
+    /// Statistics of the norm of the velocity difference
+    pub velocity_km_s: DiffStatistics,
+}
+
+impl TrajComparison {
+    fn new(frame: Frame, differences: Vec<RicDifference>) -> Self {
+        let epochs: Vec<Epoch> = differences.iter().map(|diff| diff.epoch).collect();
+        let stats = |value: fn(&RicDifference) -> f64| {
+            let values: Vec<f64> = differences.iter().map(value).collect();
+            DiffStatistics::new(&epochs, &values)
+        };
+
+        Self {
+            frame,
+            radial_km: stats(|diff| diff.pos_km.x),
+            in_track_km: stats(|diff| diff.pos_km.y),
+            cross_track_km: stats(|diff| diff.pos_km.z),
+            position_km: stats(|diff| diff.pos_km.norm()),
+            radial_km_s: stats(|diff| diff.vel_km_s.x),
+            in_track_km_s: stats(|diff| diff.vel_km_s.y),
+            cross_track_km_s: stats(|diff| diff.vel_km_s.z),
+            velocity_km_s: stats(|diff| diff.vel_km_s.norm()),
+            differences,
+        }
+    }
+}
+
+impl fmt::Display for TrajComparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Comparison in {} of {} epochs from {} to {}",
+            self.frame,
+            self.differences.len(),
+            self.differences[0].epoch,
+            self.differences[self.differences.len() - 1].epoch
+        )?;
+        for (name, stats) in [
+            ("radial (km)", &self.radial_km),
+            ("in-track (km)", &self.in_track_km),
+            ("cross-track (km)", &self.cross_track_km),
+            ("position (km)", &self.position_km),
+            ("radial (km/s)", &self.radial_km_s),
+            ("in-track (km/s)", &self.in_track_km_s),
+            ("cross-track (km/s)", &self.cross_track_km_s),
+            ("velocity (km/s)", &self.velocity_km_s),
+        ] {
+            writeln!(
+                f,
+                "{name:>18}: RMS = {:.6e}\tmax = {:.6e} at {}\tdrift = {:.6e} /s",
+                stats.rms, stats.max_abs, stats.epoch_of_max, stats.drift_rate_per_s
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl<S: Interpolatable> Traj<S>
+where
+    DefaultAllocator: Allocator<S::VecLength> + Allocator<S::Size> + Allocator<S::Size, S::Size>,
+{
+    /// Compares this trajectory to the "other" trajectory every `step` over their overlapping time span, in the RIC frame of this trajectory.
+    ///
+    /// Both trajectories are interpolated on the common time grid, so they may have different sampling.
+    /// If the other trajectory is in another frame, its states are transformed into the frame of this trajectory with the almanac.
+    ///
+    /// # Notes
+    /// Unlike [`Traj::ric_diff_to_parquet`], the differences are not smoothed, such that the statistics are those of the raw differences.
+    pub fn compare(
+        &self,
+        other: &Self,
+        step: Duration,
+        almanac: Option<Arc<Almanac>>,
+    ) -> Result<TrajComparison, NyxError> {
+        if self.states.is_empty() || other.states.is_empty() {
+            return Err(NyxError::Trajectory {
+                source: TrajError::CreationError {
+                    msg: "Cannot compare empty trajectories".to_string(),
+                },
+            });
+        }
+
+        let frame = self.first().orbit().frame;
+        let other_frame = other.first().orbit().frame;
+        let same_frame =
+            frame.ephem_origin_match(other_frame) && frame.orient_origin_match(other_frame);
+        if !same_frame && almanac.is_none() {
+            return Err(NyxError::Trajectory {
+                source: TrajError::CreationError {
+                    msg: format!(
+                        "Comparing {frame} to {other_frame} requires an almanac to transform the states"
+                    ),
+                },
+            });
+        }
+
+        let start = self.first().epoch().max(other.first().epoch());
+        let end = self.last().epoch().min(other.last().epoch());
+        if start > end {
+            return Err(NyxError::Trajectory {
+                source: TrajError::CreationError {
+                    msg: format!(
+                        "Trajectories do not overlap: {} - {} and {} - {}",
+                        self.first().epoch(),
+                        self.last().epoch(),
+                        other.first().epoch(),
+                        other.last().epoch()
+                    ),
+                },
+            });
+        }
+
+        let mut differences = Vec::new();
+        for epoch in TimeSeries::inclusive(start, end, step) {
+            let self_orbit = self.at(epoch)?.orbit();
+            let mut other_orbit = other.at(epoch)?.orbit();
+            if !same_frame {
+                if let Some(almanac) = &almanac {
+                    other_orbit = almanac.transform_to(other_orbit, frame, None).context(
+                        FromAlmanacSnafu {
+                            action: "transforming trajectory for comparison",
+                        },
+                    )?;
+                }
+            }
+
+            let ric_diff =
+                self_orbit
+                    .ric_difference(&other_orbit)
+                    .map_err(|e| NyxError::MathDomain {
+                        msg: format!("RIC difference at {epoch}: {e}"),
+                    })?;
+            differences.push(RicDifference {
+                epoch,
+                pos_km: ric_diff.radius_km,
+                vel_km_s: ric_diff.velocity_km_s,
+            });
+        }
+
+        Ok(TrajComparison::new(frame, differences))
+    }
+}
+
+#[cfg(test)]
+mod ut_comparison {
+    use super::super::fixtures;
+    use crate::errors::NyxError;
+    use crate::linalg::Vector3;
+    use crate::md::prelude::{BspExportCfg, Frame, Traj};
+    use crate::time::Unit;
+    use crate::{Spacecraft, State};
+    use anise::prelude::Almanac;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    fn leo_traj() -> Traj<Spacecraft> {
+        let path: PathBuf = [
+            env!("CARGO_MANIFEST_DIR"),
+            "data",
+            "03_tests",
+            "ccsds",
+            "oem",
+            "LEO_10s.oem",
+        ]
+        .iter()
+        .collect();
+        Traj::from_oem_file(path, None).unwrap()
+    }
+
+    #[test]
+    fn compare_drifting_traj() {
+        let traj = leo_traj();
+
+        // Shift the other trajectory radially by 10 meters per minute, and resample it
+        let start = traj.first().epoch();
+        let mut other = traj.clone();
+        for state in other.states.iter_mut() {
+            let minutes = (state.epoch() - start).to_unit(Unit::Minute);
+            let r_hat = state.orbit.radius_km / state.orbit.rmag_km();
+            state.orbit.radius_km += r_hat * 1e-2 * minutes;
+        }
+        let other = other.resample(Unit::Second * 30).unwrap();
+
+        let cmp = traj.compare(&other, Unit::Minute * 1, None).unwrap();
+
+        assert_eq!(cmp.differences[0].epoch, start);
+        assert!(cmp.differences[0].pos_km.norm() < 1e-9);
+        let last = cmp.differences.last().unwrap();
+        assert_eq!(cmp.position_km.epoch_of_max, last.epoch);
+        let minutes = (last.epoch - start).to_unit(Unit::Minute);
+        // Self minus other is negative in the radial direction
+        assert!((last.pos_km - Vector3::new(-1e-2 * minutes, 0.0, 0.0)).norm() < 1e-6);
+        assert!((cmp.radial_km.max_abs - 1e-2 * minutes).abs() < 1e-6);
+        assert!((cmp.radial_km.drift_rate_per_s + 1e-2 / 60.0).abs() < 1e-9);
+        assert!(cmp.in_track_km.max_abs < 1e-6);
+        assert!(cmp.cross_track_km.max_abs < 1e-6);
+    }
+
+    #[test]
+    fn compare_frames_with_almanac() {
+        let traj = leo_traj();
+
+        // Compare the trajectory to itself expressed relative to another spacecraft, whose ephemeris is loaded in the almanac.
+        let other_sc = fixtures::circular_traj(
+            traj.first().epoch(),
+            traj.last().epoch() - traj.first().epoch(),
+            Unit::Minute * 1,
+        );
+        let path: PathBuf = [
+            env!("CARGO_MANIFEST_DIR"),
+            "data",
+            "04_output",
+            "ut_comparison_other_sc.bsp",
+        ]
+        .iter()
+        .collect();
+        other_sc
+            .to_bsp(&path, BspExportCfg::builder().naif_id(-10000002).build())
+            .unwrap();
+        let almanac = Arc::new(Almanac::default().load(path.to_str().unwrap()).unwrap());
+
+        let other_sc_j2000 = Frame::from_ephem_j2000(-10000002);
+        let mut other = traj.clone();
+        for state in other.states.iter_mut() {
+            state.orbit = almanac
+                .transform_to(state.orbit, other_sc_j2000, None)
+                .unwrap();
+        }
+        assert!((other.first().orbit.radius_km - traj.first().orbit.radius_km).norm() > 1.0);
+
+        let cmp = traj
+            .compare(&other, Unit::Minute * 1, Some(almanac))
+            .unwrap();
+        assert_eq!(cmp.frame, traj.first().orbit.frame);
+        assert!(!cmp.differences.is_empty());
+        for diff in &cmp.differences {
+            assert!(
+                diff.pos_km.norm() < 1e-9,
+                "{} km at {}",
+                diff.pos_km.norm(),
+                diff.epoch
+            );
+            assert!(
+                diff.vel_km_s.norm() < 1e-12,
+                "{} km/s at {}",
+                diff.vel_km_s.norm(),
+                diff.epoch
+            );
+        }
+    }
+
+    #[test]
+    fn compare_frames_without_almanac() {
+        let traj = leo_traj();
+        let mut other = traj.clone();
+        let moon_j2000 = Frame::from_name("Moon", "J2000").unwrap();
+        for state in other.states.iter_mut() {
+            state.orbit.frame = moon_j2000;
+        }
+
+        assert!(matches!(
+            traj.compare(&other, Unit::Minute * 1, None),
+            Err(NyxError::Trajectory { .. })
+        ));
+    }
+}
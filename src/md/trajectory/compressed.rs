@@ -0,0 +1,443 @@
+/*
+    Nyx, blazing fast astrodynamics
+    Copyright (C) 2018-onwards Christopher Rabotin <christopher.rabotin@gmail.com>
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published
+    by the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use super::{Traj, TrajError};
+use crate::cosmic::Frame;
+use crate::polyfit::ChebyshevSeries;
+use crate::time::{Duration, Epoch, TimeSeries, Unit};
+use crate::{Orbit, Spacecraft, State};
+use log::{debug, info};
+use std::fmt;
+use typed_builder::TypedBuilder;
+
+/// Configuration of the Chebyshev compression of a spacecraft trajectory, cf. [`Traj::compress`].
+#[derive(Copy, Clone, Debug, TypedBuilder)]
+#[builder(doc)]
+pub struct CompressionCfg {
+    /// Duration of each segment. This interval is halved until every segment is within the tolerance.
+    #[builder(default = Unit::Hour * 1)]
+    pub interval: Duration,
+    /// Maximum position error of the compressed trajectory, in km
+    #[builder(default = 1e-6)]
+    pub tolerance_km: f64,
+    /// Maximum velocity error of the compressed trajectory, in km/s
+    #[builder(default = 1e-9)]
+    pub tolerance_km_s: f64,
+    /// Maximum degree of the Chebyshev series of each segment
+    #[builder(default = 24)]
+    pub max_degree: usize,
+    /// Smallest interval allowed when halving the segments to meet the tolerance
+    #[builder(default = Unit::Second * 60)]
+    pub min_interval: Duration,
+}
+
+impl Default for CompressionCfg {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+/// A segment of a compressed trajectory, valid from its start epoch for its duration.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChebyshevSegment {
+    pub start: Epoch,
+    pub duration: Duration,
+    /// Spacecraft at the start of the segment, used for all of the parameters which are not fitted
+    pub template: Spacecraft,
+    /// Position series, in km
+    pub position_km: [ChebyshevSeries; 3],
+    /// Velocity series, in km/s
+    pub velocity_km_s: [ChebyshevSeries; 3],
+    /// Propellant mass series, in kg
+    pub prop_mass_kg: ChebyshevSeries,
+}
+
+impl ChebyshevSegment {
+    /// Fits a segment of the provided degree to the trajectory, in the least squares sense, cf. [`Samples`].
+    fn fit(
+        samples: &Samples,
+        start: Epoch,
+        duration: Duration,
+        degree: usize,
+    ) -> Result<Self, TrajError> {
+        let states = &samples.fit;
+
+        let taus: Vec<f64> = states
+            .iter()
+            .map(|state| 2.0 * (state.epoch() - start).to_seconds() / duration.to_seconds() - 1.0)
+            .collect();
+        let fit_component =
+            |value: &dyn Fn(&Spacecraft) -> f64| -> Result<ChebyshevSeries, TrajError> {
+                let values: Vec<f64> = states.iter().map(value).collect();
+                ChebyshevSeries::least_squares(&taus, &values, degree).ok_or_else(|| {
+                    TrajError::CreationError {
+                        msg: format!(
+                            "Chebyshev fit of degree {degree} failed for segment starting {start}"
+                        ),
+                    }
+                })
+            };
+
+        let mut template = samples.start;
+        template.stm = None;
+
+        Ok(Self {
+            start,
+            duration,
+            template,
+            position_km: [
+                fit_component(&|sc| sc.orbit.radius_km.x)?,
+                fit_component(&|sc| sc.orbit.radius_km.y)?,
+                fit_component(&|sc| sc.orbit.radius_km.z)?,
+            ],
+            velocity_km_s: [
+                fit_component(&|sc| sc.orbit.velocity_km_s.x)?,
+                fit_component(&|sc| sc.orbit.velocity_km_s.y)?,
+                fit_component(&|sc| sc.orbit.velocity_km_s.z)?,
+            ],
+            prop_mass_kg: fit_component(&|sc| sc.mass.prop_mass_kg)?,
+        })
+    }
+
+    /// Evaluates this segment at the provided epoch, which must be within the segment.
+    pub fn at(&self, epoch: Epoch, frame: Frame) -> Spacecraft {
+        let tau = 2.0 * (epoch - self.start).to_seconds() / self.duration.to_seconds() - 1.0;
+        let orbit = Orbit::new(
+            self.position_km[0].eval(tau),
+            self.position_km[1].eval(tau),
+            self.position_km[2].eval(tau),
+            self.velocity_km_s[0].eval(tau),
+            self.velocity_km_s[1].eval(tau),
+            self.velocity_km_s[2].eval(tau),
+            epoch,
+            frame,
+        );
+        let mut sc = self.template.with_orbit(orbit);
+        sc.mass.prop_mass_kg = self.prop_mass_kg.eval(tau);
+        sc
+    }
+
+    /// Returns the degree of the series of this segment
+    pub fn degree(&self) -> usize {
+        self.position_km[0].degree()
+    }
+
+    /// Returns the largest position and velocity errors of this segment with respect to the samples of the trajectory,
+    /// including those between the samples used for the fit, cf. [`Samples`].
+    fn max_errors(&self, samples: &Samples) -> (f64, f64) {
+        let mut max_err_km = 0.0_f64;
+        let mut max_err_km_s = 0.0_f64;
+        for truth in samples.fit.iter().chain(&samples.check) {
+            let fitted = self.at(truth.epoch(), truth.orbit.frame);
+            max_err_km = max_err_km.max((truth.orbit.radius_km - fitted.orbit.radius_km).norm());
+            max_err_km_s =
+                max_err_km_s.max((truth.orbit.velocity_km_s - fitted.orbit.velocity_km_s).norm());
+        }
+        (max_err_km, max_err_km_s)
+    }
+}
+
+/// States of the trajectory used to fit a segment of a given degree, and to check the errors of this fit.
+///
+/// The fit uses every other stored state of the trajectory, and its last one, to avoid fitting the interpolation noise. The errors
+/// are then checked at the states in between, which the fit does not constrain. If the segment contains too few stored states,
+/// the trajectory is also interpolated at the Chebyshev nodes for the fit, and at the nodes of the next degree, which are
+/// interleaved with them, for the check.
+struct Samples {
+    /// State at the start of the segment
+    start: Spacecraft,
+    fit: Vec<Spacecraft>,
+    check: Vec<Spacecraft>,
+}
+
+impl Samples {
+    fn new(
+        traj: &Traj<Spacecraft>,
+        start: Epoch,
+        duration: Duration,
+        degree: usize,
+    ) -> Result<Self, TrajError> {
+        let stored = stored_states(traj, start, duration);
+        let (mut fit, mut check) = (Vec::new(), Vec::new());
+        if stored.len() >= 2 * (degree + 1) {
+            for (idx, state) in stored.iter().enumerate() {
+                if idx % 2 == 0 || idx == stored.len() - 1 {
+                    fit.push(*state);
+                } else {
+                    check.push(*state);
+                }
+            }
+        } else {
+            fit = stored;
+            for tau in ChebyshevSeries::nodes(degree + 1) {
+                fit.push(traj.at(start + duration * (0.5 * (tau + 1.0)))?);
+            }
+            for tau in ChebyshevSeries::nodes(degree + 2) {
+                check.push(traj.at(start + duration * (0.5 * (tau + 1.0)))?);
+            }
+        }
+
+        Ok(Self {
+            start: traj.at(start)?,
+            fit,
+            check,
+        })
+    }
+}
+
+/// Returns the states of the trajectory between `start` and `start + duration`, both included.
+fn stored_states(traj: &Traj<Spacecraft>, start: Epoch, duration: Duration) -> Vec<Spacecraft> {
+    let first = traj.states.partition_point(|state| state.epoch() < start);
+    let last = traj
+        .states
+        .partition_point(|state| state.epoch() <= start + duration);
+    traj.states[first..last].to_vec()
+}
+
+/// A spacecraft trajectory compressed into fixed-interval Chebyshev segments.
+///
+/// Compared to a [`Traj`], which stores every integrator step and interpolates over neighboring states on each query,
+/// the segment of any epoch is found in constant time and evaluated directly.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChebyshevTraj {
+    /// Optionally name this trajectory
+    pub name: Option<String>,
+    pub frame: Frame,
+    pub start: Epoch,
+    pub end: Epoch,
+    /// Duration of every segment, except possibly the last one which ends at the end of the trajectory
+    pub interval: Duration,
+    pub segments: Vec<ChebyshevSegment>,
+}
+
+impl ChebyshevTraj {
+    /// Evaluate the compressed trajectory at this specific epoch.
+    pub fn at(&self, epoch: Epoch) -> Result<Spacecraft, TrajError> {
+        if epoch < self.start || epoch > self.end || self.segments.is_empty() {
+            return Err(TrajError::NoInterpolationData { epoch });
+        }
+        let idx = (((epoch - self.start).to_seconds() / self.interval.to_seconds()).floor()
+            as usize)
+            .min(self.segments.len() - 1);
+        Ok(self.segments[idx].at(epoch, self.frame))
+    }
+
+    /// Returns the first state of this trajectory
+    pub fn first(&self) -> Result<Spacecraft, TrajError> {
+        self.at(self.start)
+    }
+
+    /// Returns the last state of this trajectory
+    pub fn last(&self) -> Result<Spacecraft, TrajError> {
+        self.at(self.end)
+    }
+
+    /// Rebuilds a trajectory by sampling this compressed trajectory with the provided step, e.g. for exporting it.
+    pub fn to_traj(&self, step: Duration) -> Result<Traj<Spacecraft>, TrajError> {
+        let mut traj = Traj::new();
+        traj.name.clone_from(&self.name);
+        for epoch in TimeSeries::inclusive(self.start, self.end, step) {
+            traj.states.push(self.at(epoch)?);
+        }
+        if traj.last().epoch() != self.end {
+            traj.states.push(self.at(self.end)?);
+        }
+        Ok(traj)
+    }
+}
+
+impl fmt::Display for ChebyshevTraj {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let max_degree = self.segments.iter().map(|s| s.degree()).max();
+        write!(
+            f,
+            "Chebyshev trajectory {}in {:x} from {} to {} ({}, or {}) with {} segments of {} (max degree {})",
+            match &self.name {
+                Some(name) => format!("of {name} "),
+                None => String::new(),
+            },
+            self.frame,
+            self.start,
+            self.end,
+            self.end - self.start,
+            (self.end - self.start).to_unit(Unit::Day),
+            self.segments.len(),
+            self.interval,
+            max_degree.unwrap_or(0)
+        )
+    }
+}
+
+impl Traj<Spacecraft> {
+    /// Compresses this trajectory into fixed-interval Chebyshev segments, cf. [`ChebyshevTraj`].
+    ///
+    /// The degree of each segment is increased until the segment is within the position and velocity tolerances.
+    /// If any segment cannot meet the tolerances with the maximum degree, all of the segments are halved and fitted again,
+    /// such that the segment of any epoch can still be found in constant time.
+    pub fn compress(&self, cfg: CompressionCfg) -> Result<ChebyshevTraj, TrajError> {
+        if self.states.len() < 2 {
+            return Err(TrajError::CreationError {
+                msg: "cannot compress a trajectory with fewer than two states".to_string(),
+            });
+        }
+
+        let start = self.first().epoch();
+        let end = self.last().epoch();
+        let mut interval = cfg.interval.min(end - start);
+
+        'halving: loop {
+            if interval < cfg.min_interval {
+                return Err(TrajError::CreationError {
+                    msg: format!(
+                        "Chebyshev compression needs segments shorter than {} to meet the tolerances",
+                        cfg.min_interval
+                    ),
+                });
+            }
+
+            let mut segments = Vec::new();
+            let mut seg_start = start;
+            while seg_start < end {
+                let duration = interval.min(end - seg_start);
+                let mut degree = 3;
+                loop {
+                    let samples = Samples::new(self, seg_start, duration, degree)?;
+                    let segment = ChebyshevSegment::fit(&samples, seg_start, duration, degree)?;
+                    let (err_km, err_km_s) = segment.max_errors(&samples);
+                    if err_km <= cfg.tolerance_km && err_km_s <= cfg.tolerance_km_s {
+                        segments.push(segment);
+                        break;
+                    } else if degree >= cfg.max_degree {
+                        debug!(
+                            "segment starting {seg_start} exceeds tolerances with degree {degree} ({err_km:e} km, {err_km_s:e} km/s)"
+                        );
+                        interval = interval * 0.5;
+                        continue 'halving;
+                    }
+                    degree = (degree + 2).min(cfg.max_degree);
+                }
+                seg_start += duration;
+            }
+
+            let compressed = ChebyshevTraj {
+                name: self.name.clone(),
+                frame: self.first().orbit.frame,
+                start,
+                end,
+                interval,
+                segments,
+            };
+
+            info!(
+                "Compressed {} states into {} segments",
+                self.states.len(),
+                compressed.segments.len()
+            );
+
+            return Ok(compressed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod ut_compressed {
+    use super::super::fixtures::{self, circular_state};
+    use super::CompressionCfg;
+    use crate::md::prelude::Traj;
+    use crate::time::{Epoch, TimeSeries, Unit};
+    use crate::{Spacecraft, State};
+
+    /// Builds a trajectory of the circular orbit over a day, sampled every 30 seconds.
+    fn circular_traj() -> Traj<Spacecraft> {
+        let start = Epoch::from_gregorian_utc_at_midnight(2024, 1, 1);
+        fixtures::circular_traj(start, Unit::Day * 1, Unit::Second * 30)
+    }
+
+    #[test]
+    fn round_trip() {
+        let traj = circular_traj();
+        let cfg = CompressionCfg::builder()
+            .tolerance_km(1e-6)
+            .tolerance_km_s(1e-9)
+            .build();
+        let compressed = traj.compress(cfg).unwrap();
+        assert!(compressed.segments.len() < traj.states.len() / 10);
+
+        // The tolerances also hold between the states of the trajectory, which are 30 seconds apart.
+        for epoch in TimeSeries::inclusive(
+            traj.first().epoch() + Unit::Second * 15,
+            traj.last().epoch(),
+            Unit::Second * 30,
+        ) {
+            let truth = circular_state(traj.first().epoch(), epoch);
+            let fitted = compressed.at(epoch).unwrap();
+            let err_km = (truth.orbit.radius_km - fitted.orbit.radius_km).norm();
+            let err_km_s = (truth.orbit.velocity_km_s - fitted.orbit.velocity_km_s).norm();
+            assert!(err_km <= cfg.tolerance_km, "{err_km} km error at {epoch}");
+            assert!(
+                err_km_s <= cfg.tolerance_km_s,
+                "{err_km_s} km/s error at {epoch}"
+            );
+        }
+
+        for epoch in TimeSeries::inclusive(
+            traj.first().epoch(),
+            traj.last().epoch(),
+            Unit::Second * 137,
+        ) {
+            let truth = circular_state(traj.first().epoch(), epoch);
+            let fitted = compressed.at(epoch).unwrap();
+            assert_eq!(fitted.epoch(), epoch);
+            let err_km = (truth.orbit.radius_km - fitted.orbit.radius_km).norm();
+            let err_km_s = (truth.orbit.velocity_km_s - fitted.orbit.velocity_km_s).norm();
+            assert!(err_km < 1e-5, "{err_km} km error at {epoch}");
+            assert!(err_km_s < 1e-8, "{err_km_s} km/s error at {epoch}");
+            assert!((truth.mass.prop_mass_kg - fitted.mass.prop_mass_kg).abs() < 1e-6);
+        }
+
+        // Bounds are included, and nothing beyond.
+        assert!(compressed.first().is_ok());
+        assert!(compressed.last().is_ok());
+        assert!(compressed
+            .at(traj.last().epoch() + Unit::Second * 1)
+            .is_err());
+
+        let rebuilt = compressed.to_traj(Unit::Minute * 10).unwrap();
+        assert_eq!(rebuilt.last().epoch(), traj.last().epoch());
+    }
+
+    #[test]
+    fn halving() {
+        let traj = circular_traj();
+        // A single segment over a whole day cannot meet this tolerance with such a low degree.
+        let cfg = CompressionCfg::builder()
+            .interval(Unit::Day * 1)
+            .max_degree(9)
+            .build();
+        let compressed = traj.compress(cfg).unwrap();
+        assert!(compressed.interval < Unit::Day * 1);
+        assert!(compressed.segments.iter().all(|s| s.degree() <= 9));
+
+        let cfg = CompressionCfg::builder()
+            .interval(Unit::Day * 1)
+            .max_degree(3)
+            .min_interval(Unit::Hour * 1)
+            .build();
+        assert!(traj.compress(cfg).is_err());
+    }
+}
@@ -0,0 +1,802 @@
+/*
+    Nyx, blazing fast astrodynamics
+    Copyright (C) 2018-onwards Christopher Rabotin <christopher.rabotin@gmail.com>
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published
+    by the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use anise::prelude::{Almanac, Frame};
+use log::info;
+use quick_xml::escape::escape;
+use serde_json::{json, Value};
+use snafu::ResultExt;
+
+use super::Traj;
+use crate::cosmic::Spacecraft;
+use crate::errors::EventError;
+use crate::io::watermark::prj_name_ver;
+use crate::io::{InputOutputError, StdIOSnafu};
+use crate::od::GroundStation;
+use crate::time::{Duration, Epoch, Format, Formatter, TimeScale};
+use crate::State;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// Colors of the stations, cycled through if there are more stations than colors.
+const STATION_COLORS: [&str; 8] = [
+    "#e6194b", "#3cb44b", "#4363d8", "#f58231", "#911eb4", "#42d4f4", "#f032e6", "#9a6324",
+];
+
+/// Color of the ground track itself
+const TRACK_COLOR: &str = "#ffe119";
+
+/// Number of vertices of the elevation mask footprints
+const FOOTPRINT_VERTICES: usize = 72;
+
+/// Geodetic position of the spacecraft at a given epoch.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GroundTrackPoint {
+    pub epoch: Epoch,
+    pub latitude_deg: f64,
+    /// Longitude between -180 and 180 degrees
+    pub longitude_deg: f64,
+    pub height_km: f64,
+}
+
+/// Location of a ground station and the footprint of its elevation mask.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StationFootprint {
+    pub name: String,
+    pub latitude_deg: f64,
+    pub longitude_deg: f64,
+    pub height_km: f64,
+    pub elevation_mask_deg: f64,
+    /// Closed rings of (longitude, latitude) in degrees where a spacecraft at the mean height of the ground track is above the elevation mask.
+    /// The footprint is split at the antimeridian, so it has two rings if it crosses it.
+    pub footprint_deg: Vec<Vec<(f64, f64)>>,
+}
+
+/// Interval during which the spacecraft is above the elevation mask of a ground station.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StationAccess {
+    pub station: String,
+    pub start: Epoch,
+    pub end: Epoch,
+    /// Ground track during the access, split at the antimeridian
+    pub track: Vec<Vec<GroundTrackPoint>>,
+}
+
+impl StationAccess {
+    pub fn duration(&self) -> Duration {
+        self.end - self.start
+    }
+}
+
+/// Ground track of a spacecraft along with the footprints of and the accesses to ground stations, exportable to GIS tools.
+///
+/// The ground track is split at the antimeridian and at the boundaries of the trajectory, such that no line wraps around the globe.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GroundTrack {
+    pub name: String,
+    pub track: Vec<Vec<GroundTrackPoint>>,
+    pub stations: Vec<StationFootprint>,
+    pub accesses: Vec<StationAccess>,
+}
+
+impl GroundTrack {
+    fn station_color(&self, station: &str) -> &'static str {
+        let idx = self
+            .stations
+            .iter()
+            .position(|footprint| footprint.name == station)
+            .unwrap_or(0);
+        STATION_COLORS[idx % STATION_COLORS.len()]
+    }
+
+    /// Exports the ground track, the stations with their footprints, and the accesses as a GeoJSON feature collection.
+    ///
+    /// The styling properties follow the simplestyle specification, where each station and its accesses share the same color.
+    pub fn to_geojson_file<P: AsRef<Path>>(&self, path: P) -> Result<PathBuf, InputOutputError> {
+        let line_coords = |track: &[Vec<GroundTrackPoint>]| -> Value {
+            track
+                .iter()
+                .map(|line| {
+                    line.iter()
+                        .map(|pt| json!([pt.longitude_deg, pt.latitude_deg, pt.height_km * 1e3]))
+                        .collect::<Vec<Value>>()
+                })
+                .collect::<Vec<Vec<Value>>>()
+                .into()
+        };
+
+        let mut features = vec![json!({
+            "type": "Feature",
+            "geometry": {"type": "MultiLineString", "coordinates": line_coords(&self.track)},
+            "properties": {
+                "name": self.name,
+                "kind": "ground track",
+                "start": self.track.first().and_then(|line| line.first()).map(|pt| iso8601(pt.epoch)),
+                "end": self.track.last().and_then(|line| line.last()).map(|pt| iso8601(pt.epoch)),
+                "stroke": TRACK_COLOR,
+            }
+        })];
+
+        for station in &self.stations {
+            let color = self.station_color(&station.name);
+            features.push(json!({
+                "type": "Feature",
+                "geometry": {
+                    "type": "Point",
+                    "coordinates": [station.longitude_deg, station.latitude_deg, station.height_km * 1e3]
+                },
+                "properties": {
+                    "name": station.name,
+                    "kind": "station",
+                    "elevation_mask_deg": station.elevation_mask_deg,
+                    "marker-color": color,
+                }
+            }));
+            let polygons = station
+                .footprint_deg
+                .iter()
+                .map(|ring| {
+                    json!([ring
+                        .iter()
+                        .map(|(lon, lat)| json!([lon, lat]))
+                        .collect::<Vec<Value>>()])
+                })
+                .collect::<Vec<Value>>();
+            let geometry = if polygons.len() == 1 {
+                json!({"type": "Polygon", "coordinates": polygons[0]})
+            } else {
+                json!({"type": "MultiPolygon", "coordinates": polygons})
+            };
+            features.push(json!({
+                "type": "Feature",
+                "geometry": geometry,
+                "properties": {
+                    "name": format!("{} footprint", station.name),
+                    "kind": "footprint",
+                    "station": station.name,
+                    "stroke": color,
+                    "fill": color,
+                    "fill-opacity": 0.2,
+                }
+            }));
+        }
+
+        for access in &self.accesses {
+            features.push(json!({
+                "type": "Feature",
+                "geometry": {"type": "MultiLineString", "coordinates": line_coords(&access.track)},
+                "properties": {
+                    "name": format!("{} access", access.station),
+                    "kind": "access",
+                    "station": access.station,
+                    "start": iso8601(access.start),
+                    "end": iso8601(access.end),
+                    "duration_s": access.duration().to_seconds(),
+                    "stroke": self.station_color(&access.station),
+                    "stroke-width": 4,
+                }
+            }));
+        }
+
+        let collection = json!({
+            "type": "FeatureCollection",
+            "name": self.name,
+            "generator": prj_name_ver(),
+            "features": features,
+        });
+
+        let path_buf = path.as_ref().to_path_buf();
+        let file = File::create(&path_buf).context(StdIOSnafu {
+            action: "creating GeoJSON file",
+        })?;
+        let mut writer = BufWriter::new(file);
+        writeln!(writer, "{collection:#}").context(StdIOSnafu {
+            action: "writing GeoJSON file",
+        })?;
+
+        info!("Ground track written to {}", path_buf.display());
+        Ok(path_buf)
+    }
+
+    /// Exports the ground track, the stations with their footprints, and the accesses as a KML document.
+    ///
+    /// Each access has a time span, such that it can be animated with the time slider of the GIS tool.
+    pub fn to_kml_file<P: AsRef<Path>>(&self, path: P) -> Result<PathBuf, InputOutputError> {
+        let path_buf = path.as_ref().to_path_buf();
+        let file = File::create(&path_buf).context(StdIOSnafu {
+            action: "creating KML file",
+        })?;
+        let mut writer = BufWriter::new(file);
+        self.write_kml(&mut writer).context(StdIOSnafu {
+            action: "writing KML file",
+        })?;
+
+        info!("Ground track written to {}", path_buf.display());
+        Ok(path_buf)
+    }
+
+    fn write_kml<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        let write_lines = |writer: &mut W, track: &[Vec<GroundTrackPoint>]| {
+            writeln!(writer, "      <MultiGeometry>")?;
+            for line in track {
+                let coords = line
+                    .iter()
+                    .map(|pt| {
+                        format!(
+                            "{},{},{}",
+                            pt.longitude_deg,
+                            pt.latitude_deg,
+                            pt.height_km * 1e3
+                        )
+                    })
+                    .collect::<Vec<String>>()
+                    .join(" ");
+                writeln!(
+                    writer,
+                    "        <LineString><altitudeMode>absolute</altitudeMode><coordinates>{coords}</coordinates></LineString>"
+                )?;
+            }
+            writeln!(writer, "      </MultiGeometry>")
+        };
+
+        writeln!(writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(writer, r#"<kml xmlns="http://www.opengis.net/kml/2.2">"#)?;
+        writeln!(writer, "<Document>")?;
+        writeln!(writer, "  <name>{}</name>", escape(&self.name))?;
+        writeln!(
+            writer,
+            "  <description>Built by {}</description>",
+            escape(prj_name_ver())
+        )?;
+
+        writeln!(
+            writer,
+            "  <Style id=\"track\"><LineStyle><color>{}</color><width>2</width></LineStyle></Style>",
+            kml_color(TRACK_COLOR, "ff")
+        )?;
+        for (idx, station) in self.stations.iter().enumerate() {
+            let color = self.station_color(&station.name);
+            writeln!(
+                writer,
+                "  <Style id=\"station{idx}\"><IconStyle><color>{}</color></IconStyle><LineStyle><color>{}</color><width>4</width></LineStyle><PolyStyle><color>{}</color></PolyStyle></Style>",
+                kml_color(color, "ff"),
+                kml_color(color, "ff"),
+                kml_color(color, "33")
+            )?;
+        }
+        let style_of = |station: &str| {
+            self.stations
+                .iter()
+                .position(|footprint| footprint.name == station)
+                .map(|idx| format!("#station{idx}"))
+                .unwrap_or_else(|| "#track".to_string())
+        };
+
+        writeln!(writer, "  <Folder><name>Ground track</name>")?;
+        writeln!(writer, "    <Placemark>")?;
+        writeln!(writer, "      <name>{}</name>", escape(&self.name))?;
+        writeln!(writer, "      <styleUrl>#track</styleUrl>")?;
+        write_lines(writer, &self.track)?;
+        writeln!(writer, "    </Placemark>")?;
+        writeln!(writer, "  </Folder>")?;
+
+        writeln!(writer, "  <Folder><name>Stations</name>")?;
+        for station in &self.stations {
+            writeln!(writer, "    <Placemark>")?;
+            writeln!(writer, "      <name>{}</name>", escape(&station.name))?;
+            writeln!(
+                writer,
+                "      <description>Elevation mask: {} deg</description>",
+                station.elevation_mask_deg
+            )?;
+            writeln!(
+                writer,
+                "      <styleUrl>{}</styleUrl>",
+                style_of(&station.name)
+            )?;
+            writeln!(
+                writer,
+                "      <Point><coordinates>{},{},{}</coordinates></Point>",
+                station.longitude_deg,
+                station.latitude_deg,
+                station.height_km * 1e3
+            )?;
+            writeln!(writer, "    </Placemark>")?;
+
+            let polygons = station
+                .footprint_deg
+                .iter()
+                .map(|ring| {
+                    let ring = ring
+                        .iter()
+                        .map(|(lon, lat)| format!("{lon},{lat}"))
+                        .collect::<Vec<String>>()
+                        .join(" ");
+                    format!("<Polygon><tessellate>1</tessellate><outerBoundaryIs><LinearRing><coordinates>{ring}</coordinates></LinearRing></outerBoundaryIs></Polygon>")
+                })
+                .collect::<String>();
+            writeln!(writer, "    <Placemark>")?;
+            writeln!(
+                writer,
+                "      <name>{} footprint</name>",
+                escape(&station.name)
+            )?;
+            writeln!(
+                writer,
+                "      <styleUrl>{}</styleUrl>",
+                style_of(&station.name)
+            )?;
+            writeln!(writer, "      <MultiGeometry>{polygons}</MultiGeometry>")?;
+            writeln!(writer, "    </Placemark>")?;
+        }
+        writeln!(writer, "  </Folder>")?;
+
+        writeln!(writer, "  <Folder><name>Accesses</name>")?;
+        for access in &self.accesses {
+            writeln!(writer, "    <Placemark>")?;
+            writeln!(
+                writer,
+                "      <name>{} access</name>",
+                escape(&access.station)
+            )?;
+            writeln!(
+                writer,
+                "      <description>Duration: {}</description>",
+                access.duration()
+            )?;
+            writeln!(
+                writer,
+                "      <TimeSpan><begin>{}</begin><end>{}</end></TimeSpan>",
+                iso8601(access.start),
+                iso8601(access.end)
+            )?;
+            writeln!(
+                writer,
+                "      <styleUrl>{}</styleUrl>",
+                style_of(&access.station)
+            )?;
+            write_lines(writer, &access.track)?;
+            writeln!(writer, "    </Placemark>")?;
+        }
+        writeln!(writer, "  </Folder>")?;
+
+        writeln!(writer, "</Document>")?;
+        writeln!(writer, "</kml>")
+    }
+}
+
+impl Traj<Spacecraft> {
+    /// Computes the ground track of this trajectory every `step` in the provided body fixed frame, along with the footprints of the ground stations
+    /// and the intervals during which the spacecraft is above their elevation mask.
+    ///
+    /// The footprints are computed for a spherical body and a spacecraft at the mean height of the ground track.
+    /// The accesses are computed in the frame of each ground station.
+    pub fn to_ground_track(
+        &self,
+        body_fixed_frame: Frame,
+        step: Duration,
+        stations: &[GroundStation],
+        almanac: Arc<Almanac>,
+    ) -> Result<GroundTrack, Box<dyn Error>> {
+        let body_fixed_frame = almanac.frame_info(body_fixed_frame)?;
+        let traj = self.to_frame(body_fixed_frame, almanac.clone())?;
+
+        let mut track = Vec::new();
+        for segment in traj.segments() {
+            let points = segment
+                .every(step)
+                .map(ground_track_point)
+                .collect::<Result<Vec<_>, _>>()?;
+            track.extend(split_at_antimeridian(&points));
+        }
+
+        let heights: Vec<f64> = track.iter().flatten().map(|pt| pt.height_km).collect();
+        let mean_height_km = heights.iter().sum::<f64>() / heights.len().max(1) as f64;
+        let body_radius_km = body_fixed_frame.mean_equatorial_radius_km()?;
+
+        let mut footprints = Vec::with_capacity(stations.len());
+        let mut accesses = Vec::new();
+        for station in stations {
+            footprints.push(StationFootprint {
+                name: station.name.clone(),
+                latitude_deg: station.latitude_deg,
+                longitude_deg: station.longitude_deg,
+                height_km: station.height_km,
+                elevation_mask_deg: station.elevation_mask_deg,
+                footprint_deg: footprint(
+                    station.latitude_deg,
+                    station.longitude_deg,
+                    station.elevation_mask_deg,
+                    body_radius_km,
+                    mean_height_km,
+                ),
+            });
+
+            let station_frame = almanac.frame_info(station.frame)?;
+            let station_traj = if station_frame == body_fixed_frame {
+                traj.clone()
+            } else {
+                self.to_frame(station_frame, almanac.clone())?
+            };
+
+            let arcs = match station_traj.find_arcs(station, None, almanac.clone()) {
+                Ok(arcs) => arcs,
+                // The spacecraft is never above the elevation mask of this station
+                Err(EventError::NotFound { .. }) => continue,
+                Err(e) => return Err(Box::new(e)),
+            };
+
+            for arc in arcs {
+                let start = arc.rise.state.epoch();
+                let end = arc.fall.state.epoch();
+                let mut points = traj
+                    .every_between(step, start, end)
+                    .map(ground_track_point)
+                    .collect::<Result<Vec<_>, _>>()?;
+                // Ensure that the access track ends exactly at the end of the access
+                if points.last().map(|pt| pt.epoch) != Some(end) {
+                    points.push(ground_track_point(traj.at(end)?)?);
+                }
+                accesses.push(StationAccess {
+                    station: station.name.clone(),
+                    start,
+                    end,
+                    track: split_at_antimeridian(&points),
+                });
+            }
+        }
+
+        accesses.sort_by_key(|access| access.start);
+
+        Ok(GroundTrack {
+            name: self
+                .name
+                .clone()
+                .unwrap_or_else(|| "Spacecraft".to_string()),
+            track,
+            stations: footprints,
+            accesses,
+        })
+    }
+}
+
+fn ground_track_point(state: Spacecraft) -> Result<GroundTrackPoint, Box<dyn Error>> {
+    let (latitude_deg, _, height_km) = state.orbit.latlongalt()?;
+    Ok(GroundTrackPoint {
+        epoch: state.epoch(),
+        latitude_deg,
+        longitude_deg: state.orbit.longitude_deg(),
+        height_km,
+    })
+}
+
+/// Splits the ground track into lines which do not cross the antimeridian, adding the interpolated crossing point to both lines.
+fn split_at_antimeridian(points: &[GroundTrackPoint]) -> Vec<Vec<GroundTrackPoint>> {
+    let mut lines = Vec::new();
+    let mut line: Vec<GroundTrackPoint> = Vec::new();
+
+    for point in points {
+        if let Some(prev) = line.last().copied() {
+            let delta_lon_deg = point.longitude_deg - prev.longitude_deg;
+            if delta_lon_deg.abs() > 180.0 {
+                // Unwrap the longitude of this point to find where the track crosses the antimeridian
+                let boundary_deg = 180.0_f64.copysign(prev.longitude_deg);
+                let unwrapped_deg = point.longitude_deg - 360.0_f64.copysign(delta_lon_deg);
+                let frac =
+                    (boundary_deg - prev.longitude_deg) / (unwrapped_deg - prev.longitude_deg);
+                let crossing = GroundTrackPoint {
+                    epoch: prev.epoch + (point.epoch - prev.epoch) * frac,
+                    latitude_deg: prev.latitude_deg
+                        + frac * (point.latitude_deg - prev.latitude_deg),
+                    longitude_deg: boundary_deg,
+                    height_km: prev.height_km + frac * (point.height_km - prev.height_km),
+                };
+                line.push(crossing);
+                lines.push(line);
+                line = vec![GroundTrackPoint {
+                    longitude_deg: -boundary_deg,
+                    ..crossing
+                }];
+            }
+        }
+        line.push(*point);
+    }
+
+    if line.len() > 1 {
+        lines.push(line);
+    }
+    lines
+}
+
+/// Clips the closed ring to the longitudes on the side of the provided bound which includes the prime meridian, adding the interpolated crossing points.
+fn clip_ring(ring: &[(f64, f64)], bound_deg: f64) -> Vec<(f64, f64)> {
+    let inside = |lon_deg: f64| lon_deg * bound_deg.signum() <= bound_deg.abs();
+    let mut clipped = Vec::with_capacity(ring.len() + 2);
+    for edge in ring.windows(2) {
+        let ((lon0, lat0), (lon1, lat1)) = (edge[0], edge[1]);
+        if inside(lon0) {
+            clipped.push(edge[0]);
+        }
+        if inside(lon0) != inside(lon1) {
+            let frac = (bound_deg - lon0) / (lon1 - lon0);
+            clipped.push((bound_deg, lat0 + frac * (lat1 - lat0)));
+        }
+    }
+    if let Some(first) = clipped.first().copied() {
+        clipped.push(first);
+    }
+    clipped
+}
+
+/// Splits the closed ring, whose longitudes are continuous, into closed rings which do not cross the antimeridian.
+fn split_ring_at_antimeridian(ring: &[(f64, f64)]) -> Vec<Vec<(f64, f64)>> {
+    // Each copy of the ring shifted by a full turn is clipped to the longitudes between -180 and 180 degrees.
+    [-360.0, 0.0, 360.0]
+        .iter()
+        .filter_map(|shift_deg| {
+            let shifted: Vec<(f64, f64)> = ring
+                .iter()
+                .map(|(lon_deg, lat_deg)| (lon_deg + shift_deg, *lat_deg))
+                .collect();
+            let clipped = clip_ring(&clip_ring(&shifted, 180.0), -180.0);
+            // A polygon needs at least three vertices besides the closing one
+            (clipped.len() > 3).then_some(clipped)
+        })
+        .collect()
+}
+
+/// Computes the closed rings of the footprint of the elevation mask of a station, on a spherical body, for a spacecraft at the provided height.
+/// The footprint is split at the antimeridian.
+fn footprint(
+    latitude_deg: f64,
+    longitude_deg: f64,
+    elevation_mask_deg: f64,
+    body_radius_km: f64,
+    height_km: f64,
+) -> Vec<Vec<(f64, f64)>> {
+    let mask = elevation_mask_deg.to_radians();
+    // Earth central angle between the station and the spacecraft seen at the elevation mask
+    let central_angle = ((body_radius_km * mask.cos() / (body_radius_km + height_km))
+        .clamp(-1.0, 1.0)
+        .acos()
+        - mask)
+        .max(0.0);
+
+    let lat = latitude_deg.to_radians();
+    let ring: Vec<(f64, f64)> = (0..=FOOTPRINT_VERTICES)
+        .map(|idx| {
+            let azimuth = (idx % FOOTPRINT_VERTICES) as f64 / FOOTPRINT_VERTICES as f64
+                * std::f64::consts::TAU;
+            let vertex_lat = (lat.sin() * central_angle.cos()
+                + lat.cos() * central_angle.sin() * azimuth.cos())
+            .asin();
+            let delta_lon = (azimuth.sin() * central_angle.sin() * lat.cos())
+                .atan2(central_angle.cos() - lat.sin() * vertex_lat.sin());
+            (
+                longitude_deg + delta_lon.to_degrees(),
+                vertex_lat.to_degrees(),
+            )
+        })
+        .collect();
+
+    split_ring_at_antimeridian(&ring)
+}
+
+/// Formats the epoch in UTC as an ISO 8601 timestamp understood by GIS tools.
+fn iso8601(epoch: Epoch) -> String {
+    format!(
+        "{}Z",
+        Formatter::new(
+            epoch.to_time_scale(TimeScale::UTC),
+            Format::from_str("%Y-%m-%dT%H:%M:%S.%f").unwrap()
+        )
+    )
+}
+
+/// Converts an RGB hex color to the KML "aabbggrr" format.
+fn kml_color(rgb: &str, alpha: &str) -> String {
+    format!("{alpha}{}{}{}", &rgb[5..7], &rgb[3..5], &rgb[1..3])
+}
+
+#[cfg(test)]
+mod ut_gis {
+    use super::{
+        footprint, kml_color, split_at_antimeridian, GroundTrack, GroundTrackPoint, StationAccess,
+        StationFootprint,
+    };
+    use crate::time::{Epoch, Unit};
+    use serde_json::Value;
+    use std::fs::read_to_string;
+    use std::path::PathBuf;
+
+    #[test]
+    fn antimeridian_split() {
+        let epoch = Epoch::from_gregorian_utc_at_midnight(2024, 1, 1);
+        let points: Vec<GroundTrackPoint> = [170.0, 178.0, -174.0, -166.0]
+            .iter()
+            .enumerate()
+            .map(|(idx, lon)| GroundTrackPoint {
+                epoch: epoch + Unit::Minute * idx as i64,
+                latitude_deg: 10.0 * idx as f64,
+                longitude_deg: *lon,
+                height_km: 500.0,
+            })
+            .collect();
+
+        let lines = split_at_antimeridian(&points);
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].len(), 3);
+        assert_eq!(lines[1].len(), 3);
+
+        // The track crosses the antimeridian a quarter of the way between the second and third points
+        let crossing = lines[0].last().unwrap();
+        assert_eq!(crossing.longitude_deg, 180.0);
+        assert!((crossing.latitude_deg - 12.5).abs() < 1e-12);
+        assert_eq!(crossing.epoch, epoch + Unit::Second * 75);
+        assert_eq!(lines[1][0].longitude_deg, -180.0);
+        assert_eq!(lines[1][0].epoch, crossing.epoch);
+
+        // Westward tracks are split too
+        let mut reversed = points.clone();
+        reversed.reverse();
+        let lines = split_at_antimeridian(&reversed);
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].last().unwrap().longitude_deg, -180.0);
+        assert_eq!(lines[1][0].longitude_deg, 180.0);
+    }
+
+    #[test]
+    fn station_footprint() {
+        // For a 500 km altitude and a 10 degree mask, the central angle is close to 16 degrees
+        let expected = ((6378.0 * 10.0_f64.to_radians().cos() / 6878.0).acos()
+            - 10.0_f64.to_radians())
+        .to_degrees();
+
+        let rings = footprint(0.0, 0.0, 10.0, 6378.0, 500.0);
+        assert_eq!(rings.len(), 1);
+        let ring = &rings[0];
+        assert_eq!(ring.len(), super::FOOTPRINT_VERTICES + 1);
+        assert_eq!(ring.first(), ring.last());
+        assert!((ring[0].1 - expected).abs() < 1e-9);
+        assert!(ring[0].0.abs() < 1e-9);
+        assert!((ring[FOOTPRINT_QUARTER].0 - expected).abs() < 1e-9);
+
+        // The footprint of a station near the antimeridian is split in two polygons on either side of it
+        let rings = footprint(0.0, 179.0, 10.0, 6378.0, 500.0);
+        assert_eq!(rings.len(), 2);
+        for ring in &rings {
+            assert_eq!(ring.first(), ring.last());
+            assert!(ring.iter().all(|(lon, _)| lon.abs() <= 180.0));
+        }
+        let (west, east) = (&rings[0], &rings[1]);
+        // The west part is the overflow of the footprint past the antimeridian
+        let west_min = west
+            .iter()
+            .map(|(lon, _)| *lon)
+            .fold(f64::INFINITY, f64::min);
+        let west_max = west
+            .iter()
+            .map(|(lon, _)| *lon)
+            .fold(f64::NEG_INFINITY, f64::max);
+        assert_eq!(west_min, -180.0);
+        assert!((west_max - (179.0 + expected - 360.0)).abs() < 1e-9);
+        let east_max = east
+            .iter()
+            .map(|(lon, _)| *lon)
+            .fold(f64::NEG_INFINITY, f64::max);
+        assert_eq!(east_max, 180.0);
+        assert!((east[0].0 - 179.0).abs() < 1e-9);
+        assert!((east[0].1 - expected).abs() < 1e-9);
+        // Both parts meet at the same latitudes on the antimeridian
+        let mut west_edge: Vec<f64> = west
+            .iter()
+            .filter(|(lon, _)| *lon == -180.0)
+            .map(|(_, lat)| *lat)
+            .collect();
+        let mut east_edge: Vec<f64> = east
+            .iter()
+            .filter(|(lon, _)| *lon == 180.0)
+            .map(|(_, lat)| *lat)
+            .collect();
+        west_edge.sort_by(f64::total_cmp);
+        west_edge.dedup();
+        east_edge.sort_by(f64::total_cmp);
+        east_edge.dedup();
+        assert_eq!(west_edge.len(), 2);
+        for (west_lat, east_lat) in west_edge.iter().zip(&east_edge) {
+            assert!((west_lat - east_lat).abs() < 1e-9);
+        }
+
+        assert_eq!(kml_color("#e6194b", "ff"), "ff4b19e6");
+    }
+
+    const FOOTPRINT_QUARTER: usize = super::FOOTPRINT_VERTICES / 4;
+
+    #[test]
+    fn gis_files() {
+        let epoch = Epoch::from_gregorian_utc_at_midnight(2024, 1, 1);
+        let point = |minutes: i64, longitude_deg: f64| GroundTrackPoint {
+            epoch: epoch + Unit::Minute * minutes,
+            latitude_deg: 0.0,
+            longitude_deg,
+            height_km: 500.0,
+        };
+        let ground_track = GroundTrack {
+            name: "Demo <sat>".to_string(),
+            track: split_at_antimeridian(&[point(0, 170.0), point(1, 175.0), point(2, -178.0)]),
+            stations: vec![StationFootprint {
+                name: "Station".to_string(),
+                latitude_deg: 0.0,
+                longitude_deg: 175.0,
+                height_km: 0.0,
+                elevation_mask_deg: 10.0,
+                footprint_deg: footprint(0.0, 175.0, 10.0, 6378.0, 500.0),
+            }],
+            accesses: vec![StationAccess {
+                station: "Station".to_string(),
+                start: epoch,
+                end: epoch + Unit::Minute * 1,
+                track: vec![vec![point(0, 170.0), point(1, 175.0)]],
+            }],
+        };
+
+        let path: PathBuf = [
+            env!("CARGO_MANIFEST_DIR"),
+            "data",
+            "04_output",
+            "ground_track.geojson",
+        ]
+        .iter()
+        .collect();
+        ground_track.to_geojson_file(&path).unwrap();
+        let geojson: Value = serde_json::from_str(&read_to_string(&path).unwrap()).unwrap();
+        let features = geojson["features"].as_array().unwrap();
+        // Ground track, station, footprint and access
+        assert_eq!(features.len(), 4);
+        assert_eq!(
+            features[0]["geometry"]["coordinates"]
+                .as_array()
+                .unwrap()
+                .len(),
+            2
+        );
+        // The footprint crosses the antimeridian
+        assert_eq!(features[2]["geometry"]["type"], "MultiPolygon");
+        assert_eq!(
+            features[3]["properties"]["start"],
+            "2024-01-01T00:00:00.000000000Z"
+        );
+        assert_eq!(
+            features[3]["properties"]["stroke"],
+            features[1]["properties"]["marker-color"]
+        );
+
+        let kml = read_to_string(
+            ground_track
+                .to_kml_file(path.with_extension("kml"))
+                .unwrap(),
+        )
+        .unwrap();
+        assert!(kml.contains("<name>Demo &lt;sat&gt;</name>"));
+        assert!(kml.contains("<TimeSpan><begin>2024-01-01T00:00:00.000000000Z</begin><end>2024-01-01T00:01:00.000000000Z</end></TimeSpan>"));
+    }
+}
@@ -19,13 +19,24 @@
 use anise::math::{cartesian::CartesianState, interpolation::InterpolationError};
 use snafu::prelude::*;
 
+mod bsp;
+mod comparison;
+mod compressed;
+mod gis;
 mod interpolatable;
+mod oem;
 mod sc_traj;
 mod traj;
 mod traj_it;
 
+pub use bsp::{BspDataType, BspExportCfg};
+pub use comparison::{DiffStatistics, RicDifference, TrajComparison};
+pub use compressed::{ChebyshevSegment, ChebyshevTraj, CompressionCfg};
+pub use gis::{GroundTrack, GroundTrackPoint, StationAccess, StationFootprint};
 pub use interpolatable::Interpolatable;
 pub(crate) use interpolatable::INTERPOLATION_SAMPLES;
+pub use oem::OemFormat;
+pub(crate) use oem::{lower_triangle_to_matrix, write_oem, OemCovariance, OemSegment};
 pub use traj::Traj;
 
 pub use crate::io::ExportCfg;
@@ -104,3 +115,48 @@ fn smooth_state_diff_in_place(ric_diff: &mut [CartesianState], window_size: usiz
         }
     }
 }
+
+/// Trajectories shared by the unit tests of the trajectory module
+#[cfg(test)]
+mod fixtures {
+    use super::Traj;
+    use crate::time::{Duration, Epoch, TimeSeries};
+    use crate::{Orbit, Spacecraft};
+    use anise::constants::frames::EARTH_J2000;
+
+    /// State on a circular Earth orbit of 7000 km of radius, with a linearly decreasing propellant mass
+    pub(super) fn circular_state(start: Epoch, epoch: Epoch) -> Spacecraft {
+        let radius_km = 7000.0;
+        let speed_km_s = (398_600.441_8_f64 / radius_km).sqrt();
+        let rate = speed_km_s / radius_km;
+        let t = (epoch - start).to_seconds();
+        let orbit = Orbit::cartesian(
+            radius_km * (rate * t).cos(),
+            radius_km * (rate * t).sin(),
+            0.0,
+            -speed_km_s * (rate * t).sin(),
+            speed_km_s * (rate * t).cos(),
+            0.0,
+            epoch,
+            EARTH_J2000,
+        );
+        let mut sc = Spacecraft::from(orbit);
+        sc.mass.prop_mass_kg = 100.0 - 1e-4 * t;
+        sc
+    }
+
+    /// Trajectory of the circular orbit over the provided duration from `start`, sampled at the provided step
+    pub(super) fn circular_traj(
+        start: Epoch,
+        duration: Duration,
+        step: Duration,
+    ) -> Traj<Spacecraft> {
+        let mut traj = Traj::new();
+        traj.name = Some("circular".to_string());
+        for epoch in TimeSeries::inclusive(start, start + duration, step) {
+            traj.states.push(circular_state(start, epoch));
+        }
+        traj.finalize();
+        traj
+    }
+}
@@ -0,0 +1,1234 @@
+/*
+    Nyx, blazing fast astrodynamics
+    Copyright (C) 2018-onwards Christopher Rabotin <christopher.rabotin@gmail.com>
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published
+    by the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use anise::prelude::{Almanac, Frame, Orbit};
+use log::{debug, info, warn};
+use quick_xml::escape::{escape, unescape};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use serde_derive::{Deserialize, Serialize};
+
+use super::{ExportCfg, Traj};
+use crate::cosmic::Spacecraft;
+use crate::dynamics::guidance::LocalFrame;
+use crate::errors::NyxError;
+use crate::io::ccsds::{ccsds_frame_names, covar_elements, COMPONENTS};
+use crate::io::watermark::prj_name_ver;
+use crate::io::Provenance;
+use crate::linalg::{Matrix6, SMatrix};
+use crate::od::estimate::KfEstimate;
+use crate::time::{Epoch, Format, Formatter, TimeUnits};
+use crate::State;
+use std::collections::HashMap;
+use std::fs::{read_to_string, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// Encoding of a CCSDS OEM file.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OemFormat {
+    /// Keyword = Value Notation, the text format of CCSDS messages
+    #[default]
+    Kvn,
+    /// NDM/XML
+    Xml,
+}
+
+/// Orbit covariance written to a CCSDS OEM covariance block, in km and km/s.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct OemCovariance {
+    pub epoch: Epoch,
+    /// Frame of the covariance: either the inertial frame of the states or the RIC frame
+    pub frame: LocalFrame,
+    pub covar: Matrix6<f64>,
+}
+
+impl OemCovariance {
+    /// Builds the OEM covariance from the orbit part of an estimate covariance, which is expressed in the inertial frame of the state.
+    pub(crate) fn from_inertial(
+        orbit: &Orbit,
+        inertial_covar: Matrix6<f64>,
+        frame: LocalFrame,
+    ) -> Result<Self, NyxError> {
+        let covar = match frame {
+            LocalFrame::Inertial => inertial_covar,
+            LocalFrame::RIC => {
+                // The inverse of the rotation is its transpose, including its time derivative
+                let dcm_inertial2ric = orbit
+                    .dcm_from_ric_to_inertial()
+                    .map_err(|e| NyxError::CCSDS {
+                        msg: format!("could not rotate covariance to RIC: {e}"),
+                    })?
+                    .transpose()
+                    .state_dcm();
+                dcm_inertial2ric * inertial_covar * dcm_inertial2ric.transpose()
+            }
+            _ => {
+                return Err(NyxError::CCSDS {
+                    msg: format!("{frame:?} covariance is not supported in OEM"),
+                })
+            }
+        };
+
+        Ok(Self {
+            epoch: orbit.epoch,
+            frame,
+            covar,
+        })
+    }
+
+    /// Returns the covariance in the inertial frame of the provided orbit.
+    pub(crate) fn inertial_covar(&self, orbit: &Orbit) -> Result<Matrix6<f64>, NyxError> {
+        match self.frame {
+            LocalFrame::RIC => {
+                let dcm_ric2inertial = orbit
+                    .dcm_from_ric_to_inertial()
+                    .map_err(|e| NyxError::CCSDS {
+                        msg: format!("could not rotate covariance from RIC: {e}"),
+                    })?
+                    .state_dcm();
+                Ok(dcm_ric2inertial * self.covar * dcm_ric2inertial.transpose())
+            }
+            _ => Ok(self.covar),
+        }
+    }
+}
+
+/// A segment of an OEM: the states of an object in a single frame, and their covariance blocks.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct OemSegment {
+    pub traj: Traj<Spacecraft>,
+    pub covariances: Vec<OemCovariance>,
+}
+
+impl OemSegment {
+    /// Builds the estimates of the covariance blocks, whose nominal states are interpolated from the trajectory of this segment.
+    fn estimates(&self) -> Result<Vec<KfEstimate<Spacecraft>>, NyxError> {
+        let mut estimates = Vec::with_capacity(self.covariances.len());
+        for covariance in &self.covariances {
+            let nominal_state = self
+                .traj
+                .at(covariance.epoch)
+                .map_err(|e| NyxError::CCSDS {
+                    msg: format!("no state for covariance at {}: {e}", covariance.epoch),
+                })?;
+            let mut covar = SMatrix::<f64, 9, 9>::zeros();
+            covar
+                .fixed_view_mut::<6, 6>(0, 0)
+                .copy_from(&covariance.inertial_covar(&nominal_state.orbit)?);
+            estimates.push(KfEstimate::from_covar(nominal_state, covar));
+        }
+        Ok(estimates)
+    }
+}
+
+/// Metadata of the OEM segment being parsed.
+#[derive(Default)]
+struct OemMetadata {
+    center_name: Option<String>,
+    ref_frame: Option<String>,
+    time_system: String,
+}
+
+impl OemMetadata {
+    fn frame(&self) -> Result<Frame, NyxError> {
+        match (&self.center_name, &self.ref_frame) {
+            (Some(center_name), Some(ref_frame)) => Frame::from_name(center_name, ref_frame)
+                .map_err(|e| NyxError::CCSDS {
+                    msg: format!("frame error `{center_name} {ref_frame}`: {e}"),
+                }),
+            _ => Err(NyxError::CCSDS {
+                msg: "segment metadata must specify both CENTER_NAME and REF_FRAME".to_string(),
+            }),
+        }
+    }
+
+    fn epoch(&self, epoch: &str) -> Result<Epoch, NyxError> {
+        let epoch_str = format!("{} {}", epoch.trim(), self.time_system);
+        Epoch::from_str(epoch_str.trim()).map_err(|e| NyxError::CCSDS {
+            msg: format!("Parsing epoch error: {e}"),
+        })
+    }
+
+    /// Returns the frame of the covariance, which is only inertial if it is the reference frame of the states: covariances are not rotated.
+    fn covar_frame(&self, name: &str) -> Result<LocalFrame, NyxError> {
+        match name.trim() {
+            "RTN" | "RSW" | "RIC" => Ok(LocalFrame::RIC),
+            name if Some(name) == self.ref_frame.as_deref() => {
+                Ok(LocalFrame::Inertial)
+            }
+            name => Err(NyxError::CCSDS {
+                msg: format!(
+                    "unsupported covariance frame `{name}`, expected RTN or the reference frame `{}`",
+                    self.ref_frame.as_deref().unwrap_or_default()
+                ),
+            }),
+        }
+    }
+}
+
+/// Reads all of the segments of an OEM file, either in the KVN or in the XML format.
+pub(crate) fn read_oem<P: AsRef<Path>>(
+    path: P,
+    template: Spacecraft,
+) -> Result<Vec<OemSegment>, NyxError> {
+    let contents = read_to_string(path).map_err(|e| NyxError::CCSDS {
+        msg: format!("File opening error: {e}"),
+    })?;
+
+    let mut segments = if contents.trim_start().starts_with('<') {
+        parse_oem_xml(&contents, template)?
+    } else {
+        parse_oem_kvn(&contents, template)?
+    };
+
+    segments.retain(|segment| !segment.traj.states.is_empty());
+    if segments.is_empty() {
+        return Err(NyxError::CCSDS {
+            msg: "OEM does not contain any state".to_string(),
+        });
+    }
+    for segment in &mut segments {
+        segment.traj.finalize();
+    }
+
+    Ok(segments)
+}
+
+fn parse_oem_kvn(contents: &str, template: Spacecraft) -> Result<Vec<OemSegment>, NyxError> {
+    let mut segments = Vec::new();
+    let mut segment = OemSegment::default();
+    let mut meta = OemMetadata::default();
+
+    let mut in_meta = false;
+    let mut in_data = false;
+    let mut in_covar = false;
+    let mut frame = None;
+    let mut covar_epoch = None;
+    let mut covar_frame = LocalFrame::Inertial;
+    let mut covar_values = Vec::with_capacity(21);
+
+    for (lno, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with("COMMENT") {
+            continue;
+        }
+
+        let with_line = |e: NyxError| match e {
+            NyxError::CCSDS { msg } => NyxError::CCSDS {
+                msg: format!("[line: {}] {msg}", lno + 1),
+            },
+            e => e,
+        };
+
+        if line.starts_with("META_START") {
+            // Each segment starts with its own metadata
+            if !segment.traj.states.is_empty() {
+                segments.push(segment);
+            }
+            segment = OemSegment::default();
+            meta = OemMetadata::default();
+            in_meta = true;
+            in_data = false;
+        } else if line.starts_with("META_STOP") {
+            in_meta = false;
+            in_data = true;
+            frame = Some(meta.frame().map_err(with_line)?);
+        } else if in_meta {
+            if let Some((key, value)) = line.split_once('=') {
+                let value = value.trim().to_string();
+                match key.trim() {
+                    "OBJECT_NAME" => {
+                        debug!("[line: {}] Found object {value}", lno + 1);
+                        segment.traj.name = Some(value);
+                    }
+                    "CENTER_NAME" => meta.center_name = Some(value),
+                    "REF_FRAME" => meta.ref_frame = Some(value),
+                    "TIME_SYSTEM" => {
+                        debug!("[line: {}] Found time system `{value}`", lno + 1);
+                        meta.time_system = value;
+                    }
+                    _ => {}
+                }
+            }
+        } else if line.starts_with("COVARIANCE_START") {
+            debug!("[line: {}] Found covariance", lno + 1);
+            in_data = false;
+            in_covar = true;
+        } else if line.starts_with("COVARIANCE_STOP") {
+            in_covar = false;
+        } else if in_covar {
+            if let Some((key, value)) = line.split_once('=') {
+                match key.trim() {
+                    "EPOCH" => {
+                        covar_epoch = Some(meta.epoch(value).map_err(with_line)?);
+                        covar_frame = LocalFrame::Inertial;
+                        covar_values.clear();
+                    }
+                    "COV_REF_FRAME" => {
+                        covar_frame = meta.covar_frame(value).map_err(with_line)?;
+                    }
+                    _ => {}
+                }
+            } else {
+                for value in line.split_whitespace() {
+                    covar_values.push(value.parse::<f64>().map_err(|e| NyxError::CCSDS {
+                        msg: format!("[line: {}] covariance value error: {e}", lno + 1),
+                    })?);
+                }
+                if covar_values.len() == 21 {
+                    let epoch = covar_epoch.take().ok_or(NyxError::CCSDS {
+                        msg: format!("[line: {}] covariance without an EPOCH", lno + 1),
+                    })?;
+                    segment.covariances.push(OemCovariance {
+                        epoch,
+                        frame: covar_frame,
+                        covar: lower_triangle_to_matrix(&covar_values),
+                    });
+                    covar_values.clear();
+                }
+            }
+        } else if in_data {
+            // Split the line into components, accelerations are ignored if present
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            if parts.len() < 7 {
+                debug!("[line: {}] Could not understand `{parts:?}`", lno + 1);
+                continue;
+            }
+            let values = parts[1..7]
+                .iter()
+                .map(|part| part.parse::<f64>())
+                .collect::<Result<Vec<f64>, _>>();
+            match values {
+                Ok(values) => {
+                    let epoch = meta.epoch(parts[0]).map_err(with_line)?;
+                    let orbit = Orbit::new(
+                        values[0],
+                        values[1],
+                        values[2],
+                        values[3],
+                        values[4],
+                        values[5],
+                        epoch,
+                        frame.unwrap(),
+                    );
+                    segment.traj.states.push(template.with_orbit(orbit));
+                }
+                Err(_) => {
+                    debug!("[line: {}] Could not parse `{parts:?}`", lno + 1);
+                }
+            }
+        }
+    }
+
+    segments.push(segment);
+
+    Ok(segments)
+}
+
+fn parse_oem_xml(contents: &str, template: Spacecraft) -> Result<Vec<OemSegment>, NyxError> {
+    let xml_err = |e: &dyn std::fmt::Display| NyxError::CCSDS {
+        msg: format!("XML error: {e}"),
+    };
+
+    // Text is not trimmed by the reader because it would strip the spaces around escaped characters.
+    let mut reader = Reader::from_str(contents);
+
+    let mut segments = Vec::new();
+    let mut segment = OemSegment::default();
+    let mut meta = OemMetadata::default();
+    let mut frame = None;
+    // Values of the leaf elements of the current metadata, state vector or covariance matrix
+    let mut values: HashMap<String, String> = HashMap::new();
+    let mut text = String::new();
+
+    let value_of = |values: &HashMap<String, String>, key: &str| -> Result<f64, NyxError> {
+        values
+            .get(key)
+            .ok_or(NyxError::CCSDS {
+                msg: format!("missing `{key}` in XML OEM"),
+            })?
+            .parse::<f64>()
+            .map_err(|e| NyxError::CCSDS {
+                msg: format!("could not parse `{key}`: {e}"),
+            })
+    };
+
+    loop {
+        match reader.read_event().map_err(|e| xml_err(&e))? {
+            Event::Start(e) => {
+                text.clear();
+                match e.local_name().as_ref() {
+                    b"segment" => {
+                        segment = OemSegment::default();
+                        meta = OemMetadata::default();
+                        values.clear();
+                    }
+                    // The metadata was used at its end, so each record only has its own values.
+                    b"stateVector" | b"covarianceMatrix" => values.clear(),
+                    _ => {}
+                }
+            }
+            Event::Text(e) => text.push_str(&e.decode().map_err(|e| xml_err(&e))?),
+            Event::GeneralRef(e) => {
+                let entity = format!("&{};", e.decode().map_err(|e| xml_err(&e))?);
+                text.push_str(&unescape(&entity).map_err(|e| xml_err(&e))?);
+            }
+            Event::End(e) => {
+                let name = String::from_utf8_lossy(e.local_name().as_ref()).to_string();
+                match name.as_str() {
+                    "metadata" => {
+                        segment.traj.name = values.get("OBJECT_NAME").cloned();
+                        meta.center_name = values.get("CENTER_NAME").cloned();
+                        meta.ref_frame = values.get("REF_FRAME").cloned();
+                        meta.time_system = values.get("TIME_SYSTEM").cloned().unwrap_or_default();
+                        frame = Some(meta.frame()?);
+                    }
+                    "stateVector" => {
+                        let epoch = meta.epoch(values.get("EPOCH").ok_or(NyxError::CCSDS {
+                            msg: "state vector without an EPOCH".to_string(),
+                        })?)?;
+                        let frame = frame.ok_or(NyxError::CCSDS {
+                            msg: "state vector before the segment metadata".to_string(),
+                        })?;
+                        let orbit = Orbit::new(
+                            value_of(&values, "X")?,
+                            value_of(&values, "Y")?,
+                            value_of(&values, "Z")?,
+                            value_of(&values, "X_DOT")?,
+                            value_of(&values, "Y_DOT")?,
+                            value_of(&values, "Z_DOT")?,
+                            epoch,
+                            frame,
+                        );
+                        segment.traj.states.push(template.with_orbit(orbit));
+                    }
+                    "covarianceMatrix" => {
+                        let epoch = meta.epoch(values.get("EPOCH").ok_or(NyxError::CCSDS {
+                            msg: "covariance without an EPOCH".to_string(),
+                        })?)?;
+                        let covar_frame = match values.get("COV_REF_FRAME") {
+                            Some(name) => meta.covar_frame(name)?,
+                            None => LocalFrame::Inertial,
+                        };
+                        let covar_values = covar_elements()
+                            .map(|(_, _, name)| value_of(&values, &name))
+                            .collect::<Result<Vec<f64>, NyxError>>()?;
+                        segment.covariances.push(OemCovariance {
+                            epoch,
+                            frame: covar_frame,
+                            covar: lower_triangle_to_matrix(&covar_values),
+                        });
+                    }
+                    "segment" => segments.push(std::mem::take(&mut segment)),
+                    _ => {
+                        values.insert(name, text.trim().to_string());
+                    }
+                }
+                text.clear();
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(segments)
+}
+
+/// Merges the segments into a single trajectory, returning the epochs at which each segment after the first one starts.
+///
+/// Segments in a different frame than the first one are converted with the Almanac, or an error is returned if none is provided.
+fn merge_segments(
+    segments: Vec<OemSegment>,
+    almanac: Option<Arc<Almanac>>,
+) -> Result<(Traj<Spacecraft>, Vec<Epoch>), NyxError> {
+    let frame = segments[0].traj.first().orbit.frame;
+    let mut merged = Traj::new();
+    merged.name = segments[0].traj.name.clone();
+    let mut boundaries = Vec::with_capacity(segments.len() - 1);
+
+    for (i, segment) in segments.into_iter().enumerate() {
+        let traj = if segment.traj.first().orbit.frame == frame {
+            segment.traj
+        } else if let Some(almanac) = &almanac {
+            segment.traj.to_frame(frame, almanac.clone())?
+        } else {
+            return Err(NyxError::CCSDS {
+                msg: format!(
+                    "segment #{} is in {:x} instead of {frame:x}: use `from_oem_file_merged` to convert it",
+                    i + 1,
+                    segment.traj.first().orbit.frame
+                ),
+            });
+        };
+        if i > 0 {
+            boundaries.push(traj.first().epoch());
+            // The segments are not interpolated across, unless they continue each other from the same state
+            if merged.states.last() != Some(traj.first()) {
+                merged.boundaries.push(traj.first().epoch());
+            }
+        }
+        merged.states.extend(traj.states);
+    }
+
+    merged.finalize();
+
+    Ok((merged, boundaries))
+}
+
+impl Traj<Spacecraft> {
+    /// Initialize a new spacecraft trajectory from the path to a CCSDS OEM file, either in the KVN or in the XML format.
+    ///
+    /// CCSDS OEM only contains the orbit information but Nyx builds spacecraft trajectories.
+    /// If not spacecraft template is provided, then a default massless spacecraft will be built.
+    /// All of the segments of the OEM are merged, and must be in the same frame (use [`Self::from_oem_file_merged`] otherwise).
+    /// Covariance blocks are ignored, use [`Self::from_oem_file_with_covar`] to read them.
+    pub fn from_oem_file<P: AsRef<Path>>(
+        path: P,
+        tpl_option: Option<Spacecraft>,
+    ) -> Result<Self, NyxError> {
+        Ok(Self::from_oem_file_with_covar(path, tpl_option)?.0)
+    }
+
+    /// Initialize a new spacecraft trajectory and the estimates of its covariance blocks from the path to a CCSDS OEM file.
+    ///
+    /// The nominal state of each estimate is the state of the trajectory of its segment at the epoch of the covariance block, and its covariance
+    /// is rotated to the inertial frame of the trajectory if the block is in the RIC frame (`RTN` or `RSW` in the OEM).
+    /// Only the orbit part of the covariance is set, the covariance of the other spacecraft parameters is zero.
+    pub fn from_oem_file_with_covar<P: AsRef<Path>>(
+        path: P,
+        tpl_option: Option<Spacecraft>,
+    ) -> Result<(Self, Vec<KfEstimate<Spacecraft>>), NyxError> {
+        let segments = read_oem(path, tpl_option.unwrap_or_default())?;
+
+        let mut estimates = Vec::new();
+        for segment in &segments {
+            estimates.extend(segment.estimates()?);
+        }
+
+        Ok((merge_segments(segments, None)?.0, estimates))
+    }
+
+    /// Initialize one spacecraft trajectory per segment of a CCSDS OEM file, each in the frame of its segment.
+    pub fn from_oem_segments<P: AsRef<Path>>(
+        path: P,
+        tpl_option: Option<Spacecraft>,
+    ) -> Result<Vec<Self>, NyxError> {
+        Ok(read_oem(path, tpl_option.unwrap_or_default())?
+            .into_iter()
+            .map(|segment| segment.traj)
+            .collect())
+    }
+
+    /// Initialize a single spacecraft trajectory from all of the segments of a CCSDS OEM file, in the frame of the first segment.
+    ///
+    /// Also returns the start epoch of each segment after the first one, e.g. the epochs of the maneuvers separating the segments.
+    /// Unless consecutive segments share the same state at their common epoch, the start of each segment is a boundary of the trajectory:
+    /// the states on both sides of it are kept and never interpolated across.
+    pub fn from_oem_file_merged<P: AsRef<Path>>(
+        path: P,
+        tpl_option: Option<Spacecraft>,
+        almanac: Arc<Almanac>,
+    ) -> Result<(Self, Vec<Epoch>), NyxError> {
+        merge_segments(
+            read_oem(path, tpl_option.unwrap_or_default())?,
+            Some(almanac),
+        )
+    }
+
+    /// Exports this trajectory to a CCSDS OEM file in the KVN format, with one OEM segment per segment of the trajectory.
+    pub fn to_oem_file<P: AsRef<Path>>(
+        &self,
+        path: P,
+        cfg: ExportCfg,
+    ) -> Result<PathBuf, NyxError> {
+        Self::segments_to_oem_file(&self.segments(), path, cfg, OemFormat::Kvn)
+    }
+
+    /// Exports this trajectory to a CCSDS OEM file in the NDM/XML format, with one OEM segment per segment of the trajectory.
+    pub fn to_oem_xml_file<P: AsRef<Path>>(
+        &self,
+        path: P,
+        cfg: ExportCfg,
+    ) -> Result<PathBuf, NyxError> {
+        Self::segments_to_oem_file(&self.segments(), path, cfg, OemFormat::Xml)
+    }
+
+    /// Exports several trajectories to a CCSDS OEM file with one segment per trajectory, e.g. before and after a maneuver.
+    ///
+    /// The start epoch, end epoch and step of the export configuration apply to each segment.
+    pub fn segments_to_oem_file<P: AsRef<Path>>(
+        segments: &[Self],
+        path: P,
+        cfg: ExportCfg,
+        format: OemFormat,
+    ) -> Result<PathBuf, NyxError> {
+        let mut oem_segments = Vec::with_capacity(segments.len());
+        for traj in segments {
+            if traj.states.is_empty() {
+                return Err(NyxError::CCSDS {
+                    msg: "Cannot export an empty trajectory to OEM".to_string(),
+                });
+            }
+            // Build the states iterator -- this does require copying the current states but I can't either get a reference or a copy of all the states.
+            let states =
+                if cfg.start_epoch.is_some() || cfg.end_epoch.is_some() || cfg.step.is_some() {
+                    // Must interpolate the data!
+                    let start = cfg
+                        .start_epoch
+                        .unwrap_or_else(|| traj.first().epoch())
+                        .max(traj.first().epoch());
+                    let end = cfg
+                        .end_epoch
+                        .unwrap_or_else(|| traj.last().epoch())
+                        .min(traj.last().epoch());
+                    let step = cfg.step.unwrap_or_else(|| 1.minutes());
+                    traj.every_between(step, start, end).collect()
+                } else {
+                    traj.states.to_vec()
+                };
+            if states.is_empty() {
+                warn!("No state of {traj} within the export bounds, skipping segment");
+                continue;
+            }
+            oem_segments.push(OemSegment {
+                traj: Traj {
+                    name: traj.name.clone(),
+                    states,
+                    boundaries: Vec::new(),
+                },
+                covariances: Vec::new(),
+            });
+        }
+
+        write_oem(path, cfg, format, &oem_segments, None)
+    }
+}
+
+/// Writes the segments, i.e. their states and covariance blocks, to a CCSDS OEM file.
+///
+/// The default provenance is recorded if the export configuration does not provide one, cf. [`ExportCfg::provenance_or`].
+pub(crate) fn write_oem<P: AsRef<Path>>(
+    path: P,
+    cfg: ExportCfg,
+    format: OemFormat,
+    segments: &[OemSegment],
+    default_provenance: Option<Provenance>,
+) -> Result<PathBuf, NyxError> {
+    if segments.is_empty() || segments.iter().any(|seg| seg.traj.states.is_empty()) {
+        return Err(NyxError::CCSDS {
+            msg: "Cannot export an empty trajectory to OEM".to_string(),
+        });
+    }
+    let tick = Epoch::now().unwrap();
+    info!("Exporting trajectory to CCSDS OEM file...");
+
+    // Grab the path here before we move stuff.
+    let path_buf = cfg.actual_path(path);
+
+    let provenance = cfg.provenance_or(default_provenance, None);
+    let metadata = cfg.metadata.unwrap_or_default();
+
+    let file = File::create(&path_buf).map_err(|e| NyxError::CCSDS {
+        msg: format!("File creation error: {e}"),
+    })?;
+    let mut writer = BufWriter::new(file);
+
+    match format {
+        OemFormat::Kvn => write_oem_kvn(&mut writer, &metadata, &provenance, segments),
+        OemFormat::Xml => write_oem_xml(&mut writer, &metadata, &provenance, segments),
+    }
+    .map_err(|e| NyxError::CCSDS {
+        msg: format!("Could not write: {e}"),
+    })?;
+
+    // Return the path this was written to
+    let tock_time = Epoch::now().unwrap() - tick;
+    info!(
+        "Trajectory written to {} in {tock_time}",
+        path_buf.display()
+    );
+    Ok(path_buf)
+}
+
+fn write_oem_kvn<W: Write>(
+    writer: &mut W,
+    metadata: &HashMap<String, String>,
+    provenance: &Provenance,
+    segments: &[OemSegment],
+) -> std::io::Result<()> {
+    // Epoch formmatter.
+    let iso8601_no_ts = Format::from_str("%Y-%m-%dT%H:%M:%S.%f").unwrap();
+
+    // Write mandatory metadata
+    writeln!(writer, "CCSDS_OEM_VERS = 2.0")?;
+
+    writeln!(
+        writer,
+        "COMMENT Built by {} -- https://nyxspace.com/\n",
+        prj_name_ver()
+    )?;
+    writeln!(
+        writer,
+        "COMMENT Nyx Space provided under the AGPL v3 open source license -- https://nyxspace.com/pricing\n"
+    )?;
+    for line in provenance.comment_lines().map_err(std::io::Error::other)? {
+        writeln!(writer, "COMMENT {line}")?;
+    }
+
+    writeln!(
+        writer,
+        "CREATION_DATE = {}",
+        Formatter::new(Epoch::now().unwrap(), iso8601_no_ts)
+    )?;
+    writeln!(
+        writer,
+        "ORIGINATOR = {}\n",
+        metadata
+            .get("originator")
+            .unwrap_or(&"Nyx Space".to_string())
+    )?;
+
+    for segment in segments {
+        let states = &segment.traj.states;
+        writeln!(writer, "META_START")?;
+        // Write optional metadata
+        if let Some(object_name) = metadata.get("object_name") {
+            writeln!(writer, "\tOBJECT_NAME = {object_name}")?;
+        } else if let Some(object_name) = &segment.traj.name {
+            writeln!(writer, "\tOBJECT_NAME = {object_name}")?;
+        }
+
+        let first_orbit = states[0].orbit;
+        let (center, ref_frame) = ccsds_frame_names(first_orbit.frame);
+        writeln!(writer, "\tREF_FRAME = {ref_frame}")?;
+
+        writeln!(writer, "\tCENTER_NAME = {center}",)?;
+
+        writeln!(writer, "\tTIME_SYSTEM = {}", first_orbit.epoch.time_scale)?;
+
+        let start = Formatter::new(states[0].epoch(), iso8601_no_ts);
+        let stop = Formatter::new(states[states.len() - 1].epoch(), iso8601_no_ts);
+        writeln!(writer, "\tSTART_TIME = {start}")?;
+        writeln!(writer, "\tUSEABLE_START_TIME = {start}")?;
+        writeln!(writer, "\tUSEABLE_STOP_TIME = {stop}")?;
+        writeln!(writer, "\tSTOP_TIME = {stop}")?;
+
+        writeln!(writer, "META_STOP\n")?;
+
+        for sc_state in states {
+            let state = sc_state.orbit;
+            writeln!(
+                writer,
+                "{} {:E} {:E} {:E} {:E} {:E} {:E}",
+                Formatter::new(state.epoch, iso8601_no_ts),
+                state.radius_km.x,
+                state.radius_km.y,
+                state.radius_km.z,
+                state.velocity_km_s.x,
+                state.velocity_km_s.y,
+                state.velocity_km_s.z
+            )?;
+        }
+
+        if !segment.covariances.is_empty() {
+            writeln!(writer, "\nCOVARIANCE_START")?;
+            for covariance in &segment.covariances {
+                writeln!(
+                    writer,
+                    "EPOCH = {}",
+                    Formatter::new(covariance.epoch, iso8601_no_ts)
+                )?;
+                writeln!(
+                    writer,
+                    "COV_REF_FRAME = {}",
+                    match covariance.frame {
+                        LocalFrame::RIC => "RTN",
+                        _ => &ref_frame,
+                    }
+                )?;
+                // Lower triangular part of the covariance, row by row
+                for i in 0..6 {
+                    let row: Vec<String> = (0..=i)
+                        .map(|j| format!("{:E}", covariance.covar[(i, j)]))
+                        .collect();
+                    writeln!(writer, "{}", row.join(" "))?;
+                }
+            }
+            writeln!(writer, "COVARIANCE_STOP")?;
+        }
+
+        #[allow(clippy::writeln_empty_string)]
+        writeln!(writer, "")?;
+    }
+
+    Ok(())
+}
+
+fn write_oem_xml<W: Write>(
+    writer: &mut W,
+    metadata: &HashMap<String, String>,
+    provenance: &Provenance,
+    segments: &[OemSegment],
+) -> std::io::Result<()> {
+    // Epoch formmatter.
+    let iso8601_no_ts = Format::from_str("%Y-%m-%dT%H:%M:%S.%f").unwrap();
+
+    writeln!(writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        writer,
+        r#"<oem xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" xsi:noNamespaceSchemaLocation="https://sanaregistry.org/r/ndmxml_unqualified/ndmxml-2.0.0-master-2.0.xsd" id="CCSDS_OEM_VERS" version="2.0">"#
+    )?;
+    writeln!(writer, "  <header>")?;
+    writeln!(
+        writer,
+        "    <COMMENT>Built by {} -- https://nyxspace.com/</COMMENT>",
+        escape(prj_name_ver())
+    )?;
+    writeln!(
+        writer,
+        "    <COMMENT>Nyx Space provided under the AGPL v3 open source license -- https://nyxspace.com/pricing</COMMENT>"
+    )?;
+    for line in provenance.comment_lines().map_err(std::io::Error::other)? {
+        writeln!(writer, "    <COMMENT>{}</COMMENT>", escape(&line))?;
+    }
+    writeln!(
+        writer,
+        "    <CREATION_DATE>{}</CREATION_DATE>",
+        Formatter::new(Epoch::now().unwrap(), iso8601_no_ts)
+    )?;
+    writeln!(
+        writer,
+        "    <ORIGINATOR>{}</ORIGINATOR>",
+        escape(
+            metadata
+                .get("originator")
+                .map(String::as_str)
+                .unwrap_or("Nyx Space")
+        )
+    )?;
+    writeln!(writer, "  </header>")?;
+    writeln!(writer, "  <body>")?;
+
+    for segment in segments {
+        let states = &segment.traj.states;
+        let first_orbit = states[0].orbit;
+        let (center, ref_frame) = ccsds_frame_names(first_orbit.frame);
+        let start = Formatter::new(states[0].epoch(), iso8601_no_ts);
+        let stop = Formatter::new(states[states.len() - 1].epoch(), iso8601_no_ts);
+
+        writeln!(writer, "    <segment>")?;
+        writeln!(writer, "      <metadata>")?;
+        if let Some(object_name) = metadata.get("object_name").or(segment.traj.name.as_ref()) {
+            writeln!(
+                writer,
+                "        <OBJECT_NAME>{}</OBJECT_NAME>",
+                escape(object_name)
+            )?;
+        }
+        writeln!(
+            writer,
+            "        <CENTER_NAME>{}</CENTER_NAME>",
+            escape(&center)
+        )?;
+        writeln!(
+            writer,
+            "        <REF_FRAME>{}</REF_FRAME>",
+            escape(&ref_frame)
+        )?;
+        writeln!(
+            writer,
+            "        <TIME_SYSTEM>{}</TIME_SYSTEM>",
+            first_orbit.epoch.time_scale
+        )?;
+        writeln!(writer, "        <START_TIME>{start}</START_TIME>")?;
+        writeln!(
+            writer,
+            "        <USEABLE_START_TIME>{start}</USEABLE_START_TIME>"
+        )?;
+        writeln!(
+            writer,
+            "        <USEABLE_STOP_TIME>{stop}</USEABLE_STOP_TIME>"
+        )?;
+        writeln!(writer, "        <STOP_TIME>{stop}</STOP_TIME>")?;
+        writeln!(writer, "      </metadata>")?;
+        writeln!(writer, "      <data>")?;
+
+        for sc_state in states {
+            let state = sc_state.orbit;
+            writeln!(writer, "        <stateVector>")?;
+            writeln!(
+                writer,
+                "          <EPOCH>{}</EPOCH>",
+                Formatter::new(state.epoch, iso8601_no_ts)
+            )?;
+            let values = [
+                state.radius_km.x,
+                state.radius_km.y,
+                state.radius_km.z,
+                state.velocity_km_s.x,
+                state.velocity_km_s.y,
+                state.velocity_km_s.z,
+            ];
+            for (name, value) in COMPONENTS.iter().zip(values) {
+                writeln!(writer, "          <{name}>{value:E}</{name}>")?;
+            }
+            writeln!(writer, "        </stateVector>")?;
+        }
+
+        for covariance in &segment.covariances {
+            writeln!(writer, "        <covarianceMatrix>")?;
+            writeln!(
+                writer,
+                "          <EPOCH>{}</EPOCH>",
+                Formatter::new(covariance.epoch, iso8601_no_ts)
+            )?;
+            writeln!(
+                writer,
+                "          <COV_REF_FRAME>{}</COV_REF_FRAME>",
+                match covariance.frame {
+                    LocalFrame::RIC => "RTN".to_string(),
+                    _ => escape(&ref_frame).to_string(),
+                }
+            )?;
+            for (i, j, name) in covar_elements() {
+                writeln!(
+                    writer,
+                    "          <{name}>{:E}</{name}>",
+                    covariance.covar[(i, j)]
+                )?;
+            }
+            writeln!(writer, "        </covarianceMatrix>")?;
+        }
+
+        writeln!(writer, "      </data>")?;
+        writeln!(writer, "    </segment>")?;
+    }
+
+    writeln!(writer, "  </body>")?;
+    writeln!(writer, "</oem>")?;
+
+    Ok(())
+}
+
+/// Builds the symmetric matrix from its lower triangular part, row by row.
+pub(crate) fn lower_triangle_to_matrix(values: &[f64]) -> Matrix6<f64> {
+    let mut covar = Matrix6::zeros();
+    let mut k = 0;
+    for i in 0..6 {
+        for j in 0..=i {
+            covar[(i, j)] = values[k];
+            covar[(j, i)] = values[k];
+            k += 1;
+        }
+    }
+    covar
+}
+
+#[cfg(test)]
+mod ut_oem_covar {
+    use super::{write_oem, OemCovariance, OemFormat, OemSegment};
+    use crate::dynamics::guidance::LocalFrame;
+    use crate::io::ExportCfg;
+    use crate::linalg::Matrix6;
+    use crate::md::prelude::Traj;
+    use crate::od::estimate::Estimate;
+    use crate::{Spacecraft, State};
+    use std::path::PathBuf;
+
+    #[test]
+    fn oem_covariance_round_trip() {
+        let path: PathBuf = [
+            env!("CARGO_MANIFEST_DIR"),
+            "data",
+            "03_tests",
+            "ccsds",
+            "oem",
+            "LEO_10s.oem",
+        ]
+        .iter()
+        .collect();
+
+        let traj: Traj<Spacecraft> = Traj::from_oem_file(path, None).unwrap();
+
+        // Correlated covariance, in km and km/s
+        let mut sqrt_covar = Matrix6::<f64>::identity() * 1e-3;
+        for i in 0..6 {
+            for j in 0..i {
+                sqrt_covar[(i, j)] = 1e-5 * (i + j) as f64;
+            }
+        }
+        let inertial_covar = sqrt_covar * sqrt_covar.transpose();
+
+        let covariances = [
+            OemCovariance::from_inertial(&traj.states[5].orbit, inertial_covar, LocalFrame::RIC)
+                .unwrap(),
+            OemCovariance::from_inertial(
+                &traj.states[42].orbit,
+                inertial_covar,
+                LocalFrame::Inertial,
+            )
+            .unwrap(),
+        ];
+        assert!(OemCovariance::from_inertial(
+            &traj.states[5].orbit,
+            inertial_covar,
+            LocalFrame::VNC
+        )
+        .is_err());
+
+        let out: PathBuf = [
+            env!("CARGO_MANIFEST_DIR"),
+            "data",
+            "04_output",
+            "LEO_10s_covar.oem",
+        ]
+        .iter()
+        .collect();
+        let segment = OemSegment {
+            traj: traj.clone(),
+            covariances: covariances.to_vec(),
+        };
+        let out = write_oem(
+            out,
+            ExportCfg::default(),
+            OemFormat::Kvn,
+            std::slice::from_ref(&segment),
+            None,
+        )
+        .unwrap();
+
+        let (reloaded, estimates) = Traj::from_oem_file_with_covar(&out, None).unwrap();
+        assert_eq!(reloaded, traj);
+        assert_eq!(estimates.len(), 2);
+        for (estimate, state_idx) in estimates.iter().zip([5, 42]) {
+            assert_eq!(estimate.epoch(), traj.states[state_idx].epoch());
+            let err = (estimate.covar.fixed_view::<6, 6>(0, 0) - inertial_covar).abs();
+            assert!(err.max() < 1e-15, "{err}");
+            // Covariance of the other parameters is not set
+            assert_eq!(estimate.covar[(6, 6)], 0.0);
+        }
+
+        // Inertial covariances in another frame than the states are rejected instead of being used without rotation.
+        let contents = std::fs::read_to_string(&out).unwrap();
+        assert!(contents.contains("COV_REF_FRAME = ICRF"));
+        std::fs::write(
+            &out,
+            contents.replace("COV_REF_FRAME = ICRF", "COV_REF_FRAME = EME2000"),
+        )
+        .unwrap();
+        assert!(Traj::from_oem_file_with_covar(&out, None).is_err());
+    }
+}
+
+#[cfg(test)]
+mod ut_oem_segments {
+    use super::{write_oem, OemCovariance, OemFormat, OemSegment};
+    use crate::dynamics::guidance::LocalFrame;
+    use crate::io::ExportCfg;
+    use crate::linalg::Matrix6;
+    use crate::md::prelude::Traj;
+    use crate::od::estimate::Estimate;
+    use crate::{Spacecraft, State};
+    use anise::constants::frames::MOON_J2000;
+    use rstest::rstest;
+    use std::path::PathBuf;
+
+    fn leo_traj() -> Traj<Spacecraft> {
+        let path: PathBuf = [
+            env!("CARGO_MANIFEST_DIR"),
+            "data",
+            "03_tests",
+            "ccsds",
+            "oem",
+            "LEO_10s.oem",
+        ]
+        .iter()
+        .collect();
+
+        Traj::from_oem_file(path, None).unwrap()
+    }
+
+    #[rstest]
+    #[case(OemFormat::Kvn, "LEO_10s_segments.oem")]
+    #[case(OemFormat::Xml, "LEO_10s_segments.xml")]
+    fn oem_segments_round_trip(#[case] format: OemFormat, #[case] filename: &str) {
+        let traj = leo_traj();
+        let split = traj.states.len() / 2;
+
+        let first = Traj {
+            name: Some("pre-maneuver <&>".to_string()),
+            states: traj.states[..=split].to_vec(),
+            boundaries: Vec::new(),
+        };
+        // Second segment is in another frame, like after a change of central body
+        let second = Traj {
+            name: traj.name.clone(),
+            states: traj.states[split..]
+                .iter()
+                .map(|sc| {
+                    let mut orbit = sc.orbit;
+                    orbit.frame = MOON_J2000;
+                    sc.with_orbit(orbit)
+                })
+                .collect(),
+            boundaries: Vec::new(),
+        };
+
+        let covar = Matrix6::from_diagonal_element(1e-6);
+        let segments = [
+            OemSegment {
+                traj: first.clone(),
+                covariances: vec![OemCovariance::from_inertial(
+                    &first.states[3].orbit,
+                    covar,
+                    LocalFrame::RIC,
+                )
+                .unwrap()],
+            },
+            OemSegment {
+                traj: second.clone(),
+                covariances: vec![OemCovariance::from_inertial(
+                    &second.states[7].orbit,
+                    covar,
+                    LocalFrame::Inertial,
+                )
+                .unwrap()],
+            },
+        ];
+
+        let out: PathBuf = [env!("CARGO_MANIFEST_DIR"), "data", "04_output", filename]
+            .iter()
+            .collect();
+        let out = write_oem(out, ExportCfg::default(), format, &segments, None).unwrap();
+
+        let reloaded = Traj::from_oem_segments(&out, None).unwrap();
+        assert_eq!(reloaded, vec![first.clone(), second.clone()]);
+
+        // Merging requires the segments to be in the same frame, or an Almanac to convert them
+        assert!(Traj::from_oem_file(&out, None).is_err());
+
+        // Writing the trajectories directly leads to the same segments
+        let out = Traj::segments_to_oem_file(
+            &[first.clone(), second.clone()],
+            out,
+            ExportCfg::default(),
+            format,
+        )
+        .unwrap();
+        assert_eq!(
+            Traj::from_oem_segments(&out, None).unwrap(),
+            vec![first, second]
+        );
+
+        // Merged trajectory when all segments are in the same frame, the boundary state is only kept once
+        let same_frame = [
+            OemSegment {
+                traj: Traj {
+                    name: traj.name.clone(),
+                    states: traj.states[..=split].to_vec(),
+                    boundaries: Vec::new(),
+                },
+                covariances: segments[0].covariances.clone(),
+            },
+            OemSegment {
+                traj: Traj {
+                    name: traj.name.clone(),
+                    states: traj.states[split..].to_vec(),
+                    boundaries: Vec::new(),
+                },
+                covariances: vec![OemCovariance::from_inertial(
+                    &traj.states[split + 7].orbit,
+                    covar,
+                    LocalFrame::Inertial,
+                )
+                .unwrap()],
+            },
+        ];
+        let out = write_oem(out, ExportCfg::default(), format, &same_frame, None).unwrap();
+        let (merged, estimates) = Traj::from_oem_file_with_covar(&out, None).unwrap();
+        assert_eq!(merged, traj);
+        assert_eq!(estimates.len(), 2);
+        assert_eq!(estimates[0].epoch(), traj.states[3].epoch());
+        assert_eq!(estimates[1].epoch(), traj.states[split + 7].epoch());
+        for estimate in estimates {
+            let err = (estimate.covar.fixed_view::<6, 6>(0, 0) - covar).abs();
+            assert!(err.max() < 1e-15, "{err}");
+        }
+    }
+
+    #[test]
+    fn oem_xml_incomplete_records() {
+        let traj = leo_traj();
+        let covar = Matrix6::from_diagonal_element(1e-6);
+        let segment = OemSegment {
+            traj: traj.clone(),
+            covariances: [3, 7]
+                .iter()
+                .map(|idx| {
+                    OemCovariance::from_inertial(&traj.states[*idx].orbit, covar, LocalFrame::RIC)
+                        .unwrap()
+                })
+                .collect(),
+        };
+
+        let out: PathBuf = [
+            env!("CARGO_MANIFEST_DIR"),
+            "data",
+            "04_output",
+            "LEO_10s_incomplete.xml",
+        ]
+        .iter()
+        .collect();
+        let out = write_oem(
+            out,
+            ExportCfg::default(),
+            OemFormat::Xml,
+            std::slice::from_ref(&segment),
+            None,
+        )
+        .unwrap();
+        assert!(Traj::from_oem_file_with_covar(&out, None).is_ok());
+        let contents = std::fs::read_to_string(&out).unwrap();
+
+        // Each record must provide all of its values instead of reusing those of the previous record.
+        for element in ["<EPOCH>", "<X>", "<Z_DOT>", "<CX_X>", "<CZ_DOT_Z_DOT>"] {
+            let mut count = 0;
+            let malformed = contents
+                .lines()
+                .filter(|line| {
+                    if line.trim_start().starts_with(element) {
+                        count += 1;
+                        count != 2
+                    } else {
+                        true
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+            assert!(count >= 2);
+            std::fs::write(&out, malformed).unwrap();
+            assert!(
+                Traj::from_oem_file_with_covar(&out, None).is_err(),
+                "second {element} is missing"
+            );
+        }
+    }
+}
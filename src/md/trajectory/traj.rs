@@ -56,6 +56,9 @@ where
     pub name: Option<String>,
     /// We use a vector because we know that the states are produced in a chronological manner (the direction does not matter).
     pub states: Vec<S>,
+    /// Epochs of the discontinuities of this trajectory (e.g. impulsive maneuvers or state resets), in chronological order.
+    /// The trajectory is never interpolated across a boundary, and stores both the state before and the state after it, in that order.
+    pub boundaries: Vec<Epoch>,
 }
 
 impl<S: Interpolatable> Traj<S>
@@ -66,31 +69,141 @@ where
         Self {
             name: None,
             states: Vec::new(),
+            boundaries: Vec::new(),
         }
     }
     /// Orders the states, can be used to store the states out of order
+    ///
+    /// States sharing the same epoch are removed, except for the states before and after a boundary.
     pub fn finalize(&mut self) {
-        // Remove duplicate epochs
-        self.states.dedup_by(|a, b| a.epoch().eq(&b.epoch()));
-        // And sort
+        self.boundaries.sort();
+        self.boundaries.dedup();
+        // Stable sort, such that the states on either side of a boundary remain in their order
         self.states.sort_by_key(|a| a.epoch());
+        // Remove duplicate epochs, but keep the first and last states at a boundary
+        let mut states: Vec<S> = Vec::with_capacity(self.states.len());
+        for state in self.states.drain(..) {
+            let epoch = state.epoch();
+            match states.last() {
+                Some(prev) if prev.epoch() == epoch => {
+                    if self.boundaries.binary_search(&epoch).is_ok() {
+                        if states.len() > 1 && states[states.len() - 2].epoch() == epoch {
+                            *states.last_mut().unwrap() = state;
+                        } else {
+                            states.push(state);
+                        }
+                    }
+                }
+                _ => states.push(state),
+            }
+        }
+        self.states = states;
+    }
+
+    /// Returns the index of the end of the segment before this boundary, and of the start of the segment after it.
+    fn boundary_indices(&self, boundary: Epoch) -> (usize, usize) {
+        let first_at = self.states.partition_point(|s| s.epoch() < boundary);
+        let after = self.states.partition_point(|s| s.epoch() <= boundary);
+        // The state before the boundary ends this segment, and the state after it starts the next one
+        if after - first_at >= 2 {
+            (first_at + 1, after - 1)
+        } else {
+            (first_at, first_at)
+        }
+    }
+
+    /// Returns the index ranges of the states of each continuous segment of this trajectory, i.e. between its boundaries.
+    fn segment_ranges(&self) -> Vec<ops::Range<usize>> {
+        let mut ranges = Vec::with_capacity(self.boundaries.len() + 1);
+        let mut start = 0;
+        for boundary in &self.boundaries {
+            let (end, next_start) = self.boundary_indices(*boundary);
+            if end > start {
+                ranges.push(start..end);
+                start = next_start;
+            }
+        }
+        if start < self.states.len() {
+            ranges.push(start..self.states.len());
+        }
+        ranges
+    }
+
+    /// Returns each continuous segment of this trajectory, i.e. between its boundaries, as its own trajectory.
+    pub fn segments(&self) -> Vec<Self> {
+        self.segment_ranges()
+            .into_iter()
+            .map(|range| Self {
+                name: self.name.clone(),
+                states: self.states[range].to_vec(),
+                boundaries: Vec::new(),
+            })
+            .collect()
+    }
+
+    /// Returns the state before the discontinuity at this boundary, if the trajectory stores it.
+    pub fn before_boundary(&self, boundary: Epoch) -> Option<S> {
+        let first_at = self.states.partition_point(|s| s.epoch() < boundary);
+        let after = self.states.partition_point(|s| s.epoch() <= boundary);
+        if self.boundaries.binary_search(&boundary).is_ok() && after - first_at >= 2 {
+            Some(self.states[first_at])
+        } else {
+            None
+        }
+    }
+
+    /// Returns whether this epoch is in a gap between two segments of this trajectory, where it is never interpolated.
+    pub(super) fn in_gap(&self, epoch: Epoch) -> bool {
+        self.boundaries.iter().any(|boundary| {
+            let (end, next_start) = self.boundary_indices(*boundary);
+            end > 0
+                && next_start < self.states.len()
+                && epoch > self.states[end - 1].epoch()
+                && epoch < self.states[next_start].epoch()
+        })
     }
 
     /// Evaluate the trajectory at this specific epoch.
+    ///
+    /// At a boundary, this returns the state after the discontinuity, cf. [`Self::before_boundary`] for the state before it.
     pub fn at(&self, epoch: Epoch) -> Result<S, TrajError> {
         if self.states.is_empty() || self.first().epoch() > epoch || self.last().epoch() < epoch {
             return Err(TrajError::NoInterpolationData { epoch });
         }
-        match self
-            .states
-            .binary_search_by(|state| state.epoch().cmp(&epoch))
-        {
-            Ok(idx) => {
-                // Oh wow, we actually had this exact state!
-                Ok(self.states[idx])
+        let after = self.states.partition_point(|s| s.epoch() <= epoch);
+        if after > 0 && self.states[after - 1].epoch() == epoch {
+            // Oh wow, we actually had this exact state!
+            return Ok(self.states[after - 1]);
+        }
+
+        // Only interpolate within the segment of this epoch
+        let segment = if self.boundaries.is_empty() {
+            &self.states[..]
+        } else {
+            // The segment of this epoch lies between the boundaries on either side of it.
+            let next = self
+                .boundaries
+                .partition_point(|boundary| *boundary <= epoch);
+            let start = match next.checked_sub(1) {
+                Some(prev) => self.boundary_indices(self.boundaries[prev]).1,
+                None => 0,
+            };
+            let end = match self.boundaries.get(next) {
+                Some(boundary) => self.boundary_indices(*boundary).0,
+                None => self.states.len(),
+            };
+            if start < after && after < end {
+                &self.states[start..end]
+            } else {
+                // This epoch is in a gap between two segments
+                return Err(TrajError::NoInterpolationData { epoch });
             }
+        };
+
+        match segment.binary_search_by(|state| state.epoch().cmp(&epoch)) {
+            Ok(idx) => Ok(segment[idx]),
             Err(idx) => {
-                if idx == 0 || idx >= self.states.len() {
+                if idx == 0 || idx >= segment.len() {
                     // The binary search returns where we should insert the data, so if it's at either end of the list, then we're out of bounds.
                     // This condition should have been handled by the check at the start of this function.
                     return Err(TrajError::NoInterpolationData { epoch });
@@ -103,19 +216,16 @@ where
 
                 // Ensure that we aren't fetching out of the window
                 let mut first_idx = idx.saturating_sub(num_left);
-                let last_idx = self.states.len().min(first_idx + INTERPOLATION_SAMPLES);
+                let last_idx = segment.len().min(first_idx + INTERPOLATION_SAMPLES);
 
                 // Check that we have enough samples
-                if last_idx == self.states.len() {
+                if last_idx == segment.len() {
                     first_idx = last_idx.saturating_sub(2 * num_left);
                 }
 
-                let mut states = Vec::with_capacity(last_idx - first_idx);
-                for idx in first_idx..last_idx {
-                    states.push(self.states[idx]);
-                }
+                let states = segment[first_idx..last_idx].to_vec();
 
-                self.states[idx]
+                segment[idx]
                     .interpolate(epoch, &states)
                     .context(InterpolationSnafu)
             }
@@ -140,14 +250,14 @@ where
 
     /// Creates an iterator through the trajectory by the provided step size between the provided bounds
     pub fn every_between(&self, step: Duration, start: Epoch, end: Epoch) -> TrajIterator<'_, S> {
-        TrajIterator {
-            time_series: TimeSeries::inclusive(
+        TrajIterator::new(
+            TimeSeries::inclusive(
                 start.max(self.first().epoch()),
                 end.min(self.last().epoch()),
                 step,
             ),
-            traj: self,
-        }
+            self,
+        )
     }
 
     /// Returns a new trajectory that only contains states that fall within the given epoch range.
@@ -158,6 +268,7 @@ where
             .copied()
             .filter(|s| bound.contains(&s.epoch()))
             .collect::<Vec<_>>();
+        self.boundaries.retain(|epoch| bound.contains(epoch));
         self
     }
 
@@ -249,7 +360,7 @@ where
                 })?,
         )]);
 
-        let mut fields = match cfg.fields {
+        let mut fields = match cfg.fields.clone() {
             Some(fields) => fields,
             None => S::export_params(),
         };
@@ -332,10 +443,17 @@ where
         // Serialize all of the devices and add that to the parquet file too.
         let mut metadata = HashMap::new();
         metadata.insert("Purpose".to_string(), "Trajectory data".to_string());
-        if let Some(add_meta) = cfg.metadata {
-            for (k, v) in add_meta {
-                metadata.insert(k, v);
-            }
+        metadata.extend(cfg.all_metadata(None, Some(almanac.as_ref()))?);
+        // Store the boundaries of the exported states, such that the segments are restored when reading this file.
+        let (first_epoch, last_epoch) = (states[0].epoch(), states[states.len() - 1].epoch());
+        let boundaries = self
+            .boundaries
+            .iter()
+            .filter(|epoch| (first_epoch..=last_epoch).contains(*epoch))
+            .map(|epoch| epoch.to_time_scale(TimeScale::UTC).to_isoformat())
+            .collect::<Vec<String>>();
+        if !boundaries.is_empty() {
+            metadata.insert("Boundaries (UTC)".to_string(), boundaries.join(","));
         }
 
         let props = pq_writer(Some(metadata));
@@ -368,6 +486,8 @@ where
         }
 
         let mut traj = Self::new();
+        // The iterator includes the states on both sides of each boundary
+        traj.boundaries = self.boundaries.clone();
         for state in self.every(step) {
             traj.states.push(state);
         }
@@ -389,7 +509,11 @@ where
         }
 
         let mut traj = Self::new();
+        traj.boundaries = self.boundaries.clone();
         for epoch in epochs {
+            if let Some(state) = self.before_boundary(*epoch) {
+                traj.states.push(state);
+            }
             traj.states.push(self.at(*epoch)?);
         }
 
@@ -457,7 +581,7 @@ where
 
         let mut cfg = cfg;
 
-        let mut fields = match cfg.fields {
+        let mut fields = match cfg.fields.clone() {
             Some(fields) => fields,
             None => S::export_params(),
         };
@@ -550,11 +674,7 @@ where
             "Purpose".to_string(),
             "Trajectory difference data".to_string(),
         );
-        if let Some(add_meta) = cfg.metadata {
-            for (k, v) in add_meta {
-                metadata.insert(k, v);
-            }
-        }
+        metadata.extend(cfg.all_metadata(None, None)?);
 
         let props = pq_writer(Some(metadata));
 
@@ -594,6 +714,8 @@ where
     type Output = Result<Traj<S>, NyxError>;
 
     /// Add one trajectory to another, returns an error if the frames don't match
+    ///
+    /// If the other trajectory starts at the end of this one from a different state, a boundary is added at that epoch.
     fn add(self, other: &Traj<S>) -> Self::Output {
         if self.first().frame() != other.first().frame() {
             Err(NyxError::Trajectory {
@@ -616,6 +738,17 @@ where
             }
 
             let mut me = self.clone();
+            if other.first().epoch() == self.last().epoch() && other.first() != self.last() {
+                // The other trajectory starts where this one ends but from another state, e.g. after an impulsive maneuver
+                me.boundaries.push(self.last().epoch());
+                me.states.push(*other.first());
+            }
+            me.boundaries.extend(
+                other
+                    .boundaries
+                    .iter()
+                    .filter(|epoch| **epoch > self.last().epoch()),
+            );
             // Now start adding the other segments while correcting the index
             for state in &other
                 .states
@@ -658,7 +791,7 @@ where
             let dur = self.last().epoch() - self.first().epoch();
             write!(
                 f,
-                "Trajectory {}in {} from {} to {} ({}, or {:.3} s) [{} states]",
+                "Trajectory {}in {} from {} to {} ({}, or {:.3} s) [{} states{}]",
                 match &self.name {
                     Some(name) => format!("of {name} "),
                     None => String::new(),
@@ -668,7 +801,11 @@ where
                 self.last().epoch(),
                 dur,
                 dur.to_seconds(),
-                self.states.len()
+                self.states.len(),
+                match self.boundaries.len() {
+                    0 => String::new(),
+                    n => format!(", {n} boundaries"),
+                }
             )
         }
     }
@@ -691,3 +828,148 @@ where
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod ut_traj_boundaries {
+    use crate::md::prelude::Traj;
+    use crate::time::Unit;
+    use crate::{Spacecraft, State};
+    use anise::almanac::Almanac;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    #[test]
+    fn maneuver_boundary() {
+        let path: PathBuf = [
+            env!("CARGO_MANIFEST_DIR"),
+            "data",
+            "03_tests",
+            "ccsds",
+            "oem",
+            "LEO_10s.oem",
+        ]
+        .iter()
+        .collect();
+        let traj: Traj<Spacecraft> = Traj::from_oem_file(path, None).unwrap();
+        let split = traj.states.len() / 2;
+
+        // Second leg starts from the end of the first one with an impulsive maneuver of 1 m/s
+        let mut pre_mnvr = traj.clone();
+        pre_mnvr.states.truncate(split + 1);
+        let mut post_mnvr = traj.clone();
+        post_mnvr.states.drain(..split);
+        for state in post_mnvr.states.iter_mut() {
+            state.orbit.velocity_km_s.x += 1e-3;
+        }
+        let mnvr_epoch = traj.states[split].epoch();
+
+        let merged = (&pre_mnvr + &post_mnvr).unwrap();
+        assert_eq!(merged.boundaries, vec![mnvr_epoch]);
+        assert_eq!(merged.states.len(), traj.states.len() + 1);
+        assert_eq!(merged.at(mnvr_epoch).unwrap(), post_mnvr.states[0]);
+        assert_eq!(
+            merged.before_boundary(mnvr_epoch).unwrap(),
+            pre_mnvr.states[split]
+        );
+
+        // Interpolation only uses the states of the same side of the maneuver
+        for offset in [Unit::Second * 5, Unit::Second * 25] {
+            assert_eq!(
+                merged.at(mnvr_epoch - offset).unwrap(),
+                pre_mnvr.at(mnvr_epoch - offset).unwrap()
+            );
+            assert_eq!(
+                merged.at(mnvr_epoch + offset).unwrap(),
+                post_mnvr.at(mnvr_epoch + offset).unwrap()
+            );
+        }
+
+        // Iterating includes both sides of the maneuver
+        let states = merged.every(Unit::Second * 10).collect::<Vec<Spacecraft>>();
+        assert_eq!(states, merged.states);
+        let states = merged.every(Unit::Second * 7).collect::<Vec<Spacecraft>>();
+        let idx = states
+            .iter()
+            .position(|state| state.epoch() >= mnvr_epoch)
+            .unwrap();
+        assert_eq!(states[idx], pre_mnvr.states[split]);
+        assert_eq!(states[idx + 1], post_mnvr.states[0]);
+        assert!(states[idx + 2].epoch() > mnvr_epoch);
+
+        let segments = merged.segments();
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments[0].states, pre_mnvr.states);
+        assert_eq!(segments[1].states, post_mnvr.states);
+
+        // The boundaries are preserved through a Parquet file
+        let path: PathBuf = [
+            env!("CARGO_MANIFEST_DIR"),
+            "data",
+            "04_output",
+            "ut_traj_boundaries.parquet",
+        ]
+        .iter()
+        .collect();
+        let out_path = merged
+            .to_parquet_simple(path, Arc::new(Almanac::default()))
+            .unwrap();
+        let reloaded = Traj::<Spacecraft>::from_parquet(out_path).unwrap();
+        assert_eq!(reloaded.boundaries, merged.boundaries);
+        assert_eq!(reloaded.states.len(), merged.states.len());
+        assert_eq!(reloaded.segments().len(), 2);
+        let before = reloaded.before_boundary(mnvr_epoch).unwrap();
+        assert!(
+            (before.orbit.velocity_km_s - pre_mnvr.states[split].orbit.velocity_km_s).norm()
+                < 1e-12
+        );
+
+        let resampled = merged.resample(Unit::Second * 20).unwrap();
+        assert_eq!(resampled.boundaries, merged.boundaries);
+        assert_eq!(resampled.at(mnvr_epoch).unwrap(), post_mnvr.states[0]);
+        assert_eq!(
+            resampled.before_boundary(mnvr_epoch).unwrap(),
+            pre_mnvr.states[split]
+        );
+
+        // Continuing from the same state does not add a boundary
+        let mut continued = traj.clone();
+        continued.states.drain(..split);
+        let merged = (&pre_mnvr + &continued).unwrap();
+        assert!(merged.boundaries.is_empty());
+        assert_eq!(merged.states, traj.states);
+    }
+
+    #[test]
+    fn gap_boundary() {
+        let path: PathBuf = [
+            env!("CARGO_MANIFEST_DIR"),
+            "data",
+            "03_tests",
+            "ccsds",
+            "oem",
+            "LEO_10s.oem",
+        ]
+        .iter()
+        .collect();
+        let traj: Traj<Spacecraft> = Traj::from_oem_file(path, None).unwrap();
+        let split = traj.states.len() / 2;
+
+        // Remove a minute of states, and start a new segment after this gap
+        let mut gapped = traj.clone();
+        gapped.states.drain(split..split + 6);
+        let restart = gapped.states[split].epoch();
+        gapped.boundaries.push(restart);
+        gapped.finalize();
+
+        let in_gap = restart - Unit::Second * 25;
+        assert!(gapped.in_gap(in_gap));
+        assert!(gapped.at(in_gap).is_err());
+        assert!(!gapped.in_gap(restart));
+        assert!(!gapped.in_gap(gapped.states[split - 1].epoch()));
+        assert_eq!(gapped.segments().len(), 2);
+
+        // Iterating skips the gap and continues in the next segment
+        let states = gapped.every(Unit::Second * 10).collect::<Vec<Spacecraft>>();
+        assert_eq!(states, gapped.states);
+    }
+}
@@ -19,8 +19,10 @@
 use super::{Interpolatable, Traj};
 use crate::linalg::allocator::Allocator;
 use crate::linalg::DefaultAllocator;
-use crate::time::TimeSeries;
+use crate::time::{Epoch, TimeSeries};
 use log::{error, log_enabled};
+use std::collections::VecDeque;
+
 pub struct TrajIterator<'a, S: Interpolatable>
 where
     DefaultAllocator: Allocator<S::VecLength> + Allocator<S::Size> + Allocator<S::Size, S::Size>,
@@ -28,6 +30,24 @@ where
     pub time_series: TimeSeries,
     /// A shared pointer to the original trajectory.
     pub traj: &'a Traj<S>,
+    /// Epoch of the previous item of the time series, used to find the boundaries crossed since then
+    prev_epoch: Option<Epoch>,
+    /// States to return before moving on in the time series, i.e. both sides of the boundaries
+    pending: VecDeque<S>,
+}
+
+impl<'a, S: Interpolatable> TrajIterator<'a, S>
+where
+    DefaultAllocator: Allocator<S::VecLength> + Allocator<S::Size> + Allocator<S::Size, S::Size>,
+{
+    pub fn new(time_series: TimeSeries, traj: &'a Traj<S>) -> Self {
+        Self {
+            time_series,
+            traj,
+            prev_epoch: None,
+            pending: VecDeque::new(),
+        }
+    }
 }
 
 impl<S: Interpolatable> Iterator for TrajIterator<'_, S>
@@ -37,13 +57,47 @@ where
     type Item = S;
 
     fn next(&mut self) -> Option<Self::Item> {
-        match self.time_series.next() {
-            Some(next_epoch) => match self.traj.at(next_epoch) {
-                Ok(item) => Some(item),
+        loop {
+            if let Some(state) = self.pending.pop_front() {
+                return Some(state);
+            }
+
+            let next_epoch = self.time_series.next()?;
+
+            // Include both sides of the boundaries crossed since the previous epoch
+            let mut at_boundary = false;
+            for boundary in self
+                .traj
+                .boundaries
+                .iter()
+                .filter(|boundary| match self.prev_epoch {
+                    Some(prev) => **boundary > prev && **boundary <= next_epoch,
+                    None => **boundary == next_epoch,
+                })
+            {
+                if let Some(state) = self.traj.before_boundary(*boundary) {
+                    self.pending.push_back(state);
+                }
+                if let Ok(state) = self.traj.at(*boundary) {
+                    self.pending.push_back(state);
+                }
+                at_boundary |= *boundary == next_epoch;
+            }
+            self.prev_epoch = Some(next_epoch);
+            if at_boundary {
+                continue;
+            }
+
+            match self.traj.at(next_epoch) {
+                Ok(item) => self.pending.push_back(item),
                 Err(e) => {
                     if next_epoch >= self.traj.first().epoch()
                         && next_epoch <= self.traj.last().epoch()
                     {
+                        if self.traj.in_gap(next_epoch) {
+                            // Segments are never interpolated across the gaps between them
+                            continue;
+                        }
                         let msg = format!(
                             "{e} out of bounds in {}! Please submit bug report with exported traj",
                             self.traj
@@ -54,10 +108,9 @@ where
                             eprintln!("{msg}");
                         };
                     }
-                    None
+                    return None;
                 }
-            },
-            None => None,
+            }
         }
     }
 }
@@ -28,11 +28,15 @@ pub use crate::od::*;
 use crate::propagators::Propagator;
 pub use crate::time::{Duration, Epoch, Unit};
 use anise::prelude::Almanac;
+use consider::{
+    dynamics_sensitivity, initial_sensitivity, measurement_sensitivity, to_dmatrix,
+    zero_considered_components, ConsiderKind, ConsiderParameter,
+};
 use indexmap::IndexSet;
 use log::error;
 use log::{debug, info, trace, warn};
 use msr::sensitivity::TrackerSensitivity; // Assuming this is the correct path
-use nalgebra::{Cholesky, Dyn, Matrix, VecStorage};
+use nalgebra::{Cholesky, DMatrix, Dyn, Matrix, VecStorage};
 use snafu::prelude::*;
 use solution::msr::MeasurementType;
 use std::collections::BTreeMap;
@@ -41,9 +45,15 @@ use std::ops::Add;
 use std::sync::Arc;
 use typed_builder::TypedBuilder;
 
+mod multi_arc;
+mod robust;
 mod solution;
 
-pub use solution::BLSSolution;
+pub use multi_arc::MultiArcSolution;
+pub use robust::RobustWeighting;
+pub use solution::{BLSIterationStats, BLSSolution};
+
+use super::process::ResidRejectCrit;
 
 use self::msr::TrackingDataArc;
 
@@ -116,6 +126,17 @@ pub struct BatchLeastSquares<
     /// Use diagonal scaling (D = sqrt(diag(H^T W H))) in LM
     #[builder(default = true)]
     pub lm_use_diag_scaling: bool,
+    /// Parameters which are not estimated, but whose uncertainty is considered in the covariance of the solution
+    #[builder(default)]
+    pub consider: Vec<ConsiderParameter>,
+    /// Sigma editing: from the second iteration onward, measurements whose residual normalized by the measurement noise is greater
+    /// than this number of times the RMS of the normalized residuals of the previous iteration are edited out of that iteration.
+    /// Edited measurements are re-admitted in later iterations if their residual falls back within the threshold.
+    #[builder(default, setter(strip_option))]
+    pub resid_crit: Option<ResidRejectCrit>,
+    /// Robust M-estimator weighting of the measurements which are not edited out
+    #[builder(default, setter(strip_option))]
+    pub robust_weighting: Option<RobustWeighting>,
     pub almanac: Arc<Almanac>,
 }
 
@@ -147,6 +168,46 @@ where
         &self,
         initial_guess: D::StateType,
         arc: &TrackingDataArc,
+    ) -> Result<BLSSolution<D::StateType>, ODError> {
+        self.process(initial_guess, None, arc)
+    }
+
+    /// Processes a tracking data arc to estimate the state using Batch Least Squares, starting from the provided a priori estimate.
+    ///
+    /// The inverse of the a priori covariance weighs the a priori state in the solution. Components with a zero a priori variance,
+    /// e.g. the Cr, Cd, and mass of a spacecraft whose covariance only includes orbital uncertainties, are weighed as without a priori.
+    pub fn estimate_with_a_priori(
+        &self,
+        a_priori: KfEstimate<D::StateType>,
+        arc: &TrackingDataArc,
+    ) -> Result<BLSSolution<D::StateType>, ODError> {
+        let n = <D::StateType as State>::Size::DIM;
+        let known: Vec<usize> = (0..n).filter(|&i| a_priori.covar[(i, i)] > 0.0).collect();
+        let known_covar = DMatrix::from_fn(known.len(), known.len(), |i, j| {
+            a_priori.covar[(known[i], known[j])]
+        });
+        let known_info = known_covar
+            .cholesky()
+            .ok_or(ODError::SingularInformationMatrix)?
+            .inverse();
+
+        let mut a_priori_info = StateMatrix::<D>::identity();
+        for (i, &row) in known.iter().enumerate() {
+            for (j, &col) in known.iter().enumerate() {
+                a_priori_info[(row, col)] = known_info[(i, j)];
+            }
+        }
+
+        self.process(a_priori.state(), Some(a_priori_info), arc)
+    }
+
+    /// Batch least squares iterations from the initial guess. Without a priori information matrix, the information matrix is
+    /// initialized to identity and the a priori deviation is ignored.
+    fn process(
+        &self,
+        initial_guess: D::StateType,
+        a_priori_info: Option<StateMatrix<D>>,
+        arc: &TrackingDataArc,
     ) -> Result<BLSSolution<D::StateType>, ODError> {
         let measurements = &arc.measurements;
         let num_measurements = measurements.len();
@@ -168,11 +229,19 @@ where
 
         let mut current_estimate = initial_guess;
         let mut current_covariance = StateMatrix::<D>::zeros();
+        let mut consider_sensitivity = None;
         let mut converged = false;
         let mut corr_pos_km = f64::MAX;
         let mut lambda = self.lm_lambda_init;
         let mut current_rms = f64::MAX;
         let mut iter: usize = 0;
+        // Sum of all of the corrections applied to the initial guess, i.e. the opposite of the a priori deviation.
+        let mut total_correction = OVector::<f64, <D::StateType as State>::Size>::zeros();
+        let mut iterations = Vec::new();
+        let mut residuals = Vec::new();
+        let mut estimates = Vec::new();
+        // RMS of the normalized residuals of the last accepted iteration, against which the residuals are edited and weighted.
+        let mut resid_scale = f64::INFINITY;
 
         let mut unknown_trackers = IndexSet::new();
 
@@ -183,11 +252,18 @@ where
 
             // Re-initialize matrices for this iteration
             // Information Matrix: Lambda = H^T * W * H
-            let mut info_matrix = StateMatrix::<D>::identity();
+            let mut info_matrix = a_priori_info.unwrap_or_else(StateMatrix::<D>::identity);
             // Normal Matrix: N = H^T * W * dy
-            let mut normal_matrix = OVector::<f64, <D::StateType as State>::Size>::zeros();
+            let mut normal_matrix = match &a_priori_info {
+                // The a priori deviation from the current estimate is the opposite of the corrections applied so far.
+                Some(a_priori_info) => -(a_priori_info * total_correction),
+                None => OVector::<f64, <D::StateType as State>::Size>::zeros(),
+            };
             // Sum of squares of weighted residuals for RMS calculation and LM cost
             let mut sum_sq_weighted_residuals = 0.0;
+            // Sum of squares of the normalized residuals of the measurements which were not edited out
+            let mut sum_sq_normalized_residuals = 0.0;
+            let mut num_admitted: usize = 0;
 
             // Set up a single propagator for the whole iteration.
             let mut prop_inst = self.prop.with(current_estimate.with_stm(), self.almanac.clone()).quiet();
@@ -196,6 +272,16 @@ where
             // Store the STM to the start of the batch.
             let mut stm = StateMatrix::<D>::identity();
 
+            // Map of the consider parameters to the state, Phi(t_i, t_0) * S_0 + Theta(t_i, t_0)
+            let mut consider_map = initial_sensitivity::<<D::StateType as State>::Size>(&self.consider);
+            // Cross information matrix of the estimated and consider parameters: M_xc = H^T * W * H_c
+            let mut consider_info = DMatrix::<f64>::zeros(
+                <D::StateType as State>::Size::DIM,
+                self.consider.len(),
+            );
+            // Reference state, STM to the start of the batch, sensitivity, and prefit residual of each measurement of this iteration.
+            let mut msr_records = Vec::new();
+
             for (epoch_ref, msr) in measurements.iter() {
                 let msr_epoch = *epoch_ref;
 
@@ -210,6 +296,7 @@ where
                     let next_step = delta_t.min(prop_inst.step_size).min(self.max_step);
 
                     // Propagate reference state from the previous state to msr_epoch
+                    let step_start = prop_inst.state;
                     let this_state = prop_inst.for_duration(next_step).context(ODPropSnafu)?;
                     epoch = this_state.epoch();
 
@@ -217,6 +304,19 @@ where
                     let step_stm = this_state.stm().expect("STM unavailable");
                     // Compute the STM Phi(t_{i+1}, t_0) = Phi(t_{i+1}, t_i) * Phi(t_i, t_0)
                     stm = step_stm * stm;
+                    // Reset the STM so that the next one is only that of the next step.
+                    prop_inst.state.reset_stm();
+
+                    if !self.consider.is_empty() {
+                        consider_map = to_dmatrix(&step_stm) * consider_map
+                            + dynamics_sensitivity(
+                                &self.consider,
+                                &self.prop,
+                                self.almanac.clone(),
+                                step_start,
+                                next_step,
+                            )?;
+                    }
 
                     if (epoch - msr_epoch).abs() < self.epoch_precision {
                         // Get the correct tracking device
@@ -277,10 +377,57 @@ where
                             let r_variance = r_matrix[(0, 0)];
 
                             ensure!(r_variance > 0.0, SingularNoiseRkSnafu);
-                            let weight = 1.0 / r_variance;
+                            let normalized_resid = residual / r_variance.sqrt();
+
+                            // Edit the outliers out of this iteration and apply the robust weights, if configured.
+                            let scaled_resid = normalized_resid / resid_scale;
+                            let edited = self
+                                .resid_crit
+                                .is_some_and(|crit| scaled_resid.abs() > crit.num_sigmas);
+                            let weight = if edited {
+                                0.0
+                            } else {
+                                sum_sq_normalized_residuals += normalized_resid.powi(2);
+                                num_admitted += 1;
+                                self.robust_weighting
+                                    .map_or(1.0, |robust| robust.weight(scaled_resid))
+                                    / r_variance
+                            };
 
                             // Compute H_matrix = H_tilde * Phi(t_i, t_0) (sensitivity wrt initial state X_0)
-                            let h_matrix = h_tilde * stm;
+                            let mut h_matrix = &h_tilde * stm;
+
+                            if !self.consider.is_empty() {
+                                // Considered state components are not estimated: their sensitivity is that of the consider parameters.
+                                for param in &self.consider {
+                                    if let ConsiderKind::StateComponent { index } = param.kind {
+                                        h_matrix[(0, index)] = 0.0;
+                                    }
+                                }
+                                let h_consider = to_dmatrix(&h_tilde) * &consider_map
+                                    + measurement_sensitivity::<U1, _, _>(
+                                        &self.consider,
+                                        device,
+                                        &msr_types,
+                                        this_state,
+                                        self.almanac.clone(),
+                                    )?;
+                                consider_info += to_dmatrix(&h_matrix.transpose()) * h_consider * weight;
+                            }
+
+                            let mut prefit = Residual::accepted(
+                                msr_epoch,
+                                OVector::<f64, U1>::new(residual),
+                                OVector::<f64, U1>::new(residual),
+                                normalized_resid,
+                                r_matrix.diagonal(),
+                                OVector::<f64, U1>::new(real_obs),
+                                OVector::<f64, U1>::new(computed_obs),
+                            );
+                            prefit.tracker = Some(device.name());
+                            prefit.msr_types = msr_types;
+                            prefit.rejected = edited;
+                            msr_records.push((this_state, stm, h_matrix.clone(), prefit));
 
                             // Accumulate Information Matrix: info_matrix += H^T * W * H
                             // Recall that the weight is a scalar, so we can move it to the end of the operation.
@@ -302,6 +449,7 @@ where
 
             // Use num_measurements for consistency
             let current_iter_rms = (sum_sq_weighted_residuals / num_measurements as f64).sqrt();
+            let lm_lambda = (self.solver == BLSSolver::LevenbergMarquardt).then_some(lambda);
 
             match self.solver {
                 BLSSolver::NormalEquations => {
@@ -375,14 +523,29 @@ where
                 }
             }
 
+            let corr_vel_km_s = state_correction.fixed_rows::<3>(3).norm();
+            iterations.push(BLSIterationStats {
+                iteration: iter,
+                rms: current_iter_rms,
+                num_residuals: msr_records.len(),
+                num_edited: msr_records.len() - num_admitted,
+                corr_pos_km: state_correction.fixed_rows::<3>(0).norm(),
+                corr_vel_km_s,
+                lm_lambda,
+                accepted: iteration_cost_decreased,
+            });
+
             // --- Update State Estimate ---
             // Only update if the step is considered successful (esp. for LM)
             // Also hit if using normal equations because iteration_cost_decreased is forced to true
             if iteration_cost_decreased {
                 current_estimate = current_estimate + state_correction;
+                total_correction += state_correction;
+                if num_admitted > 0 {
+                    resid_scale = (sum_sq_normalized_residuals / num_admitted as f64).sqrt();
+                }
                 corr_pos_km = state_correction.fixed_rows::<3>(0).norm();
 
-                let corr_vel_km_s = state_correction.fixed_rows::<3>(3).norm();
                 info!(
                     "[{iter}/{}] RMS: {current_iter_rms:.3}; corrections: {:.3} m\t{:.3} m/s",
                     self.max_iterations,
@@ -399,6 +562,35 @@ where
                     }
                };
 
+                if !self.consider.is_empty() {
+                    // Sensitivity of the estimation errors to the consider parameters: S = -P * M_xc
+                    let mut sensitivity = -to_dmatrix(&current_covariance) * &consider_info;
+                    for (j, param) in self.consider.iter().enumerate() {
+                        if let ConsiderKind::StateComponent { index } = param.kind {
+                            sensitivity[(index, j)] = 1.0;
+                        }
+                    }
+                    zero_considered_components(&self.consider, &mut current_covariance)?;
+                    consider_sensitivity = Some(sensitivity);
+                }
+
+                // Map the correction and the covariance to each measurement to compute the postfit residuals.
+                residuals.clear();
+                estimates.clear();
+                for (ref_state, stm, h_matrix, mut residual) in msr_records {
+                    residual.postfit -= h_matrix * state_correction;
+                    residuals.push(residual);
+                    let covar = stm * current_covariance * stm.transpose();
+                    estimates.push(KfEstimate {
+                        nominal_state: ref_state,
+                        state_deviation: stm * state_correction,
+                        covar,
+                        covar_bar: covar,
+                        predicted: false,
+                        stm,
+                    });
+                }
+
                 // --- Check Convergence ---
                 if corr_pos_km < self.tolerance_pos_km {
                     info!("Converged in {iter} iterations.");
@@ -426,6 +618,11 @@ where
             final_rms: current_rms,
             final_corr_pos_km: corr_pos_km,
             converged,
+            consider: self.consider.clone(),
+            consider_sensitivity,
+            iterations,
+            residuals,
+            estimates,
         })
     }
 }
@@ -0,0 +1,654 @@
+/*
+    Nyx, blazing fast astrodynamics
+    Copyright (C) 2018-onwards Christopher Rabotin <christopher.rabotin@gmail.com>
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published
+    by the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use super::{BLSIterationStats, BLSSolution, BLSSolver, BatchLeastSquares, StateMatrix};
+use crate::dynamics::Dynamics;
+use crate::linalg::allocator::Allocator;
+use crate::linalg::{DMatrix, DVector, DefaultAllocator, DimName, OMatrix, OVector, U1};
+use crate::md::trajectory::Interpolatable;
+use crate::od::consider::{
+    dynamics_sensitivity, initial_sensitivity, measurement_sensitivity, to_dmatrix, ConsiderKind,
+    ConsiderParameter,
+};
+use crate::od::estimate::{KfEstimate, Residual};
+use crate::od::msr::sensitivity::TrackerSensitivity;
+use crate::od::msr::TrackingDataArc;
+use crate::od::{
+    InvalidMeasurementSnafu, NoTrackingArcsSnafu, ODError, ODPropSnafu, SingularNoiseRkSnafu,
+    TooFewMeasurementsSnafu,
+};
+use crate::time::Duration;
+use crate::State;
+use indexmap::IndexSet;
+use log::{debug, error, info, warn};
+use snafu::prelude::*;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::Add;
+
+/// The global parameters have converged once their corrections are below this fraction of their standard deviation.
+const GLOBAL_CONVERGENCE_SIGMA_FRACTION: f64 = 1e-2;
+
+/// Solution of the multi-arc Batch Least Squares estimation: the local solution of each arc and the global parameters.
+#[derive(Debug, Clone)]
+pub struct MultiArcSolution<StateType: State>
+where
+    DefaultAllocator: Allocator<<StateType as State>::Size>
+        + Allocator<<StateType as State>::Size, <StateType as State>::Size>
+        + Allocator<<StateType as State>::VecLength>,
+    <DefaultAllocator as Allocator<<StateType as State>::Size>>::Buffer<f64>: Copy,
+    <DefaultAllocator as Allocator<<StateType as State>::Size, <StateType as State>::Size>>::Buffer<
+        f64,
+    >: Copy,
+{
+    /// Solution of each arc, in the order of the arcs. The estimated state and the covariance of each arc include the global state components.
+    pub arcs: Vec<BLSSolution<StateType>>,
+    /// Parameters estimated globally, i.e. common to all arcs, with their a priori standard deviation
+    pub global: Vec<ConsiderParameter>,
+    /// Estimated corrections of the global parameters, with respect to the initial guesses of the state components and GM,
+    /// and with respect to zero for the measurement biases and tracker location errors
+    pub global_corrections: DVector<f64>,
+    /// Covariance of the global parameters
+    pub global_covar: DMatrix<f64>,
+    /// Cross covariance between the state of each arc and the global parameters, of size n x g
+    pub cross_covar: Vec<DMatrix<f64>>,
+    pub num_iterations: usize,
+    pub converged: bool,
+}
+
+impl<StateType: State> MultiArcSolution<StateType>
+where
+    DefaultAllocator: Allocator<<StateType as State>::Size>
+        + Allocator<<StateType as State>::Size, <StateType as State>::Size>
+        + Allocator<<StateType as State>::VecLength>,
+    <DefaultAllocator as Allocator<<StateType as State>::Size>>::Buffer<f64>: Copy,
+    <DefaultAllocator as Allocator<<StateType as State>::Size, <StateType as State>::Size>>::Buffer<
+        f64,
+    >: Copy,
+{
+    /// Returns the standard deviation of each global parameter.
+    pub fn global_sigmas(&self) -> DVector<f64> {
+        self.global_covar.diagonal().map(f64::sqrt)
+    }
+}
+
+impl<StateType> fmt::Display for MultiArcSolution<StateType>
+where
+    StateType: State,
+    DefaultAllocator: Allocator<<StateType as State>::Size>
+        + Allocator<<StateType as State>::Size, <StateType as State>::Size>
+        + Allocator<<StateType as State>::VecLength>,
+    <DefaultAllocator as Allocator<<StateType as State>::Size>>::Buffer<f64>: Copy,
+    <DefaultAllocator as Allocator<<StateType as State>::Size, <StateType as State>::Size>>::Buffer<
+        f64,
+    >: Copy,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Converged: {}", self.converged)?;
+        writeln!(f, "Iterations: {}", self.num_iterations)?;
+        let sigmas = self.global_sigmas();
+        for (j, param) in self.global.iter().enumerate() {
+            writeln!(
+                f,
+                "Global {param}: correction = {:.6e} ± {:.6e}",
+                self.global_corrections[j], sigmas[j]
+            )?;
+        }
+        for (i, arc) in self.arcs.iter().enumerate() {
+            writeln!(
+                f,
+                "Arc #{i}: RMS = {:.3}\t{}",
+                arc.final_rms,
+                arc.estimated_state.orbit()
+            )?;
+        }
+        write!(f, "Global Covariance:\n{:.3e}", self.global_covar)
+    }
+}
+
+/// Normal equations of one arc for one iteration, partitioned between the local state and the global parameters.
+struct ArcNormals<S: State>
+where
+    DefaultAllocator: Allocator<S::Size> + Allocator<S::Size, S::Size> + Allocator<S::VecLength>,
+{
+    /// Local information matrix, Lambda_xx = H_x^T * W * H_x, including the unit a priori information
+    info: DMatrix<f64>,
+    /// Cross information matrix, M_xg = H_x^T * W * H_g
+    cross_info: DMatrix<f64>,
+    /// Contribution of this arc to the global information matrix, H_g^T * W * H_g
+    global_info: DMatrix<f64>,
+    normal: DVector<f64>,
+    global_normal: DVector<f64>,
+    sum_sq_weighted_residuals: f64,
+    sum_sq_normalized_residuals: f64,
+    num_admitted: usize,
+    /// Reference state, STM to the start of the arc, local and global sensitivities, and prefit residual of each measurement
+    records: Vec<(
+        S,
+        OMatrix<f64, S::Size, S::Size>,
+        DMatrix<f64>,
+        DMatrix<f64>,
+        Residual<U1>,
+    )>,
+}
+
+impl<D, Trk> BatchLeastSquares<D, Trk>
+where
+    D: Dynamics,
+    Trk: TrackerSensitivity<D::StateType, D::StateType> + Clone,
+    D::StateType: Interpolatable
+        + Add<OVector<f64, <D::StateType as State>::Size>, Output = D::StateType>
+        + std::fmt::Debug,
+    <D::StateType as State>::Size: DimName,
+    <DefaultAllocator as Allocator<<D::StateType as State>::VecLength>>::Buffer<f64>: Send,
+    <DefaultAllocator as Allocator<<D::StateType as State>::Size>>::Buffer<f64>: Copy,
+    <DefaultAllocator as Allocator<<D::StateType as State>::Size, <D::StateType as State>::Size>>::Buffer<f64>: Copy,
+    DefaultAllocator: Allocator<<D::StateType as State>::Size>
+        + Allocator<<D::StateType as State>::VecLength>
+        + Allocator<U1>
+        + Allocator<U1, <D::StateType as State>::Size>
+        + Allocator<<D::StateType as State>::Size, U1>
+        + Allocator<U1, U1>
+        + Allocator<<D::StateType as State>::Size, <D::StateType as State>::Size>,
+{
+    /// Processes several disjoint tracking data arcs, each with its own initial guess, to estimate the state at the start of
+    /// each arc along with parameters common to all arcs.
+    ///
+    /// The global parameters use the same definitions as the consider parameters, and their standard deviation is used as
+    /// a priori information. A global state component, e.g. the coefficient of reflectivity, is corrected identically in
+    /// the state of every arc; the GM correction is applied to the frame of the state of every arc.
+    ///
+    /// The local states are eliminated from the normal equations of each arc, so the global parameters are solved for
+    /// first, and the local corrections are then back-substituted. Only the normal equations solver is supported, and
+    /// consider parameters cannot be combined with the multi-arc estimation.
+    ///
+    /// The estimation has converged once the position correction of every arc is below the position tolerance, and the
+    /// correction of every global parameter is below one percent of its standard deviation.
+    pub fn estimate_multi_arc(
+        &self,
+        arcs: &[(D::StateType, &TrackingDataArc)],
+        global: &[ConsiderParameter],
+    ) -> Result<MultiArcSolution<D::StateType>, ODError> {
+        let n = <D::StateType as State>::Size::DIM;
+        let g = global.len();
+
+        ensure!(
+            !arcs.is_empty(),
+            NoTrackingArcsSnafu {
+                action: "multi-arc BLSE"
+            }
+        );
+        for (_, arc) in arcs {
+            ensure!(
+                arc.measurements.len() >= 2,
+                TooFewMeasurementsSnafu {
+                    need: 2_usize,
+                    action: "multi-arc BLSE"
+                }
+            );
+        }
+        ensure!(
+            self.solver == BLSSolver::NormalEquations,
+            crate::od::ODLimitationSnafu {
+                action: "multi-arc estimation only supports the normal equations solver"
+            }
+        );
+        ensure!(
+            self.consider.is_empty(),
+            crate::od::ODLimitationSnafu {
+                action: "multi-arc estimation does not support consider parameters"
+            }
+        );
+        for param in global {
+            if let ConsiderKind::StateComponent { index } = param.kind {
+                ensure!(
+                    index < n,
+                    crate::od::ODLimitationSnafu {
+                        action: "global state component is not in the state vector"
+                    }
+                );
+            }
+        }
+
+        info!(
+            "Multi-arc Batch Least Squares estimation of {} arcs with {g} global parameters",
+            arcs.len()
+        );
+
+        // A priori information of the global parameters.
+        let global_a_priori_info =
+            DMatrix::from_diagonal(&DVector::from_iterator(g, global.iter().map(|p| p.sigma.powi(-2))));
+
+        // Maps each global state component to the state vector, of size n x g.
+        let mut component_map = DMatrix::<f64>::zeros(n, g);
+        for (j, param) in global.iter().enumerate() {
+            if let ConsiderKind::StateComponent { index } = param.kind {
+                component_map[(index, j)] = 1.0;
+            }
+        }
+
+        let mut arc_states: Vec<D::StateType> = arcs.iter().map(|(state, _)| *state).collect();
+        let mut global_corrections = DVector::<f64>::zeros(g);
+        let mut global_covar = DMatrix::<f64>::zeros(g, g);
+        let mut arc_covars = vec![StateMatrix::<D>::zeros(); arcs.len()];
+        let mut cross_covar = vec![DMatrix::<f64>::zeros(n, g); arcs.len()];
+        let mut arc_iterations = vec![Vec::new(); arcs.len()];
+        let mut arc_residuals = vec![Vec::new(); arcs.len()];
+        let mut arc_estimates = vec![Vec::new(); arcs.len()];
+        let mut arc_rms = vec![f64::MAX; arcs.len()];
+        let mut arc_corr_pos_km = vec![f64::MAX; arcs.len()];
+        let mut resid_scale = f64::INFINITY;
+        let mut converged = false;
+        let mut iter: usize = 0;
+        let mut unknown_trackers = IndexSet::new();
+
+        while iter < self.max_iterations {
+            iter += 1;
+
+            let mut devices = self.devices_with_locations(global, &global_corrections)?;
+
+            // Reduced normal equations of the global parameters, after elimination of the local states.
+            let mut reduced_info = global_a_priori_info.clone();
+            let mut reduced_normal = -(&global_a_priori_info * &global_corrections);
+            let mut sum_sq_normalized_residuals = 0.0;
+            let mut num_admitted = 0;
+            let mut all_normals = Vec::with_capacity(arcs.len());
+
+            for (i, (_, arc)) in arcs.iter().enumerate() {
+                let normals = self.accumulate_arc(
+                    arc_states[i],
+                    arc,
+                    &mut devices,
+                    global,
+                    &global_corrections,
+                    resid_scale,
+                    &mut unknown_trackers,
+                )?;
+
+                let info_inv = normals
+                    .info
+                    .clone()
+                    .cholesky()
+                    .ok_or(ODError::SingularInformationMatrix)?
+                    .inverse();
+                let info_inv_cross = &info_inv * &normals.cross_info;
+
+                reduced_info += &normals.global_info - normals.cross_info.transpose() * &info_inv_cross;
+                reduced_normal += &normals.global_normal - info_inv_cross.transpose() * &normals.normal;
+                sum_sq_normalized_residuals += normals.sum_sq_normalized_residuals;
+                num_admitted += normals.num_admitted;
+
+                all_normals.push((normals, info_inv, info_inv_cross));
+            }
+
+            // Solve for the global corrections, and back-substitute them in the local corrections.
+            global_covar = reduced_info
+                .cholesky()
+                .ok_or(ODError::SingularInformationMatrix)?
+                .inverse();
+            let global_correction = &global_covar * &reduced_normal;
+
+            let mut max_corr_pos_km: f64 = 0.0;
+            for (i, (normals, info_inv, info_inv_cross)) in all_normals.into_iter().enumerate() {
+                let local_correction =
+                    &info_inv * (&normals.normal - &normals.cross_info * &global_correction);
+                let state_correction = OVector::<f64, <D::StateType as State>::Size>::from_column_slice(
+                    (&local_correction + &component_map * &global_correction).as_slice(),
+                );
+
+                let corr_pos_km = state_correction.fixed_rows::<3>(0).norm();
+                max_corr_pos_km = max_corr_pos_km.max(corr_pos_km);
+                let num_residuals = normals.records.len();
+                arc_rms[i] = (normals.sum_sq_weighted_residuals / arcs[i].1.measurements.len() as f64).sqrt();
+                arc_corr_pos_km[i] = corr_pos_km;
+                arc_iterations[i].push(BLSIterationStats {
+                    iteration: iter,
+                    rms: arc_rms[i],
+                    num_residuals,
+                    num_edited: num_residuals - normals.num_admitted,
+                    corr_pos_km,
+                    corr_vel_km_s: state_correction.fixed_rows::<3>(3).norm(),
+                    lm_lambda: None,
+                    accepted: true,
+                });
+
+                // Covariance of the local state and of its cross covariance with the global parameters.
+                let mut local_covar = &info_inv
+                    + &info_inv_cross * &global_covar * info_inv_cross.transpose();
+                let mut local_cross = -(&info_inv_cross * &global_covar);
+                for param in global {
+                    if let ConsiderKind::StateComponent { index } = param.kind {
+                        local_covar.row_mut(index).fill(0.0);
+                        local_covar.column_mut(index).fill(0.0);
+                        local_cross.row_mut(index).fill(0.0);
+                    }
+                }
+                // Add the uncertainty of the global state components to the covariance of the state of this arc.
+                let mapped_cross = &local_cross * component_map.transpose();
+                let covar = local_covar
+                    + &mapped_cross
+                    + mapped_cross.transpose()
+                    + &component_map * &global_covar * component_map.transpose();
+                arc_covars[i] = StateMatrix::<D>::from_fn(|row, col| covar[(row, col)]);
+                cross_covar[i] = local_cross + &component_map * &global_covar;
+
+                // Map the corrections and the covariance to each measurement to compute the postfit residuals.
+                arc_residuals[i].clear();
+                arc_estimates[i].clear();
+                for (ref_state, stm, h_local, h_global, mut residual) in normals.records {
+                    residual.postfit[0] -= (h_local * &local_correction)[0] + (h_global * &global_correction)[0];
+                    arc_residuals[i].push(residual);
+                    let covar = stm * arc_covars[i] * stm.transpose();
+                    arc_estimates[i].push(KfEstimate {
+                        nominal_state: ref_state,
+                        state_deviation: stm * state_correction,
+                        covar,
+                        covar_bar: covar,
+                        predicted: false,
+                        stm,
+                    });
+                }
+
+                arc_states[i] = arc_states[i] + state_correction;
+            }
+
+            // Apply the GM corrections to the frame of the state of each arc.
+            for (j, param) in global.iter().enumerate() {
+                if matches!(param.kind, ConsiderKind::GravParam) {
+                    for state in arc_states.iter_mut() {
+                        let mut orbit = state.orbit();
+                        let mu_km3_s2 = orbit.frame.mu_km3_s2().map_err(|_| ODError::ODLimitation {
+                            action: "estimating GM requires the gravitational parameter of the frame of the estimated state",
+                        })?;
+                        orbit.frame = orbit.frame.with_mu_km3_s2(mu_km3_s2 + global_correction[j]);
+                        state.set_orbit(orbit);
+                    }
+                }
+            }
+            global_corrections += &global_correction;
+
+            if num_admitted > 0 {
+                resid_scale = (sum_sq_normalized_residuals / num_admitted as f64).sqrt();
+            }
+
+            info!(
+                "[{iter}/{}] largest position correction: {:.3} m; global corrections: {:.3e}",
+                self.max_iterations,
+                max_corr_pos_km * 1e3,
+                global_correction.transpose()
+            );
+
+            // The global corrections must also be negligible compared to the uncertainty of the global parameters.
+            let global_converged = global_correction
+                .iter()
+                .zip(global_covar.diagonal().iter())
+                .all(|(correction, variance)| {
+                    correction.abs() <= GLOBAL_CONVERGENCE_SIGMA_FRACTION * variance.sqrt()
+                });
+            if max_corr_pos_km < self.tolerance_pos_km && global_converged {
+                info!("Converged in {iter} iterations.");
+                converged = true;
+                break;
+            }
+        }
+
+        if !converged {
+            warn!("Not converged after {} iterations.", self.max_iterations);
+        }
+
+        let arc_solutions = arc_states
+            .into_iter()
+            .zip(arc_covars)
+            .zip(arc_iterations.into_iter().zip(arc_residuals))
+            .zip(arc_estimates)
+            .enumerate()
+            .map(
+                |(i, (((estimated_state, covariance), (iterations, residuals)), estimates))| {
+                    BLSSolution {
+                        estimated_state,
+                        covariance,
+                        num_iterations: iter,
+                        final_rms: arc_rms[i],
+                        final_corr_pos_km: arc_corr_pos_km[i],
+                        converged,
+                        consider: Vec::new(),
+                        consider_sensitivity: None,
+                        iterations,
+                        residuals,
+                        estimates,
+                    }
+                },
+            )
+            .collect();
+
+        Ok(MultiArcSolution {
+            arcs: arc_solutions,
+            global: global.to_vec(),
+            global_corrections,
+            global_covar,
+            cross_covar,
+            num_iterations: iter,
+            converged,
+        })
+    }
+
+    /// Returns the tracking devices with the estimated location errors of the global parameters applied.
+    fn devices_with_locations(
+        &self,
+        global: &[ConsiderParameter],
+        global_corrections: &DVector<f64>,
+    ) -> Result<BTreeMap<String, Trk>, ODError> {
+        let mut devices = self.devices.clone();
+        for (j, param) in global.iter().enumerate() {
+            if let ConsiderKind::TrackerLocation { tracker, param } = &param.kind {
+                let device = devices.get_mut(tracker).ok_or(ODError::ODLimitation {
+                    action: "global tracker location parameters require the tracker to be configured",
+                })?;
+                *device = device
+                    .with_offset(*param, global_corrections[j])
+                    .ok_or(ODError::ODLimitation {
+                        action: "tracker does not support location parameters",
+                    })?;
+            }
+        }
+        Ok(devices)
+    }
+
+    /// Accumulates the normal equations of one arc from the provided reference state at its start.
+    #[allow(clippy::too_many_arguments)]
+    fn accumulate_arc(
+        &self,
+        start_state: D::StateType,
+        arc: &TrackingDataArc,
+        devices: &mut BTreeMap<String, Trk>,
+        global: &[ConsiderParameter],
+        global_corrections: &DVector<f64>,
+        resid_scale: f64,
+        unknown_trackers: &mut IndexSet<String>,
+    ) -> Result<ArcNormals<D::StateType>, ODError> {
+        let n = <D::StateType as State>::Size::DIM;
+        let g = global.len();
+
+        let mut normals = ArcNormals {
+            info: DMatrix::identity(n, n),
+            cross_info: DMatrix::zeros(n, g),
+            global_info: DMatrix::zeros(g, g),
+            normal: DVector::zeros(n),
+            global_normal: DVector::zeros(g),
+            sum_sq_weighted_residuals: 0.0,
+            sum_sq_normalized_residuals: 0.0,
+            num_admitted: 0,
+            records: Vec::new(),
+        };
+
+        let mut prop_inst = self
+            .prop
+            .with(start_state.with_stm(), self.almanac.clone())
+            .quiet();
+        let mut epoch = start_state.epoch();
+        let mut stm = StateMatrix::<D>::identity();
+        // Map of the global parameters to the state, Phi(t_i, t_0) * S_0 + Theta(t_i, t_0)
+        let mut global_map = initial_sensitivity::<<D::StateType as State>::Size>(global);
+
+        for (epoch_ref, msr) in arc.measurements.iter() {
+            let msr_epoch = *epoch_ref;
+
+            loop {
+                let delta_t = msr_epoch - epoch;
+                if delta_t <= Duration::ZERO {
+                    break;
+                }
+
+                let next_step = delta_t.min(prop_inst.step_size).min(self.max_step);
+
+                let step_start = prop_inst.state;
+                let this_state = prop_inst.for_duration(next_step).context(ODPropSnafu)?;
+                epoch = this_state.epoch();
+
+                let step_stm = this_state.stm().expect("STM unavailable");
+                stm = step_stm * stm;
+                prop_inst.state.reset_stm();
+
+                if !global.is_empty() {
+                    global_map = to_dmatrix(&step_stm) * global_map
+                        + dynamics_sensitivity(
+                            global,
+                            &self.prop,
+                            self.almanac.clone(),
+                            step_start,
+                            next_step,
+                        )?;
+                }
+
+                if (epoch - msr_epoch).abs() >= self.epoch_precision {
+                    continue;
+                }
+
+                let device = match devices.get_mut(&msr.tracker) {
+                    Some(d) => d,
+                    None => {
+                        if !unknown_trackers.contains(&msr.tracker) {
+                            error!(
+                                "Tracker {} is not in the list of configured devices",
+                                msr.tracker
+                            );
+                        }
+                        unknown_trackers.insert(msr.tracker.clone());
+                        continue;
+                    }
+                };
+
+                for msr_type in msr.data.keys().copied() {
+                    let mut msr_types = IndexSet::new();
+                    msr_types.insert(msr_type);
+
+                    let h_tilde =
+                        device.h_tilde::<U1>(msr, &msr_types, &this_state, self.almanac.clone())?;
+
+                    let computed_meas =
+                        match device.measure_instantaneous(this_state, None, self.almanac.clone())? {
+                            Some(cm) => cm,
+                            None => {
+                                debug!("Device {} does not expect measurement at epoch {msr_epoch}, skipping", msr.tracker);
+                                continue;
+                            }
+                        };
+
+                    // The estimated global measurement biases are part of the computed observation.
+                    let mut computed_obs = computed_meas.observation::<U1>(&msr_types)[0];
+                    for (j, param) in global.iter().enumerate() {
+                        if let ConsiderKind::MeasurementBias { tracker, msr_type: bias_type } = &param.kind {
+                            if *tracker == msr.tracker && *bias_type == msr_type {
+                                computed_obs += global_corrections[j];
+                            }
+                        }
+                    }
+
+                    let real_obs = msr.observation::<U1>(&msr_types)[0];
+                    ensure!(
+                        real_obs.is_finite(),
+                        InvalidMeasurementSnafu {
+                            epoch: msr_epoch,
+                            val: real_obs
+                        }
+                    );
+
+                    let residual = real_obs - computed_obs;
+
+                    let r_matrix = device.measurement_covar_matrix::<U1>(&msr_types, msr_epoch)?;
+                    let r_variance = r_matrix[(0, 0)];
+                    ensure!(r_variance > 0.0, SingularNoiseRkSnafu);
+                    let normalized_resid = residual / r_variance.sqrt();
+
+                    let scaled_resid = normalized_resid / resid_scale;
+                    let edited = self
+                        .resid_crit
+                        .is_some_and(|crit| scaled_resid.abs() > crit.num_sigmas);
+                    let weight = if edited {
+                        0.0
+                    } else {
+                        normals.sum_sq_normalized_residuals += normalized_resid.powi(2);
+                        normals.num_admitted += 1;
+                        self.robust_weighting
+                            .map_or(1.0, |robust| robust.weight(scaled_resid))
+                            / r_variance
+                    };
+
+                    // Global state components are not part of the local state: their sensitivity is that of the global parameters.
+                    let mut h_local = to_dmatrix(&(&h_tilde * stm));
+                    for param in global {
+                        if let ConsiderKind::StateComponent { index } = param.kind {
+                            h_local[(0, index)] = 0.0;
+                        }
+                    }
+                    let h_global = to_dmatrix(&h_tilde) * &global_map
+                        + measurement_sensitivity::<U1, _, _>(
+                            global,
+                            device,
+                            &msr_types,
+                            this_state,
+                            self.almanac.clone(),
+                        )?;
+
+                    normals.info += h_local.transpose() * &h_local * weight;
+                    normals.cross_info += h_local.transpose() * &h_global * weight;
+                    normals.global_info += h_global.transpose() * &h_global * weight;
+                    normals.normal += h_local.transpose() * (residual * weight);
+                    normals.global_normal += h_global.transpose() * (residual * weight);
+                    normals.sum_sq_weighted_residuals += weight * residual * residual;
+
+                    let mut prefit = Residual::accepted(
+                        msr_epoch,
+                        OVector::<f64, U1>::new(residual),
+                        OVector::<f64, U1>::new(residual),
+                        normalized_resid,
+                        r_matrix.diagonal(),
+                        OVector::<f64, U1>::new(real_obs),
+                        OVector::<f64, U1>::new(computed_obs),
+                    );
+                    prefit.tracker = Some(device.name());
+                    prefit.msr_types = msr_types;
+                    prefit.rejected = edited;
+                    normals.records.push((this_state, stm, h_local, h_global, prefit));
+                }
+            }
+        }
+
+        Ok(normals)
+    }
+}
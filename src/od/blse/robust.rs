@@ -0,0 +1,87 @@
+/*
+    Nyx, blazing fast astrodynamics
+    Copyright (C) 2018-onwards Christopher Rabotin <christopher.rabotin@gmail.com>
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published
+    by the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::io::ConfigRepr;
+use serde_derive::{Deserialize, Serialize};
+
+/// Robust M-estimator weighting of the measurements in the Batch Least Squares estimator.
+///
+/// The weight of each measurement is scaled by a function of its residual normalized by the measurement noise and by the
+/// RMS of the normalized residuals of the previous iteration. All measurements have a unit weight in the first iteration.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub enum RobustWeighting {
+    /// Huber weights: unit weight up to `k`, and decreasing as `k / |u|` beyond, which bounds the influence of outliers.
+    Huber { k: f64 },
+    /// Tukey biweight: smoothly decreasing weights `(1 - (u / c)^2)^2` up to `c`, and zero beyond, which ignores outliers.
+    Tukey { c: f64 },
+}
+
+impl RobustWeighting {
+    /// Huber weights with the usual tuning constant of 1.345, i.e. 95% efficiency for normally distributed residuals.
+    pub fn huber() -> Self {
+        Self::Huber { k: 1.345 }
+    }
+
+    /// Tukey biweight with the usual tuning constant of 4.685, i.e. 95% efficiency for normally distributed residuals.
+    pub fn tukey() -> Self {
+        Self::Tukey { c: 4.685 }
+    }
+
+    /// Returns the weight factor of the provided normalized residual, between zero and one.
+    pub fn weight(&self, normalized_resid: f64) -> f64 {
+        let u = normalized_resid.abs();
+        match self {
+            Self::Huber { k } => {
+                if u <= *k {
+                    1.0
+                } else {
+                    k / u
+                }
+            }
+            Self::Tukey { c } => {
+                if u <= *c {
+                    (1.0 - (u / c).powi(2)).powi(2)
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+}
+
+impl ConfigRepr for RobustWeighting {}
+
+#[cfg(test)]
+mod ut_robust {
+    use super::RobustWeighting;
+
+    #[test]
+    fn robust_weights() {
+        let huber = RobustWeighting::huber();
+        assert_eq!(huber.weight(0.0), 1.0);
+        assert_eq!(huber.weight(-1.0), 1.0);
+        assert!((huber.weight(2.69) - 0.5).abs() < f64::EPSILON);
+        assert!(huber.weight(100.0) > 0.0);
+
+        let tukey = RobustWeighting::tukey();
+        assert_eq!(tukey.weight(0.0), 1.0);
+        assert!(tukey.weight(1.0) < 1.0);
+        assert!(tukey.weight(-4.0) < tukey.weight(2.0));
+        assert_eq!(tukey.weight(5.0), 0.0);
+    }
+}
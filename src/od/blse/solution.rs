@@ -20,7 +20,7 @@
 #![allow(unused_imports)] // Keep imports for context even if slightly unused in snippet
 
 use crate::linalg::allocator::Allocator;
-use crate::linalg::{DefaultAllocator, DimName, OMatrix, OVector, U1}; // Use U1 for MsrSize
+use crate::linalg::{DMatrix, DefaultAllocator, DimName, OMatrix, OVector, U1}; // Use U1 for MsrSize
 use crate::md::trajectory::{Interpolatable, Traj}; // May not need Traj if we propagate point-to-point
 pub use crate::od::estimate::*;
 pub use crate::od::ground_station::*;
@@ -29,9 +29,12 @@ pub use crate::od::*;
 use crate::propagators::Propagator;
 pub use crate::time::{Duration, Epoch, Unit};
 use anise::prelude::Almanac;
+use consider::{consider_contribution, ConsiderParameter};
 use indexmap::IndexSet;
 use log::{debug, info, trace, warn};
 use msr::sensitivity::TrackerSensitivity; // Assuming this is the correct path
+use msr::MeasurementType;
+use process::ODSolution;
 use snafu::prelude::*;
 use std::collections::BTreeMap;
 use std::fmt;
@@ -40,6 +43,49 @@ use std::ops::Add;
 use std::sync::Arc;
 use typed_builder::TypedBuilder;
 
+/// Statistics of one iteration of the Batch Least Squares estimator.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BLSIterationStats {
+    /// Iteration number, starting at 1
+    pub iteration: usize,
+    /// Root mean square of the weighted prefit residuals of this iteration
+    pub rms: f64,
+    /// Number of residuals processed in this iteration
+    pub num_residuals: usize,
+    /// Number of residuals edited out of this iteration by the sigma editing
+    pub num_edited: usize,
+    /// Norm of the position correction, in kilometers
+    pub corr_pos_km: f64,
+    /// Norm of the velocity correction, in kilometers per second
+    pub corr_vel_km_s: f64,
+    /// Damping factor of this iteration, only for the Levenberg-Marquardt solver
+    pub lm_lambda: Option<f64>,
+    /// Whether the correction was applied to the estimate, always true for the normal equations
+    pub accepted: bool,
+}
+
+impl fmt::Display for BLSIterationStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[{}] RMS: {:.3}; {} residuals ({} edited); corrections: {:.3} m\t{:.3} m/s",
+            self.iteration,
+            self.rms,
+            self.num_residuals,
+            self.num_edited,
+            self.corr_pos_km * 1e3,
+            self.corr_vel_km_s * 1e3
+        )?;
+        if let Some(lambda) = self.lm_lambda {
+            write!(f, "; lambda: {lambda:.3e}")?;
+        }
+        if !self.accepted {
+            write!(f, " (rejected)")?;
+        }
+        Ok(())
+    }
+}
+
 // Define a simple BLS solution struct
 #[derive(Debug, Clone)]
 pub struct BLSSolution<StateType: State>
@@ -47,6 +93,10 @@ where
     DefaultAllocator: Allocator<<StateType as State>::Size>
         + Allocator<<StateType as State>::Size, <StateType as State>::Size>
         + Allocator<<StateType as State>::VecLength>,
+    <DefaultAllocator as Allocator<<StateType as State>::Size>>::Buffer<f64>: Copy,
+    <DefaultAllocator as Allocator<<StateType as State>::Size, <StateType as State>::Size>>::Buffer<
+        f64,
+    >: Copy,
 {
     pub estimated_state: StateType,
     pub covariance: OMatrix<f64, <StateType as State>::Size, <StateType as State>::Size>,
@@ -54,6 +104,89 @@ where
     pub final_rms: f64,
     pub final_corr_pos_km: f64,
     pub converged: bool,
+    /// Parameters considered, but not estimated, in the estimation
+    pub consider: Vec<ConsiderParameter>,
+    /// Sensitivity of the estimation errors to the consider parameters, None without consider parameters
+    pub consider_sensitivity: Option<DMatrix<f64>>,
+    /// Statistics of each iteration
+    pub iterations: Vec<BLSIterationStats>,
+    /// Prefit and postfit residuals of each measurement of the final iteration, flagged as rejected if edited out of that iteration
+    pub residuals: Vec<Residual<U1>>,
+    /// Solution mapped to the epoch of each residual, with the reference trajectory of the final iteration as nominal state
+    pub estimates: Vec<KfEstimate<StateType>>,
+}
+
+impl<StateType: State> BLSSolution<StateType>
+where
+    DefaultAllocator: Allocator<<StateType as State>::Size>
+        + Allocator<<StateType as State>::Size, <StateType as State>::Size>
+        + Allocator<<StateType as State>::VecLength>,
+    <DefaultAllocator as Allocator<<StateType as State>::Size>>::Buffer<f64>: Copy,
+    <DefaultAllocator as Allocator<<StateType as State>::Size, <StateType as State>::Size>>::Buffer<
+        f64,
+    >: Copy,
+{
+    /// Returns the residuals of the measurements edited out of the final iteration.
+    pub fn edited_residuals(&self) -> Vec<&Residual<U1>> {
+        self.residuals
+            .iter()
+            .filter(|residual| residual.rejected)
+            .collect()
+    }
+
+    /// Returns the contribution of the consider parameters to the covariance of the solution, or None if no parameters were considered.
+    pub fn consider_covar_contribution(
+        &self,
+    ) -> Option<OMatrix<f64, <StateType as State>::Size, <StateType as State>::Size>> {
+        self.consider_sensitivity
+            .as_ref()
+            .map(|sensitivity| consider_contribution(&self.consider, sensitivity))
+    }
+
+    /// Returns the consider covariance of the solution, i.e. the sum of the computed covariance and of the contribution of
+    /// the consider parameters, or None if no parameters were considered.
+    pub fn consider_covar(
+        &self,
+    ) -> Option<OMatrix<f64, <StateType as State>::Size, <StateType as State>::Size>> {
+        self.consider_covar_contribution()
+            .map(|contribution| self.covariance + contribution)
+    }
+}
+
+impl<StateType> BLSSolution<StateType>
+where
+    StateType: Interpolatable + Add<OVector<f64, <StateType as State>::Size>, Output = StateType>,
+    <DefaultAllocator as Allocator<<StateType as State>::VecLength>>::Buffer<f64>: Send,
+    <DefaultAllocator as Allocator<<StateType as State>::Size>>::Buffer<f64>: Copy,
+    <DefaultAllocator as Allocator<<StateType as State>::Size, <StateType as State>::Size>>::Buffer<
+        f64,
+    >: Copy,
+    DefaultAllocator: Allocator<<StateType as State>::Size>
+        + Allocator<<StateType as State>::VecLength>
+        + Allocator<U1, <StateType as State>::Size>
+        + Allocator<<StateType as State>::Size, U1>
+        + Allocator<<StateType as State>::Size, <StateType as State>::Size>,
+{
+    /// Returns the residuals of the final iteration and the solution mapped to each of them as an OD solution, e.g. to compute
+    /// its residual statistics or to export it to Parquet.
+    pub fn to_od_solution<Trk: TrackerSensitivity<StateType, StateType>>(
+        &self,
+        devices: BTreeMap<String, Trk>,
+    ) -> ODSolution<StateType, KfEstimate<StateType>, U1, Trk> {
+        let msr_types = self
+            .residuals
+            .iter()
+            .flat_map(|residual| residual.msr_types.iter().copied())
+            .collect::<IndexSet<MeasurementType>>();
+
+        let mut od_sol =
+            ODSolution::<StateType, KfEstimate<StateType>, U1, Trk>::new(devices, msr_types);
+        od_sol.consider = self.consider.clone();
+        for (estimate, residual) in self.estimates.iter().zip(self.residuals.iter()) {
+            od_sol.push_measurement_update(*estimate, residual.clone(), None);
+        }
+        od_sol
+    }
 }
 
 impl<StateType> fmt::Display for BLSSolution<StateType>
@@ -62,13 +195,33 @@ where
     DefaultAllocator: Allocator<<StateType as State>::Size>
         + Allocator<<StateType as State>::Size, <StateType as State>::Size>
         + Allocator<<StateType as State>::VecLength>,
+    <DefaultAllocator as Allocator<<StateType as State>::Size>>::Buffer<f64>: Copy,
+    <DefaultAllocator as Allocator<<StateType as State>::Size, <StateType as State>::Size>>::Buffer<
+        f64,
+    >: Copy,
 {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         writeln!(f, "Converged: {}", self.converged)?;
         writeln!(f, "Iterations: {}", self.num_iterations)?;
         writeln!(f, "Final RMS: {}", self.final_rms)?;
         writeln!(f, "Final State: {}", self.estimated_state.orbit())?;
-        write!(f, "Final Covariance:\n{:.3e}", self.covariance)
+        for stats in &self.iterations {
+            writeln!(f, "{stats}")?;
+        }
+        write!(f, "Final Covariance:\n{:.3e}", self.covariance)?;
+        if let Some(covar) = self.consider_covar() {
+            let params = self
+                .consider
+                .iter()
+                .map(|param| param.to_string())
+                .collect::<Vec<_>>();
+            write!(
+                f,
+                "\nConsider Covariance ({}):\n{covar:.3e}",
+                params.join(", ")
+            )?;
+        }
+        Ok(())
     }
 }
 
@@ -0,0 +1,455 @@
+/*
+    Nyx, blazing fast astrodynamics
+    Copyright (C) 2018-onwards Christopher Rabotin <christopher.rabotin@gmail.com>
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published
+    by the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::dynamics::Dynamics;
+use crate::linalg::allocator::Allocator;
+use crate::linalg::{DMatrix, DefaultAllocator, DimName, OMatrix, OVector};
+use crate::md::trajectory::Interpolatable;
+use crate::md::StateParameter;
+use crate::propagators::Propagator;
+use crate::State;
+use anise::prelude::Almanac;
+use hifitime::Duration;
+use indexmap::IndexSet;
+use serde::{Deserialize, Serialize};
+use snafu::ResultExt;
+use std::fmt;
+use std::sync::Arc;
+
+use super::msr::sensitivity::TrackerSensitivity;
+use super::msr::MeasurementType;
+use super::{ODDynamicsSnafu, ODError, ODPropSnafu};
+
+/// Kind of parameter which is not estimated but considered in the covariance of the estimate.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ConsiderKind {
+    /// Component of the state vector which is not estimated, e.g. index 6 for the coefficient of reflectivity of a spacecraft.
+    /// Its sensitivity is mapped by the STM, and its covariance in the estimate is set to zero.
+    StateComponent { index: usize },
+    /// Gravitational parameter of the central body of the estimated state, in km^3/s^2.
+    GravParam,
+    /// Constant bias on the measurements of the provided type from the provided tracker, in the unit of the measurement.
+    MeasurementBias {
+        tracker: String,
+        msr_type: MeasurementType,
+    },
+    /// Location error of the provided tracker, either its latitude or longitude in degrees, or its height in kilometers.
+    TrackerLocation {
+        tracker: String,
+        param: StateParameter,
+    },
+}
+
+/// A parameter which is not estimated, but whose uncertainty is accounted for in the covariance of the estimate.
+///
+/// The consider covariance is the sum of the computed covariance and of the contribution of the consider parameters,
+/// S * Pcc * S^T, where S is the sensitivity of the estimation errors to the consider parameters and Pcc is their a priori covariance.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ConsiderParameter {
+    pub kind: ConsiderKind,
+    /// A priori standard deviation, in the unit of the parameter
+    pub sigma: f64,
+}
+
+impl ConsiderParameter {
+    /// Considers the provided component of the state vector, which will not be estimated.
+    pub fn state_component(index: usize, sigma: f64) -> Self {
+        Self {
+            kind: ConsiderKind::StateComponent { index },
+            sigma,
+        }
+    }
+
+    /// Considers the gravitational parameter of the central body, in km^3/s^2.
+    pub fn grav_param(sigma_km3_s2: f64) -> Self {
+        Self {
+            kind: ConsiderKind::GravParam,
+            sigma: sigma_km3_s2,
+        }
+    }
+
+    /// Considers a constant bias on the measurements of the provided type from the provided tracker.
+    pub fn measurement_bias(tracker: String, msr_type: MeasurementType, sigma: f64) -> Self {
+        Self {
+            kind: ConsiderKind::MeasurementBias { tracker, msr_type },
+            sigma,
+        }
+    }
+
+    /// Considers the location error of the provided tracker: only the latitude and longitude (in degrees) and height (in km) are supported.
+    pub fn tracker_location(
+        tracker: String,
+        param: StateParameter,
+        sigma: f64,
+    ) -> Result<Self, ODError> {
+        ensure_location_param(param)?;
+        Ok(Self {
+            kind: ConsiderKind::TrackerLocation { tracker, param },
+            sigma,
+        })
+    }
+}
+
+impl fmt::Display for ConsiderParameter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            ConsiderKind::StateComponent { index } => write!(f, "state component #{index}")?,
+            ConsiderKind::GravParam => write!(f, "GM")?,
+            ConsiderKind::MeasurementBias { tracker, msr_type } => {
+                write!(f, "{tracker} {msr_type:?} bias")?
+            }
+            ConsiderKind::TrackerLocation { tracker, param } => write!(f, "{tracker} {param}")?,
+        }
+        write!(f, " (σ = {})", self.sigma)
+    }
+}
+
+fn ensure_location_param(param: StateParameter) -> Result<(), ODError> {
+    match param {
+        StateParameter::Latitude | StateParameter::Longitude | StateParameter::Height => Ok(()),
+        _ => Err(ODError::ODLimitation {
+            action:
+                "tracker location consider parameters must be the latitude, longitude, or height",
+        }),
+    }
+}
+
+/// Returns the a priori covariance of the consider parameters.
+pub(crate) fn consider_covar(params: &[ConsiderParameter]) -> DMatrix<f64> {
+    DMatrix::from_diagonal(&nalgebra::DVector::from_iterator(
+        params.len(),
+        params.iter().map(|param| param.sigma.powi(2)),
+    ))
+}
+
+/// Returns the contribution of the consider parameters to the covariance of the estimate, S * Pcc * S^T.
+pub(crate) fn consider_contribution<N: DimName>(
+    params: &[ConsiderParameter],
+    sensitivity: &DMatrix<f64>,
+) -> OMatrix<f64, N, N>
+where
+    DefaultAllocator: Allocator<N, N>,
+{
+    let contribution = sensitivity * consider_covar(params) * sensitivity.transpose();
+    OMatrix::<f64, N, N>::from_iterator(contribution.iter().copied())
+}
+
+/// Returns the initial sensitivity of the estimation errors to the consider parameters: only the considered state components have an error.
+pub(crate) fn initial_sensitivity<N: DimName>(params: &[ConsiderParameter]) -> DMatrix<f64> {
+    let mut sensitivity = DMatrix::zeros(N::DIM, params.len());
+    for (j, param) in params.iter().enumerate() {
+        if let ConsiderKind::StateComponent { index } = param.kind {
+            sensitivity[(index, j)] = 1.0;
+        }
+    }
+    sensitivity
+}
+
+/// Zeros the rows and columns of the considered state components in the provided covariance, since these are not estimated.
+pub(crate) fn zero_considered_components<N: DimName>(
+    params: &[ConsiderParameter],
+    covar: &mut OMatrix<f64, N, N>,
+) -> Result<(), ODError>
+where
+    DefaultAllocator: Allocator<N, N>,
+{
+    for param in params {
+        if let ConsiderKind::StateComponent { index } = param.kind {
+            if index >= N::DIM {
+                return Err(ODError::ODLimitation {
+                    action: "considered state component is beyond the size of the estimated state",
+                });
+            }
+            covar.row_mut(index).fill(0.0);
+            covar.column_mut(index).fill(0.0);
+        }
+    }
+    Ok(())
+}
+
+/// Converts a statically sized matrix (e.g. the STM or the measurement sensitivity matrix) into a dynamic matrix,
+/// for multiplication with the sensitivity matrix of the consider parameters.
+pub(crate) fn to_dmatrix<R: DimName, C: DimName>(matrix: &OMatrix<f64, R, C>) -> DMatrix<f64>
+where
+    DefaultAllocator: Allocator<R, C>,
+{
+    DMatrix::from_iterator(R::DIM, C::DIM, matrix.iter().copied())
+}
+
+/// Computes the sensitivity of the state to the consider parameters of the dynamics over the provided step, by finite differencing.
+///
+/// This is the Theta matrix of the consider covariance analysis, such that the true state error is STM * x + Theta * c.
+pub(crate) fn dynamics_sensitivity<D: Dynamics>(
+    params: &[ConsiderParameter],
+    prop: &Propagator<D>,
+    almanac: Arc<Almanac>,
+    start: D::StateType,
+    step: Duration,
+) -> Result<DMatrix<f64>, ODError>
+where
+    DefaultAllocator: Allocator<<D::StateType as State>::Size>
+        + Allocator<<D::StateType as State>::Size, <D::StateType as State>::Size>
+        + Allocator<<D::StateType as State>::VecLength>,
+{
+    let n = <D::StateType as State>::Size::DIM;
+    let mut theta = DMatrix::zeros(n, params.len());
+
+    if !params
+        .iter()
+        .any(|param| matches!(param.kind, ConsiderKind::GravParam))
+    {
+        return Ok(theta);
+    }
+
+    let mut start = start;
+    start.unset_stm();
+
+    let propagate =
+        |state: D::StateType| -> Result<OVector<f64, <D::StateType as State>::VecLength>, ODError> {
+            let mut prop_instance = prop.with(state, almanac.clone()).quiet();
+            if !prop_instance.fixed_step {
+                prop_instance.set_step(step, false);
+            }
+            Ok(prop_instance
+                .for_duration(step)
+                .context(ODPropSnafu)?
+                .to_vector())
+        };
+
+    let nominal = propagate(start)?;
+
+    for (j, param) in params.iter().enumerate() {
+        if !matches!(param.kind, ConsiderKind::GravParam) {
+            continue;
+        }
+        let mut orbit = start.orbit();
+        let mu_km3_s2 = orbit
+            .frame
+            .mu_km3_s2()
+            .map_err(|_| ODError::ODLimitation {
+                action: "considering GM requires the gravitational parameter of the frame of the estimated state",
+            })?;
+        let delta = mu_km3_s2 * 1e-6;
+        orbit.frame = orbit.frame.with_mu_km3_s2(mu_km3_s2 + delta);
+        let mut perturbed = start;
+        perturbed.set_orbit(orbit);
+
+        let perturbed = propagate(perturbed)?;
+        for i in 0..n {
+            theta[(i, j)] = (perturbed[i] - nominal[i]) / delta;
+        }
+    }
+
+    Ok(theta)
+}
+
+/// Computes the sensitivity of the measurements of the provided types to the consider parameters, as an M x q matrix.
+pub(crate) fn measurement_sensitivity<M: DimName, S: Interpolatable, Trk>(
+    params: &[ConsiderParameter],
+    device: &Trk,
+    msr_types: &IndexSet<MeasurementType>,
+    state: S,
+    almanac: Arc<Almanac>,
+) -> Result<DMatrix<f64>, ODError>
+where
+    Trk: TrackerSensitivity<S, S>,
+    DefaultAllocator:
+        Allocator<S::Size> + Allocator<S::VecLength> + Allocator<S::Size, S::Size> + Allocator<M>,
+{
+    let mut h_c = DMatrix::zeros(M::DIM, params.len());
+
+    for (j, param) in params.iter().enumerate() {
+        match &param.kind {
+            ConsiderKind::MeasurementBias { tracker, msr_type } => {
+                if *tracker != device.name() {
+                    continue;
+                }
+                if let Some(i) = msr_types.get_index_of(msr_type) {
+                    h_c[(i, j)] = 1.0;
+                }
+            }
+            ConsiderKind::TrackerLocation { tracker, param } => {
+                if *tracker != device.name() {
+                    continue;
+                }
+                let delta = match param {
+                    StateParameter::Height => 1e-3,
+                    _ => 1e-5,
+                };
+                let mut observations = Vec::with_capacity(2);
+                for offset in [delta, -delta] {
+                    let mut perturbed =
+                        device
+                            .with_offset(*param, offset)
+                            .ok_or(ODError::ODLimitation {
+                                action: "tracker does not support location consider parameters",
+                            })?;
+                    match perturbed.measure_instantaneous(state, None, almanac.clone())? {
+                        Some(msr) => observations.push(msr.observation::<M>(msr_types)),
+                        None => break,
+                    }
+                }
+                if let [plus, minus] = observations.as_slice() {
+                    for i in 0..msr_types.len() {
+                        h_c[(i, j)] = (plus[i] - minus[i]) / (2.0 * delta);
+                    }
+                }
+            }
+            ConsiderKind::StateComponent { .. } | ConsiderKind::GravParam => {}
+        }
+    }
+
+    Ok(h_c)
+}
+
+/// Tracks the sensitivity of the estimation errors of a sequential filter to the consider parameters.
+#[derive(Clone, Debug)]
+pub(crate) struct ConsiderTracker<S: State>
+where
+    DefaultAllocator: Allocator<S::Size> + Allocator<S::Size, S::Size> + Allocator<S::VecLength>,
+{
+    /// Sensitivity of the estimation errors to the consider parameters, of size n x q
+    pub(crate) sensitivity: DMatrix<f64>,
+    /// Nominal state when the sensitivity was last updated, from which the STM and the dynamics sensitivity are mapped
+    reference: S,
+}
+
+impl<S: State> ConsiderTracker<S>
+where
+    DefaultAllocator: Allocator<S::Size> + Allocator<S::Size, S::Size> + Allocator<S::VecLength>,
+{
+    pub(crate) fn new(params: &[ConsiderParameter], reference: S) -> Self {
+        Self {
+            sensitivity: initial_sensitivity::<S::Size>(params),
+            reference,
+        }
+    }
+
+    /// Maps the sensitivity to the epoch of the provided nominal state, whose STM must be mapped from the reference state.
+    pub(crate) fn time_update<D: Dynamics<StateType = S>>(
+        &mut self,
+        params: &[ConsiderParameter],
+        prop: &Propagator<D>,
+        almanac: Arc<Almanac>,
+        nominal_state: &S,
+    ) -> Result<(), ODError> {
+        let duration = nominal_state.epoch() - self.reference.epoch();
+        if duration == Duration::ZERO {
+            // Already mapped to this epoch, e.g. when processing several measurement windows.
+            return Ok(());
+        }
+        let stm = nominal_state.stm().context(ODDynamicsSnafu)?;
+        let theta = dynamics_sensitivity(params, prop, almanac, self.reference, duration)?;
+        self.sensitivity = to_dmatrix(&stm) * &self.sensitivity + theta;
+        Ok(())
+    }
+
+    /// Updates the sensitivity with the gain of a measurement update, its sensitivity matrix, and the sensitivity of the measurements to the consider parameters.
+    pub(crate) fn measurement_update<M: DimName>(
+        &mut self,
+        gain: &OMatrix<f64, S::Size, M>,
+        h_tilde: &OMatrix<f64, M, S::Size>,
+        h_consider: &DMatrix<f64>,
+    ) where
+        DefaultAllocator: Allocator<S::Size, M> + Allocator<M, S::Size>,
+    {
+        let gain = to_dmatrix(gain);
+        let i_kh =
+            DMatrix::<f64>::identity(S::Size::DIM, S::Size::DIM) - &gain * to_dmatrix(h_tilde);
+        self.sensitivity = i_kh * &self.sensitivity - gain * h_consider;
+    }
+
+    /// Sets the nominal state from which the next time update is mapped, i.e. each time the STM is reset.
+    pub(crate) fn rebase(&mut self, reference: S) {
+        self.reference = reference;
+    }
+}
+
+#[cfg(test)]
+mod ut_consider {
+    use super::*;
+    use crate::linalg::Const;
+
+    #[test]
+    fn consider_covar_contribution() {
+        let params = vec![
+            ConsiderParameter::state_component(6, 0.1),
+            ConsiderParameter::grav_param(1e-3),
+        ];
+        assert_eq!(format!("{}", params[0]), "state component #6 (σ = 0.1)");
+        assert!(ConsiderParameter::tracker_location(
+            "DSS-65".to_string(),
+            StateParameter::Epoch,
+            1e-3
+        )
+        .is_err());
+
+        let sensitivity = initial_sensitivity::<Const<9>>(&params);
+        let contribution = consider_contribution::<Const<9>>(&params, &sensitivity);
+        // Only the considered component has an uncertainty at the start.
+        assert!((contribution[(6, 6)] - 0.01).abs() < 1e-15);
+        assert_eq!(contribution.iter().filter(|v| **v != 0.0).count(), 1);
+
+        let mut covar = OMatrix::<f64, Const<9>, Const<9>>::identity();
+        zero_considered_components(&params, &mut covar).unwrap();
+        assert_eq!(covar[(6, 6)], 0.0);
+        assert_eq!(covar[(5, 5)], 1.0);
+    }
+
+    #[test]
+    fn consider_tracker_measurement_update() {
+        use crate::linalg::{Matrix1x6, Vector6};
+        use crate::{Orbit, Spacecraft};
+        use anise::constants::frames::EARTH_J2000;
+
+        let orbit = Orbit::new(
+            -2436.45,
+            -2436.45,
+            6891.037,
+            5.088_611,
+            -5.088_611,
+            0.0,
+            hifitime::Epoch::from_gregorian_tai_at_midnight(2020, 1, 1),
+            EARTH_J2000.with_mu_km3_s2(398_600.441_5),
+        );
+        let params = vec![
+            ConsiderParameter::state_component(6, 0.1),
+            ConsiderParameter::measurement_bias("DSS-65".to_string(), MeasurementType::Range, 1e-3),
+        ];
+        let mut tracker = ConsiderTracker::new(&params, Spacecraft::builder().orbit(orbit).build());
+        tracker.sensitivity[(0, 0)] = 2.0;
+
+        let mut gain = OMatrix::<f64, Const<9>, Const<1>>::zeros();
+        gain.fixed_rows_mut::<6>(0)
+            .copy_from(&Vector6::new(0.5, 0.1, 0.0, 0.0, 0.0, 0.0));
+        let mut h_tilde = OMatrix::<f64, Const<1>, Const<9>>::zeros();
+        h_tilde
+            .fixed_columns_mut::<6>(0)
+            .copy_from(&Matrix1x6::new(1.0, 0.0, 0.0, 0.0, 0.0, 0.0));
+        let h_consider = DMatrix::from_row_slice(1, 2, &[0.0, 1.0]);
+
+        let expected = (DMatrix::identity(9, 9) - to_dmatrix(&gain) * to_dmatrix(&h_tilde))
+            * &tracker.sensitivity
+            - to_dmatrix(&gain) * &h_consider;
+        tracker.measurement_update(&gain, &h_tilde, &h_consider);
+        assert!((tracker.sensitivity.clone() - expected).norm() < 1e-15);
+        // The bias leaks into the estimate through the gain.
+        assert_eq!(tracker.sensitivity[(0, 1)], -0.5);
+        assert_eq!(tracker.sensitivity[(0, 0)], 1.0);
+    }
+}
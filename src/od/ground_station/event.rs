@@ -73,3 +73,25 @@ where
         1e-3
     }
 }
+
+/// Owned ground stations evaluate the same elevation event as their reference, which allows them to be shared, e.g. in an `EventCombination`.
+impl<S: Interpolatable> EventEvaluator<S> for GroundStation
+where
+    DefaultAllocator: Allocator<S::Size> + Allocator<S::Size, S::Size> + Allocator<S::VecLength>,
+{
+    fn eval(&self, rx_gs_frame: &S, almanac: Arc<Almanac>) -> Result<f64, EventError> {
+        (&self).eval(rx_gs_frame, almanac)
+    }
+
+    fn eval_string(&self, state: &S, almanac: Arc<Almanac>) -> Result<String, EventError> {
+        (&self).eval_string(state, almanac)
+    }
+
+    fn epoch_precision(&self) -> Duration {
+        EventEvaluator::<S>::epoch_precision(&self)
+    }
+
+    fn value_precision(&self) -> f64 {
+        EventEvaluator::<S>::value_precision(&self)
+    }
+}
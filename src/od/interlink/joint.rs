@@ -0,0 +1,631 @@
+/*
+    Nyx, blazing fast astrodynamics
+    Copyright (C) 2018-onwards Christopher Rabotin <christopher.rabotin@gmail.com>
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published
+    by the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::dynamics::SpacecraftDynamics;
+use crate::linalg::{DMatrix, DVector, OMatrix, OVector, U1};
+use crate::od::estimate::{Estimate, KfEstimate, Residual};
+use crate::od::ground_station::GroundStation;
+use crate::od::interlink::sensitivity::link_partials;
+use crate::od::interlink::InterlinkTxSpacecraft;
+use crate::od::msr::sensitivity::TrackerSensitivity;
+use crate::od::msr::{Measurement, MeasurementType, TrackingDataArc};
+use crate::od::noise::StochasticNoise;
+use crate::od::process::{ODSolution, ResidRejectCrit};
+use crate::od::snc::ProcessNoise3D;
+use crate::od::{
+    InvalidMeasurementSnafu, ODAlmanacSnafu, ODDynamicsSnafu, ODError, ODPropSnafu,
+    SingularNoiseRkSnafu, TrackingDevice,
+};
+use crate::propagators::Propagator;
+use crate::time::Epoch;
+use crate::{Spacecraft, State};
+use anise::prelude::Almanac;
+use indexmap::{IndexMap, IndexSet};
+use log::{debug, error, info};
+use nalgebra::DimName;
+use snafu::prelude::*;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+use typed_builder::TypedBuilder;
+
+/// Size of the state of each vehicle in the joint state vector
+const SC_SIZE: usize = <Spacecraft as State>::Size::DIM;
+
+type SpacecraftMatrix = OMatrix<f64, <Spacecraft as State>::Size, <Spacecraft as State>::Size>;
+
+/// An inter-satellite link between two of the jointly estimated spacecraft.
+///
+/// The range and range rate of the link are computed from the estimated states of both spacecraft, so their partial
+/// derivatives apply to the transmitter and to the receiver.
+#[derive(Clone, Debug)]
+pub struct Crosslink {
+    /// Name of the transmitting vehicle
+    pub transmitter: String,
+    /// Name of the receiving vehicle
+    pub receiver: String,
+    /// Measurement types supported by the link, only range and Doppler
+    pub measurement_types: IndexSet<MeasurementType>,
+    pub stochastic_noises: IndexMap<MeasurementType, StochasticNoise>,
+}
+
+impl Crosslink {
+    /// Builds the crosslink of the provided interlink transmitter, whose name is that of its trajectory, to the provided receiver.
+    pub fn from_interlink(transmitter: &InterlinkTxSpacecraft, receiver: String) -> Self {
+        Self {
+            transmitter: transmitter.name(),
+            receiver,
+            measurement_types: transmitter.measurement_types.clone(),
+            stochastic_noises: transmitter.stochastic_noises.clone().unwrap_or_default(),
+        }
+    }
+
+    /// Returns the measurement noise variance of the provided measurement type at the provided epoch.
+    pub fn measurement_covar(
+        &self,
+        msr_type: MeasurementType,
+        epoch: Epoch,
+    ) -> Result<f64, ODError> {
+        Ok(self
+            .stochastic_noises
+            .get(&msr_type)
+            .ok_or(ODError::NoiseNotConfigured {
+                kind: format!("{msr_type:?}"),
+            })?
+            .covariance(epoch))
+    }
+
+    /// Returns the constant bias of the provided measurement type.
+    pub fn measurement_bias(&self, msr_type: MeasurementType) -> Result<f64, ODError> {
+        Ok(self
+            .stochastic_noises
+            .get(&msr_type)
+            .ok_or(ODError::NoiseNotConfigured {
+                kind: format!("{msr_type:?}"),
+            })?
+            .bias
+            .and_then(|gm| gm.constant)
+            .unwrap_or(0.0))
+    }
+}
+
+/// Joint estimate of several spacecraft, with the full covariance of their stacked states.
+#[derive(Clone, Debug, PartialEq)]
+pub struct JointEstimate {
+    /// State of each vehicle by name, in the order of the joint state vector
+    pub vehicles: IndexMap<String, Spacecraft>,
+    /// Covariance of the joint state vector, made of the nine state components of each vehicle
+    pub covar: DMatrix<f64>,
+    /// Whether this estimate is the result of a time update only
+    pub predicted: bool,
+}
+
+impl JointEstimate {
+    /// Builds a joint estimate from the estimate of each vehicle, all at the same epoch, assuming uncorrelated vehicles.
+    pub fn new(estimates: Vec<(String, KfEstimate<Spacecraft>)>) -> Result<Self, ODError> {
+        ensure!(
+            !estimates.is_empty(),
+            crate::od::ODLimitationSnafu {
+                action: "joint estimation requires at least one vehicle"
+            }
+        );
+        let epoch = estimates[0].1.epoch();
+        ensure!(
+            estimates
+                .iter()
+                .all(|(_, estimate)| estimate.epoch() == epoch),
+            crate::od::ODLimitationSnafu {
+                action: "all vehicles of a joint estimate must be at the same epoch"
+            }
+        );
+
+        let mut covar = DMatrix::zeros(SC_SIZE * estimates.len(), SC_SIZE * estimates.len());
+        let mut vehicles = IndexMap::new();
+        for (i, (name, estimate)) in estimates.into_iter().enumerate() {
+            covar
+                .view_mut((SC_SIZE * i, SC_SIZE * i), (SC_SIZE, SC_SIZE))
+                .copy_from(&estimate.covar);
+            vehicles.insert(name, estimate.state());
+        }
+
+        Ok(Self {
+            vehicles,
+            covar,
+            predicted: false,
+        })
+    }
+
+    /// Epoch of this estimate
+    pub fn epoch(&self) -> Epoch {
+        self.vehicles[0].epoch()
+    }
+
+    /// Returns the estimate of the provided vehicle, with its marginal covariance, or None if it is not estimated.
+    pub fn vehicle_estimate(&self, name: &str) -> Option<KfEstimate<Spacecraft>> {
+        let i = self.vehicles.get_index_of(name)?;
+        let mut estimate = KfEstimate::from_covar(self.vehicles[i], self.block(i, i));
+        estimate.predicted = self.predicted;
+        Some(estimate)
+    }
+
+    /// Returns the cross covariance between the states of the two provided vehicles, or None if either is not estimated.
+    pub fn cross_covar(&self, first: &str, second: &str) -> Option<SpacecraftMatrix> {
+        let i = self.vehicles.get_index_of(first)?;
+        let j = self.vehicles.get_index_of(second)?;
+        Some(self.block(i, j))
+    }
+
+    fn block(&self, i: usize, j: usize) -> SpacecraftMatrix {
+        SpacecraftMatrix::from_fn(|row, col| self.covar[(SC_SIZE * i + row, SC_SIZE * j + col)])
+    }
+}
+
+impl fmt::Display for JointEstimate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "=== JOINT ESTIMATE @ {} ===", self.epoch())?;
+        for (i, (name, sc)) in self.vehicles.iter().enumerate() {
+            let pos_sigma_km = (0..3)
+                .map(|k| self.covar[(SC_SIZE * i + k, SC_SIZE * i + k)])
+                .sum::<f64>()
+                .sqrt();
+            writeln!(
+                f,
+                "{name}: {}\t(σ pos = {:.3} m)",
+                sc.orbit,
+                pos_sigma_km * 1e3
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Sequential joint estimation of several spacecraft from the ground tracking of each vehicle and from crosslinks between them.
+///
+/// This is an extended Kalman filter on the stacked states of all vehicles: each vehicle is propagated with its own STM,
+/// and the covariance of the joint state captures the correlations introduced by the crosslinks.
+#[derive(Clone, TypedBuilder)]
+#[builder(doc)]
+pub struct JointODProcess {
+    /// Propagator used for the reference trajectory of each vehicle
+    pub prop: Propagator<SpacecraftDynamics>,
+    /// Ground stations tracking the vehicles
+    #[builder(default)]
+    pub ground_stations: BTreeMap<String, GroundStation>,
+    /// Crosslinks between the vehicles, by name of the tracker of their measurements
+    #[builder(default)]
+    pub crosslinks: BTreeMap<String, Crosslink>,
+    /// Process noise of each vehicle, by name of the vehicle
+    #[builder(default)]
+    pub process_noise: BTreeMap<String, ProcessNoise3D>,
+    /// Residual rejection criteria
+    #[builder(default, setter(strip_option))]
+    pub resid_crit: Option<ResidRejectCrit>,
+    pub almanac: Arc<Almanac>,
+}
+
+impl JointODProcess {
+    /// Processes the ground tracking arc of each vehicle, by name of the vehicle, and the crosslink arcs, whose trackers are
+    /// the names of the configured crosslinks, in chronological order from the initial joint estimate.
+    ///
+    /// Each measurement type is processed as a scalar measurement, and the estimate after each is stored in the solution.
+    pub fn process_arcs(
+        &self,
+        initial_estimate: JointEstimate,
+        ground_arcs: &BTreeMap<String, TrackingDataArc>,
+        crosslink_arcs: &[TrackingDataArc],
+    ) -> Result<JointODSolution, ODError> {
+        for name in ground_arcs.keys() {
+            ensure!(
+                initial_estimate.vehicles.contains_key(name),
+                crate::od::ODLimitationSnafu {
+                    action: "ground tracking arc of a vehicle which is not estimated"
+                }
+            );
+        }
+        for link in self.crosslinks.values() {
+            ensure!(
+                initial_estimate.vehicles.contains_key(&link.transmitter)
+                    && initial_estimate.vehicles.contains_key(&link.receiver),
+                crate::od::ODLimitationSnafu {
+                    action: "crosslink between vehicles which are not estimated"
+                }
+            );
+        }
+
+        // Merge all of the measurements in chronological order, ground measurements first at the same epoch.
+        let mut measurements: Vec<(Epoch, Option<&str>, &Measurement)> = ground_arcs
+            .iter()
+            .flat_map(|(name, arc)| {
+                arc.measurements
+                    .iter()
+                    .map(move |(epoch, msr)| (*epoch, Some(name.as_str()), msr))
+            })
+            .chain(crosslink_arcs.iter().flat_map(|arc| {
+                arc.measurements
+                    .iter()
+                    .map(|(epoch, msr)| (*epoch, None, msr))
+            }))
+            .collect();
+        measurements.sort_by_key(|(epoch, vehicle, _)| (*epoch, vehicle.is_none()));
+
+        info!(
+            "Joint estimation of {} vehicles with {} measurements",
+            initial_estimate.vehicles.len(),
+            measurements.len()
+        );
+
+        let mut devices = self.ground_stations.clone();
+        let mut process_noise = self.process_noise.clone();
+        let mut unknown_trackers = IndexSet::new();
+        let mut estimate = initial_estimate;
+        let mut solution = JointODSolution::default();
+
+        for (epoch, vehicle, msr) in measurements {
+            if epoch != estimate.epoch() {
+                estimate = self.time_update(&estimate, epoch, &mut process_noise)?;
+            }
+
+            for msr_type in msr.data.keys().copied() {
+                let mut msr_types = IndexSet::new();
+                msr_types.insert(msr_type);
+
+                let real_obs = msr.observation::<U1>(&msr_types)[0];
+                ensure!(
+                    real_obs.is_finite(),
+                    InvalidMeasurementSnafu {
+                        epoch,
+                        val: real_obs
+                    }
+                );
+
+                let mut h_row = DVector::zeros(estimate.covar.nrows());
+
+                let (computed_obs, r_variance, observed) = match vehicle {
+                    Some(name) => {
+                        let Some(device) = devices.get_mut(&msr.tracker) else {
+                            if !unknown_trackers.contains(&msr.tracker) {
+                                error!(
+                                    "Tracker {} is not in the list of configured devices",
+                                    msr.tracker
+                                );
+                            }
+                            unknown_trackers.insert(msr.tracker.clone());
+                            continue;
+                        };
+                        let i = estimate.vehicles.get_index_of(name).unwrap();
+                        let sc = estimate.vehicles[i];
+
+                        let Some(computed_meas) =
+                            device.measure_instantaneous(sc, None, self.almanac.clone())?
+                        else {
+                            debug!(
+                                "{} does not expect a measurement of {name} at {epoch}, skipping",
+                                msr.tracker
+                            );
+                            continue;
+                        };
+
+                        let h_tilde =
+                            device.h_tilde::<U1>(msr, &msr_types, &sc, self.almanac.clone())?;
+                        for k in 0..SC_SIZE {
+                            h_row[SC_SIZE * i + k] = h_tilde[k];
+                        }
+
+                        let computed_obs = computed_meas.observation::<U1>(&msr_types)[0]
+                            - device.measurement_bias_vector::<U1>(&msr_types, epoch)?[0];
+                        let r_variance =
+                            device.measurement_covar_matrix::<U1>(&msr_types, epoch)?[(0, 0)];
+
+                        (computed_obs, r_variance, vec![name.to_string()])
+                    }
+                    None => {
+                        let Some(link) = self.crosslinks.get(&msr.tracker) else {
+                            if !unknown_trackers.contains(&msr.tracker) {
+                                error!(
+                                    "Tracker {} is not in the list of configured crosslinks",
+                                    msr.tracker
+                                );
+                            }
+                            unknown_trackers.insert(msr.tracker.clone());
+                            continue;
+                        };
+                        let tx_idx = estimate.vehicles.get_index_of(&link.transmitter).unwrap();
+                        let rx_idx = estimate.vehicles.get_index_of(&link.receiver).unwrap();
+
+                        let rx = estimate.vehicles[rx_idx].orbit;
+                        let tx = self
+                            .almanac
+                            .transform_to(estimate.vehicles[tx_idx].orbit, rx.frame, None)
+                            .context(ODAlmanacSnafu {
+                                action: "transforming crosslink transmitter to receiver frame",
+                            })?;
+
+                        let delta_r = rx.radius_km - tx.radius_km;
+                        let delta_v = rx.velocity_km_s - tx.velocity_km_s;
+                        let range_km = delta_r.norm();
+                        let range_rate_km_s = delta_r.dot(&delta_v) / range_km;
+
+                        let partials =
+                            link_partials(msr_type, &delta_r, &delta_v, range_km, range_rate_km_s)?;
+                        for k in 0..6 {
+                            h_row[SC_SIZE * rx_idx + k] += partials[k];
+                            h_row[SC_SIZE * tx_idx + k] -= partials[k];
+                        }
+
+                        let computed_obs = match msr_type {
+                            MeasurementType::Range => range_km,
+                            _ => range_rate_km_s,
+                        } - link.measurement_bias(msr_type)?;
+                        let r_variance = link.measurement_covar(msr_type, epoch)?;
+
+                        (
+                            computed_obs,
+                            r_variance,
+                            vec![link.transmitter.clone(), link.receiver.clone()],
+                        )
+                    }
+                };
+
+                let mut residual = self.measurement_update(
+                    &mut estimate,
+                    &h_row,
+                    epoch,
+                    real_obs,
+                    computed_obs,
+                    r_variance,
+                )?;
+                debug!(
+                    "processed {msr_type:?} from {} @ {epoch} (ratio = {:.3})",
+                    msr.tracker, residual.ratio
+                );
+                residual.tracker = Some(msr.tracker.clone());
+                residual.msr_types = msr_types;
+
+                solution.estimates.push(estimate.clone());
+                solution.residuals.push(Some(residual));
+                solution.observed.push(observed);
+            }
+        }
+
+        info!(
+            "Joint estimation completed with {} residuals",
+            solution.residuals.len()
+        );
+
+        Ok(solution)
+    }
+
+    /// Propagates each vehicle and the joint covariance to the provided epoch, applying the process noise of each vehicle.
+    fn time_update(
+        &self,
+        estimate: &JointEstimate,
+        epoch: Epoch,
+        process_noise: &mut BTreeMap<String, ProcessNoise3D>,
+    ) -> Result<JointEstimate, ODError> {
+        let size = estimate.covar.nrows();
+        let delta_t_s = (epoch - estimate.epoch()).to_seconds();
+        let mut stm = DMatrix::zeros(size, size);
+        let mut noise = DMatrix::zeros(size, size);
+        let mut vehicles = IndexMap::with_capacity(estimate.vehicles.len());
+
+        for (i, (name, sc)) in estimate.vehicles.iter().enumerate() {
+            let mut state = self
+                .prop
+                .with(sc.with_stm(), self.almanac.clone())
+                .quiet()
+                .until_epoch(epoch)
+                .context(ODPropSnafu)?;
+            stm.view_mut((SC_SIZE * i, SC_SIZE * i), (SC_SIZE, SC_SIZE))
+                .copy_from(&state.stm().context(ODDynamicsSnafu)?);
+            state.unset_stm();
+
+            if let Some(snc) = process_noise.get_mut(name) {
+                if snc.init_epoch.is_none() {
+                    snc.init_epoch = Some(estimate.epoch());
+                }
+                if let Some(snc_covar) =
+                    snc.state_covar::<<Spacecraft as State>::Size>(state.orbit, delta_t_s)?
+                {
+                    noise
+                        .view_mut((SC_SIZE * i, SC_SIZE * i), (SC_SIZE, SC_SIZE))
+                        .copy_from(&snc_covar);
+                }
+                snc.prev_epoch = Some(epoch);
+            }
+
+            vehicles.insert(name.clone(), state);
+        }
+
+        Ok(JointEstimate {
+            vehicles,
+            covar: &stm * &estimate.covar * stm.transpose() + noise,
+            predicted: true,
+        })
+    }
+
+    /// Updates the joint estimate with a scalar measurement of the provided sensitivity with respect to the joint state.
+    fn measurement_update(
+        &self,
+        estimate: &mut JointEstimate,
+        h_row: &DVector<f64>,
+        epoch: Epoch,
+        real_obs: f64,
+        computed_obs: f64,
+        r_variance: f64,
+    ) -> Result<Residual<U1>, ODError> {
+        ensure!(r_variance > 0.0, SingularNoiseRkSnafu);
+
+        let p_ht = &estimate.covar * h_row;
+        let innovation_var = h_row.dot(&p_ht) + r_variance;
+        let prefit = real_obs - computed_obs;
+        let ratio = prefit / innovation_var.sqrt();
+
+        if let Some(resid_reject) = self.resid_crit {
+            if ratio.abs() > resid_reject.num_sigmas {
+                return Ok(Residual::rejected(
+                    epoch,
+                    OVector::<f64, U1>::new(prefit),
+                    ratio,
+                    OVector::<f64, U1>::new(r_variance),
+                    OVector::<f64, U1>::new(real_obs),
+                    OVector::<f64, U1>::new(computed_obs),
+                ));
+            }
+        }
+
+        let gain = p_ht / innovation_var;
+        let correction = &gain * prefit;
+
+        // Joseph update of the covariance
+        let i_kh = DMatrix::<f64>::identity(h_row.len(), h_row.len()) - &gain * h_row.transpose();
+        estimate.covar =
+            &i_kh * &estimate.covar * i_kh.transpose() + &gain * gain.transpose() * r_variance;
+        estimate.predicted = false;
+
+        // The reference state of each vehicle is replaced by its estimate, as in the extended Kalman filter.
+        for (i, sc) in estimate.vehicles.values_mut().enumerate() {
+            *sc = *sc
+                + OVector::<f64, <Spacecraft as State>::Size>::from_fn(|k, _| {
+                    correction[SC_SIZE * i + k]
+                });
+        }
+
+        Ok(Residual::accepted(
+            epoch,
+            OVector::<f64, U1>::new(prefit),
+            OVector::<f64, U1>::new(prefit - h_row.dot(&correction)),
+            ratio,
+            OVector::<f64, U1>::new(r_variance),
+            OVector::<f64, U1>::new(real_obs),
+            OVector::<f64, U1>::new(computed_obs),
+        ))
+    }
+}
+
+/// Solution of the joint estimation: the joint estimate after each scalar measurement, and its residual.
+#[derive(Clone, Debug, Default)]
+pub struct JointODSolution {
+    /// Joint estimate after each measurement
+    pub estimates: Vec<JointEstimate>,
+    /// Residual of each measurement
+    pub residuals: Vec<Option<Residual<U1>>>,
+    /// Names of the vehicles observed by each measurement: the tracked vehicle for ground measurements, and both ends of crosslinks
+    pub observed: Vec<Vec<String>>,
+}
+
+impl JointODSolution {
+    /// Returns the estimates of the provided vehicle, with their marginal covariance.
+    pub fn vehicle_estimates(&self, name: &str) -> Vec<KfEstimate<Spacecraft>> {
+        self.estimates
+            .iter()
+            .filter_map(|estimate| estimate.vehicle_estimate(name))
+            .collect()
+    }
+
+    /// Returns the solution of the provided vehicle as an OD solution, e.g. to compute its residual statistics or to export it.
+    /// The residuals of the measurements which do not observe this vehicle are not included, and their estimates are stored
+    /// as time updates.
+    pub fn to_od_solution(
+        &self,
+        name: &str,
+        devices: BTreeMap<String, GroundStation>,
+    ) -> ODSolution<Spacecraft, KfEstimate<Spacecraft>, U1, GroundStation> {
+        let msr_types = self
+            .residuals
+            .iter()
+            .flatten()
+            .flat_map(|residual| residual.msr_types.iter().copied())
+            .collect::<IndexSet<MeasurementType>>();
+
+        let mut od_sol = ODSolution::new(devices, msr_types);
+        for ((estimate, residual), observed) in self
+            .estimates
+            .iter()
+            .zip(self.residuals.iter())
+            .zip(self.observed.iter())
+        {
+            let Some(vehicle_estimate) = estimate.vehicle_estimate(name) else {
+                continue;
+            };
+            match residual {
+                Some(residual) if observed.iter().any(|vehicle| vehicle == name) => {
+                    od_sol.push_measurement_update(vehicle_estimate, residual.clone(), None)
+                }
+                _ => od_sol.push_time_update(vehicle_estimate),
+            }
+        }
+        od_sol
+    }
+}
+
+#[cfg(test)]
+mod ut_joint {
+    use super::*;
+    use crate::dynamics::OrbitalDynamics;
+    use crate::linalg::Vector6;
+    use crate::Orbit;
+    use anise::constants::frames::EARTH_J2000;
+
+    #[test]
+    fn joint_crosslink_update() {
+        let epoch = Epoch::from_gregorian_utc_at_midnight(2020, 1, 1);
+        let eme2k = EARTH_J2000.with_mu_km3_s2(398_600.441_5);
+        let estimate_of = |x_km: f64| {
+            let sc = Spacecraft::from(Orbit::new(x_km, 0.0, 0.0, 0.0, 7.5, 0.0, epoch, eme2k));
+            let mut diag = OVector::<f64, <Spacecraft as State>::Size>::zeros();
+            diag.fixed_rows_mut::<6>(0)
+                .copy_from(&Vector6::new(1.0, 1.0, 1.0, 1e-6, 1e-6, 1e-6));
+            KfEstimate::from_diag(sc, diag)
+        };
+
+        let mut estimate = JointEstimate::new(vec![
+            ("A".to_string(), estimate_of(7000.0)),
+            ("B".to_string(), estimate_of(7100.0)),
+        ])
+        .unwrap();
+        assert_eq!(estimate.covar.nrows(), 18);
+        assert_eq!(estimate.cross_covar("A", "B").unwrap().norm(), 0.0);
+        assert!(estimate.vehicle_estimate("C").is_none());
+
+        let odp = JointODProcess::builder()
+            .prop(Propagator::default(SpacecraftDynamics::new(
+                OrbitalDynamics::two_body(),
+            )))
+            .almanac(Arc::new(Almanac::default()))
+            .build();
+
+        // A range measurement of B from A along X only observes the difference of their X positions.
+        let mut h_row = DVector::zeros(18);
+        h_row[0] = -1.0;
+        h_row[SC_SIZE] = 1.0;
+        let residual = odp
+            .measurement_update(&mut estimate, &h_row, epoch, 100.5, 100.0, 1e-6)
+            .unwrap();
+
+        assert!(!residual.rejected);
+        assert!(residual.postfit[0].abs() < residual.prefit[0].abs());
+        // The correction is shared between both vehicles, which become correlated.
+        let x_a = estimate.vehicles["A"].orbit.radius_km.x;
+        let x_b = estimate.vehicles["B"].orbit.radius_km.x;
+        assert!((x_a - (7000.0 - 0.25)).abs() < 1e-3);
+        assert!((x_b - (7100.0 + 0.25)).abs() < 1e-3);
+        let cross = estimate.cross_covar("A", "B").unwrap();
+        assert!(cross[(0, 0)] > 0.4);
+        assert!(estimate.vehicle_estimate("A").unwrap().covar[(0, 0)] < 1.0);
+    }
+}
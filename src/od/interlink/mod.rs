@@ -16,7 +16,9 @@
     along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
+pub mod joint;
 pub mod sensitivity;
 pub mod trk_device;
 
+pub use joint::{Crosslink, JointEstimate, JointODProcess, JointODSolution};
 pub use trk_device::InterlinkTxSpacecraft;
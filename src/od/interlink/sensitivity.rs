@@ -25,7 +25,7 @@ use crate::od::{ODAlmanacSnafu, ODError, TrackingDevice};
 use crate::{Spacecraft, State};
 use anise::prelude::Almanac;
 use indexmap::IndexSet;
-use nalgebra::{DimName, OMatrix, U1};
+use nalgebra::{DimName, OMatrix, Vector3, Vector6, U1};
 use snafu::ResultExt;
 use std::marker::PhantomData;
 use std::sync::Arc;
@@ -102,64 +102,122 @@ impl ScalarSensitivityT<Spacecraft, Spacecraft, InterlinkTxSpacecraft>
         let delta_r = receiver.radius_km - transmitter.radius_km;
         let delta_v = receiver.velocity_km_s - transmitter.velocity_km_s;
 
-        match msr_type {
-            MeasurementType::Doppler => {
-                // If we have a simultaneous measurement of the range, use that, otherwise we compute the expected range.
-                let ρ_km = msr.data.get(&MeasurementType::Range).ok_or_else(|| {
-                    ODError::MeasurementSimError {
-                        details: "Range measurement data is missing".to_string(),
-                    }
+        // If we have a simultaneous measurement of the range, use that, otherwise we compute the expected range.
+        let ρ_km =
+            msr.data
+                .get(&MeasurementType::Range)
+                .ok_or_else(|| ODError::MeasurementSimError {
+                    details: "Range measurement data is missing".to_string(),
                 })?;
-                let ρ_dot_km_s = msr.data.get(&MeasurementType::Doppler).ok_or_else(|| {
-                    ODError::MeasurementSimError {
-                        details: "Doppler measurement data is missing".to_string(),
-                    }
-                })?;
-                let m11 = delta_r.x / ρ_km;
-                let m12 = delta_r.y / ρ_km;
-                let m13 = delta_r.z / ρ_km;
-                let m21 = delta_v.x / ρ_km - ρ_dot_km_s * delta_r.x / ρ_km.powi(2);
-                let m22 = delta_v.y / ρ_km - ρ_dot_km_s * delta_r.y / ρ_km.powi(2);
-                let m23 = delta_v.z / ρ_km - ρ_dot_km_s * delta_r.z / ρ_km.powi(2);
-
-                let sensitivity_row =
-                    OMatrix::<f64, U1, <Spacecraft as State>::Size>::from_row_slice(&[
-                        m21, m22, m23, m11, m12, m13, 0.0, 0.0, 0.0,
-                    ]);
-
-                Ok(Self {
-                    sensitivity_row,
-                    _rx: PhantomData::<_>,
-                    _tx: PhantomData::<_>,
-                })
-            }
-            MeasurementType::Range => {
-                let ρ_km = msr.data.get(&MeasurementType::Range).ok_or_else(|| {
-                    ODError::MeasurementSimError {
-                        details: "Range measurement data is missing".to_string(),
-                    }
-                })?;
-                let m11 = delta_r.x / ρ_km;
-                let m12 = delta_r.y / ρ_km;
-                let m13 = delta_r.z / ρ_km;
-
-                let sensitivity_row =
-                    OMatrix::<f64, U1, <Spacecraft as State>::Size>::from_row_slice(&[
-                        m11, m12, m13, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
-                    ]);
-
-                Ok(Self {
-                    sensitivity_row,
-                    _rx: PhantomData::<_>,
-                    _tx: PhantomData::<_>,
-                })
+        let ρ_dot_km_s = if msr_type == MeasurementType::Doppler {
+            *msr.data.get(&MeasurementType::Doppler).ok_or_else(|| {
+                ODError::MeasurementSimError {
+                    details: "Doppler measurement data is missing".to_string(),
+                }
+            })?
+        } else {
+            0.0
+        };
+
+        let partials = link_partials(msr_type, &delta_r, &delta_v, *ρ_km, ρ_dot_km_s)?;
+
+        let mut sensitivity_row = OMatrix::<f64, U1, <Spacecraft as State>::Size>::zeros();
+        for (i, partial) in partials.iter().enumerate() {
+            sensitivity_row[i] = *partial;
+        }
+
+        Ok(Self {
+            sensitivity_row,
+            _rx: PhantomData::<_>,
+            _tx: PhantomData::<_>,
+        })
+    }
+}
+
+/// Returns the partial derivatives of the range or of the range rate of a link with respect to the position and velocity of
+/// the receiver, from the position and velocity of the receiver relative to the transmitter. The partial derivatives with
+/// respect to the position and velocity of the transmitter are their opposite.
+pub(crate) fn link_partials(
+    msr_type: MeasurementType,
+    delta_r: &Vector3<f64>,
+    delta_v: &Vector3<f64>,
+    range_km: f64,
+    range_rate_km_s: f64,
+) -> Result<Vector6<f64>, ODError> {
+    match msr_type {
+        MeasurementType::Range => {
+            let d_range = delta_r / range_km;
+            Ok(Vector6::new(d_range.x, d_range.y, d_range.z, 0.0, 0.0, 0.0))
+        }
+        MeasurementType::Doppler => {
+            let d_range = delta_r / range_km;
+            let d_range_rate = delta_v / range_km - delta_r * range_rate_km_s / range_km.powi(2);
+            Ok(Vector6::new(
+                d_range_rate.x,
+                d_range_rate.y,
+                d_range_rate.z,
+                d_range.x,
+                d_range.y,
+                d_range.z,
+            ))
+        }
+        MeasurementType::Azimuth
+        | MeasurementType::Elevation
+        | MeasurementType::ReceiveFrequency
+        | MeasurementType::TransmitFrequency => Err(ODError::MeasurementSimError {
+            details: format!("{msr_type:?} is not supported for interlink"),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod ut_link_partials {
+    use super::*;
+
+    #[test]
+    fn link_partials_finite_diff() {
+        let delta_r = Vector3::new(1200.0, -300.0, 450.0);
+        let delta_v = Vector3::new(0.1, 0.7, -0.2);
+
+        let range = |dr: &Vector3<f64>, _: &Vector3<f64>| dr.norm();
+        let range_rate = |dr: &Vector3<f64>, dv: &Vector3<f64>| dr.dot(dv) / dr.norm();
+
+        for (msr_type, model) in [
+            (
+                MeasurementType::Range,
+                &range as &dyn Fn(&Vector3<f64>, &Vector3<f64>) -> f64,
+            ),
+            (MeasurementType::Doppler, &range_rate),
+        ] {
+            let partials = link_partials(
+                msr_type,
+                &delta_r,
+                &delta_v,
+                range(&delta_r, &delta_v),
+                range_rate(&delta_r, &delta_v),
+            )
+            .unwrap();
+
+            for i in 0..6 {
+                let step = 1e-4;
+                let (mut r_plus, mut v_plus) = (delta_r, delta_v);
+                let (mut r_minus, mut v_minus) = (delta_r, delta_v);
+                if i < 3 {
+                    r_plus[i] += step;
+                    r_minus[i] -= step;
+                } else {
+                    v_plus[i - 3] += step;
+                    v_minus[i - 3] -= step;
+                }
+                let expected = (model(&r_plus, &v_plus) - model(&r_minus, &v_minus)) / (2.0 * step);
+                assert!(
+                    (partials[i] - expected).abs() < 1e-9,
+                    "{msr_type:?} partial #{i}: {} != {expected}",
+                    partials[i]
+                );
             }
-            MeasurementType::Azimuth
-            | MeasurementType::Elevation
-            | MeasurementType::ReceiveFrequency
-            | MeasurementType::TransmitFrequency => Err(ODError::MeasurementSimError {
-                details: format!("{msr_type:?} is not supported for interlink"),
-            }),
         }
+
+        assert!(link_partials(MeasurementType::Azimuth, &delta_r, &delta_v, 1.0, 0.0).is_err());
     }
 }
@@ -40,6 +40,7 @@ use std::sync::Arc;
 
 // Defines a (transmitter) spacecraft capable of inter-satellite links.
 // NOTE: There is _no_ `InterlinkRxSpacecraft`, instead you must independently build their trajectories and provide them to the InterlinkArcSim.
+// To estimate both ends of the link, use the `JointODProcess` with a `Crosslink` built from this transmitter.
 #[derive(Clone, Debug)]
 pub struct InterlinkTxSpacecraft {
     /// Trajectory of the transmitter spacercaft
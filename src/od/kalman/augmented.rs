@@ -0,0 +1,720 @@
+/*
+    Nyx, blazing fast astrodynamics
+    Copyright (C) 2018-onwards Christopher Rabotin <christopher.rabotin@gmail.com>
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published
+    by the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::cosmic::AstroPhysicsSnafu;
+use crate::errors::StateAstroSnafu;
+use crate::linalg::allocator::Allocator;
+use crate::linalg::{DMatrix, DVector, DefaultAllocator, DimName, Matrix3, OMatrix, OVector};
+use crate::md::StateParameter;
+use crate::od::consider::to_dmatrix;
+use crate::od::estimate::{Estimate, KfEstimate, Residual};
+use crate::od::msr::MeasurementType;
+use crate::od::process::ResidRejectCrit;
+use crate::od::{ODDynamicsSnafu, ODError, ODStateSnafu, State};
+use crate::time::Epoch;
+use indexmap::IndexSet;
+use snafu::prelude::*;
+
+use super::{
+    BiasEstimate, BiasedQuantity, DmcEstimate, DynamicModelCompensation, EstimatedBias,
+    KalmanFilter, KalmanVariant,
+};
+
+/// Parameters augmenting the state of the Kalman filter, with their cross covariance with the state.
+///
+/// The augmented parameters are the estimated tracking biases, in order, followed by the radial, in-track, and cross-track
+/// accelerations of the dynamic model compensation, if any.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct AugmentedStates {
+    pub(crate) biases: Vec<EstimatedBias>,
+    pub(crate) dmc: Option<DynamicModelCompensation>,
+    /// Estimated value of each parameter
+    pub(crate) value: DVector<f64>,
+    /// Covariance of the parameters, of size p x p
+    pub(crate) covar: DMatrix<f64>,
+    /// Cross covariance of the state and of the parameters, of size n x p
+    pub(crate) cross_covar: DMatrix<f64>,
+    epoch: Epoch,
+}
+
+impl AugmentedStates {
+    pub(crate) fn new(
+        biases: Vec<EstimatedBias>,
+        dmc: Option<DynamicModelCompensation>,
+        state_size: usize,
+        epoch: Epoch,
+    ) -> Self {
+        let mut a_priori: Vec<(f64, f64)> =
+            biases.iter().map(|bias| bias.model.a_priori()).collect();
+        if let Some(dmc) = &dmc {
+            a_priori.extend(dmc.a_priori_sigmas().map(|sigma| (0.0, sigma)));
+        }
+        let num_params = a_priori.len();
+
+        Self {
+            value: DVector::from_iterator(num_params, a_priori.iter().map(|(value, _)| *value)),
+            covar: DMatrix::from_diagonal(&DVector::from_iterator(
+                num_params,
+                a_priori.iter().map(|(_, sigma)| sigma.powi(2)),
+            )),
+            cross_covar: DMatrix::zeros(state_size, num_params),
+            biases,
+            dmc,
+            epoch,
+        }
+    }
+
+    /// Number of augmented parameters
+    pub(crate) fn num_params(&self) -> usize {
+        self.value.len()
+    }
+
+    /// Maps the parameters and their covariance to the provided epoch, where the STM maps the state from the previous estimate.
+    ///
+    /// With dynamic model compensation, `ric_to_inertial` must be the rotation from the RIC frame of the previous estimate, and this
+    /// returns the effect of the estimated accelerations over the step on the state deviation and on the state covariance.
+    pub(crate) fn time_update(
+        &mut self,
+        stm: &DMatrix<f64>,
+        ric_to_inertial: Option<Matrix3<f64>>,
+        epoch: Epoch,
+    ) -> Option<(DVector<f64>, DMatrix<f64>)> {
+        let n = stm.nrows();
+        let p = self.num_params();
+        let dt_s = (epoch - self.epoch).to_seconds();
+
+        let mut decay = DVector::<f64>::zeros(p);
+        let mut noise = DVector::<f64>::zeros(p);
+        // Only the stochastic part of the biases decays, not their permanent offset.
+        let mut offset = DVector::<f64>::zeros(p);
+        for (j, bias) in self.biases.iter().enumerate() {
+            (decay[j], noise[j]) = bias.model.transition(dt_s);
+            offset[j] = bias.model.offset();
+        }
+
+        // Sensitivity of the state to the accelerations at the start of the step.
+        let mut phi_xp = DMatrix::<f64>::zeros(n, p);
+        if let Some(dmc) = &self.dmc {
+            let offset = self.biases.len();
+            let dcm = ric_to_inertial.unwrap_or_else(Matrix3::identity);
+            for (k, axis) in dmc.transition(dt_s).iter().enumerate() {
+                decay[offset + k] = axis.decay;
+                noise[offset + k] = axis.noise;
+                for i in 0..3 {
+                    phi_xp[(i, offset + k)] = dcm[(i, k)] * axis.pos_sensitivity;
+                    phi_xp[(i + 3, offset + k)] = dcm[(i, k)] * axis.vel_sensitivity;
+                }
+            }
+        }
+        let decay = DMatrix::from_diagonal(&decay);
+
+        let contribution = self.dmc.map(|_| {
+            let cross = stm * &self.cross_covar * phi_xp.transpose();
+            (
+                &phi_xp * &self.value,
+                &cross + cross.transpose() + &phi_xp * &self.covar * phi_xp.transpose(),
+            )
+        });
+
+        self.cross_covar = (stm * &self.cross_covar + &phi_xp * &self.covar) * &decay;
+        self.covar = &decay * &self.covar * &decay + DMatrix::from_diagonal(&noise);
+        self.value = &decay * (&self.value - &offset) + offset;
+        self.epoch = epoch;
+
+        contribution
+    }
+
+    /// Returns whether the time tags of this tracker are biased, in which case its measurement sensitivity requires the time derivative of the observations.
+    pub(crate) fn has_time_tag_bias(&self, tracker: &str) -> bool {
+        self.biases
+            .iter()
+            .any(|bias| bias.tracker == tracker && bias.quantity == BiasedQuantity::TimeTag)
+    }
+
+    /// Returns the sensitivity of the provided measurements of this tracker to the parameters, of size M x p.
+    ///
+    /// The sensitivity to a time-tag bias is the opposite of the time derivative of the observations, if provided, and zero otherwise.
+    pub(crate) fn sensitivity<M: DimName>(
+        &self,
+        tracker: &str,
+        msr_types: &IndexSet<MeasurementType>,
+        obs_rates: Option<&[f64]>,
+    ) -> DMatrix<f64> {
+        let mut h_params = DMatrix::zeros(M::DIM, self.num_params());
+        for (j, bias) in self.biases.iter().enumerate() {
+            if bias.tracker != tracker {
+                continue;
+            }
+            match bias.quantity {
+                BiasedQuantity::Observation(msr_type) => {
+                    if let Some(i) = msr_types.get_index_of(&msr_type) {
+                        h_params[(i, j)] = 1.0;
+                    }
+                }
+                BiasedQuantity::TimeTag => {
+                    if let Some(obs_rates) = obs_rates {
+                        for (i, rate) in obs_rates.iter().take(msr_types.len()).enumerate() {
+                            h_params[(i, j)] = -rate;
+                        }
+                    }
+                }
+            }
+        }
+        h_params
+    }
+
+    /// Returns the estimate of the tracking biases, if any are estimated.
+    pub(crate) fn bias_estimate(&self) -> Option<BiasEstimate> {
+        let b = self.biases.len();
+        (b > 0).then(|| BiasEstimate {
+            value: self.value.rows(0, b).into_owned(),
+            covar: self.covar.view((0, 0), (b, b)).into_owned(),
+        })
+    }
+
+    /// Returns the estimate of the accelerations of the dynamic model compensation, if enabled.
+    pub(crate) fn dmc_estimate(&self) -> Option<DmcEstimate> {
+        let offset = self.biases.len();
+        self.dmc.map(|_| DmcEstimate {
+            accel_km_s2: self.value.fixed_rows::<3>(offset).into_owned(),
+            covar: self.covar.fixed_view::<3, 3>(offset, offset).into_owned(),
+        })
+    }
+}
+
+impl<T, A> KalmanFilter<T, A>
+where
+    A: DimName,
+    T: State,
+    DefaultAllocator: Allocator<<T as State>::Size>
+        + Allocator<<T as State>::VecLength>
+        + Allocator<A>
+        + Allocator<<T as State>::Size, <T as State>::Size>
+        + Allocator<A, A>
+        + Allocator<<T as State>::Size, A>
+        + Allocator<A, <T as State>::Size>,
+    <DefaultAllocator as Allocator<<T as State>::Size>>::Buffer<f64>: Copy,
+    <DefaultAllocator as Allocator<<T as State>::Size, <T as State>::Size>>::Buffer<f64>: Copy,
+{
+    /// Maps the provided augmented states from the previous estimate to the provided epoch.
+    ///
+    /// Returns the contribution of the dynamic model compensation, if enabled, to the state deviation and to the covariance.
+    #[allow(clippy::type_complexity)]
+    pub(crate) fn augmented_time_update(
+        &self,
+        augmented: &mut AugmentedStates,
+        stm: &OMatrix<f64, <T as State>::Size, <T as State>::Size>,
+        epoch: Epoch,
+    ) -> Result<
+        Option<(
+            OVector<f64, <T as State>::Size>,
+            OMatrix<f64, <T as State>::Size, <T as State>::Size>,
+        )>,
+        ODError,
+    > {
+        let ric_to_inertial = match augmented.dmc {
+            Some(_) => Some(
+                self.prev_estimate
+                    .state()
+                    .orbit()
+                    .dcm3x3_from_ric_to_inertial()
+                    .context(AstroPhysicsSnafu)
+                    .context(StateAstroSnafu {
+                        param: StateParameter::Epoch,
+                    })
+                    .context(ODStateSnafu {
+                        action: "rotating DMC accelerations from RIC into the state frame",
+                    })?
+                    .rot_mat,
+            ),
+            None => None,
+        };
+
+        Ok(augmented
+            .time_update(&to_dmatrix(stm), ric_to_inertial, epoch)
+            .map(|(dx, dp)| {
+                (
+                    OVector::<f64, <T as State>::Size>::from_fn(|i, _| dx[i]),
+                    OMatrix::<f64, <T as State>::Size, <T as State>::Size>::from_fn(|i, j| {
+                        dp[(i, j)]
+                    }),
+                )
+            }))
+    }
+
+    /// Computes the measurement update of the state augmented with the estimated biases and accelerations, if any, for the provided tracker.
+    ///
+    /// The augmented covariance always uses the conventional formulation (Joseph update). Without augmented states, this is a
+    /// normal measurement update. The time derivative of the computed observations is only needed to estimate time-tag biases.
+    #[allow(clippy::too_many_arguments, clippy::type_complexity)]
+    pub(crate) fn augmented_measurement_update<M: DimName>(
+        &mut self,
+        nominal_state: T,
+        real_obs: OVector<f64, M>,
+        computed_obs: OVector<f64, M>,
+        r_k: OMatrix<f64, M, M>,
+        h_tilde: OMatrix<f64, M, <T as State>::Size>,
+        tracker: &str,
+        msr_types: &IndexSet<MeasurementType>,
+        obs_rates: Option<OVector<f64, M>>,
+        resid_rejection: Option<ResidRejectCrit>,
+    ) -> Result<
+        (
+            KfEstimate<T>,
+            Residual<M>,
+            Option<OMatrix<f64, <T as State>::Size, M>>,
+        ),
+        ODError,
+    >
+    where
+        DefaultAllocator: Allocator<M>
+            + Allocator<M, M>
+            + Allocator<M, <T as State>::Size>
+            + Allocator<<T as State>::Size, M>
+            + Allocator<nalgebra::Const<1>, M>,
+    {
+        let mut augmented = match &self.augmented {
+            Some(augmented) => augmented.clone(),
+            None => {
+                return self.measurement_update(
+                    nominal_state,
+                    real_obs,
+                    computed_obs,
+                    r_k,
+                    h_tilde,
+                    resid_rejection,
+                )
+            }
+        };
+
+        let n = <T as State>::Size::DIM;
+        let num_params = augmented.num_params();
+        let epoch = nominal_state.epoch();
+
+        let stm = nominal_state.stm().context(ODDynamicsSnafu)?;
+        let (mut covar_bar, _) = self.propagate_covar(&stm, &nominal_state)?;
+        let mut state_bar = if matches!(self.variant, KalmanVariant::DeviationTracking) {
+            stm * self.prev_estimate.state_deviation
+        } else {
+            OVector::<f64, <T as State>::Size>::zeros()
+        };
+        if let Some((dx, dp)) = self.augmented_time_update(&mut augmented, &stm, epoch)? {
+            state_bar += dx;
+            covar_bar += dp;
+        }
+
+        // The computed observation includes the estimated biases.
+        let h_params = augmented.sensitivity::<M>(
+            tracker,
+            msr_types,
+            obs_rates.as_ref().map(|rates| rates.as_slice()),
+        );
+        let params_bar = augmented.value.clone();
+        let bias_obs = &h_params * &params_bar;
+        let computed_obs = computed_obs + OVector::<f64, M>::from_fn(|i, _| bias_obs[i]);
+        let prefit = real_obs.clone() - computed_obs.clone();
+
+        // Build the augmented covariance and sensitivity matrix.
+        let mut aug_covar_bar = DMatrix::<f64>::zeros(n + num_params, n + num_params);
+        aug_covar_bar
+            .view_mut((0, 0), (n, n))
+            .copy_from(&to_dmatrix(&covar_bar));
+        aug_covar_bar
+            .view_mut((0, n), (n, num_params))
+            .copy_from(&augmented.cross_covar);
+        aug_covar_bar
+            .view_mut((n, 0), (num_params, n))
+            .copy_from(&augmented.cross_covar.transpose());
+        aug_covar_bar
+            .view_mut((n, n), (num_params, num_params))
+            .copy_from(&augmented.covar);
+
+        let mut aug_h = DMatrix::<f64>::zeros(M::DIM, n + num_params);
+        aug_h
+            .view_mut((0, 0), (M::DIM, n))
+            .copy_from(&to_dmatrix(&h_tilde));
+        aug_h
+            .view_mut((0, n), (M::DIM, num_params))
+            .copy_from(&h_params);
+
+        let r_k_dyn = to_dmatrix(&r_k);
+        let s_k = &aug_h * &aug_covar_bar * aug_h.transpose() + &r_k_dyn;
+        let s_k_static = OMatrix::<f64, M, M>::from_fn(|i, j| s_k[(i, j)]);
+
+        // Same rejection criteria as the measurement update without augmented states.
+        let r_k_chol = match s_k_static.clone().cholesky() {
+            Some(s_k_chol) => s_k_chol.l(),
+            None => r_k.clone().cholesky().ok_or(ODError::SingularNoiseRk)?.l(),
+        };
+
+        let ratio = s_k_static
+            .diagonal()
+            .iter()
+            .copied()
+            .enumerate()
+            .map(|(idx, r)| prefit[idx] / r.sqrt())
+            .sum::<f64>()
+            / (M::DIM as f64);
+
+        if let Some(resid_reject) = resid_rejection {
+            if ratio.abs() > resid_reject.num_sigmas {
+                // Reject this whole measurement and perform only a time update, which also maps the augmented states.
+                let pred_est = self.time_update(nominal_state)?;
+                return Ok((
+                    pred_est,
+                    Residual::rejected(
+                        epoch,
+                        prefit,
+                        ratio,
+                        r_k_chol.diagonal(),
+                        real_obs,
+                        computed_obs,
+                    ),
+                    None,
+                ));
+            }
+        }
+
+        let s_k_inv = s_k.try_inverse().ok_or(ODError::SingularKalmanGain)?;
+        let aug_gain = &aug_covar_bar * aug_h.transpose() * s_k_inv;
+
+        let innovation = &prefit - &h_tilde * state_bar;
+        let correction = &aug_gain * DVector::from_iterator(M::DIM, innovation.iter().copied());
+
+        let state_hat =
+            state_bar + OVector::<f64, <T as State>::Size>::from_fn(|i, _| correction[i]);
+        let params_correction = correction.rows(n, num_params).into_owned();
+        augmented.value = &params_bar + &params_correction;
+
+        // Joseph update of the augmented covariance.
+        let first_term =
+            DMatrix::<f64>::identity(n + num_params, n + num_params) - &aug_gain * &aug_h;
+        let aug_covar = &first_term * &aug_covar_bar * first_term.transpose()
+            + &aug_gain * &r_k_dyn * aug_gain.transpose();
+
+        let covar = OMatrix::<f64, <T as State>::Size, <T as State>::Size>::from_fn(|i, j| {
+            aug_covar[(i, j)]
+        });
+        augmented.cross_covar = aug_covar.view((0, n), (n, num_params)).into_owned();
+        augmented.covar = aug_covar
+            .view((n, n), (num_params, num_params))
+            .into_owned();
+
+        let postfit = match self.variant {
+            KalmanVariant::ReferenceUpdate | KalmanVariant::Unscented { .. } => {
+                let bias_postfit = &h_params * &params_correction;
+                &prefit
+                    - (&h_tilde * state_hat)
+                    - OVector::<f64, M>::from_fn(|i, _| bias_postfit[i])
+            }
+            KalmanVariant::DeviationTracking => &prefit - (&h_tilde * state_bar),
+        };
+
+        let res = Residual::accepted(
+            epoch,
+            prefit,
+            postfit,
+            ratio,
+            r_k_chol.diagonal(),
+            real_obs,
+            computed_obs,
+        );
+
+        let gain = OMatrix::<f64, <T as State>::Size, M>::from_fn(|i, j| aug_gain[(i, j)]);
+
+        let estimate = KfEstimate {
+            nominal_state,
+            state_deviation: state_hat,
+            covar,
+            covar_bar,
+            stm,
+            predicted: false,
+        };
+
+        self.prev_estimate = estimate;
+        self.augmented = Some(augmented);
+        self.ud_factors = None;
+        // Update the prev epoch for all SNCs
+        for snc in &mut self.process_noise {
+            snc.prev_epoch = Some(self.prev_estimate.epoch());
+        }
+
+        Ok((estimate, res, Some(gain)))
+    }
+}
+
+#[cfg(test)]
+mod ut_augmented {
+    use super::*;
+    use crate::linalg::{Matrix1, SMatrix, SVector, Vector1, U2, U3};
+    use crate::od::kalman::BiasModel;
+    use crate::od::noise::GaussMarkov;
+    use crate::time::Unit;
+    use crate::{Orbit, Spacecraft};
+    use anise::constants::frames::EARTH_J2000;
+
+    fn spacecraft() -> Spacecraft {
+        let orbit = Orbit::new(
+            -2436.45,
+            -2436.45,
+            6891.037,
+            5.088_611,
+            -5.088_611,
+            0.0,
+            Epoch::from_gregorian_tai_at_midnight(2020, 1, 1),
+            EARTH_J2000.with_mu_km3_s2(398_600.441_5),
+        );
+        Spacecraft::builder().orbit(orbit).build()
+    }
+
+    fn kalman_filter(sc: Spacecraft) -> KalmanFilter<Spacecraft, U3> {
+        KalmanFilter::new(
+            KfEstimate::from_diag(
+                sc,
+                SVector::<f64, 9>::from_iterator([1.0, 1.0, 1.0, 1e-6, 1e-6, 1e-6, 0.0, 0.0, 0.0]),
+            ),
+            KalmanVariant::DeviationTracking,
+        )
+    }
+
+    #[test]
+    fn gauss_markov_bias_time_update() {
+        let gm = GaussMarkov::new(Unit::Hour * 1, 1e-3).unwrap();
+        let mut augmented = AugmentedStates::new(
+            vec![
+                EstimatedBias::gauss_markov("DSS-65".to_string(), MeasurementType::Range, gm),
+                EstimatedBias::constant("DSS-65".to_string(), MeasurementType::Doppler, 1e-6),
+            ],
+            None,
+            9,
+            Epoch::from_gregorian_tai_at_midnight(2020, 1, 1),
+        );
+        augmented.value[0] = 1e-2;
+        let contribution = augmented.time_update(
+            &DMatrix::identity(9, 9),
+            None,
+            Epoch::from_gregorian_tai_at_midnight(2020, 1, 1) + Unit::Hour * 1,
+        );
+        assert!(contribution.is_none());
+
+        // The Gauss-Markov bias decays towards zero, while its variance stays at its steady state.
+        let biases = augmented.bias_estimate().unwrap();
+        assert!((biases.value[0] - 1e-2 * (-1.0_f64).exp()).abs() < 1e-15);
+
+        // With a constant, the bias decays towards that constant instead.
+        let mut gm_offset = gm;
+        gm_offset.constant = Some(5e-3);
+        let mut with_offset = AugmentedStates::new(
+            vec![EstimatedBias::gauss_markov(
+                "DSS-65".to_string(),
+                MeasurementType::Range,
+                gm_offset,
+            )],
+            None,
+            9,
+            Epoch::from_gregorian_tai_at_midnight(2020, 1, 1),
+        );
+        assert_eq!(with_offset.value[0], 5e-3);
+        with_offset.time_update(
+            &DMatrix::identity(9, 9),
+            None,
+            Epoch::from_gregorian_tai_at_midnight(2020, 1, 1) + Unit::Hour * 1,
+        );
+        assert!((with_offset.value[0] - 5e-3).abs() < 1e-18);
+        with_offset.value[0] = 1e-2;
+        with_offset.time_update(
+            &DMatrix::identity(9, 9),
+            None,
+            Epoch::from_gregorian_tai_at_midnight(2020, 1, 1) + Unit::Hour * 2,
+        );
+        assert!((with_offset.value[0] - (5e-3 + 5e-3 * (-1.0_f64).exp())).abs() < 1e-15);
+        assert!((biases.covar[(0, 0)] - 1e-6).abs() < 1e-18);
+        assert_eq!(biases.covar[(1, 1)], 1e-12);
+        assert_eq!(biases.sigmas()[1], 1e-6);
+        assert!(augmented.dmc_estimate().is_none());
+
+        let msr_types = IndexSet::from([MeasurementType::Doppler, MeasurementType::Range]);
+        let h_bias = augmented.sensitivity::<U2>("DSS-65", &msr_types, None);
+        assert_eq!(h_bias, DMatrix::from_row_slice(2, 2, &[0.0, 1.0, 1.0, 0.0]));
+        assert_eq!(
+            augmented.sensitivity::<U2>("DSS-34", &msr_types, None),
+            DMatrix::zeros(2, 2)
+        );
+    }
+
+    #[test]
+    fn constant_bias_estimation() {
+        let sc = spacecraft();
+        let mut kf = kalman_filter(sc);
+        kf.augmented = Some(AugmentedStates::new(
+            vec![EstimatedBias::constant(
+                "DSS-65".to_string(),
+                MeasurementType::Range,
+                1.0,
+            )],
+            None,
+            9,
+            sc.epoch(),
+        ));
+
+        let msr_types = IndexSet::from([MeasurementType::Range]);
+        let true_bias = 0.5;
+        for i in 0..20 {
+            let mut nominal_state = sc.with_stm();
+            nominal_state.orbit.epoch = sc.epoch() + Unit::Minute * (i + 1);
+
+            // Alternate between the X and the Y position so that the bias is observable.
+            let mut h_tilde = SMatrix::<f64, 1, 9>::zeros();
+            h_tilde[(0, (i % 2) as usize)] = 1.0;
+
+            kf.augmented_measurement_update(
+                nominal_state,
+                Vector1::new(true_bias),
+                Vector1::zeros(),
+                Matrix1::new(1e-8),
+                h_tilde,
+                "DSS-65",
+                &msr_types,
+                None,
+                None,
+            )
+            .unwrap();
+        }
+
+        let augmented = kf.augmented.unwrap();
+        // Without a priori on the bias, the measurements cannot separate the bias from the position:
+        // the estimate sits between both, with correlated uncertainties.
+        let bias_hat = augmented.value[0];
+        let pos_hat = kf.prev_estimate.state_deviation[0];
+        assert!((bias_hat + pos_hat - true_bias).abs() < 1e-6);
+        assert!(augmented.covar[(0, 0)] < 1.0);
+        assert!(augmented.cross_covar[(0, 0)] < 0.0);
+    }
+
+    #[test]
+    fn time_tag_bias_estimation() {
+        let sc = spacecraft();
+        let mut kf = kalman_filter(sc);
+        // Without any uncertainty on the state, the residuals are only explained by the time-tag bias.
+        kf.prev_estimate.covar = SMatrix::<f64, 9, 9>::zeros();
+        kf.augmented = Some(AugmentedStates::new(
+            vec![EstimatedBias::time_tag(
+                "DSS-65".to_string(),
+                BiasModel::Constant { sigma: 1e-2 },
+            )],
+            None,
+            9,
+            sc.epoch(),
+        ));
+        let augmented = kf.augmented.as_ref().unwrap();
+        assert!(augmented.has_time_tag_bias("DSS-65"));
+        assert!(!augmented.has_time_tag_bias("DSS-34"));
+
+        let msr_types = IndexSet::from([MeasurementType::Range, MeasurementType::Doppler]);
+        assert_eq!(
+            augmented.sensitivity::<U2>("DSS-65", &msr_types, Some(&[2.0, -1e-3])),
+            DMatrix::from_row_slice(2, 1, &[-2.0, 1e-3])
+        );
+        assert_eq!(
+            augmented.sensitivity::<U2>("DSS-65", &msr_types, None),
+            DMatrix::zeros(2, 1)
+        );
+
+        // Measurements time tagged 1 ms late.
+        let msr_types = IndexSet::from([MeasurementType::Range]);
+        let true_bias_s = 1e-3;
+        for i in 0..10 {
+            let mut nominal_state = sc.with_stm();
+            nominal_state.orbit.epoch = sc.epoch() + Unit::Minute * (i + 1);
+            let range_rate_km_s = 3.0 - 0.5 * i as f64;
+
+            kf.augmented_measurement_update(
+                nominal_state,
+                Vector1::new(-range_rate_km_s * true_bias_s),
+                Vector1::zeros(),
+                Matrix1::new(1e-10),
+                SMatrix::<f64, 1, 9>::zeros(),
+                "DSS-65",
+                &msr_types,
+                Some(Vector1::new(range_rate_km_s)),
+                None,
+            )
+            .unwrap();
+        }
+
+        let biases = kf.augmented.unwrap().bias_estimate().unwrap();
+        assert!((biases.value[0] - true_bias_s).abs() < 1e-7);
+        assert!(biases.sigmas()[0] < 1e-5);
+    }
+
+    #[test]
+    fn dmc_time_update() {
+        let sc = spacecraft();
+        let dmc = DynamicModelCompensation::new(Unit::Hour * 1, 1e-9).unwrap();
+        let mut kf = kalman_filter(sc);
+        kf.augmented = Some(AugmentedStates::new(
+            vec![EstimatedBias::constant(
+                "DSS-65".to_string(),
+                MeasurementType::Range,
+                1.0,
+            )],
+            Some(dmc),
+            9,
+            sc.epoch(),
+        ));
+        // Start with a known in-track acceleration and no uncertainty on the state.
+        kf.prev_estimate.covar = SMatrix::<f64, 9, 9>::zeros();
+        kf.augmented.as_mut().unwrap().value[2] = 1e-8;
+
+        let mut nominal_state = sc.with_stm();
+        nominal_state.orbit.epoch = sc.epoch() + Unit::Second * 60;
+        let est = kf.time_update(nominal_state).unwrap();
+
+        // The in-track acceleration pushes the state along the velocity, which is the in-track direction of this circular orbit.
+        let in_track = sc
+            .orbit
+            .dcm3x3_from_ric_to_inertial()
+            .unwrap()
+            .rot_mat
+            .column(1)
+            * 1e-8;
+        let transition = dmc.transition(60.0)[1];
+        let dv = est.state_deviation.fixed_rows::<3>(3).into_owned();
+        assert!((dv - in_track * transition.vel_sensitivity).norm() < 1e-18);
+        let dr = est.state_deviation.fixed_rows::<3>(0).into_owned();
+        assert!((dr - in_track * transition.pos_sensitivity).norm() < 1e-15);
+        // Over a short step, this is nearly the effect of a constant acceleration.
+        assert!((dr.norm() - 0.5 * 1e-8 * 60.0_f64.powi(2)).abs() < 1e-2 * dr.norm());
+
+        // The uncertainty of the accelerations now maps into the state covariance.
+        assert!(est.covar[(0, 0)] > 0.0);
+        assert!(est.covar[(3, 3)] > 0.0);
+        let dmc_est = kf.augmented.as_ref().unwrap().dmc_estimate().unwrap();
+        assert!((dmc_est.accel_km_s2[1] - 1e-8 * (-60.0_f64 / 3600.0).exp()).abs() < 1e-20);
+        assert!((dmc_est.sigmas_km_s2()[0] - 1e-9).abs() < 1e-15);
+        assert_eq!(
+            kf.augmented
+                .as_ref()
+                .unwrap()
+                .bias_estimate()
+                .unwrap()
+                .value[0],
+            0.0
+        );
+    }
+}
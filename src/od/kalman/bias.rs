@@ -0,0 +1,173 @@
+/*
+    Nyx, blazing fast astrodynamics
+    Copyright (C) 2018-onwards Christopher Rabotin <christopher.rabotin@gmail.com>
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published
+    by the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::linalg::{DMatrix, DVector};
+use crate::od::msr::MeasurementType;
+use crate::od::noise::GaussMarkov;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Model of a tracking measurement bias estimated by the Kalman filter.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum BiasModel {
+    /// Constant bias of the provided a priori standard deviation, in the unit of the measurement.
+    Constant { sigma: f64 },
+    /// First order Gauss-Markov bias: its time constant is `tau` and its a priori and steady state standard deviation is `process_noise`,
+    /// in the unit of the measurement. The `constant` of the process, if any, is a permanent offset of the bias: only the
+    /// stochastic part of the bias around this offset decays.
+    GaussMarkov(GaussMarkov),
+}
+
+impl BiasModel {
+    /// A priori value and standard deviation of the bias
+    pub(crate) fn a_priori(&self) -> (f64, f64) {
+        match self {
+            Self::Constant { sigma } => (0.0, *sigma),
+            Self::GaussMarkov(gm) => (gm.constant.unwrap_or(0.0), gm.process_noise),
+        }
+    }
+
+    /// Permanent offset of the bias, towards which the estimated bias decays
+    pub(crate) fn offset(&self) -> f64 {
+        match self {
+            Self::Constant { .. } => 0.0,
+            Self::GaussMarkov(gm) => gm.constant.unwrap_or(0.0),
+        }
+    }
+
+    /// Returns the decay of the stochastic part of the bias over the provided duration, and the variance of the process noise over that duration.
+    pub(crate) fn transition(&self, dt_s: f64) -> (f64, f64) {
+        match self {
+            Self::Constant { .. } => (1.0, 0.0),
+            Self::GaussMarkov(gm) => {
+                let decay = (-dt_s / gm.tau.to_seconds()).exp();
+                (decay, gm.process_noise.powi(2) * (1.0 - decay.powi(2)))
+            }
+        }
+    }
+}
+
+/// Quantity of the tracking data of a tracker which is biased.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BiasedQuantity {
+    /// Observations of this measurement type, biased in the unit of the measurement.
+    Observation(MeasurementType),
+    /// Time tags of all of the measurements, which are later than their actual epoch by the bias, in seconds.
+    TimeTag,
+}
+
+impl From<MeasurementType> for BiasedQuantity {
+    fn from(msr_type: MeasurementType) -> Self {
+        Self::Observation(msr_type)
+    }
+}
+
+/// A tracking measurement bias, per tracker and per measurement type or on the time tags, estimated as an augmented state of the Kalman filter.
+///
+/// An observation bias is added to the computed observations of that tracker, i.e. real observation = computed observation + bias.
+/// A time-tag bias shifts the computed observations by their time derivative, i.e. real observation = computed observation - rate * bias.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct EstimatedBias {
+    pub tracker: String,
+    pub quantity: BiasedQuantity,
+    pub model: BiasModel,
+}
+
+impl EstimatedBias {
+    /// Estimates a constant bias on the measurements of the provided type from the provided tracker.
+    pub fn constant(tracker: String, msr_type: MeasurementType, sigma: f64) -> Self {
+        Self {
+            tracker,
+            quantity: msr_type.into(),
+            model: BiasModel::Constant { sigma },
+        }
+    }
+
+    /// Estimates a first order Gauss-Markov bias on the measurements of the provided type from the provided tracker.
+    pub fn gauss_markov(tracker: String, msr_type: MeasurementType, gm: GaussMarkov) -> Self {
+        Self {
+            tracker,
+            quantity: msr_type.into(),
+            model: BiasModel::GaussMarkov(gm),
+        }
+    }
+
+    /// Estimates a bias on the time tags of the measurements from the provided tracker, whose model is in seconds.
+    pub fn time_tag(tracker: String, model: BiasModel) -> Self {
+        Self {
+            tracker,
+            quantity: BiasedQuantity::TimeTag,
+            model,
+        }
+    }
+}
+
+impl fmt::Display for EstimatedBias {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.quantity {
+            BiasedQuantity::Observation(msr_type) => write!(
+                f,
+                "Bias {} {:?} ({})",
+                self.tracker,
+                msr_type,
+                msr_type.unit()
+            ),
+            BiasedQuantity::TimeTag => write!(f, "Time tag bias {} (s)", self.tracker),
+        }
+    }
+}
+
+/// Estimate of the tracking measurement biases at a given epoch, ordered as the estimated biases of the OD process.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BiasEstimate {
+    /// Estimated value of each bias, in the unit of its measurement
+    pub value: DVector<f64>,
+    /// Covariance of the estimated biases
+    pub covar: DMatrix<f64>,
+}
+
+impl BiasEstimate {
+    /// Returns the 1-sigma uncertainty of each bias.
+    pub fn sigmas(&self) -> DVector<f64> {
+        self.covar.diagonal().map(|var| var.sqrt())
+    }
+}
+
+#[cfg(test)]
+mod ut_bias {
+    use super::*;
+    use crate::time::Unit;
+
+    #[test]
+    fn bias_model_transition() {
+        let constant = BiasModel::Constant { sigma: 1e-3 };
+        assert_eq!(constant.a_priori(), (0.0, 1e-3));
+        assert_eq!(constant.transition(3600.0), (1.0, 0.0));
+
+        let mut gm = GaussMarkov::new(Unit::Hour * 1, 1e-3).unwrap();
+        gm.constant = Some(5e-3);
+        let gm = BiasModel::GaussMarkov(gm);
+        assert_eq!(gm.a_priori(), (5e-3, 1e-3));
+        assert_eq!(gm.offset(), 5e-3);
+        // After one time constant, the bias decays by 1/e and the variance of the process noise
+        // keeps the variance of the bias at its steady state.
+        let (decay, noise) = gm.transition(3600.0);
+        assert!((decay - (-1.0_f64).exp()).abs() < 1e-15);
+        assert!((decay.powi(2) * 1e-6 + noise - 1e-6).abs() < 1e-18);
+    }
+}
@@ -0,0 +1,137 @@
+/*
+    Nyx, blazing fast astrodynamics
+    Copyright (C) 2018-onwards Christopher Rabotin <christopher.rabotin@gmail.com>
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published
+    by the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::io::ConfigError;
+use crate::linalg::{Matrix3, Vector3};
+use crate::od::noise::GaussMarkov;
+use hifitime::Duration;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Dynamic model compensation (DMC): first order Gauss-Markov empirical accelerations in the RIC frame of the estimated orbit,
+/// estimated alongside the state to absorb unmodeled dynamics.
+///
+/// The `process_noise` of each Gauss-Markov process is the a priori and steady state standard deviation of that acceleration, in km/s^2.
+/// The nominal trajectory is propagated without these accelerations: their effect on the position and velocity is mapped by the
+/// analytical STM of a first order Gauss-Markov acceleration over each step, cf. Tapley, Schutz, and Born, section 4.9.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DynamicModelCompensation {
+    pub radial: GaussMarkov,
+    pub in_track: GaussMarkov,
+    pub cross_track: GaussMarkov,
+}
+
+impl DynamicModelCompensation {
+    /// Initializes the compensation with the same time constant and standard deviation (in km/s^2) on all three axes.
+    pub fn new(tau: Duration, sigma_km_s2: f64) -> Result<Self, ConfigError> {
+        let gm = GaussMarkov::new(tau, sigma_km_s2)?;
+        Ok(Self {
+            radial: gm,
+            in_track: gm,
+            cross_track: gm,
+        })
+    }
+
+    /// Returns the Gauss-Markov process of the radial, in-track, and cross-track accelerations.
+    pub fn axes(&self) -> [GaussMarkov; 3] {
+        [self.radial, self.in_track, self.cross_track]
+    }
+
+    /// Returns the a priori standard deviation of each acceleration, in km/s^2.
+    pub(crate) fn a_priori_sigmas(&self) -> [f64; 3] {
+        self.axes().map(|gm| gm.process_noise)
+    }
+
+    /// Returns the transition of each acceleration over the provided duration, as the decay of the acceleration, the variance of
+    /// its process noise, and the sensitivity of the position and of the velocity to the acceleration at the start of the step.
+    pub(crate) fn transition(&self, dt_s: f64) -> [DmcTransition; 3] {
+        self.axes().map(|gm| {
+            let tau_s = gm.tau.to_seconds();
+            let decay = (-dt_s / tau_s).exp();
+            DmcTransition {
+                decay,
+                noise: gm.process_noise.powi(2) * (1.0 - decay.powi(2)),
+                pos_sensitivity: tau_s * dt_s - tau_s.powi(2) * (1.0 - decay),
+                vel_sensitivity: tau_s * (1.0 - decay),
+            }
+        })
+    }
+}
+
+impl fmt::Display for DynamicModelCompensation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "DMC R: {}; I: {}; C: {}",
+            self.radial, self.in_track, self.cross_track
+        )
+    }
+}
+
+/// Transition of one empirical acceleration over a step.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct DmcTransition {
+    pub(crate) decay: f64,
+    pub(crate) noise: f64,
+    pub(crate) pos_sensitivity: f64,
+    pub(crate) vel_sensitivity: f64,
+}
+
+/// Estimate of the empirical accelerations of the dynamic model compensation at a given epoch.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DmcEstimate {
+    /// Estimated radial, in-track, and cross-track accelerations, in km/s^2
+    pub accel_km_s2: Vector3<f64>,
+    /// Covariance of the estimated accelerations, in (km/s^2)^2
+    pub covar: Matrix3<f64>,
+}
+
+impl DmcEstimate {
+    /// Returns the 1-sigma uncertainty of each acceleration, in km/s^2.
+    pub fn sigmas_km_s2(&self) -> Vector3<f64> {
+        self.covar.diagonal().map(|var| var.sqrt())
+    }
+}
+
+#[cfg(test)]
+mod ut_dmc {
+    use super::DynamicModelCompensation;
+    use hifitime::Unit;
+
+    #[test]
+    fn dmc_transition() {
+        let dmc = DynamicModelCompensation::new(Unit::Hour * 1, 1e-9).unwrap();
+        assert!(DynamicModelCompensation::new(Unit::Hour * -1, 1e-9).is_err());
+
+        // Over a short step, the acceleration is nearly constant.
+        let dt_s = 1.0;
+        for axis in dmc.transition(dt_s) {
+            assert!((axis.vel_sensitivity - dt_s).abs() < 1e-3);
+            assert!((axis.pos_sensitivity - 0.5 * dt_s.powi(2)).abs() < 1e-3);
+            assert!(axis.noise < 1e-20);
+        }
+
+        // Over many time constants, the acceleration is uncorrelated from its initial value.
+        let dt_s = 36_000.0;
+        for axis in dmc.transition(dt_s) {
+            assert!(axis.decay < 1e-4);
+            assert!((axis.noise - 1e-18).abs() < 1e-24);
+            assert!((axis.vel_sensitivity - 3600.0).abs() < 1.0);
+        }
+    }
+}
@@ -16,22 +16,19 @@
     along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
-use crate::cosmic::AstroPhysicsSnafu;
 pub use crate::errors::NyxError;
-use crate::errors::StateAstroSnafu;
 use crate::linalg::allocator::Allocator;
 use crate::linalg::{DefaultAllocator, DimName, OMatrix, OVector};
-use crate::md::StateParameter;
 pub use crate::od::estimate::{Estimate, KfEstimate, Residual};
 use crate::od::prelude::KalmanVariant;
 use crate::od::process::ResidRejectCrit;
 pub use crate::od::snc::ProcessNoise;
-use crate::od::{ODDynamicsSnafu, ODError, ODStateSnafu, State};
+use crate::od::{ODDynamicsSnafu, ODError, State};
 pub use crate::time::{Epoch, Unit};
 use log::info;
 use snafu::prelude::*;
 
-use super::KalmanFilter;
+use super::{CovarFormulation, KalmanFilter, UDFactors};
 
 impl<T, A> KalmanFilter<T, A>
 where
@@ -56,98 +53,89 @@ where
         self.prev_estimate = *est;
     }
 
-    /// Computes a time update/prediction (i.e. advances the filter estimate with the updated STM).
-    ///
-    /// May return a FilterError if the STM was not updated.
-    pub fn time_update(&mut self, nominal_state: T) -> Result<KfEstimate<T>, ODError> {
-        let stm = nominal_state.stm().context(ODDynamicsSnafu)?;
-        let mut covar_bar = stm * self.prev_estimate.covar * stm.transpose();
-
-        // Try to apply an SNC, if applicable
+    /// Returns the process noise covariance to add to the covariance propagated to the epoch of the provided state, if any process noise applies at that epoch.
+    pub(crate) fn process_noise_covar(
+        &mut self,
+        nominal_state: &T,
+    ) -> Result<Option<OMatrix<f64, <T as State>::Size, <T as State>::Size>>, ODError> {
+        let delta_t = (nominal_state.epoch() - self.prev_estimate.epoch()).to_seconds();
         for (i, snc) in self.process_noise.iter().enumerate().rev() {
-            if let Some(mut snc_matrix) = snc.to_matrix(nominal_state.epoch()) {
-                if let Some(local_frame) = snc.local_frame {
-                    // Rotate the SNC from the definition frame into the state frame.
-                    let dcm = local_frame
-                        .dcm_to_inertial(nominal_state.orbit())
-                        .context(AstroPhysicsSnafu)
-                        .context(StateAstroSnafu {
-                            param: StateParameter::Epoch,
-                        })
-                        .context(ODStateSnafu {
-                            action: "rotating SNC from definition frame into state frame",
-                        })?;
-
-                    // Note: the SNC must be a diagonal matrix, so we only update the diagonals!
-                    match A::DIM {
-                        3 => {
-                            let new_snc = dcm.rot_mat
-                                * snc_matrix.fixed_view::<3, 3>(0, 0)
-                                * dcm.rot_mat.transpose();
-                            for i in 0..A::DIM {
-                                snc_matrix[(i, i)] = new_snc[(i, i)];
-                            }
-                        }
-                        6 => {
-                            let new_snc = dcm.state_dcm()
-                                * snc_matrix.fixed_view::<6, 6>(0, 0)
-                                * dcm.transpose().state_dcm();
-                            for i in 0..A::DIM {
-                                snc_matrix[(i, i)] = new_snc[(i, i)];
-                            }
-                        }
-                        _ => {
-                            return Err(ODError::ODLimitation {
-                                action: "only process noises of size 3x3 or 6x6 are supported",
-                            })
-                        }
-                    }
-                }
+            if let Some(snc_covar) = snc.state_covar(nominal_state.orbit(), delta_t)? {
                 // Check if we're using another SNC than the one before
                 if self.prev_used_snc != i {
                     info!("Switched to {i}-th {snc}");
                     self.prev_used_snc = i;
                 }
+                return Ok(Some(snc_covar));
+            }
+        }
+        Ok(None)
+    }
 
-                // Let's compute the Gamma matrix, an approximation of the time integral
-                // which assumes that the acceleration is constant between these two measurements.
-                let mut gamma = OMatrix::<f64, <T as State>::Size, A>::zeros();
-                let delta_t = (nominal_state.epoch() - self.prev_estimate.epoch()).to_seconds();
-                for blk in 0..A::dim() / 3 {
-                    for i in 0..3 {
-                        let idx_i = i + A::dim() * blk;
-                        let idx_j = i + 3 * blk;
-                        let idx_k = i + 3 + A::dim() * blk;
-                        // For first block
-                        // (0, 0) (1, 1) (2, 2) <=> \Delta t^2/2
-                        // (3, 0) (4, 1) (5, 2) <=> \Delta t
-                        // Second block
-                        // (6, 3) (7, 4) (8, 5) <=> \Delta t^2/2
-                        // (9, 3) (10, 4) (11, 5) <=> \Delta t
-                        // * \Delta t^2/2
-                        // (i, i) when blk = 0
-                        // (i + A::dim() * blk, i + 3) when blk = 1
-                        // (i + A::dim() * blk, i + 3 * blk)
-                        // * \Delta t
-                        // (i + 3, i) when blk = 0
-                        // (i + 3, i + 9) when blk = 1 (and I think i + 12 + 3)
-                        // (i + 3 + A::dim() * blk, i + 3 * blk)
-                        gamma[(idx_i, idx_j)] = delta_t.powi(2) / 2.0;
-                        gamma[(idx_k, idx_j)] = delta_t;
-                    }
+    /// Propagates the covariance of the previous estimate with the provided STM and adds any process noise.
+    ///
+    /// Also returns the UD factors of the propagated covariance if the filter uses the UD factorized formulation.
+    #[allow(clippy::type_complexity)]
+    pub(crate) fn propagate_covar(
+        &mut self,
+        stm: &OMatrix<f64, <T as State>::Size, <T as State>::Size>,
+        nominal_state: &T,
+    ) -> Result<
+        (
+            OMatrix<f64, <T as State>::Size, <T as State>::Size>,
+            Option<UDFactors<<T as State>::Size>>,
+        ),
+        ODError,
+    > {
+        let snc_covar = self.process_noise_covar(nominal_state)?;
+
+        match self.formulation {
+            CovarFormulation::Conventional => {
+                let mut covar_bar = stm * self.prev_estimate.covar * stm.transpose();
+                if let Some(snc_covar) = snc_covar {
+                    covar_bar += snc_covar;
                 }
-                // Let's add the process noise
-                covar_bar += &gamma * snc_matrix * &gamma.transpose();
-                // And break so we don't add any more process noise
-                break;
+                Ok((covar_bar, None))
+            }
+            CovarFormulation::UDFactorized => {
+                // Reuse the factors of the previous estimate unless its covariance was modified since.
+                let prev_factors = match &self.ud_factors {
+                    Some(factors) if factors.covar() == self.prev_estimate.covar => factors.clone(),
+                    _ => UDFactors::from_covar(&self.prev_estimate.covar),
+                };
+                let factors = prev_factors.time_update(stm, snc_covar.as_ref());
+                Ok((factors.covar(), Some(factors)))
             }
         }
+    }
 
-        let state_bar = if matches!(self.variant, KalmanVariant::DeviationTracking) {
+    /// Computes a time update/prediction (i.e. advances the filter estimate with the updated STM).
+    ///
+    /// May return a FilterError if the STM was not updated.
+    pub fn time_update(&mut self, nominal_state: T) -> Result<KfEstimate<T>, ODError> {
+        let stm = nominal_state.stm().context(ODDynamicsSnafu)?;
+
+        // Try to apply an SNC, if applicable
+        let (mut covar_bar, mut ud_factors) = self.propagate_covar(&stm, &nominal_state)?;
+
+        let mut state_bar = if matches!(self.variant, KalmanVariant::DeviationTracking) {
             stm * self.prev_estimate.state_deviation
         } else {
             OVector::<f64, <T as State>::Size>::zeros()
         };
+
+        if let Some(mut augmented) = self.augmented.take() {
+            let dmc_contribution =
+                self.augmented_time_update(&mut augmented, &stm, nominal_state.epoch());
+            self.augmented = Some(augmented);
+            if let Some((dx, dp)) = dmc_contribution? {
+                // The estimated accelerations are not part of the nominal dynamics.
+                state_bar += dx;
+                covar_bar += dp;
+                ud_factors = None;
+            }
+        }
+        self.ud_factors = ud_factors;
         let estimate = KfEstimate {
             nominal_state,
             state_deviation: state_bar,
@@ -195,88 +183,8 @@ where
         // Grab the state transition matrix.
         let stm = nominal_state.stm().context(ODDynamicsSnafu)?;
 
-        // Propagate the covariance.
-        let mut covar_bar = stm * self.prev_estimate.covar * stm.transpose();
-
-        // Apply any process noise as in a normal time update, if applicable
-        for (i, snc) in self.process_noise.iter().enumerate().rev() {
-            if let Some(mut snc_matrix) = snc.to_matrix(epoch) {
-                if let Some(local_frame) = snc.local_frame {
-                    // Rotate the SNC from the definition frame into the state frame.
-                    let dcm = local_frame
-                        .dcm_to_inertial(nominal_state.orbit())
-                        .context(AstroPhysicsSnafu)
-                        .context(StateAstroSnafu {
-                            param: StateParameter::Epoch,
-                        })
-                        .context(ODStateSnafu {
-                            action: "rotating SNC from definition frame into state frame",
-                        })?;
-
-                    // Note: the SNC must be a diagonal matrix, so we only update the diagonals!
-                    match A::DIM {
-                        3 => {
-                            let new_snc = dcm.rot_mat
-                                * snc_matrix.fixed_view::<3, 3>(0, 0)
-                                * dcm.rot_mat.transpose();
-                            for i in 0..A::DIM {
-                                snc_matrix[(i, i)] = new_snc[(i, i)];
-                            }
-                        }
-                        6 => {
-                            let new_snc = dcm.state_dcm()
-                                * snc_matrix.fixed_view::<6, 6>(0, 0)
-                                * dcm.transpose().state_dcm();
-                            for i in 0..A::DIM {
-                                snc_matrix[(i, i)] = new_snc[(i, i)];
-                            }
-                        }
-                        _ => {
-                            return Err(ODError::ODLimitation {
-                                action: "only process noises of size 3x3 or 6x6 are supported",
-                            })
-                        }
-                    }
-                }
-                // Check if we're using another SNC than the one before
-                if self.prev_used_snc != i {
-                    info!("Switched to {i}-th {snc}");
-                    self.prev_used_snc = i;
-                }
-
-                // Let's compute the Gamma matrix, an approximation of the time integral
-                // which assumes that the acceleration is constant between these two measurements.
-                let mut gamma = OMatrix::<f64, <T as State>::Size, A>::zeros();
-                let delta_t = (nominal_state.epoch() - self.prev_estimate.epoch()).to_seconds();
-                for blk in 0..A::dim() / 3 {
-                    for i in 0..3 {
-                        let idx_i = i + A::dim() * blk;
-                        let idx_j = i + 3 * blk;
-                        let idx_k = i + 3 + A::dim() * blk;
-                        // For first block
-                        // (0, 0) (1, 1) (2, 2) <=> \Delta t^2/2
-                        // (3, 0) (4, 1) (5, 2) <=> \Delta t
-                        // Second block
-                        // (6, 3) (7, 4) (8, 5) <=> \Delta t^2/2
-                        // (9, 3) (10, 4) (11, 5) <=> \Delta t
-                        // * \Delta t^2/2
-                        // (i, i) when blk = 0
-                        // (i + A::dim() * blk, i + 3) when blk = 1
-                        // (i + A::dim() * blk, i + 3 * blk)
-                        // * \Delta t
-                        // (i + 3, i) when blk = 0
-                        // (i + 3, i + 9) when blk = 1 (and I think i + 12 + 3)
-                        // (i + 3 + A::dim() * blk, i + 3 * blk)
-                        gamma[(idx_i, idx_j)] = delta_t.powi(2) / 2.0;
-                        gamma[(idx_k, idx_j)] = delta_t;
-                    }
-                }
-                // Let's add the process noise
-                covar_bar += &gamma * snc_matrix * &gamma.transpose();
-                // And break so we don't add any more process noise
-                break;
-            }
-        }
+        // Propagate the covariance and apply any process noise as in a normal time update, if applicable
+        let (covar_bar, ud_factors_bar) = self.propagate_covar(&stm, &nominal_state)?;
 
         // Project the propagated covariance into the measurement space.
         let h_p_ht = &h_tilde * covar_bar * &h_tilde.transpose();
@@ -337,50 +245,69 @@ where
 
         let gain = covar_bar * &h_tilde.transpose() * &s_k_inv;
 
-        // Compute the state estimate, depends on the variant.
-        let (state_hat, res) = match self.variant {
-            KalmanVariant::ReferenceUpdate => {
-                // In EKF, the state hat is actually the state deviation. We trust the gain to be correct,
-                // so we just apply it directly to the prefit residual.
-                let state_hat = &gain * &prefit;
-                let postfit = &prefit - (&h_tilde * state_hat);
-                (
-                    state_hat,
-                    Residual::accepted(
-                        epoch,
-                        prefit,
-                        postfit,
-                        ratio,
-                        r_k_chol.diagonal(),
-                        real_obs,
-                        computed_obs,
-                    ),
-                )
+        // The CKF tracks the state deviation, whereas the EKF and UKF replace the reference state.
+        let state_bar = if matches!(self.variant, KalmanVariant::DeviationTracking) {
+            stm * self.prev_estimate.state_deviation
+        } else {
+            OVector::<f64, <T as State>::Size>::zeros()
+        };
+
+        // Compute the state estimate and the covariance, depends on the formulation.
+        let (state_hat, covar) = match ud_factors_bar {
+            None => {
+                let state_hat = state_bar + &gain * (&prefit - &h_tilde * state_bar);
+
+                // Compute covariance (Joseph update)
+                let first_term = OMatrix::<f64, <T as State>::Size, <T as State>::Size>::identity()
+                    - &gain * &h_tilde;
+                let covar = first_term * covar_bar * first_term.transpose()
+                    + &gain * &r_k * &gain.transpose();
+
+                self.ud_factors = None;
+                (state_hat, covar)
             }
-            KalmanVariant::DeviationTracking => {
-                // Time update
-                let state_bar = stm * self.prev_estimate.state_deviation;
-                let postfit = &prefit - (&h_tilde * state_bar);
-                (
-                    state_bar + &gain * &postfit,
-                    Residual::accepted(
-                        epoch,
-                        prefit,
-                        postfit,
-                        ratio,
-                        r_k_chol.diagonal(),
-                        real_obs,
-                        computed_obs,
-                    ),
-                )
+            Some(mut factors) => {
+                // Decorrelate the measurements to process each of them as a scalar measurement of unit variance.
+                let r_k_chol_inv = r_k
+                    .clone()
+                    .cholesky()
+                    .ok_or(ODError::SingularNoiseRk)?
+                    .l()
+                    .try_inverse()
+                    .ok_or(ODError::SingularNoiseRk)?;
+                let h_decorr = &r_k_chol_inv * &h_tilde;
+                let prefit_decorr = &r_k_chol_inv * &prefit;
+
+                let mut state_hat = state_bar;
+                for (i, h_row) in h_decorr.row_iter().enumerate() {
+                    let h = h_row.transpose();
+                    let innovation = prefit_decorr[i] - h.dot(&state_hat);
+                    let scalar_gain = factors.measurement_update(&h, 1.0);
+                    state_hat += scalar_gain * innovation;
+                }
+
+                let covar = factors.covar();
+                self.ud_factors = Some(factors);
+                (state_hat, covar)
+            }
+        };
+
+        let postfit = match self.variant {
+            KalmanVariant::ReferenceUpdate | KalmanVariant::Unscented { .. } => {
+                &prefit - (&h_tilde * state_hat)
             }
+            KalmanVariant::DeviationTracking => &prefit - (&h_tilde * state_bar),
         };
 
-        // Compute covariance (Joseph update)
-        let first_term =
-            OMatrix::<f64, <T as State>::Size, <T as State>::Size>::identity() - &gain * &h_tilde;
-        let covar =
-            first_term * covar_bar * first_term.transpose() + &gain * &r_k * &gain.transpose();
+        let res = Residual::accepted(
+            epoch,
+            prefit,
+            postfit,
+            ratio,
+            r_k_chol.diagonal(),
+            real_obs,
+            computed_obs,
+        );
 
         // And wrap up
         let estimate = KfEstimate {
@@ -402,7 +329,10 @@ where
     }
 
     pub fn replace_state(&self) -> bool {
-        matches!(self.variant, KalmanVariant::ReferenceUpdate)
+        matches!(
+            self.variant,
+            KalmanVariant::ReferenceUpdate | KalmanVariant::Unscented { .. }
+        )
     }
 
     /// Overwrites all of the process noises to the one provided
@@ -26,7 +26,7 @@ pub use crate::od::snc::ProcessNoise;
 use crate::od::State;
 pub use crate::time::{Epoch, Unit};
 
-use super::{KalmanFilter, KalmanVariant};
+use super::{CovarFormulation, KalmanFilter, KalmanVariant};
 
 impl<T> KalmanFilter<T, U3>
 where
@@ -47,7 +47,10 @@ where
             prev_estimate: initial_estimate,
             process_noise: vec![],
             variant,
+            formulation: CovarFormulation::Conventional,
             prev_used_snc: 0,
+            ud_factors: None,
+            augmented: None,
         }
     }
 }
@@ -77,10 +80,19 @@ where
             prev_estimate: initial_estimate,
             process_noise: vec![process_noise],
             variant,
+            formulation: CovarFormulation::Conventional,
             prev_used_snc: 0,
+            ud_factors: None,
+            augmented: None,
         }
     }
 
+    /// Sets the numerical formulation of the covariance updates.
+    pub fn with_formulation(mut self, formulation: CovarFormulation) -> Self {
+        self.formulation = formulation;
+        self
+    }
+
     /// Set (or replaces) the existing process noise configuration.
     pub fn with_process_noise(mut self, mut process_noise: ProcessNoise<A>) -> Self {
         process_noise.init_epoch = Some(self.prev_estimate.epoch());
@@ -24,8 +24,17 @@ pub use crate::od::snc::ProcessNoise;
 use crate::od::State;
 pub use crate::time::{Epoch, Unit};
 
+pub mod augmented;
+pub mod bias;
+pub mod dmc;
 pub mod filtering;
 pub mod initializers;
+pub mod ud;
+
+pub(crate) use augmented::AugmentedStates;
+pub use bias::{BiasEstimate, BiasModel, BiasedQuantity, EstimatedBias};
+pub use dmc::{DmcEstimate, DynamicModelCompensation};
+pub use ud::UDFactors;
 
 /// Defines both a Classical and an Extended Kalman filter (CKF and EKF)
 /// T: Type of state
@@ -53,7 +62,13 @@ where
     pub process_noise: Vec<ProcessNoise<A>>,
     /// The variant of this Kalman filter.
     pub variant: KalmanVariant,
+    /// The numerical formulation of the covariance updates.
+    pub formulation: CovarFormulation,
     pub prev_used_snc: usize,
+    /// UD factors of the covariance of the previous estimate, only used by the UD factorized formulation.
+    pub(crate) ud_factors: Option<UDFactors<<T as State>::Size>>,
+    /// Tracking measurement biases and dynamic model compensation accelerations augmenting the estimated state, if any.
+    pub(crate) augmented: Option<AugmentedStates>,
 }
 
 #[derive(Copy, Clone, Debug, Default, PartialEq)]
@@ -64,4 +79,34 @@ pub enum KalmanVariant {
     ReferenceUpdate,
     /// Tracks the state deviation (formerly called Classical Kalman Filter (CKF)) and does not update the reference in the process' propagator.
     DeviationTracking,
+    /// Configures the filter as an Unscented Kalman Filter (UKF), which propagates sigma points of the covariance with the process' propagator
+    /// and computes the measurements of each sigma point, instead of linearizing the dynamics and the measurements.
+    /// The sigma points are spread by `alpha` around the mean, `beta` incorporates prior knowledge of the distribution (2 is optimal for Gaussians),
+    /// and `kappa` is the secondary scaling parameter.
+    Unscented { alpha: f64, beta: f64, kappa: f64 },
+}
+
+impl KalmanVariant {
+    /// Unscented Kalman Filter whose sigma points are spread by the square root of the state size times the standard deviations,
+    /// with equal weights of 1/(2n) for all sigma points but the mean. The mean has a weight of zero in the mean of the sigma points,
+    /// but a weight of 2 (i.e. 1 - alpha^2 + beta) in their covariance.
+    pub fn unscented() -> Self {
+        Self::Unscented {
+            alpha: 1.0,
+            beta: 2.0,
+            kappa: 0.0,
+        }
+    }
+}
+
+/// Numerical formulation of the covariance time and measurement updates of the Kalman filter.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum CovarFormulation {
+    /// Propagates the full covariance with the STM and uses the Joseph form of the measurement update.
+    #[default]
+    Conventional,
+    /// Maintains the UD factorization of the covariance (P = U D U^T), with a Thornton time update and a Bierman measurement update
+    /// of each decorrelated measurement. The covariance stays symmetric and positive semi-definite on long arcs of very precise measurements.
+    /// This formulation is ignored by the unscented Kalman filter.
+    UDFactorized,
 }
@@ -0,0 +1,321 @@
+/*
+    Nyx, blazing fast astrodynamics
+    Copyright (C) 2018-onwards Christopher Rabotin <christopher.rabotin@gmail.com>
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published
+    by the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::linalg::allocator::Allocator;
+use crate::linalg::{DMatrix, DefaultAllocator, DimName, OMatrix, OVector};
+
+/// UD factors of a covariance matrix, P = U D U^T where U is unit upper triangular and D is diagonal.
+#[derive(Clone, Debug, PartialEq)]
+pub struct UDFactors<N: DimName>
+where
+    DefaultAllocator: Allocator<N> + Allocator<N, N>,
+{
+    /// Unit upper triangular factor
+    pub u: OMatrix<f64, N, N>,
+    /// Diagonal factor, always non-negative
+    pub d: OVector<f64, N>,
+}
+
+impl<N: DimName> UDFactors<N>
+where
+    DefaultAllocator: Allocator<N> + Allocator<N, N>,
+{
+    /// Factorizes the provided positive semi-definite covariance.
+    ///
+    /// Negative pivots, which may only stem from a covariance that is not positive semi-definite, are set to zero.
+    pub fn from_covar(covar: &OMatrix<f64, N, N>) -> Self {
+        let n = N::DIM;
+        let mut u = OMatrix::<f64, N, N>::identity();
+        let mut d = OVector::<f64, N>::zeros();
+        let tol = f64::EPSILON * covar.diagonal().amax();
+
+        for j in (0..n).rev() {
+            let pivot = covar[(j, j)] - (j + 1..n).map(|k| d[k] * u[(j, k)].powi(2)).sum::<f64>();
+            if pivot <= tol {
+                continue;
+            }
+            d[j] = pivot;
+            for i in 0..j {
+                u[(i, j)] = (covar[(i, j)]
+                    - (j + 1..n)
+                        .map(|k| d[k] * u[(i, k)] * u[(j, k)])
+                        .sum::<f64>())
+                    / pivot;
+            }
+        }
+
+        Self { u, d }
+    }
+
+    /// Rebuilds the covariance from its factors, which is symmetric by construction.
+    pub fn covar(&self) -> OMatrix<f64, N, N> {
+        let n = N::DIM;
+        let mut covar = OMatrix::<f64, N, N>::zeros();
+        for i in 0..n {
+            for j in i..n {
+                let val = (j..n)
+                    .map(|k| self.u[(i, k)] * self.d[k] * self.u[(j, k)])
+                    .sum::<f64>();
+                covar[(i, j)] = val;
+                covar[(j, i)] = val;
+            }
+        }
+        covar
+    }
+
+    /// Thornton time update: returns the factors of STM * P * STM^T + Q using a modified weighted Gram-Schmidt orthogonalization.
+    pub fn time_update(
+        &self,
+        stm: &OMatrix<f64, N, N>,
+        process_noise: Option<&OMatrix<f64, N, N>>,
+    ) -> Self {
+        let n = N::DIM;
+        let noise = process_noise.map(Self::from_covar);
+        let cols = if noise.is_some() { 2 * n } else { n };
+
+        // The rows of W are orthogonalized with respect to the weights of the previous D and of the process noise D.
+        let phi_u = stm * &self.u;
+        let mut w = DMatrix::<f64>::zeros(n, cols);
+        let mut weights = vec![0.0; cols];
+        for i in 0..n {
+            for k in 0..n {
+                w[(i, k)] = phi_u[(i, k)];
+            }
+            weights[i] = self.d[i];
+        }
+        if let Some(noise) = &noise {
+            for i in 0..n {
+                for k in 0..n {
+                    w[(i, n + k)] = noise.u[(i, k)];
+                }
+                weights[n + i] = noise.d[i];
+            }
+        }
+
+        let mut u = OMatrix::<f64, N, N>::identity();
+        let mut d = OVector::<f64, N>::zeros();
+        for j in (0..n).rev() {
+            let d_j = (0..cols)
+                .map(|k| weights[k] * w[(j, k)].powi(2))
+                .sum::<f64>();
+            if d_j <= 0.0 {
+                continue;
+            }
+            d[j] = d_j;
+            for i in 0..j {
+                let u_ij = (0..cols)
+                    .map(|k| weights[k] * w[(i, k)] * w[(j, k)])
+                    .sum::<f64>()
+                    / d_j;
+                u[(i, j)] = u_ij;
+                for k in 0..cols {
+                    w[(i, k)] -= u_ij * w[(j, k)];
+                }
+            }
+        }
+
+        Self { u, d }
+    }
+
+    /// Bierman measurement update of a scalar measurement of sensitivity `h` and variance `r`, updating the factors in place.
+    ///
+    /// Returns the Kalman gain of this scalar measurement.
+    pub fn measurement_update(&mut self, h: &OVector<f64, N>, r: f64) -> OVector<f64, N> {
+        let n = N::DIM;
+        let f = self.u.transpose() * h;
+        let v = self.d.component_mul(&f);
+        let mut gain = OVector::<f64, N>::zeros();
+
+        let mut alpha = r + v[0] * f[0];
+        self.d[0] *= r / alpha;
+        gain[0] = v[0];
+        for j in 1..n {
+            let beta = alpha;
+            alpha += v[j] * f[j];
+            let lambda = -f[j] / beta;
+            self.d[j] *= beta / alpha;
+            for i in 0..j {
+                let u_ij = self.u[(i, j)];
+                self.u[(i, j)] = u_ij + lambda * gain[i];
+                gain[i] += u_ij * v[j];
+            }
+            gain[j] = v[j];
+        }
+
+        gain / alpha
+    }
+}
+
+#[cfg(test)]
+mod ut_ud {
+    use super::UDFactors;
+    use crate::linalg::{Matrix2, Matrix6, SMatrix, SVector, Vector2, Vector6, U3};
+    use crate::od::kalman::{CovarFormulation, KalmanFilter, KalmanVariant};
+    use crate::od::prelude::KfEstimate;
+    use crate::time::{Epoch, Unit};
+    use crate::{Orbit, Spacecraft, State};
+    use anise::constants::frames::EARTH_J2000;
+
+    fn spacecraft() -> Spacecraft {
+        let orbit = Orbit::new(
+            -2436.45,
+            -2436.45,
+            6891.037,
+            5.088_611,
+            -5.088_611,
+            0.0,
+            Epoch::from_gregorian_tai_at_midnight(2020, 1, 1),
+            EARTH_J2000.with_mu_km3_s2(398_600.441_5),
+        );
+        Spacecraft::builder().orbit(orbit).build()
+    }
+
+    /// Runs a sequence of range and range-rate like measurement updates and returns the final estimate of the filter.
+    fn filter(
+        formulation: CovarFormulation,
+        init_covar: SMatrix<f64, 9, 9>,
+        msr_noise: f64,
+        num_msrs: usize,
+    ) -> KalmanFilter<Spacecraft, U3> {
+        let sc = spacecraft();
+        let mut kf = KalmanFilter::new(
+            KfEstimate::from_covar(sc, init_covar),
+            KalmanVariant::DeviationTracking,
+        )
+        .with_formulation(formulation);
+
+        for i in 0..num_msrs {
+            let epoch = sc.epoch() + Unit::Minute * (i as i64 + 1);
+            let mut nominal_state = sc.with_stm();
+            nominal_state.orbit.epoch = epoch;
+
+            // Nearly collinear sensitivities, as for a ground station tracking a slowly moving spacecraft.
+            let angle = 1e-3 * (i % 7) as f64;
+            let mut h_tilde = SMatrix::<f64, 2, 9>::zeros();
+            h_tilde[(0, 0)] = angle.cos();
+            h_tilde[(0, 1)] = angle.sin();
+            h_tilde[(0, 2)] = 1e-2;
+            h_tilde[(1, 3)] = angle.cos();
+            h_tilde[(1, 4)] = angle.sin();
+            h_tilde[(1, 0)] = 1e-4;
+
+            let real_obs = Vector2::new(1e-3 * (i % 3) as f64, -1e-6 * (i % 5) as f64);
+            kf.measurement_update(
+                nominal_state,
+                real_obs,
+                Vector2::zeros(),
+                Matrix2::from_diagonal_element(msr_noise.powi(2)),
+                h_tilde,
+                None,
+            )
+            .unwrap();
+        }
+        kf
+    }
+
+    fn init_covar(pos_km2: f64, vel_km2_s2: f64) -> SMatrix<f64, 9, 9> {
+        SMatrix::<f64, 9, 9>::from_diagonal(&SVector::<f64, 9>::from_iterator([
+            pos_km2, pos_km2, pos_km2, vel_km2_s2, vel_km2_s2, vel_km2_s2, 0.0, 0.0, 0.0,
+        ]))
+    }
+
+    fn covar() -> Matrix6<f64> {
+        let a = Matrix6::from_fn(|i, j| {
+            ((i + 2 * j) % 5) as f64 * 0.1 + if i == j { 1.0 } else { 0.0 }
+        });
+        a * a.transpose()
+    }
+
+    #[test]
+    fn ud_round_trip() {
+        let covar = covar();
+        let factors = UDFactors::from_covar(&covar);
+        assert!((factors.covar() - covar).norm() < 1e-12);
+        for i in 0..6 {
+            assert_eq!(factors.u[(i, i)], 1.0);
+            for j in 0..i {
+                assert_eq!(factors.u[(i, j)], 0.0);
+            }
+        }
+    }
+
+    #[test]
+    fn ud_time_update() {
+        let covar = covar();
+        let stm = Matrix6::identity() + Matrix6::from_fn(|i, j| (i * j) as f64 * 1e-2);
+        let noise = Matrix6::from_diagonal(&Vector6::new(0.0, 0.0, 0.0, 1e-4, 1e-4, 1e-4));
+
+        let expected = stm * covar * stm.transpose() + noise;
+        let factors = UDFactors::from_covar(&covar).time_update(&stm, Some(&noise));
+        assert!((factors.covar() - expected).norm() < 1e-12);
+
+        let expected = stm * covar * stm.transpose();
+        let factors = UDFactors::from_covar(&covar).time_update(&stm, None);
+        assert!((factors.covar() - expected).norm() < 1e-12);
+    }
+
+    #[test]
+    fn bierman_update() {
+        let covar = covar();
+        let h = Vector6::new(1.0, 0.5, 0.0, 0.0, 0.2, 0.0);
+        let r = 0.25;
+
+        let s = (h.transpose() * covar * h)[(0, 0)] + r;
+        let expected_gain = covar * h / s;
+        let expected_covar = covar - expected_gain * h.transpose() * covar;
+
+        let mut factors = UDFactors::from_covar(&covar);
+        let gain = factors.measurement_update(&h, r);
+        assert!((gain - expected_gain).norm() < 1e-12);
+        assert!((factors.covar() - expected_covar).norm() < 1e-12);
+    }
+
+    #[test]
+    fn ud_matches_conventional_filter() {
+        let covar = init_covar(1.0, 1e-6);
+        let conventional = filter(CovarFormulation::Conventional, covar, 1e-3, 20);
+        let ud = filter(CovarFormulation::UDFactorized, covar, 1e-3, 20);
+
+        let conv_est = conventional.prev_estimate;
+        let ud_est = ud.prev_estimate;
+        assert!(
+            (conv_est.state_deviation - ud_est.state_deviation).norm()
+                < 1e-9 * conv_est.state_deviation.norm().max(1.0)
+        );
+        assert!((conv_est.covar - ud_est.covar).norm() < 1e-9 * conv_est.covar.norm());
+        assert!(ud.ud_factors.is_some());
+        assert!(conventional.ud_factors.is_none());
+    }
+
+    #[test]
+    fn ud_positive_definite_ill_conditioned() {
+        // Very large a priori and very precise measurements
+        let covar = init_covar(1e8, 1e2);
+        let kf = filter(CovarFormulation::UDFactorized, covar, 1e-9, 500);
+
+        let factors = kf.ud_factors.unwrap();
+        for i in 0..6 {
+            assert!(factors.d[i] > 0.0, "D[{i}] = {}", factors.d[i]);
+        }
+        let est_covar = kf.prev_estimate.covar;
+        assert_eq!(est_covar, est_covar.transpose());
+        for i in 0..6 {
+            assert!(est_covar[(i, i)] > 0.0);
+        }
+    }
+}
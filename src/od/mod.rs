@@ -61,6 +61,9 @@ pub mod snc;
 /// Provides the Batch least squares initial state solver.
 pub mod blse;
 
+/// Provides the consider parameters of the Kalman filter and of the batch least squares.
+pub mod consider;
+
 #[cfg(feature = "premium")]
 pub mod interlink;
 
@@ -91,6 +94,7 @@ pub type InterlinkKalmanOD = self::process::KalmanODProcess<
 
 #[allow(unused_imports)]
 pub mod prelude {
+    pub use super::consider::{ConsiderKind, ConsiderParameter};
     pub use super::estimate::*;
     pub use super::ground_station::*;
     pub use super::kalman::KalmanVariant;
@@ -115,6 +119,8 @@ pub enum ODError {
     ODDynamicsError { source: DynamicsError },
     #[snafu(display("at least {need} measurements required for {action}"))]
     TooFewMeasurements { need: usize, action: &'static str },
+    #[snafu(display("no tracking arc provided for {action}"))]
+    NoTrackingArcs { action: &'static str },
     #[snafu(display("invalid step size: {step}"))]
     StepSizeError { step: Duration },
     #[snafu(display("filter iteration did not converge in {loops} iterations"))]
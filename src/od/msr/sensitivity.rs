@@ -19,6 +19,7 @@
 use crate::linalg::allocator::Allocator;
 use crate::linalg::DefaultAllocator;
 use crate::md::prelude::Interpolatable;
+use crate::md::StateParameter;
 use crate::od::{GroundStation, ODAlmanacSnafu, ODError, TrackingDevice};
 use crate::{Spacecraft, State};
 use anise::prelude::Almanac;
@@ -66,6 +67,12 @@ where
     ) -> Result<OMatrix<f64, M, SolveState::Size>, ODError>
     where
         DefaultAllocator: Allocator<M> + Allocator<M, SolveState::Size>;
+
+    /// Returns a copy of this tracker with the provided parameter offset, used to compute the sensitivity of the measurements
+    /// to consider parameters. Returns None if this parameter is not supported by this tracker.
+    fn with_offset(&self, _param: StateParameter, _offset: f64) -> Option<Self> {
+        None
+    }
 }
 
 struct ScalarSensitivity<SolveState: State, Rx, Tx>
@@ -114,6 +121,17 @@ where
         }
         Ok(mat)
     }
+
+    fn with_offset(&self, param: StateParameter, offset: f64) -> Option<Self> {
+        let mut me = self.clone();
+        match param {
+            StateParameter::Latitude => me.latitude_deg += offset,
+            StateParameter::Longitude => me.longitude_deg += offset,
+            StateParameter::Height => me.height_km += offset,
+            _ => return None,
+        }
+        Some(me)
+    }
 }
 
 impl ScalarSensitivityT<Spacecraft, Spacecraft, GroundStation>
@@ -112,6 +112,10 @@ impl TrackingDataArc {
             })?;
             let line = line.trim();
 
+            if line.starts_with("COMMENT") {
+                continue;
+            }
+
             if line == "DATA_START" {
                 in_data_section = true;
                 continue;
@@ -367,6 +371,7 @@ impl TrackingDataArc {
         // Grab the path here before we move stuff.
         let path_buf = cfg.actual_path(path);
 
+        let provenance = cfg.provenance_or(None, None);
         let metadata = cfg.metadata.unwrap_or_default();
 
         let file = File::create(&path_buf).context(StdIOSnafu {
@@ -395,6 +400,9 @@ impl TrackingDataArc {
             "COMMENT Nyx Space provided under the AGPL v3 open source license -- https://nyxspace.com/pricing\n"
         )
         .map_err(err_hdlr)?;
+        for line in provenance.comment_lines()? {
+            writeln!(writer, "COMMENT {line}").map_err(err_hdlr)?;
+        }
         writeln!(
             writer,
             "CREATION_DATE = {}",
@@ -316,11 +316,7 @@ impl TrackingDataArc {
         // Serialize all of the devices and add that to the parquet file too.
         let mut metadata = HashMap::new();
         metadata.insert("Purpose".to_string(), "Tracking Arc Data".to_string());
-        if let Some(add_meta) = cfg.metadata {
-            for (k, v) in add_meta {
-                metadata.insert(k, v);
-            }
-        }
+        metadata.extend(cfg.all_metadata(None, None)?);
 
         if let Some(modulos) = &self.moduli {
             for (msr_type, v) in modulos {
@@ -69,11 +69,15 @@ where
         Self {
             prop,
             kf_variant,
+            covar_formulation: Default::default(),
             devices,
             resid_crit,
             process_noise: vec![],
             max_step: Unit::Minute * 1,
             epoch_precision: Unit::Microsecond * 1,
+            consider: Vec::new(),
+            biases: Vec::new(),
+            dmc: None,
             almanac,
             _msr_size: PhantomData::<MsrSize>,
         }
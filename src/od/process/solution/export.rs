@@ -16,11 +16,16 @@
     along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
+use crate::dynamics::guidance::LocalFrame;
+use crate::errors::NyxError;
+use crate::io::ccsds::OrbitComprehensiveMessage;
 use crate::io::watermark::pq_writer;
 use crate::io::{ArrowSnafu, ExportCfg, ParquetSnafu, StdIOSnafu};
 use crate::linalg::allocator::Allocator;
 use crate::linalg::{DefaultAllocator, DimName};
-use crate::md::trajectory::Interpolatable;
+use crate::md::trajectory::{
+    write_oem, Interpolatable, OemCovariance, OemFormat, OemSegment, Traj,
+};
 use crate::md::StateParameter;
 use crate::od::estimate::*;
 use crate::State;
@@ -153,7 +158,7 @@ where
             ),
         ]);
 
-        let mut fields = match cfg.fields {
+        let mut fields = match cfg.fields.clone() {
             Some(fields) => fields,
             None => Spacecraft::export_params(),
         };
@@ -341,36 +346,78 @@ where
             ));
         }
 
+        // Add the estimated bias columns
+        for bias in &self.estimated_biases {
+            hdrs.push(Field::new(format!("{bias}"), DataType::Float64, true));
+        }
+        for bias in &self.estimated_biases {
+            hdrs.push(Field::new(format!("Sigma {bias}"), DataType::Float64, true));
+        }
+
+        // Add the DMC acceleration columns
+        const DMC_AXES: [&str; 3] = ["R", "I", "C"];
+        if self.dmc.is_some() {
+            for axis in DMC_AXES {
+                hdrs.push(Field::new(
+                    format!("DMC acceleration {axis} (RIC) (km/s^2)"),
+                    DataType::Float64,
+                    true,
+                ));
+            }
+            for axis in DMC_AXES {
+                hdrs.push(Field::new(
+                    format!("Sigma DMC acceleration {axis} (RIC) (km/s^2)"),
+                    DataType::Float64,
+                    true,
+                ));
+            }
+        }
+
         // Build the schema
         let schema = Arc::new(Schema::new(hdrs));
         let mut record: Vec<Arc<dyn Array>> = Vec::new();
 
         // Build the states iterator -- this does require copying the current states but I can't either get a reference or a copy of all the states.
-        let (estimates, residuals) =
-            if cfg.start_epoch.is_some() || cfg.end_epoch.is_some() || cfg.step.is_some() {
-                // Must interpolate the data!
-                let start = cfg
-                    .start_epoch
-                    .unwrap_or_else(|| self.estimates.first().unwrap().state().epoch());
-                let end = cfg
-                    .end_epoch
-                    .unwrap_or_else(|| self.estimates.last().unwrap().state().epoch());
-
-                let mut residuals: Vec<Option<Residual<MsrSize>>> =
-                    Vec::with_capacity(self.residuals.len());
-                let mut estimates = Vec::with_capacity(self.estimates.len());
-
-                for (estimate, residual) in self.estimates.iter().zip(self.residuals.iter()) {
-                    if estimate.epoch() >= start && estimate.epoch() <= end {
-                        estimates.push(*estimate);
-                        residuals.push(residual.clone());
-                    }
+        let (estimates, residuals, bias_estimates, dmc_estimates) = if cfg.start_epoch.is_some()
+            || cfg.end_epoch.is_some()
+            || cfg.step.is_some()
+        {
+            // Must interpolate the data!
+            let start = cfg
+                .start_epoch
+                .unwrap_or_else(|| self.estimates.first().unwrap().state().epoch());
+            let end = cfg
+                .end_epoch
+                .unwrap_or_else(|| self.estimates.last().unwrap().state().epoch());
+
+            let mut residuals: Vec<Option<Residual<MsrSize>>> =
+                Vec::with_capacity(self.residuals.len());
+            let mut estimates = Vec::with_capacity(self.estimates.len());
+            let mut bias_estimates = Vec::with_capacity(self.bias_estimates.len());
+            let mut dmc_estimates = Vec::with_capacity(self.dmc_estimates.len());
+
+            for (estimate, (residual, (bias_estimate, dmc_estimate))) in self.estimates.iter().zip(
+                self.residuals
+                    .iter()
+                    .zip(self.bias_estimates.iter().zip(self.dmc_estimates.iter())),
+            ) {
+                if estimate.epoch() >= start && estimate.epoch() <= end {
+                    estimates.push(*estimate);
+                    residuals.push(residual.clone());
+                    bias_estimates.push(bias_estimate.clone());
+                    dmc_estimates.push(*dmc_estimate);
                 }
+            }
 
-                (estimates, residuals)
-            } else {
-                (self.estimates.to_vec(), self.residuals.to_vec())
-            };
+            (estimates, residuals, bias_estimates, dmc_estimates)
+        } else {
+            (
+                self.estimates.to_vec(),
+                self.residuals.to_vec(),
+                self.bias_estimates.to_vec(),
+                self.dmc_estimates.to_vec(),
+            )
+        };
 
         // Build all of the records
 
@@ -594,6 +641,56 @@ where
             record.push(Arc::new(data.finish()));
         }
 
+        // Add the estimated biases and their uncertainties
+        for j in 0..self.estimated_biases.len() {
+            let mut data = Float64Builder::new();
+            for opt_bias in &bias_estimates {
+                if let Some(bias_est) = opt_bias {
+                    data.append_value(bias_est.value[j]);
+                } else {
+                    data.append_null();
+                }
+            }
+            record.push(Arc::new(data.finish()));
+        }
+        for j in 0..self.estimated_biases.len() {
+            let mut data = Float64Builder::new();
+            for opt_bias in &bias_estimates {
+                if let Some(bias_est) = opt_bias {
+                    data.append_value(bias_est.covar[(j, j)].sqrt());
+                } else {
+                    data.append_null();
+                }
+            }
+            record.push(Arc::new(data.finish()));
+        }
+
+        // Add the DMC accelerations and their uncertainties
+        if self.dmc.is_some() {
+            for i in 0..3 {
+                let mut data = Float64Builder::new();
+                for opt_dmc in &dmc_estimates {
+                    if let Some(dmc_est) = opt_dmc {
+                        data.append_value(dmc_est.accel_km_s2[i]);
+                    } else {
+                        data.append_null();
+                    }
+                }
+                record.push(Arc::new(data.finish()));
+            }
+            for i in 0..3 {
+                let mut data = Float64Builder::new();
+                for opt_dmc in &dmc_estimates {
+                    if let Some(dmc_est) = opt_dmc {
+                        data.append_value(dmc_est.sigmas_km_s2()[i]);
+                    } else {
+                        data.append_null();
+                    }
+                }
+                record.push(Arc::new(data.finish()));
+            }
+        }
+
         info!("Serialized {} estimates and residuals", estimates.len());
 
         // Serialize all of the devices and add that to the parquet file too.
@@ -602,11 +699,10 @@ where
             "Purpose".to_string(),
             "Orbit determination results".to_string(),
         );
-        if let Some(add_meta) = cfg.metadata {
-            for (k, v) in add_meta {
-                metadata.insert(k, v);
-            }
-        }
+        metadata.extend(
+            cfg.all_metadata(self.provenance.clone(), None)
+                .context(ODIOSnafu)?,
+        );
 
         let props = pq_writer(Some(metadata));
 
@@ -650,4 +746,86 @@ where
         );
         Ok(path_buf)
     }
+
+    /// Exports the estimated states and their covariance to a CCSDS OEM file, cf. [`Traj::from_oem_file_with_covar`](crate::md::prelude::Traj::from_oem_file_with_covar) to read it back.
+    ///
+    /// The covariance of the orbit is exported either in the inertial frame of the estimates or in their RIC frame (`RTN` per CCSDS).
+    /// If several estimates share the same epoch (e.g. a time update followed by a measurement update), only the last one is exported.
+    pub fn to_oem_file<P: AsRef<Path>>(
+        &self,
+        path: P,
+        covar_frame: LocalFrame,
+        cfg: ExportCfg,
+    ) -> Result<PathBuf, NyxError> {
+        if self.estimates.is_empty() {
+            return Err(NyxError::NoStateData {
+                msg: "No estimates to export to OEM: run the OD process first".to_string(),
+            });
+        }
+
+        if cfg.step.is_some() || cfg.start_epoch.is_some() || cfg.end_epoch.is_some() {
+            warn!("The `step`, `start_epoch` and `end_epoch` parameters in the export are not supported for orbit determination exports.");
+        }
+
+        let estimates = self.unique_estimates();
+
+        let states: Vec<Spacecraft> = estimates.iter().map(|est| est.state()).collect();
+        let covariances = estimates
+            .iter()
+            .map(|est| {
+                OemCovariance::from_inertial(
+                    &est.state().orbit,
+                    est.covar().fixed_view::<6, 6>(0, 0).into_owned(),
+                    covar_frame,
+                )
+            })
+            .collect::<Result<Vec<_>, NyxError>>()?;
+
+        let segment = OemSegment {
+            traj: Traj {
+                name: None,
+                states,
+                boundaries: Vec::new(),
+            },
+            covariances,
+        };
+
+        write_oem(
+            path,
+            cfg,
+            OemFormat::Kvn,
+            &[segment],
+            self.provenance.clone(),
+        )
+    }
+
+    /// Builds a CCSDS OCM of this solution: the estimated trajectory and its covariance history, cf. [`OrbitComprehensiveMessage::to_file`] to export it.
+    ///
+    /// The covariance of the orbit is written either in the inertial frame of the estimates or in their RIC frame (`RTN` per CCSDS).
+    /// If several estimates share the same epoch, only the last one is kept in the covariance history.
+    pub fn to_ocm(
+        &self,
+        object_name: String,
+        covar_frame: LocalFrame,
+    ) -> Result<OrbitComprehensiveMessage, NyxError> {
+        let mut ocm = OrbitComprehensiveMessage::new(object_name, self.to_traj()?);
+        ocm.estimates = self.unique_estimates().into_iter().copied().collect();
+        ocm.covar_frame = covar_frame;
+        ocm.provenance = self.provenance.clone();
+        Ok(ocm)
+    }
+
+    /// Estimates of this solution, keeping only the last one of those which share the same epoch.
+    fn unique_estimates(&self) -> Vec<&KfEstimate<Spacecraft>> {
+        let mut estimates: Vec<&KfEstimate<Spacecraft>> = Vec::with_capacity(self.estimates.len());
+        for estimate in &self.estimates {
+            match estimates.last() {
+                Some(prev) if prev.epoch() == estimate.epoch() => {
+                    *estimates.last_mut().unwrap() = estimate;
+                }
+                _ => estimates.push(estimate),
+            }
+        }
+        estimates
+    }
 }
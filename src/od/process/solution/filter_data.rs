@@ -17,7 +17,7 @@
 */
 
 use crate::linalg::allocator::Allocator;
-use crate::linalg::{DefaultAllocator, DimName, OMatrix};
+use crate::linalg::{DMatrix, DefaultAllocator, DimName, OMatrix};
 use crate::md::trajectory::Interpolatable;
 pub use crate::od::estimate::*;
 pub use crate::od::*;
@@ -25,6 +25,7 @@ use indexmap::IndexSet;
 use msr::sensitivity::TrackerSensitivity;
 use std::ops::Add;
 
+use self::kalman::{BiasEstimate, DmcEstimate};
 use self::msr::MeasurementType;
 
 use super::ODSolution;
@@ -53,6 +54,12 @@ where
     pub gain: Option<OMatrix<f64, <StateType as State>::Size, MsrSize>>,
     /// Filter-smoother consistency ratios, all None before running the smoother.
     pub filter_smoother_ratio: Option<OVector<f64, <StateType as State>::Size>>,
+    /// Sensitivity of the estimation errors to the consider parameters, if any.
+    pub consider_sensitivity: Option<DMatrix<f64>>,
+    /// Estimate of the tracking measurement biases, if any.
+    pub bias_estimate: Option<BiasEstimate>,
+    /// Estimate of the DMC accelerations, if any.
+    pub dmc_estimate: Option<DmcEstimate>,
 }
 
 impl<StateType, EstType, MsrSize, Trk> ODSolution<StateType, EstType, MsrSize, Trk>
@@ -90,12 +97,23 @@ where
         let mut residuals = Vec::new();
         let mut gains = Vec::new();
         let mut filter_smoother_ratios = Vec::new();
-
-        for (est, (resid_opt, (gain_opt, fsr_opt))) in self.estimates.iter().zip(
-            self.residuals
-                .iter()
-                .zip(self.gains.iter().zip(self.filter_smoother_ratios.iter())),
-        ) {
+        let mut consider_sensitivities = Vec::new();
+        let mut bias_estimates = Vec::new();
+        let mut dmc_estimates = Vec::new();
+
+        for (est, (resid_opt, (gain_opt, (fsr_opt, (consider_opt, (bias_opt, dmc_opt)))))) in
+            self.estimates.iter().zip(
+                self.residuals.iter().zip(
+                    self.gains.iter().zip(
+                        self.filter_smoother_ratios.iter().zip(
+                            self.consider_sensitivities
+                                .iter()
+                                .zip(self.bias_estimates.iter().zip(self.dmc_estimates.iter())),
+                        ),
+                    ),
+                ),
+            )
+        {
             if resid_opt.is_none() {
                 continue;
             }
@@ -104,12 +122,18 @@ where
             residuals.push(resid_opt.clone());
             gains.push(gain_opt.clone());
             filter_smoother_ratios.push(fsr_opt.clone());
+            consider_sensitivities.push(consider_opt.clone());
+            bias_estimates.push(bias_opt.clone());
+            dmc_estimates.push(*dmc_opt);
         }
 
         self.estimates = estimates;
         self.residuals = residuals;
         self.gains = gains;
         self.filter_smoother_ratios = filter_smoother_ratios;
+        self.consider_sensitivities = consider_sensitivities;
+        self.bias_estimates = bias_estimates;
+        self.dmc_estimates = dmc_estimates;
 
         self
     }
@@ -120,12 +144,23 @@ where
         let mut residuals = Vec::new();
         let mut gains = Vec::new();
         let mut filter_smoother_ratios = Vec::new();
-
-        for (est, (resid_opt, (gain_opt, fsr_opt))) in self.estimates.iter().zip(
-            self.residuals
-                .iter()
-                .zip(self.gains.iter().zip(self.filter_smoother_ratios.iter())),
-        ) {
+        let mut consider_sensitivities = Vec::new();
+        let mut bias_estimates = Vec::new();
+        let mut dmc_estimates = Vec::new();
+
+        for (est, (resid_opt, (gain_opt, (fsr_opt, (consider_opt, (bias_opt, dmc_opt)))))) in
+            self.estimates.iter().zip(
+                self.residuals.iter().zip(
+                    self.gains.iter().zip(
+                        self.filter_smoother_ratios.iter().zip(
+                            self.consider_sensitivities
+                                .iter()
+                                .zip(self.bias_estimates.iter().zip(self.dmc_estimates.iter())),
+                        ),
+                    ),
+                ),
+            )
+        {
             match resid_opt {
                 None => continue, // Drop all time updates
                 Some(resid) => {
@@ -134,6 +169,9 @@ where
                         residuals.push(Some(resid.clone()));
                         gains.push(gain_opt.clone());
                         filter_smoother_ratios.push(fsr_opt.clone());
+                        consider_sensitivities.push(consider_opt.clone());
+                        bias_estimates.push(bias_opt.clone());
+                        dmc_estimates.push(*dmc_opt);
                     }
                 }
             }
@@ -143,6 +181,9 @@ where
         self.residuals = residuals;
         self.gains = gains;
         self.filter_smoother_ratios = filter_smoother_ratios;
+        self.consider_sensitivities = consider_sensitivities;
+        self.bias_estimates = bias_estimates;
+        self.dmc_estimates = dmc_estimates;
 
         self
     }
@@ -153,12 +194,23 @@ where
         let mut residuals = Vec::new();
         let mut gains = Vec::new();
         let mut filter_smoother_ratios = Vec::new();
-
-        for (est, (resid_opt, (gain_opt, fsr_opt))) in self.estimates.iter().zip(
-            self.residuals
-                .iter()
-                .zip(self.gains.iter().zip(self.filter_smoother_ratios.iter())),
-        ) {
+        let mut consider_sensitivities = Vec::new();
+        let mut bias_estimates = Vec::new();
+        let mut dmc_estimates = Vec::new();
+
+        for (est, (resid_opt, (gain_opt, (fsr_opt, (consider_opt, (bias_opt, dmc_opt)))))) in
+            self.estimates.iter().zip(
+                self.residuals.iter().zip(
+                    self.gains.iter().zip(
+                        self.filter_smoother_ratios.iter().zip(
+                            self.consider_sensitivities
+                                .iter()
+                                .zip(self.bias_estimates.iter().zip(self.dmc_estimates.iter())),
+                        ),
+                    ),
+                ),
+            )
+        {
             match resid_opt {
                 None => continue, // Drop all time updates
                 Some(resid) => {
@@ -167,6 +219,9 @@ where
                         residuals.push(Some(resid.clone()));
                         gains.push(gain_opt.clone());
                         filter_smoother_ratios.push(fsr_opt.clone());
+                        consider_sensitivities.push(consider_opt.clone());
+                        bias_estimates.push(bias_opt.clone());
+                        dmc_estimates.push(*dmc_opt);
                     }
                 }
             }
@@ -176,6 +231,9 @@ where
         self.residuals = residuals;
         self.gains = gains;
         self.filter_smoother_ratios = filter_smoother_ratios;
+        self.consider_sensitivities = consider_sensitivities;
+        self.bias_estimates = bias_estimates;
+        self.dmc_estimates = dmc_estimates;
 
         self
     }
@@ -186,12 +244,23 @@ where
         let mut residuals = Vec::new();
         let mut gains = Vec::new();
         let mut filter_smoother_ratios = Vec::new();
-
-        for (est, (resid_opt, (gain_opt, fsr_opt))) in self.estimates.iter().zip(
-            self.residuals
-                .iter()
-                .zip(self.gains.iter().zip(self.filter_smoother_ratios.iter())),
-        ) {
+        let mut consider_sensitivities = Vec::new();
+        let mut bias_estimates = Vec::new();
+        let mut dmc_estimates = Vec::new();
+
+        for (est, (resid_opt, (gain_opt, (fsr_opt, (consider_opt, (bias_opt, dmc_opt)))))) in
+            self.estimates.iter().zip(
+                self.residuals.iter().zip(
+                    self.gains.iter().zip(
+                        self.filter_smoother_ratios.iter().zip(
+                            self.consider_sensitivities
+                                .iter()
+                                .zip(self.bias_estimates.iter().zip(self.dmc_estimates.iter())),
+                        ),
+                    ),
+                ),
+            )
+        {
             if let Some(resid) = resid_opt {
                 if resid.tracker.is_none() || resid.tracker.as_ref().unwrap() == &excluded_tracker {
                     continue;
@@ -202,12 +271,18 @@ where
             residuals.push(resid_opt.clone());
             gains.push(gain_opt.clone());
             filter_smoother_ratios.push(fsr_opt.clone());
+            consider_sensitivities.push(consider_opt.clone());
+            bias_estimates.push(bias_opt.clone());
+            dmc_estimates.push(*dmc_opt);
         }
 
         self.estimates = estimates;
         self.residuals = residuals;
         self.gains = gains;
         self.filter_smoother_ratios = filter_smoother_ratios;
+        self.consider_sensitivities = consider_sensitivities;
+        self.bias_estimates = bias_estimates;
+        self.dmc_estimates = dmc_estimates;
 
         self
     }
@@ -233,6 +308,10 @@ where
         self.gains.append(&mut other.gains);
         self.filter_smoother_ratios
             .append(&mut other.filter_smoother_ratios);
+        self.consider_sensitivities
+            .append(&mut other.consider_sensitivities);
+        self.bias_estimates.append(&mut other.bias_estimates);
+        self.dmc_estimates.append(&mut other.dmc_estimates);
 
         // Sort to ensure chronological order using indices based permutations.
         // Generate indices representing original positions
@@ -256,6 +335,9 @@ where
                 self.residuals.swap(current, target);
                 self.gains.swap(current, target);
                 self.filter_smoother_ratios.swap(current, target);
+                self.consider_sensitivities.swap(current, target);
+                self.bias_estimates.swap(current, target);
+                self.dmc_estimates.swap(current, target);
             }
         }
 
@@ -272,6 +354,9 @@ where
                 residual: self.residuals[index].clone(),
                 gain: self.gains[index].clone(),
                 filter_smoother_ratio: self.filter_smoother_ratios[index].clone(),
+                consider_sensitivity: self.consider_sensitivities[index].clone(),
+                bias_estimate: self.bias_estimates[index].clone(),
+                dmc_estimate: self.dmc_estimates[index],
             })
         } else {
             None
@@ -20,7 +20,9 @@
 // potentially in a new file like `src/od/process/solution/import.rs`
 // and ensure necessary imports are present.
 
-use crate::io::{ArrowSnafu, InputOutputError, MissingDataSnafu, ParquetSnafu, StdIOSnafu};
+use crate::io::{
+    ArrowSnafu, InputOutputError, MissingDataSnafu, ParquetSnafu, Provenance, StdIOSnafu,
+};
 use crate::linalg::allocator::Allocator;
 use crate::linalg::{Const, DefaultAllocator, DimName, OMatrix, OVector, SMatrix};
 use crate::od::estimate::*;
@@ -530,12 +532,19 @@ where
 
         // --- Final Construction ---
         Ok(ODSolution {
+            consider_sensitivities: vec![None; estimates.len()],
+            bias_estimates: vec![None; estimates.len()],
+            dmc_estimates: vec![None; estimates.len()],
             estimates,
             residuals,
             gains,
             filter_smoother_ratios,
+            consider: Vec::new(),
+            estimated_biases: Vec::new(),
+            dmc: None,
             devices, // Provided by user
             measurement_types: measurement_types_found, // Determined from columns
+            provenance: Provenance::from_metadata(&metadata),
         })
     }
 }
@@ -16,10 +16,16 @@
     along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
+use crate::io::Provenance;
 use crate::linalg::allocator::Allocator;
-use crate::linalg::{DefaultAllocator, DimName};
+use crate::linalg::{DMatrix, DefaultAllocator, DimName};
 use crate::md::trajectory::{Interpolatable, Traj};
+use crate::od::consider::{consider_contribution, ConsiderParameter};
 pub use crate::od::estimate::*;
+use crate::od::kalman::{
+    AugmentedStates, BiasEstimate, BiasedQuantity, DmcEstimate, DynamicModelCompensation,
+    EstimatedBias,
+};
 pub use crate::od::*;
 use indexmap::IndexSet;
 use msr::sensitivity::TrackerSensitivity;
@@ -81,9 +87,23 @@ where
     pub gains: Vec<Option<OMatrix<f64, <StateType as State>::Size, MsrSize>>>,
     /// Filter-smoother consistency ratios, all None before running the smoother.
     pub filter_smoother_ratios: Vec<Option<OVector<f64, <StateType as State>::Size>>>,
+    /// Parameters considered, but not estimated, by the OD process
+    pub consider: Vec<ConsiderParameter>,
+    /// Sensitivity of the estimation errors to the consider parameters for each estimate, all None without consider parameters. Not smoothed by the smoother.
+    pub consider_sensitivities: Vec<Option<DMatrix<f64>>>,
+    /// Tracking measurement biases estimated by the OD process
+    pub estimated_biases: Vec<EstimatedBias>,
+    /// Estimate of the tracking measurement biases for each estimate, all None without estimated biases. Not smoothed by the smoother.
+    pub bias_estimates: Vec<Option<BiasEstimate>>,
+    /// Dynamic model compensation estimated by the OD process, if any
+    pub dmc: Option<DynamicModelCompensation>,
+    /// Estimate of the DMC accelerations for each estimate, all None without dynamic model compensation. Not smoothed by the smoother.
+    pub dmc_estimates: Vec<Option<DmcEstimate>>,
     /// Tracking devices
     pub devices: BTreeMap<String, Trk>,
     pub measurement_types: IndexSet<MeasurementType>,
+    /// Inputs of the orbit determination process, embedded in the exported files
+    pub provenance: Option<Provenance>,
 }
 
 impl<StateType, EstType, MsrSize, Trk> ODSolution<StateType, EstType, MsrSize, Trk>
@@ -110,8 +130,15 @@ where
             residuals: Vec::new(),
             gains: Vec::new(),
             filter_smoother_ratios: Vec::new(),
+            consider: Vec::new(),
+            consider_sensitivities: Vec::new(),
+            estimated_biases: Vec::new(),
+            bias_estimates: Vec::new(),
+            dmc: None,
+            dmc_estimates: Vec::new(),
             devices,
             measurement_types,
+            provenance: None,
         }
     }
 
@@ -126,6 +153,9 @@ where
         self.residuals.push(Some(residual));
         self.gains.push(gain);
         self.filter_smoother_ratios.push(None);
+        self.consider_sensitivities.push(None);
+        self.bias_estimates.push(None);
+        self.dmc_estimates.push(None);
     }
 
     /// Pushes a new time update result, ensuring proper sizes of the arrays.
@@ -134,6 +164,88 @@ where
         self.residuals.push(None);
         self.gains.push(None);
         self.filter_smoother_ratios.push(None);
+        self.consider_sensitivities.push(None);
+        self.bias_estimates.push(None);
+        self.dmc_estimates.push(None);
+    }
+
+    /// Sets the sensitivity of the estimation errors to the consider parameters of the last estimate.
+    pub(crate) fn set_consider_sensitivity(&mut self, sensitivity: DMatrix<f64>) {
+        if let Some(last) = self.consider_sensitivities.last_mut() {
+            *last = Some(sensitivity);
+        }
+    }
+
+    /// Sets the estimate of the tracking measurement biases and of the DMC accelerations of the last estimate.
+    pub(crate) fn set_augmented_estimates(&mut self, augmented: &AugmentedStates) {
+        if let Some(last) = self.bias_estimates.last_mut() {
+            *last = augmented.bias_estimate();
+        }
+        if let Some(last) = self.dmc_estimates.last_mut() {
+            *last = augmented.dmc_estimate();
+        }
+    }
+
+    /// Returns the history of the estimated bias of the provided tracker and measurement type (or time tags), as tuples of the epoch,
+    /// the estimated bias, and its 1-sigma uncertainty. Empty if this bias was not estimated.
+    pub fn bias_history(
+        &self,
+        tracker: &str,
+        quantity: impl Into<BiasedQuantity>,
+    ) -> Vec<(Epoch, f64, f64)> {
+        let quantity = quantity.into();
+        let Some(j) = self
+            .estimated_biases
+            .iter()
+            .position(|bias| bias.tracker == tracker && bias.quantity == quantity)
+        else {
+            return Vec::new();
+        };
+
+        self.estimates
+            .iter()
+            .zip(self.bias_estimates.iter())
+            .filter_map(|(est, bias_est)| {
+                bias_est.as_ref().map(|bias_est| {
+                    (
+                        est.epoch(),
+                        bias_est.value[j],
+                        bias_est.covar[(j, j)].sqrt(),
+                    )
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the history of the estimated DMC accelerations, in the RIC frame. Empty without dynamic model compensation.
+    pub fn dmc_history(&self) -> Vec<(Epoch, DmcEstimate)> {
+        self.estimates
+            .iter()
+            .zip(self.dmc_estimates.iter())
+            .filter_map(|(est, dmc_est)| dmc_est.map(|dmc_est| (est.epoch(), dmc_est)))
+            .collect()
+    }
+
+    /// Returns the contribution of the consider parameters to the covariance of the estimate at the provided index,
+    /// or None if no parameters were considered for this estimate.
+    pub fn consider_covar_contribution(
+        &self,
+        index: usize,
+    ) -> Option<OMatrix<f64, <StateType as State>::Size, <StateType as State>::Size>> {
+        self.consider_sensitivities
+            .get(index)?
+            .as_ref()
+            .map(|sensitivity| consider_contribution(&self.consider, sensitivity))
+    }
+
+    /// Returns the consider covariance of the estimate at the provided index, i.e. the sum of its computed covariance and of the
+    /// contribution of the consider parameters, or None if no parameters were considered for this estimate.
+    pub fn consider_covar(
+        &self,
+        index: usize,
+    ) -> Option<OMatrix<f64, <StateType as State>::Size, <StateType as State>::Size>> {
+        self.consider_covar_contribution(index)
+            .map(|contribution| self.estimates[index].covar() + contribution)
     }
 
     /// Returns a zipper iterator on the estimates and the associated residuals.
@@ -165,6 +277,7 @@ where
             let mut traj = Traj {
                 states: self.estimates.iter().map(|est| est.state()).collect(),
                 name: None,
+                boundaries: Vec::new(),
             };
             traj.finalize();
             Ok(traj)
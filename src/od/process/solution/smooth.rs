@@ -48,7 +48,8 @@ where
     /// Notes:
     ///  1. Gains will be scrubbed because the smoother process does not recompute the gain.
     ///  2. Prefit residuals, ratios, and measurement covariances are not updated, as these depend on the filtering process.
-    ///  3. Note: this function consumes the current OD solution to prevent reusing the wrong one.
+    ///  3. The estimated biases, DMC accelerations and consider sensitivities are not smoothed: the filtered values are kept.
+    ///  4. Note: this function consumes the current OD solution to prevent reusing the wrong one.
     ///
     ///
     /// To assess whether the smoothing process improved the solution, compare the RMS of the postfit residuals from the filter and the smoother process.
@@ -106,8 +107,15 @@ where
             residuals: Vec::with_capacity(self.residuals.len()),
             gains: Vec::with_capacity(self.estimates.len()),
             filter_smoother_ratios: Vec::with_capacity(self.estimates.len()),
+            consider: self.consider.clone(),
+            consider_sensitivities: Vec::with_capacity(self.estimates.len()),
+            estimated_biases: self.estimated_biases.clone(),
+            bias_estimates: Vec::with_capacity(self.estimates.len()),
+            dmc: self.dmc,
+            dmc_estimates: Vec::with_capacity(self.estimates.len()),
             devices: self.devices.clone(),
             measurement_types: self.measurement_types.clone(),
+            provenance: self.provenance.clone(),
         };
 
         // Set the first item of the smoothed estimates to the last estimate (we cannot smooth the very last estimate)
@@ -119,6 +127,11 @@ where
             .push(self.residuals.last().unwrap().clone());
         smoothed.gains.push(None);
         smoothed.filter_smoother_ratios.push(None);
+        smoothed
+            .consider_sensitivities
+            .push(self.consider_sensitivities[l].clone());
+        smoothed.bias_estimates.push(self.bias_estimates[l].clone());
+        smoothed.dmc_estimates.push(self.dmc_estimates[l]);
 
         loop {
             let k = l - smoothed.estimates.len();
@@ -203,6 +216,12 @@ where
             smoothed.filter_smoother_ratios.push(Some(fs_ratios));
             // Set all gains to None.
             smoothed.gains.push(None);
+            // The augmented states and consider parameters are not smoothed, so keep their filtered values.
+            smoothed
+                .consider_sensitivities
+                .push(self.consider_sensitivities[k].clone());
+            smoothed.bias_estimates.push(self.bias_estimates[k].clone());
+            smoothed.dmc_estimates.push(self.dmc_estimates[k]);
 
             if smoothed.estimates.len() == self.estimates.len() {
                 break;
@@ -235,6 +254,9 @@ where
         smoothed.estimates.reverse();
         smoothed.residuals.reverse();
         smoothed.filter_smoother_ratios.reverse();
+        smoothed.consider_sensitivities.reverse();
+        smoothed.bias_estimates.reverse();
+        smoothed.dmc_estimates.reverse();
 
         Ok(smoothed)
     }
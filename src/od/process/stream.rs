@@ -0,0 +1,748 @@
+/*
+    Nyx, blazing fast astrodynamics
+    Copyright (C) 2018-onwards Christopher Rabotin <christopher.rabotin@gmail.com>
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published
+    by the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use super::*;
+use crate::linalg::{DMatrix, DVector, OMatrix};
+use crate::od::msr::{Measurement, MeasurementType};
+use crate::propagators::PropInstance;
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+
+/// Step of the finite difference of the computed observations, whose time derivative is the sensitivity to time-tag biases.
+const TIME_TAG_RATE_STEP_S: f64 = 1.0;
+
+/// Serializable state of a streaming orbit determination, from which the stream can be resumed with the same OD process.
+///
+/// Matrices are stored in column-major order. The history of the estimates and residuals is not part of the checkpoint:
+/// export the solution of the stream to keep it.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ODStreamCheckpoint<S> {
+    /// Reference state of the propagator, at the epoch of the estimate
+    pub reference: S,
+    /// Nominal state of the last estimate
+    pub nominal_state: S,
+    pub state_deviation: Vec<f64>,
+    pub covar: Vec<f64>,
+    pub covar_bar: Vec<f64>,
+    pub stm: Vec<f64>,
+    pub predicted: bool,
+    /// Initial and previous epochs of each process noise, needed for their decay and disable time
+    pub process_noise_epochs: Vec<(Option<Epoch>, Option<Epoch>)>,
+    pub prev_used_snc: usize,
+    /// Estimated values, covariance, and cross covariance with the state of the estimated biases and DMC accelerations, if any
+    pub augmented: Option<(Vec<f64>, Vec<f64>, Vec<f64>)>,
+    /// Sensitivity of the estimate to the consider parameters, if any
+    pub consider_sensitivity: Option<Vec<f64>>,
+    /// Moduli of the ambiguous measurement types, if any
+    pub moduli: Option<IndexMap<MeasurementType, f64>>,
+}
+
+/// Incremental orbit determination: measurements are ingested one at a time as they arrive, and the estimate can be
+/// predicted forward in between. This shares the Kalman filter processing of [KalmanODProcess::process_arc], for all filter variants.
+pub struct ODStream<
+    'a,
+    D: Dynamics,
+    MsrSize: DimName,
+    Accel: DimName,
+    Trk: TrackerSensitivity<D::StateType, D::StateType>,
+> where
+    D::StateType:
+        Interpolatable + Add<OVector<f64, <D::StateType as State>::Size>, Output = D::StateType>,
+    <DefaultAllocator as Allocator<<D::StateType as State>::VecLength>>::Buffer<f64>: Send,
+    <DefaultAllocator as Allocator<<D::StateType as State>::Size>>::Buffer<f64>: Copy,
+    <DefaultAllocator as Allocator<<D::StateType as State>::Size, <D::StateType as State>::Size>>::Buffer<f64>: Copy,
+    DefaultAllocator: Allocator<<D::StateType as State>::Size>
+        + Allocator<<D::StateType as State>::VecLength>
+        + Allocator<MsrSize>
+        + Allocator<MsrSize, <D::StateType as State>::Size>
+        + Allocator<<D::StateType as State>::Size, MsrSize>
+        + Allocator<MsrSize, MsrSize>
+        + Allocator<<D::StateType as State>::Size, <D::StateType as State>::Size>
+        + Allocator<Accel>
+        + Allocator<Accel, Accel>
+        + Allocator<<D::StateType as State>::Size, Accel>
+        + Allocator<Accel, <D::StateType as State>::Size>,
+{
+    odp: &'a KalmanODProcess<D, MsrSize, Accel, Trk>,
+    prop_instance: PropInstance<'a, D>,
+    kf: KalmanFilter<D::StateType, Accel>,
+    consider: Option<ConsiderTracker<D::StateType>>,
+    devices: BTreeMap<String, Trk>,
+    /// Trajectory of the estimated states, used to compute the measurements
+    traj: Traj<D::StateType>,
+    epoch: Epoch,
+    /// Moduli of the ambiguous measurement types, applied to the ingested measurements
+    moduli: Option<IndexMap<MeasurementType, f64>>,
+    unknown_trackers: IndexSet<String>,
+    /// Estimates and residuals since the start of this stream
+    pub(super) solution: ODSolution<D::StateType, KfEstimate<D::StateType>, MsrSize, Trk>,
+}
+
+impl<
+        D: Dynamics,
+        MsrSize: DimName,
+        Accel: DimName,
+        Trk: TrackerSensitivity<D::StateType, D::StateType>,
+    > KalmanODProcess<D, MsrSize, Accel, Trk>
+where
+    D::StateType:
+        Interpolatable + Add<OVector<f64, <D::StateType as State>::Size>, Output = D::StateType>,
+    <DefaultAllocator as Allocator<<D::StateType as State>::VecLength>>::Buffer<f64>: Send,
+    <DefaultAllocator as Allocator<<D::StateType as State>::Size>>::Buffer<f64>: Copy,
+    <DefaultAllocator as Allocator<<D::StateType as State>::Size, <D::StateType as State>::Size>>::Buffer<f64>: Copy,
+    DefaultAllocator: Allocator<<D::StateType as State>::Size>
+        + Allocator<<D::StateType as State>::VecLength>
+        + Allocator<MsrSize>
+        + Allocator<MsrSize, <D::StateType as State>::Size>
+        + Allocator<<D::StateType as State>::Size, MsrSize>
+        + Allocator<MsrSize, MsrSize>
+        + Allocator<<D::StateType as State>::Size, <D::StateType as State>::Size>
+        + Allocator<Accel>
+        + Allocator<Accel, Accel>
+        + Allocator<<D::StateType as State>::Size, Accel>
+        + Allocator<Accel, <D::StateType as State>::Size>
+        + Allocator<nalgebra::Const<1>, MsrSize>,
+{
+    /// Starts an incremental orbit determination from the provided initial estimate, which processes the measurements of
+    /// the provided types as they are ingested.
+    pub fn start_stream(
+        &self,
+        initial_estimate: KfEstimate<D::StateType>,
+        measurement_types: IndexSet<MeasurementType>,
+    ) -> Result<ODStream<'_, D, MsrSize, Accel, Trk>, ODError> {
+        if matches!(self.kf_variant, KalmanVariant::Unscented { .. }) {
+            ensure!(
+                self.consider.is_empty(),
+                ODLimitationSnafu {
+                    action: "consider parameters are not supported by the unscented Kalman filter"
+                }
+            );
+            ensure!(
+                self.biases.is_empty(),
+                ODLimitationSnafu {
+                    action: "bias estimation is not supported by the unscented Kalman filter"
+                }
+            );
+            ensure!(
+                self.dmc.is_none(),
+                ODLimitationSnafu {
+                    action:
+                        "dynamic model compensation is not supported by the unscented Kalman filter"
+                }
+            );
+        }
+
+        ensure!(
+            !self.max_step.is_negative() && self.max_step != Duration::ZERO,
+            StepSizeSnafu { step: self.max_step }
+        );
+
+        // Considered state components are not estimated, so their covariance is moved to the consider covariance.
+        let mut initial_estimate = initial_estimate;
+        zero_considered_components(&self.consider, &mut initial_estimate.covar)?;
+        zero_considered_components(&self.consider, &mut initial_estimate.covar_bar)?;
+
+        // Initialize the solution.
+        let mut solution = ODSolution::new(self.devices.clone(), measurement_types);
+        solution.consider = self.consider.clone();
+        solution.estimated_biases = self.biases.clone();
+        solution.dmc = self.dmc;
+        solution.provenance = Some(
+            Provenance::new()
+                .with_almanac(&self.almanac)
+                .with_propagator(&self.prop),
+        );
+
+        // Set up the propagator instance. The reference of the unscented filter is the mean of its sigma points.
+        let reference = match self.kf_variant {
+            KalmanVariant::Unscented { .. } => initial_estimate.state(),
+            _ => initial_estimate.nominal_state(),
+        };
+        let mut prop_instance = self
+            .prop
+            .with(reference.with_stm(), self.almanac.clone())
+            .quiet();
+
+        // Update the step size of the navigation propagator if it isn't already fixed step
+        if !prop_instance.fixed_step {
+            prop_instance.set_step(self.max_step, false);
+        }
+
+        // Set up the Kalman filter.
+        let mut kf = KalmanFilter::<D::StateType, Accel> {
+            prev_estimate: initial_estimate,
+            process_noise: self.process_noise.clone(),
+            variant: self.kf_variant,
+            formulation: self.covar_formulation,
+            prev_used_snc: 0,
+            ud_factors: None,
+            augmented: (!self.biases.is_empty() || self.dmc.is_some()).then(|| {
+                AugmentedStates::new(
+                    self.biases.clone(),
+                    self.dmc,
+                    <D::StateType as State>::Size::DIM,
+                    initial_estimate.epoch(),
+                )
+            }),
+        };
+
+        kf.initialize_process_noises();
+
+        let consider = (!self.consider.is_empty())
+            .then(|| ConsiderTracker::new(&self.consider, prop_instance.state));
+
+        Ok(ODStream {
+            odp: self,
+            epoch: prop_instance.state.epoch(),
+            prop_instance,
+            kf,
+            consider,
+            devices: self.devices.clone(),
+            traj: Traj::new(),
+            moduli: None,
+            unknown_trackers: IndexSet::new(),
+            solution,
+        })
+    }
+
+    /// Resumes an incremental orbit determination from the provided checkpoint, which must have been made with the same configuration.
+    pub fn resume_stream(
+        &self,
+        checkpoint: ODStreamCheckpoint<D::StateType>,
+        measurement_types: IndexSet<MeasurementType>,
+    ) -> Result<ODStream<'_, D, MsrSize, Accel, Trk>, ODError> {
+        let n = <D::StateType as State>::Size::DIM;
+        ensure!(
+            checkpoint.state_deviation.len() == n
+                && [&checkpoint.covar, &checkpoint.covar_bar, &checkpoint.stm]
+                    .iter()
+                    .all(|matrix| matrix.len() == n * n)
+                && checkpoint.process_noise_epochs.len() == self.process_noise.len(),
+            ODLimitationSnafu {
+                action: "checkpoint does not match the state size or process noise of this OD process"
+            }
+        );
+
+        let initial_estimate = KfEstimate {
+            nominal_state: checkpoint.nominal_state,
+            state_deviation: OVector::<f64, <D::StateType as State>::Size>::from_column_slice(
+                &checkpoint.state_deviation,
+            ),
+            covar: OMatrix::<f64, <D::StateType as State>::Size, <D::StateType as State>::Size>::from_column_slice(
+                &checkpoint.covar,
+            ),
+            covar_bar: OMatrix::<f64, <D::StateType as State>::Size, <D::StateType as State>::Size>::from_column_slice(
+                &checkpoint.covar_bar,
+            ),
+            stm: OMatrix::<f64, <D::StateType as State>::Size, <D::StateType as State>::Size>::from_column_slice(
+                &checkpoint.stm,
+            ),
+            predicted: checkpoint.predicted,
+        };
+
+        let mut stream = self.start_stream(initial_estimate, measurement_types)?;
+        stream.moduli = checkpoint.moduli;
+        // Resume from the reference state of the propagator, which differs from the nominal state after an EKF update.
+        stream.prop_instance.state = checkpoint.reference.with_stm();
+        stream.epoch = checkpoint.reference.epoch();
+        // Restore the covariances as they were, including of the considered state components.
+        stream.kf.prev_estimate = initial_estimate;
+        stream.kf.prev_used_snc = checkpoint.prev_used_snc;
+        for (snc, (init_epoch, prev_epoch)) in stream
+            .kf
+            .process_noise
+            .iter_mut()
+            .zip(checkpoint.process_noise_epochs)
+        {
+            snc.init_epoch = init_epoch;
+            snc.prev_epoch = prev_epoch;
+        }
+
+        match (stream.kf.augmented.as_mut(), checkpoint.augmented) {
+            (Some(augmented), Some((value, covar, cross_covar))) => {
+                let p = augmented.num_params();
+                ensure!(
+                    value.len() == p && covar.len() == p * p && cross_covar.len() == n * p,
+                    ODLimitationSnafu {
+                        action: "checkpoint does not match the augmented states of this OD process"
+                    }
+                );
+                augmented.value = DVector::from_column_slice(&value);
+                augmented.covar = DMatrix::from_column_slice(p, p, &covar);
+                augmented.cross_covar = DMatrix::from_column_slice(n, p, &cross_covar);
+            }
+            (None, None) => {}
+            _ => {
+                return Err(ODError::ODLimitation {
+                    action: "checkpoint does not match the augmented states of this OD process",
+                })
+            }
+        }
+
+        match (stream.consider.as_mut(), checkpoint.consider_sensitivity) {
+            (Some(consider), Some(sensitivity)) => {
+                ensure!(
+                    sensitivity.len() == n * self.consider.len(),
+                    ODLimitationSnafu {
+                        action: "checkpoint does not match the consider parameters of this OD process"
+                    }
+                );
+                consider.sensitivity =
+                    DMatrix::from_column_slice(n, self.consider.len(), &sensitivity);
+                consider.rebase(stream.prop_instance.state);
+            }
+            (None, None) => {}
+            _ => {
+                return Err(ODError::ODLimitation {
+                    action: "checkpoint does not match the consider parameters of this OD process",
+                })
+            }
+        }
+
+        Ok(stream)
+    }
+}
+
+impl<
+        D: Dynamics,
+        MsrSize: DimName,
+        Accel: DimName,
+        Trk: TrackerSensitivity<D::StateType, D::StateType>,
+    > ODStream<'_, D, MsrSize, Accel, Trk>
+where
+    D::StateType:
+        Interpolatable + Add<OVector<f64, <D::StateType as State>::Size>, Output = D::StateType>,
+    <DefaultAllocator as Allocator<<D::StateType as State>::VecLength>>::Buffer<f64>: Send,
+    <DefaultAllocator as Allocator<<D::StateType as State>::Size>>::Buffer<f64>: Copy,
+    <DefaultAllocator as Allocator<<D::StateType as State>::Size, <D::StateType as State>::Size>>::Buffer<f64>: Copy,
+    DefaultAllocator: Allocator<<D::StateType as State>::Size>
+        + Allocator<<D::StateType as State>::VecLength>
+        + Allocator<MsrSize>
+        + Allocator<MsrSize, <D::StateType as State>::Size>
+        + Allocator<<D::StateType as State>::Size, MsrSize>
+        + Allocator<MsrSize, MsrSize>
+        + Allocator<<D::StateType as State>::Size, <D::StateType as State>::Size>
+        + Allocator<Accel>
+        + Allocator<Accel, Accel>
+        + Allocator<<D::StateType as State>::Size, Accel>
+        + Allocator<Accel, <D::StateType as State>::Size>
+        + Allocator<nalgebra::Const<1>, MsrSize>,
+{
+    /// Sets the moduli of the ambiguous measurement types, e.g. range modulus, to resolve the ambiguity of the ingested measurements.
+    pub fn with_moduli(mut self, moduli: IndexMap<MeasurementType, f64>) -> Self {
+        self.moduli = Some(moduli);
+        self
+    }
+
+    /// Processes the provided measurement, which must not be before the current estimate, and returns the updated estimate.
+    /// The residuals of this measurement, if any, are the last ones of the solution of the stream.
+    pub fn ingest(&mut self, msr: &Measurement) -> Result<KfEstimate<D::StateType>, ODError> {
+        ensure!(
+            msr.epoch - self.epoch > -self.odp.epoch_precision,
+            ODLimitationSnafu {
+                action: "streamed measurements must be ingested in chronological order"
+            }
+        );
+        self.advance(msr.epoch, Some(msr), self.odp.resid_crit)?;
+        Ok(self.current_estimate())
+    }
+
+    /// Predicts the estimate forward to the provided epoch, with a time update at each step, and returns the predicted estimate.
+    pub fn predict_to(&mut self, epoch: Epoch) -> Result<KfEstimate<D::StateType>, ODError> {
+        ensure!(
+            epoch - self.epoch > -self.odp.epoch_precision,
+            ODLimitationSnafu {
+                action: "streaming orbit determination cannot predict backward"
+            }
+        );
+        self.advance(epoch, None, None)?;
+        Ok(self.current_estimate())
+    }
+
+    /// Returns the current estimate of the stream.
+    pub fn current_estimate(&self) -> KfEstimate<D::StateType> {
+        *self.kf.previous_estimate()
+    }
+
+    /// Returns the estimates and residuals since the start of this stream.
+    pub fn solution(&self) -> &ODSolution<D::StateType, KfEstimate<D::StateType>, MsrSize, Trk> {
+        &self.solution
+    }
+
+    /// Consumes this stream and returns its estimates and residuals.
+    pub fn into_solution(self) -> ODSolution<D::StateType, KfEstimate<D::StateType>, MsrSize, Trk> {
+        self.solution
+    }
+
+    /// Returns a serializable checkpoint of the current state of the stream, to resume it later with [KalmanODProcess::resume_stream].
+    pub fn checkpoint(&self) -> ODStreamCheckpoint<D::StateType> {
+        let estimate = self.kf.previous_estimate();
+        let mut reference = self.prop_instance.state;
+        reference.unset_stm();
+        let mut nominal_state = estimate.nominal_state;
+        nominal_state.unset_stm();
+
+        ODStreamCheckpoint {
+            reference,
+            nominal_state,
+            state_deviation: estimate.state_deviation.as_slice().to_vec(),
+            covar: estimate.covar.as_slice().to_vec(),
+            covar_bar: estimate.covar_bar.as_slice().to_vec(),
+            stm: estimate.stm.as_slice().to_vec(),
+            predicted: estimate.predicted,
+            process_noise_epochs: self
+                .kf
+                .process_noise
+                .iter()
+                .map(|snc| (snc.init_epoch, snc.prev_epoch))
+                .collect(),
+            prev_used_snc: self.kf.prev_used_snc,
+            augmented: self.kf.augmented.as_ref().map(|augmented| {
+                (
+                    augmented.value.as_slice().to_vec(),
+                    augmented.covar.as_slice().to_vec(),
+                    augmented.cross_covar.as_slice().to_vec(),
+                )
+            }),
+            consider_sensitivity: self
+                .consider
+                .as_ref()
+                .map(|consider| consider.sensitivity.as_slice().to_vec()),
+            moduli: self.moduli.clone(),
+        }
+    }
+
+    /// Advances the filter to the target epoch with a time update at each step, and processes the measurement at that epoch, if any.
+    ///
+    /// Returns whether the measurement was rejected, or None if it was not processed, e.g. without measurement or if the
+    /// tracker is unknown or does not expect this measurement.
+    pub(crate) fn advance(
+        &mut self,
+        target: Epoch,
+        msr: Option<&Measurement>,
+        resid_crit: Option<ResidRejectCrit>,
+    ) -> Result<Option<bool>, ODError> {
+        loop {
+            let delta_t = target - self.epoch;
+
+            if msr.is_none() && delta_t < self.odp.epoch_precision {
+                // Prediction complete.
+                return Ok(None);
+            }
+
+            // Propagate for the minimum time between the maximum step size, the next step size, and the duration to the next measurement.
+            let next_step_size = delta_t
+                .min(self.prop_instance.step_size)
+                .min(self.odp.max_step);
+
+            // Remove old states from the trajectory
+            // This is a manual implementation of `retaint` because we know it's a sorted vec, so no need to resort every time
+            let mut index = self.traj.states.len();
+            while index > 0 {
+                index -= 1;
+                if self.traj.states[index].epoch() >= self.epoch {
+                    break;
+                }
+            }
+            self.traj.states.truncate(index);
+
+            debug!("propagate for {next_step_size} (Δt to next msr: {delta_t})");
+            let (_, traj_covar) = self
+                .prop_instance
+                .for_duration_with_traj(next_step_size)
+                .context(ODPropSnafu)?;
+
+            for state in traj_covar.states {
+                // NOTE: At the time being, only spacecraft estimation is possible, and the trajectory will always be the exact state
+                // that was propagated. Even once ground station biases are estimated, these won't go through the propagator.
+                self.traj.states.push(state);
+            }
+
+            // Now that we've advanced the propagator, let's see whether we're at the time of the next measurement.
+
+            // Extract the state and update the STM in the filter.
+            let nominal_state = self.prop_instance.state;
+            // Get the datetime and info needed to compute the theoretical measurement according to the model
+            self.epoch = nominal_state.epoch();
+            let epoch = self.epoch;
+
+            // Perform a measurement update, accounting for possible errors in measurement timestamps
+            if let Some(msr) =
+                msr.filter(|_| (nominal_state.epoch() - target).abs() < self.odp.epoch_precision)
+            {
+                let Some(device) = self.devices.get_mut(&msr.tracker) else {
+                    if !self.unknown_trackers.contains(&msr.tracker) {
+                        error!(
+                            "Tracker {} is not in the list of configured devices",
+                            msr.tracker
+                        );
+                    }
+                    self.unknown_trackers.insert(msr.tracker.clone());
+                    return Ok(None);
+                };
+
+                let unscented = matches!(self.kf.variant, KalmanVariant::Unscented { .. });
+
+                // The unscented filter predicts its sigma points to the measurement before updating them, whereas the other
+                // variants linearize the computed observations of the reference trajectory.
+                let (computed_meas, prev_meas, prediction) = if unscented {
+                    let prediction = self.odp.unscented_time_update(&mut self.kf, nominal_state)?;
+                    (None, None, Some(prediction))
+                } else {
+                    // Get the computed observations
+                    let Some(computed_meas) =
+                        device.measure(epoch, &self.traj, None, self.odp.almanac.clone())?
+                    else {
+                        debug!(
+                            "Device {} does not expect measurement at {epoch}, skipping",
+                            msr.tracker
+                        );
+                        return Ok(None);
+                    };
+
+                    // Previous computed observations, to differentiate them for the time-tag biases of this tracker.
+                    let prev_meas = match &self.kf.augmented {
+                        Some(augmented) if augmented.has_time_tag_bias(&device.name()) => device
+                            .measure(
+                                epoch - Unit::Second * TIME_TAG_RATE_STEP_S,
+                                &self.traj,
+                                None,
+                                self.odp.almanac.clone(),
+                            )?,
+                        _ => None,
+                    };
+                    (Some(computed_meas), prev_meas, None)
+                };
+
+                let msr_types = device.measurement_types().clone();
+
+                // Perform several measurement updates to ensure the desired dimensionality.
+                let windows = msr_types.len() / MsrSize::DIM;
+                let mut msr_rejected = false;
+                let mut updated = false;
+                for wno in 0..=windows {
+                    let mut cur_msr_types = IndexSet::new();
+                    for msr_type in msr_types
+                        .iter()
+                        .copied()
+                        .skip(wno * MsrSize::DIM)
+                        .take(MsrSize::DIM)
+                    {
+                        cur_msr_types.insert(msr_type);
+                    }
+
+                    if cur_msr_types.is_empty() {
+                        // We've processed all measurements.
+                        break;
+                    }
+
+                    // If this measurement type is unavailable, continue to the next one.
+                    if !msr.availability(&cur_msr_types).iter().any(|avail| *avail) {
+                        continue;
+                    }
+
+                    // Grab the un-modulo'd real observation
+                    let mut real_obs: OVector<f64, MsrSize> = msr.observation(&cur_msr_types);
+
+                    // Check that the observation is valid.
+                    for val in real_obs.iter().copied() {
+                        ensure!(
+                            val.is_finite(),
+                            InvalidMeasurementSnafu {
+                                epoch: target,
+                                val
+                            }
+                        );
+                    }
+
+                    let (estimate, mut residual, gain, h_tilde) = match &computed_meas {
+                        None => {
+                            match self.odp.unscented_measurement_update(
+                                &mut self.kf,
+                                device,
+                                real_obs,
+                                &cur_msr_types,
+                                self.moduli.as_ref(),
+                                resid_crit,
+                            )? {
+                                Some((estimate, residual, gain)) => {
+                                    (estimate, residual, gain, None)
+                                }
+                                None => {
+                                    debug!(
+                                        "Device {} does not see all sigma points at {epoch}, skipping",
+                                        msr.tracker
+                                    );
+                                    continue;
+                                }
+                            }
+                        }
+                        Some(computed_meas) => {
+                            // Compute device specific matrices
+                            let h_tilde = device.h_tilde::<MsrSize>(
+                                msr,
+                                &cur_msr_types,
+                                &nominal_state,
+                                self.odp.almanac.clone(),
+                            )?;
+
+                            let measurement_covar =
+                                device.measurement_covar_matrix(&cur_msr_types, epoch)?;
+
+                            // Apply any biases on the computed observation
+                            let computed_obs = computed_meas
+                                .observation::<MsrSize>(&cur_msr_types)
+                                - device
+                                    .measurement_bias_vector::<MsrSize>(&cur_msr_types, epoch)?;
+
+                            // Apply the modulo to the real obs
+                            if let Some(moduli) = &self.moduli {
+                                let mut obs_ambiguity = OVector::<f64, MsrSize>::zeros();
+
+                                for (i, msr_type) in cur_msr_types.iter().enumerate() {
+                                    if let Some(modulus) = moduli.get(msr_type) {
+                                        let k = computed_obs[i].div_euclid(*modulus);
+                                        // real_obs = measured_obs + k * modulus
+                                        obs_ambiguity[i] = k * *modulus;
+                                    }
+                                }
+                                real_obs += obs_ambiguity;
+                            }
+
+                            let obs_rates = prev_meas.as_ref().map(|prev_meas| {
+                                (computed_meas.observation::<MsrSize>(&cur_msr_types)
+                                    - prev_meas.observation::<MsrSize>(&cur_msr_types))
+                                    / TIME_TAG_RATE_STEP_S
+                            });
+
+                            let (estimate, residual, gain) = self.kf.augmented_measurement_update(
+                                nominal_state,
+                                real_obs,
+                                computed_obs,
+                                measurement_covar,
+                                h_tilde.clone(),
+                                &device.name(),
+                                &cur_msr_types,
+                                obs_rates,
+                                resid_crit,
+                            )?;
+                            (estimate, residual, gain, Some(h_tilde))
+                        }
+                    };
+
+                    if let Some(consider) = self.consider.as_mut() {
+                        consider.time_update(
+                            &self.odp.consider,
+                            &self.odp.prop,
+                            self.odp.almanac.clone(),
+                            &nominal_state,
+                        )?;
+                        if let (Some(gain), Some(h_tilde)) = (&gain, &h_tilde) {
+                            let h_consider = measurement_sensitivity::<MsrSize, _, _>(
+                                &self.odp.consider,
+                                device,
+                                &cur_msr_types,
+                                nominal_state,
+                                self.odp.almanac.clone(),
+                            )?;
+                            consider.measurement_update(gain, h_tilde, &h_consider);
+                        }
+                    }
+
+                    debug!(
+                        "processed measurement for {cur_msr_types:?} @ {epoch} from {}",
+                        device.name()
+                    );
+
+                    residual.tracker = Some(device.name());
+                    residual.msr_types = cur_msr_types;
+
+                    if residual.rejected {
+                        msr_rejected = true;
+                    }
+
+                    if self.kf.replace_state() {
+                        self.prop_instance.state = estimate.state();
+                    }
+
+                    self.prop_instance.state.reset_stm();
+
+                    self.solution
+                        .push_measurement_update(estimate, residual, gain);
+                    updated = true;
+
+                    if let Some(augmented) = &self.kf.augmented {
+                        self.solution.set_augmented_estimates(augmented);
+                    }
+
+                    if let Some(consider) = self.consider.as_mut() {
+                        consider.rebase(self.prop_instance.state);
+                        self.solution
+                            .set_consider_sensitivity(consider.sensitivity.clone());
+                    }
+                }
+
+                if let Some(prediction) = prediction.filter(|_| !updated) {
+                    // Keep the prediction at the epoch of this measurement in the solution.
+                    self.prop_instance.state = prediction.state();
+                    self.prop_instance.state.reset_stm();
+                    self.solution.push_time_update(prediction);
+                }
+
+                return Ok(updated.then_some(msr_rejected));
+            } else {
+                // No measurement can be used here, let's just do a time update and continue advancing the propagator.
+                debug!("time update {epoch:?}, next msr {target:?}");
+                let est = match self.kf.variant {
+                    KalmanVariant::Unscented { .. } => {
+                        let est = self.odp.unscented_time_update(&mut self.kf, nominal_state)?;
+                        // The reference trajectory follows the mean of the sigma points.
+                        self.prop_instance.state = est.state();
+                        est
+                    }
+                    _ => {
+                        let est = self.kf.time_update(nominal_state)?;
+                        // State deviation is always zero for an EKF time update, unless it includes the effect of the estimated DMC accelerations.
+                        if self.kf.replace_state() && self.odp.dmc.is_some() {
+                            self.prop_instance.state = est.state();
+                        }
+                        est
+                    }
+                };
+                self.solution.push_time_update(est);
+                if let Some(augmented) = &self.kf.augmented {
+                    self.solution.set_augmented_estimates(augmented);
+                }
+                if let Some(consider) = self.consider.as_mut() {
+                    consider.time_update(
+                        &self.odp.consider,
+                        &self.odp.prop,
+                        self.odp.almanac.clone(),
+                        &nominal_state,
+                    )?;
+                }
+                self.prop_instance.state.reset_stm();
+                if let Some(consider) = self.consider.as_mut() {
+                    consider.rebase(self.prop_instance.state);
+                    self.solution
+                        .set_consider_sensitivity(consider.sensitivity.clone());
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,399 @@
+/*
+    Nyx, blazing fast astrodynamics
+    Copyright (C) 2018-onwards Christopher Rabotin <christopher.rabotin@gmail.com>
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published
+    by the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::linalg::allocator::Allocator;
+use crate::linalg::{DefaultAllocator, DimName, OMatrix, OVector};
+use crate::md::trajectory::Interpolatable;
+use crate::od::msr::MeasurementType;
+use crate::od::*;
+use indexmap::{IndexMap, IndexSet};
+use msr::sensitivity::TrackerSensitivity;
+use snafu::prelude::*;
+use std::ops::Add;
+
+use super::{KalmanODProcess, ResidRejectCrit};
+use crate::od::estimate::{Estimate, KfEstimate, Residual};
+use crate::od::kalman::{KalmanFilter, KalmanVariant};
+
+/// Weights of the unscented transform, computed from the scaling parameters of the filter.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct UnscentedWeights {
+    /// Scaling of the square root of the covariance to build the sigma points, i.e. sqrt(n + lambda)
+    pub(crate) spread: f64,
+    /// Weight of the central sigma point when computing the mean
+    pub(crate) mean_0: f64,
+    /// Weight of the central sigma point when computing the covariance
+    pub(crate) covar_0: f64,
+    /// Weight of all other sigma points, for both the mean and the covariance
+    pub(crate) other: f64,
+}
+
+impl UnscentedWeights {
+    pub(crate) fn new(n: usize, alpha: f64, beta: f64, kappa: f64) -> Self {
+        let n = n as f64;
+        let lambda = alpha.powi(2) * (n + kappa) - n;
+        let mean_0 = lambda / (n + lambda);
+        Self {
+            spread: (n + lambda).sqrt(),
+            mean_0,
+            covar_0: mean_0 + 1.0 - alpha.powi(2) + beta,
+            other: 1.0 / (2.0 * (n + lambda)),
+        }
+    }
+}
+
+/// Lower triangular square root of a positive semi-definite matrix.
+///
+/// Unlike a Cholesky decomposition, this does not fail if a diagonal element is zero (e.g. unestimated parameters):
+/// the corresponding column of the square root is zero.
+pub(crate) fn psd_sqrt<N: DimName>(matrix: &OMatrix<f64, N, N>) -> OMatrix<f64, N, N>
+where
+    DefaultAllocator: Allocator<N> + Allocator<N, N>,
+{
+    let tol = f64::EPSILON * matrix.diagonal().amax();
+    let mut sqrt = OMatrix::<f64, N, N>::zeros();
+    for j in 0..N::DIM {
+        let diag = matrix[(j, j)] - (0..j).map(|k| sqrt[(j, k)].powi(2)).sum::<f64>();
+        if diag <= tol {
+            continue;
+        }
+        let diag_sqrt = diag.sqrt();
+        sqrt[(j, j)] = diag_sqrt;
+        for i in j + 1..N::DIM {
+            sqrt[(i, j)] = (matrix[(i, j)]
+                - (0..j).map(|k| sqrt[(i, k)] * sqrt[(j, k)]).sum::<f64>())
+                / diag_sqrt;
+        }
+    }
+    sqrt
+}
+
+impl<
+        D: Dynamics,
+        MsrSize: DimName,
+        Accel: DimName,
+        Trk: TrackerSensitivity<D::StateType, D::StateType>,
+    > KalmanODProcess<D, MsrSize, Accel, Trk>
+where
+    D::StateType:
+        Interpolatable + Add<OVector<f64, <D::StateType as State>::Size>, Output = D::StateType>,
+    <DefaultAllocator as Allocator<<D::StateType as State>::VecLength>>::Buffer<f64>: Send,
+    <DefaultAllocator as Allocator<<D::StateType as State>::Size>>::Buffer<f64>: Copy,
+    <DefaultAllocator as Allocator<<D::StateType as State>::Size, <D::StateType as State>::Size>>::Buffer<f64>: Copy,
+    DefaultAllocator: Allocator<<D::StateType as State>::Size>
+        + Allocator<<D::StateType as State>::VecLength>
+        + Allocator<MsrSize>
+        + Allocator<MsrSize, <D::StateType as State>::Size>
+        + Allocator<<D::StateType as State>::Size, MsrSize>
+        + Allocator<MsrSize, MsrSize>
+        + Allocator<<D::StateType as State>::Size, <D::StateType as State>::Size>
+        + Allocator<Accel>
+        + Allocator<Accel, Accel>
+        + Allocator<<D::StateType as State>::Size, Accel>
+        + Allocator<Accel, <D::StateType as State>::Size>
+        + Allocator<nalgebra::Const<1>, MsrSize>,
+{
+    fn unscented_weights(&self) -> UnscentedWeights {
+        match self.kf_variant {
+            KalmanVariant::Unscented { alpha, beta, kappa } => UnscentedWeights::new(
+                <D::StateType as State>::Size::DIM,
+                alpha,
+                beta,
+                kappa,
+            ),
+            _ => unreachable!("unscented transform used with {:?}", self.kf_variant),
+        }
+    }
+
+    /// Returns the offsets of the sigma points from the mean: the columns of the scaled square root of the covariance, then their opposites.
+    fn sigma_offsets(
+        covar: &OMatrix<f64, <D::StateType as State>::Size, <D::StateType as State>::Size>,
+        weights: UnscentedWeights,
+    ) -> Vec<OVector<f64, <D::StateType as State>::Size>> {
+        let sqrt = psd_sqrt(covar) * weights.spread;
+        let columns = sqrt.column_iter().map(|col| col.into_owned());
+        columns
+            .clone()
+            .chain(columns.map(|col| -col))
+            .collect::<Vec<_>>()
+    }
+
+    /// Returns the estimated part of the difference between two states.
+    fn state_delta(
+        state: &D::StateType,
+        reference: &D::StateType,
+    ) -> OVector<f64, <D::StateType as State>::Size> {
+        let delta = state.to_vector() - reference.to_vector();
+        OVector::<f64, <D::StateType as State>::Size>::from_iterator(delta.iter().copied())
+    }
+
+    /// Propagates the sigma points of the previous estimate of the filter until the epoch of the provided central sigma point,
+    /// i.e. the mean of the previous estimate propagated with its STM, and returns the predicted estimate from their weighted
+    /// mean and covariance, including any process noise.
+    pub(crate) fn unscented_time_update(
+        &self,
+        kf: &mut KalmanFilter<D::StateType, Accel>,
+        central: D::StateType,
+    ) -> Result<KfEstimate<D::StateType>, ODError> {
+        let weights = self.unscented_weights();
+        let prev_estimate = kf.prev_estimate;
+        let mean = prev_estimate.state();
+        let epoch = central.epoch();
+
+        let propagate = |state: D::StateType| -> Result<D::StateType, ODError> {
+            let mut prop_instance = self.prop.with(state, self.almanac.clone()).quiet();
+            if !prop_instance.fixed_step {
+                prop_instance.set_step(self.max_step, false);
+            }
+            prop_instance.until_epoch(epoch).context(ODPropSnafu)
+        };
+
+        // The STM of the central sigma point is only reported for information.
+        let stm = central.stm().context(ODDynamicsSnafu)?;
+
+        let mut deltas = Vec::with_capacity(2 * <D::StateType as State>::Size::DIM);
+        for offset in Self::sigma_offsets(&prev_estimate.covar, weights) {
+            if offset.iter().all(|val| *val == 0.0) {
+                // Unestimated parameters do not spread the sigma points.
+                deltas.push(offset);
+            } else {
+                let mut sigma = mean + offset;
+                sigma.unset_stm();
+                deltas.push(Self::state_delta(&propagate(sigma)?, &central));
+            }
+        }
+
+        let mean_delta = deltas
+            .iter()
+            .fold(OVector::<f64, <D::StateType as State>::Size>::zeros(), |acc, delta| {
+                acc + delta * weights.other
+            });
+
+        let mut covar_bar =
+            OMatrix::<f64, <D::StateType as State>::Size, <D::StateType as State>::Size>::zeros();
+        covar_bar.ger(weights.covar_0, &mean_delta, &mean_delta, 1.0);
+        for delta in &deltas {
+            let dev = delta - mean_delta;
+            covar_bar.ger(weights.other, &dev, &dev, 1.0);
+        }
+
+        let nominal_state = central + mean_delta;
+
+        if let Some(snc_covar) = kf.process_noise_covar(&nominal_state)? {
+            covar_bar += snc_covar;
+        }
+
+        let estimate = KfEstimate {
+            nominal_state,
+            state_deviation: OVector::<f64, <D::StateType as State>::Size>::zeros(),
+            covar: covar_bar,
+            covar_bar,
+            stm,
+            predicted: true,
+        };
+        kf.prev_estimate = estimate;
+        // Update the prev epoch for all SNCs
+        for snc in &mut kf.process_noise {
+            snc.prev_epoch = Some(epoch);
+        }
+        Ok(estimate)
+    }
+
+    /// Computes the measurement update of the predicted estimate of the filter from the measurements of its sigma points.
+    ///
+    /// Returns None if any sigma point is not visible from the tracking device.
+    #[allow(clippy::too_many_arguments)]
+    #[allow(clippy::type_complexity)]
+    pub(crate) fn unscented_measurement_update(
+        &self,
+        kf: &mut KalmanFilter<D::StateType, Accel>,
+        device: &mut Trk,
+        mut real_obs: OVector<f64, MsrSize>,
+        msr_types: &IndexSet<MeasurementType>,
+        moduli: Option<&IndexMap<MeasurementType, f64>>,
+        resid_rejection: Option<ResidRejectCrit>,
+    ) -> Result<
+        Option<(
+            KfEstimate<D::StateType>,
+            Residual<MsrSize>,
+            Option<OMatrix<f64, <D::StateType as State>::Size, MsrSize>>,
+        )>,
+        ODError,
+    > {
+        let weights = self.unscented_weights();
+        let pred_estimate = kf.prev_estimate;
+        let mean = pred_estimate.state();
+        let epoch = mean.epoch();
+
+        let r_k = device.measurement_covar_matrix::<MsrSize>(msr_types, epoch)?;
+        let bias = device.measurement_bias_vector::<MsrSize>(msr_types, epoch)?;
+
+        let mut measure = |state: D::StateType| -> Result<Option<OVector<f64, MsrSize>>, ODError> {
+            Ok(device
+                .measure_instantaneous(state, None, self.almanac.clone())?
+                .map(|msr| msr.observation::<MsrSize>(msr_types) - &bias))
+        };
+
+        let Some(central_obs) = measure(mean)? else {
+            return Ok(None);
+        };
+
+        let offsets = Self::sigma_offsets(&pred_estimate.covar, weights);
+        let mut sigma_obs = Vec::with_capacity(offsets.len());
+        for offset in &offsets {
+            if offset.iter().all(|val| *val == 0.0) {
+                sigma_obs.push(central_obs.clone());
+            } else {
+                match measure(mean + *offset)? {
+                    Some(obs) => sigma_obs.push(obs),
+                    None => return Ok(None),
+                }
+            }
+        }
+
+        let computed_obs = sigma_obs
+            .iter()
+            .fold(&central_obs * weights.mean_0, |acc, obs| {
+                acc + obs * weights.other
+            });
+
+        // Apply the modulo to the real obs
+        if let Some(moduli) = moduli {
+            for (i, msr_type) in msr_types.iter().enumerate() {
+                if let Some(modulus) = moduli.get(msr_type) {
+                    real_obs[i] += computed_obs[i].div_euclid(*modulus) * *modulus;
+                }
+            }
+        }
+
+        // Compute the innovation covariance and the cross covariance of the state and the measurements.
+        let central_dev = &central_obs - &computed_obs;
+        let mut s_k = r_k.clone();
+        s_k.ger(weights.covar_0, &central_dev, &central_dev, 1.0);
+        let mut p_xy = OMatrix::<f64, <D::StateType as State>::Size, MsrSize>::zeros();
+        for (offset, obs) in offsets.iter().zip(&sigma_obs) {
+            let dev = obs - &computed_obs;
+            s_k.ger(weights.other, &dev, &dev, 1.0);
+            p_xy.ger(weights.other, offset, &dev, 1.0);
+        }
+
+        let prefit = &real_obs - &computed_obs;
+
+        let r_k_chol = match s_k.clone().cholesky() {
+            Some(s_k_chol) => s_k_chol.l(),
+            None => r_k.clone().cholesky().ok_or(ODError::SingularNoiseRk)?.l(),
+        };
+
+        // Compute the ratio as the average of each component of the prefit over the square root of the innovation covariance.
+        let ratio = s_k
+            .diagonal()
+            .iter()
+            .copied()
+            .enumerate()
+            .map(|(idx, r)| prefit[idx] / r.sqrt())
+            .sum::<f64>()
+            / (MsrSize::DIM as f64);
+
+        if let Some(resid_reject) = resid_rejection {
+            if ratio.abs() > resid_reject.num_sigmas {
+                // Reject this whole measurement: the estimate remains the prediction.
+                return Ok(Some((
+                    pred_estimate,
+                    Residual::rejected(
+                        epoch,
+                        prefit,
+                        ratio,
+                        r_k_chol.diagonal(),
+                        real_obs,
+                        computed_obs,
+                    ),
+                    None,
+                )));
+            }
+        }
+
+        let s_k_inv = s_k.clone().try_inverse().ok_or(ODError::SingularKalmanGain)?;
+        let gain = &p_xy * s_k_inv;
+
+        let nominal_state = mean + &gain * &prefit;
+        let covar = pred_estimate.covar - &gain * s_k * gain.transpose();
+
+        // The postfit residual is computed from the measurement of the updated state.
+        let postfit = match measure(nominal_state)? {
+            Some(obs) => &real_obs - obs,
+            None => OVector::<f64, MsrSize>::zeros(),
+        };
+
+        let estimate = KfEstimate {
+            nominal_state,
+            state_deviation: OVector::<f64, <D::StateType as State>::Size>::zeros(),
+            covar,
+            covar_bar: pred_estimate.covar_bar,
+            stm: pred_estimate.stm,
+            predicted: false,
+        };
+        kf.prev_estimate = estimate;
+
+        Ok(Some((
+            estimate,
+            Residual::accepted(
+                epoch,
+                prefit,
+                postfit,
+                ratio,
+                r_k_chol.diagonal(),
+                real_obs,
+                computed_obs,
+            ),
+            Some(gain),
+        )))
+    }
+}
+
+#[cfg(test)]
+mod ut_unscented {
+    use super::{psd_sqrt, UnscentedWeights};
+    use crate::linalg::{Matrix3, Matrix6};
+
+    #[test]
+    fn unscented_weights_sum() {
+        for (alpha, beta, kappa) in [(1.0, 2.0, 0.0), (1e-3, 2.0, 0.0), (0.5, 2.0, 3.0 - 6.0)] {
+            let weights = UnscentedWeights::new(6, alpha, beta, kappa);
+            let mean_sum = weights.mean_0 + 12.0 * weights.other;
+            assert!((mean_sum - 1.0).abs() < 1e-9, "{weights:?}");
+            assert!((weights.covar_0 - weights.mean_0 - (1.0 - alpha.powi(2) + beta)).abs() < 1e-9);
+        }
+        // The default configuration spreads the sigma points by sqrt(n) and ignores the central point in the mean.
+        let weights = UnscentedWeights::new(6, 1.0, 2.0, 0.0);
+        assert_eq!(weights.spread, 6.0_f64.sqrt());
+        assert_eq!(weights.mean_0, 0.0);
+    }
+
+    #[test]
+    fn psd_sqrt_with_zero_variance() {
+        let covar = Matrix3::new(4.0, 2.0, 0.0, 2.0, 5.0, 0.0, 0.0, 0.0, 0.0);
+        let sqrt = psd_sqrt(&covar);
+        assert!((sqrt * sqrt.transpose() - covar).norm() < 1e-12);
+        assert_eq!(sqrt.column(2).norm(), 0.0);
+
+        let covar = Matrix6::from_diagonal_element(1e-2) + Matrix6::from_element(1e-3);
+        let sqrt = psd_sqrt(&covar);
+        let chol = covar.cholesky().unwrap().l();
+        assert!((sqrt - chol).norm() < 1e-12);
+    }
+}
@@ -16,11 +16,17 @@
     along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
+use crate::cosmic::AstroPhysicsSnafu;
 use crate::dynamics::guidance::LocalFrame;
+use crate::errors::StateAstroSnafu;
 use crate::linalg::allocator::Allocator;
 use crate::linalg::{DefaultAllocator, DimName, OMatrix, OVector, U3, U6};
+use crate::md::StateParameter;
+use crate::od::{ODError, ODStateSnafu};
 use crate::time::{Duration, Epoch};
+use anise::prelude::Orbit;
 use log::debug;
+use snafu::ResultExt;
 use std::fmt;
 
 #[allow(clippy::upper_case_acronyms)]
@@ -205,6 +211,87 @@ where
 
         Some(snc)
     }
+
+    /// Returns the covariance which this process noise adds to a state of size N over the provided duration, i.e. Gamma Q Gamma^T,
+    /// where the SNC is rotated from its local frame into the frame of the orbit, and Gamma assumes a constant acceleration.
+    /// May be None if this SNC does not apply at the epoch of the orbit, cf. [Self::to_matrix].
+    pub(crate) fn state_covar<N: DimName>(
+        &self,
+        orbit: Orbit,
+        delta_t_s: f64,
+    ) -> Result<Option<OMatrix<f64, N, N>>, ODError>
+    where
+        DefaultAllocator: Allocator<N, N> + Allocator<N, A> + Allocator<A, N>,
+    {
+        let Some(mut snc_matrix) = self.to_matrix(orbit.epoch) else {
+            return Ok(None);
+        };
+
+        if let Some(local_frame) = self.local_frame {
+            // Rotate the SNC from the definition frame into the state frame.
+            let dcm = local_frame
+                .dcm_to_inertial(orbit)
+                .context(AstroPhysicsSnafu)
+                .context(StateAstroSnafu {
+                    param: StateParameter::Epoch,
+                })
+                .context(ODStateSnafu {
+                    action: "rotating SNC from definition frame into state frame",
+                })?;
+
+            // Note: the SNC must be a diagonal matrix, so we only update the diagonals!
+            match A::DIM {
+                3 => {
+                    let new_snc =
+                        dcm.rot_mat * snc_matrix.fixed_view::<3, 3>(0, 0) * dcm.rot_mat.transpose();
+                    for i in 0..A::DIM {
+                        snc_matrix[(i, i)] = new_snc[(i, i)];
+                    }
+                }
+                6 => {
+                    let new_snc = dcm.state_dcm()
+                        * snc_matrix.fixed_view::<6, 6>(0, 0)
+                        * dcm.transpose().state_dcm();
+                    for i in 0..A::DIM {
+                        snc_matrix[(i, i)] = new_snc[(i, i)];
+                    }
+                }
+                _ => {
+                    return Err(ODError::ODLimitation {
+                        action: "only process noises of size 3x3 or 6x6 are supported",
+                    })
+                }
+            }
+        }
+
+        // Let's compute the Gamma matrix, an approximation of the time integral
+        // which assumes that the acceleration is constant between these two measurements.
+        let mut gamma = OMatrix::<f64, N, A>::zeros();
+        for blk in 0..A::dim() / 3 {
+            for i in 0..3 {
+                let idx_i = i + A::dim() * blk;
+                let idx_j = i + 3 * blk;
+                let idx_k = i + 3 + A::dim() * blk;
+                // For first block
+                // (0, 0) (1, 1) (2, 2) <=> \Delta t^2/2
+                // (3, 0) (4, 1) (5, 2) <=> \Delta t
+                // Second block
+                // (6, 3) (7, 4) (8, 5) <=> \Delta t^2/2
+                // (9, 3) (10, 4) (11, 5) <=> \Delta t
+                // * \Delta t^2/2
+                // (i, i) when blk = 0
+                // (i + A::dim() * blk, i + 3) when blk = 1
+                // (i + A::dim() * blk, i + 3 * blk)
+                // * \Delta t
+                // (i + 3, i) when blk = 0
+                // (i + 3, i + 9) when blk = 1 (and I think i + 12 + 3)
+                // (i + 3 + A::dim() * blk, i + 3 * blk)
+                gamma[(idx_i, idx_j)] = delta_t_s.powi(2) / 2.0;
+                gamma[(idx_k, idx_j)] = delta_t_s;
+            }
+        }
+        Ok(Some(&gamma * snc_matrix * &gamma.transpose()))
+    }
 }
 
 impl ProcessNoise3D {
@@ -266,3 +353,40 @@ fn test_snc_init() {
     );
     assert_eq!(snc_vel.local_frame, Some(LocalFrame::RIC));
 }
+
+#[test]
+fn test_snc_state_covar() {
+    use crate::linalg::U9;
+    use anise::constants::frames::EARTH_J2000;
+
+    let epoch = Epoch::from_et_seconds(3600.0);
+    let orbit = Orbit::cartesian(7000.0, 100.0, 0.0, 0.1, 7.5, 1.0, epoch, EARTH_J2000);
+    let delta_t_s = 10.0;
+
+    let snc = ProcessNoise3D::from_diagonal(Duration::MAX, &[1e-12, 2e-12, 3e-12]);
+    let covar = snc.state_covar::<U9>(orbit, delta_t_s).unwrap().unwrap();
+    for k in 0..3 {
+        let q = (k + 1) as f64 * 1e-12;
+        for (idx, expected) in [
+            ((k, k), q * delta_t_s.powi(4) / 4.0),
+            ((k, k + 3), q * delta_t_s.powi(3) / 2.0),
+            ((k + 3, k), q * delta_t_s.powi(3) / 2.0),
+            ((k + 3, k + 3), q * delta_t_s.powi(2)),
+        ] {
+            assert!((covar[idx] - expected).abs() < 1e-12 * expected);
+        }
+    }
+    // The mass is not affected by the process noise
+    assert_eq!(covar.row(6).norm(), 0.0);
+
+    // Rotating an isotropic SNC from a local frame does not change it
+    let mut snc_ric = ProcessNoise3D::from_diagonal(Duration::MAX, &[1e-12; 3]);
+    snc_ric.local_frame = Some(LocalFrame::RIC);
+    let covar_ric = snc_ric
+        .state_covar::<U9>(orbit, delta_t_s)
+        .unwrap()
+        .unwrap();
+    let snc = ProcessNoise3D::from_diagonal(Duration::MAX, &[1e-12; 3]);
+    let covar = snc.state_covar::<U9>(orbit, delta_t_s).unwrap().unwrap();
+    assert!((covar_ric - covar).norm() < 1e-12 * covar.norm());
+}
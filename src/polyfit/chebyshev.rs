@@ -0,0 +1,138 @@
+/*
+    Nyx, blazing fast astrodynamics
+    Copyright (C) 2018-onwards Christopher Rabotin <christopher.rabotin@gmail.com>
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published
+    by the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::linalg::{DMatrix, DVector};
+use serde_derive::{Deserialize, Serialize};
+use std::f64::consts::PI;
+use std::fmt;
+
+/// A series of Chebyshev polynomials of the first kind, defined on the normalized interval [-1, 1].
+///
+/// Unlike [`Polynomial`](super::Polynomial), the degree of the series is only known at runtime because it is chosen when fitting the data.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ChebyshevSeries {
+    /// Coefficients ordered by degree, e.g. index 0 is the coefficient of T0, 1 of T1, etc.
+    pub coefficients: Vec<f64>,
+}
+
+impl ChebyshevSeries {
+    /// Returns the `n` Chebyshev-Gauss nodes in [-1, 1], sorted in increasing order.
+    pub fn nodes(n: usize) -> Vec<f64> {
+        (0..n)
+            .map(|k| -((2 * k + 1) as f64 * PI / (2 * n) as f64).cos())
+            .collect()
+    }
+
+    /// Fits the series of the provided degree to values sampled anywhere in [-1, 1], in the least squares sense.
+    ///
+    /// Returns None if there are fewer samples than coefficients or if the system cannot be solved.
+    pub fn least_squares(x: &[f64], values: &[f64], degree: usize) -> Option<Self> {
+        let n = degree + 1;
+        if x.len() != values.len() || x.len() < n {
+            return None;
+        }
+        // Each row holds the Chebyshev polynomials evaluated at a sample.
+        let basis = DMatrix::from_fn(x.len(), n, |i, j| {
+            (j as f64 * x[i].clamp(-1.0, 1.0).acos()).cos()
+        });
+        let coefficients = basis
+            .svd(true, true)
+            .solve(&DVector::from_column_slice(values), f64::EPSILON)
+            .ok()?;
+
+        Some(Self {
+            coefficients: coefficients.iter().copied().collect(),
+        })
+    }
+
+    /// Returns the degree of this series
+    pub fn degree(&self) -> usize {
+        self.coefficients.len().saturating_sub(1)
+    }
+
+    /// Evaluates the series at the provided position in [-1, 1] using the Clenshaw recurrence
+    pub fn eval(&self, x: f64) -> f64 {
+        let (mut b_k1, mut b_k2) = (0.0, 0.0);
+        for c_k in self.coefficients.iter().skip(1).rev() {
+            let b_k = c_k + 2.0 * x * b_k1 - b_k2;
+            b_k2 = b_k1;
+            b_k1 = b_k;
+        }
+        self.coefficients.first().copied().unwrap_or(0.0) + x * b_k1 - b_k2
+    }
+}
+
+impl fmt::Display for ChebyshevSeries {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let terms: Vec<String> = self
+            .coefficients
+            .iter()
+            .enumerate()
+            .map(|(n, c_n)| format!("{c_n:e} T{n}"))
+            .collect();
+        write!(f, "{}", terms.join(" + "))
+    }
+}
+
+#[cfg(test)]
+mod ut_chebyshev {
+    use super::ChebyshevSeries;
+
+    #[test]
+    fn fit_polynomial() {
+        // A cubic is exactly represented by a series of degree three.
+        let f = |x: f64| 2.0 * x.powi(3) - 0.5 * x.powi(2) + x - 3.0;
+
+        let nodes = ChebyshevSeries::nodes(4);
+        let values: Vec<f64> = nodes.iter().map(|x| f(*x)).collect();
+        let series = ChebyshevSeries::least_squares(&nodes, &values, 3).unwrap();
+        assert_eq!(series.degree(), 3);
+
+        for x in [-1.0, -0.3, 0.0, 0.25, 0.9, 1.0] {
+            assert!((series.eval(x) - f(x)).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn least_squares() {
+        // Uniformly spaced noisy samples of a quadratic
+        let x: Vec<f64> = (0..=40).map(|i| -1.0 + i as f64 / 20.0).collect();
+        let values: Vec<f64> = x
+            .iter()
+            .enumerate()
+            .map(|(i, x)| 3.0 * x.powi(2) - 1.0 + if i % 2 == 0 { 1e-6 } else { -1e-6 })
+            .collect();
+        let series = ChebyshevSeries::least_squares(&x, &values, 2).unwrap();
+        // 3x^2 - 1 = 1.5 T2 + 0.5 T0
+        assert!((series.coefficients[0] - 0.5).abs() < 1e-6);
+        assert!(series.coefficients[1].abs() < 1e-6);
+        assert!((series.coefficients[2] - 1.5).abs() < 1e-6);
+
+        assert!(ChebyshevSeries::least_squares(&x[..2], &values[..2], 2).is_none());
+    }
+
+    #[test]
+    fn fit_cosine() {
+        let nodes = ChebyshevSeries::nodes(16);
+        let values: Vec<f64> = nodes.iter().map(|x| (3.0 * x).cos()).collect();
+        let series = ChebyshevSeries::least_squares(&nodes, &values, 15).unwrap();
+        for x in [-0.95, -0.5, 0.1, 0.7] {
+            assert!((series.eval(x) - (3.0 * x).cos()).abs() < 1e-10);
+        }
+    }
+}
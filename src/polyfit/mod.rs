@@ -16,6 +16,8 @@
     along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
+mod chebyshev;
 mod polynomial;
 
+pub use chebyshev::ChebyshevSeries;
 pub use polynomial::{CommonPolynomial, Polynomial};
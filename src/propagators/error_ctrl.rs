@@ -17,6 +17,7 @@
 */
 
 use serde::{Deserialize, Serialize};
+use typed_builder::TypedBuilder;
 
 use crate::linalg::allocator::Allocator;
 use crate::linalg::{DefaultAllocator, DimName, OVector, U3};
@@ -26,7 +27,7 @@ use crate::linalg::{DefaultAllocator, DimName, OVector, U3};
 const REL_ERR_THRESH: f64 = 0.1;
 
 /// The Error Control manages how a propagator computes the error in the current step.
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum ErrorControl {
     /// An RSS state error control which effectively for the provided vector composed of two vectors of the same unit, both of size 3 (e.g. position + velocity).
     RSSCartesianState,
@@ -67,6 +68,13 @@ pub enum ErrorControl {
     /// For example, one should probably use this for position independently of using it for the velocity.
     /// (Source)[https://github.com/ChristopherRabotin/GMAT/blob/37201a6290e7f7b941bc98ee973a527a5857104b/src/base/forcemodel/ODEModel.cpp#L3033]
     LargestStep,
+
+    /// A weighted error control with per-component absolute and relative tolerances, including the mass and estimated parameters of a spacecraft.
+    ///
+    /// Each component of the error estimate is divided by `abs_tol + rel_tol * max(|candidate|, |cur_state|)`, and the largest
+    /// of these scaled errors is returned. The error is therefore unitless and equal to one when the worst component is exactly
+    /// at its tolerance: the integrator tolerance should be set to 1.0, cf. [`IntegratorOptions::with_weighted_error_ctrl`](super::IntegratorOptions::with_weighted_error_ctrl).
+    Weighted(WeightedErrorControl),
 }
 
 impl ErrorControl {
@@ -182,6 +190,7 @@ impl ErrorControl {
                     err
                 }
             }
+            ErrorControl::Weighted(weights) => weights.estimate(error_est, candidate, cur_state),
         }
     }
 }
@@ -192,6 +201,113 @@ impl Default for ErrorControl {
     }
 }
 
+/// Absolute and relative tolerances of a component of the state vector.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ComponentTolerance {
+    /// Absolute tolerance, in the unit of the component (e.g. km for the position)
+    pub abs_tol: f64,
+    /// Relative tolerance, unitless
+    pub rel_tol: f64,
+}
+
+impl ComponentTolerance {
+    pub fn new(abs_tol: f64, rel_tol: f64) -> Self {
+        Self { abs_tol, rel_tol }
+    }
+
+    /// Returns the error scaled by the tolerance of this component, where the relative tolerance applies to the largest magnitude of the two states.
+    fn scaled(&self, error: f64, candidate: f64, cur_state: f64) -> f64 {
+        let scale = self.abs_tol + self.rel_tol * candidate.abs().max(cur_state.abs());
+        if scale > 0.0 {
+            error.abs() / scale
+        } else {
+            error.abs()
+        }
+    }
+}
+
+/// Per-component tolerances of the weighted error control.
+///
+/// The state vector is expected to be organized as the propagated vector of an orbit or of a spacecraft:
+/// `[X, Y, Z, Vx, Vy, Vz, Cr, Cd, Fuel mass, STM]`, where the Cr, Cd and fuel mass components only exist for spacecraft.
+/// The STM components are only included in the error norm if the `stm` tolerance is set.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize, TypedBuilder)]
+#[builder(doc)]
+pub struct WeightedErrorControl {
+    /// Tolerance on each position component, absolute tolerance in km
+    #[builder(default = ComponentTolerance::new(1e-9, 1e-12))]
+    pub position: ComponentTolerance,
+    /// Tolerance on each velocity component, absolute tolerance in km/s
+    #[builder(default = ComponentTolerance::new(1e-12, 1e-12))]
+    pub velocity: ComponentTolerance,
+    /// Tolerance on the coefficient of reflectivity
+    #[builder(default = ComponentTolerance::new(1e-9, 1e-9))]
+    pub coeff_reflectivity: ComponentTolerance,
+    /// Tolerance on the coefficient of drag
+    #[builder(default = ComponentTolerance::new(1e-9, 1e-9))]
+    pub coeff_drag: ComponentTolerance,
+    /// Tolerance on the propellant mass, absolute tolerance in kg
+    #[builder(default = ComponentTolerance::new(1e-6, 1e-12))]
+    pub prop_mass: ComponentTolerance,
+    /// Tolerance on each component of the STM, if it should be included in the error norm
+    #[builder(default, setter(strip_option))]
+    pub stm: Option<ComponentTolerance>,
+}
+
+impl WeightedErrorControl {
+    /// Computes the largest error of all components, each scaled by its own tolerance.
+    pub fn estimate<N: DimName>(
+        &self,
+        error_est: &OVector<f64, N>,
+        candidate: &OVector<f64, N>,
+        cur_state: &OVector<f64, N>,
+    ) -> f64
+    where
+        DefaultAllocator: Allocator<N>,
+    {
+        let state_size = Self::state_size(N::dim());
+
+        let mut max_err = 0.0_f64;
+        for i in 0..N::dim() {
+            let tol = if i >= state_size {
+                match self.stm {
+                    Some(tol) => tol,
+                    None => break,
+                }
+            } else {
+                match i {
+                    0..=2 => self.position,
+                    3..=5 => self.velocity,
+                    6 => self.coeff_reflectivity,
+                    7 => self.coeff_drag,
+                    8 => self.prop_mass,
+                    // Any other parameter is controlled like the position
+                    _ => self.position,
+                }
+            };
+            max_err = max_err.max(tol.scaled(error_est[i], candidate[i], cur_state[i]));
+        }
+        max_err
+    }
+
+    /// Returns the size of the state without its STM, i.e. `n` such that `n + n^2` is the size of the propagated vector.
+    /// If there is no such `n` of at least six, then the whole vector is considered to be the state.
+    fn state_size(vec_len: usize) -> usize {
+        let n = ((((1 + 4 * vec_len) as f64).sqrt() - 1.0) / 2.0).round() as usize;
+        if n >= 6 && n + n * n == vec_len {
+            n
+        } else {
+            vec_len
+        }
+    }
+}
+
+impl Default for WeightedErrorControl {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
 /// An RSS step error control which effectively computes the L2 norm of the provided Vector of size 3
 ///
 /// Note that this error controller should be preferably be used only with slices of a state with the same units.
@@ -249,3 +365,69 @@ impl RSSState {
         }
     }
 }
+
+#[cfg(test)]
+mod ut_error_ctrl {
+    use super::{ComponentTolerance, ErrorControl, WeightedErrorControl};
+    use crate::linalg::{Const, OVector};
+
+    #[test]
+    fn weighted_mass_error() {
+        let ctrl = ErrorControl::Weighted(
+            WeightedErrorControl::builder()
+                .prop_mass(ComponentTolerance::new(1e-3, 0.0))
+                .build(),
+        );
+
+        let mut cur_state = OVector::<f64, Const<90>>::zeros();
+        cur_state[0] = 7000.0;
+        cur_state[4] = 7.5;
+        cur_state[6] = 1.8;
+        cur_state[7] = 2.2;
+        cur_state[8] = 100.0;
+        let candidate = cur_state;
+
+        let mut error_est = OVector::<f64, Const<90>>::zeros();
+        error_est[8] = 2e-3;
+        // Mass error is twice its tolerance
+        assert!((ctrl.estimate(&error_est, &candidate, &cur_state) - 2.0).abs() < 1e-9);
+
+        error_est[8] = 0.0;
+        error_est[0] = 7e-9;
+        // Position error is 7e-9 km, scale is 1e-9 + 7000 * 1e-12 = 8e-9
+        assert!((ctrl.estimate(&error_est, &candidate, &cur_state) - 7.0 / 8.0).abs() < 1e-9);
+
+        // STM errors are ignored unless requested
+        error_est[0] = 0.0;
+        error_est[50] = 1.0;
+        assert!(ctrl.estimate(&error_est, &candidate, &cur_state).abs() < f64::EPSILON);
+
+        let ctrl = ErrorControl::Weighted(
+            WeightedErrorControl::builder()
+                .stm(ComponentTolerance::new(1e-2, 0.0))
+                .build(),
+        );
+        assert!((ctrl.estimate(&error_est, &candidate, &cur_state) - 100.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn weighted_orbit_stm() {
+        // Orbit vector is 6 + 36 STM components
+        assert_eq!(WeightedErrorControl::state_size(42), 6);
+        assert_eq!(WeightedErrorControl::state_size(90), 9);
+        assert_eq!(WeightedErrorControl::state_size(6), 6);
+        assert_eq!(WeightedErrorControl::state_size(9), 9);
+    }
+
+    #[test]
+    fn weighted_serde() {
+        let ctrl = ErrorControl::Weighted(
+            WeightedErrorControl::builder()
+                .stm(ComponentTolerance::new(1e-6, 1e-6))
+                .build(),
+        );
+        let serialized = serde_yml::to_string(&ctrl).unwrap();
+        let deserd: ErrorControl = serde_yml::from_str(&serialized).unwrap();
+        assert_eq!(deserd, ctrl);
+    }
+}
@@ -21,7 +21,7 @@ use crate::dynamics::{Dynamics, DynamicsAlmanacSnafu};
 use crate::linalg::allocator::Allocator;
 use crate::linalg::{DefaultAllocator, OVector};
 use crate::md::trajectory::{Interpolatable, Traj};
-use crate::md::EventEvaluator;
+use crate::md::{EventEvaluator, StopConditions, StoppingEvent};
 use crate::propagators::TrajectoryEventSnafu;
 use crate::time::{Duration, Epoch, Unit};
 use crate::State;
@@ -364,6 +364,27 @@ where
         }
     }
 
+    /// Propagate until the provided set of stopping conditions is met, e.g. the first of several events.
+    /// Returns which condition stopped the propagation with the details of its event, and the trajectory until `max_duration`.
+    pub fn until_stop_conditions(
+        &mut self,
+        max_duration: Duration,
+        conditions: &StopConditions<D::StateType>,
+    ) -> Result<(StoppingEvent<D::StateType>, Traj<D::StateType>), PropagationError>
+    where
+        <DefaultAllocator as Allocator<<D::StateType as State>::VecLength>>::Buffer<f64>: Send,
+        D::StateType: Interpolatable,
+    {
+        info!("Searching for {conditions}");
+
+        let (_, traj) = self.for_duration_with_traj(max_duration)?;
+        let stop = conditions
+            .find_in(&traj, None, self.almanac.clone())
+            .context(TrajectoryEventSnafu)?;
+
+        Ok((stop, traj))
+    }
+
     /// Take a single propagator step and emit the result on the TX channel (if enabled)
     pub fn single_step(&mut self) -> Result<(), PropagationError> {
         let (t, state_vec) = self.derive()?;
@@ -20,7 +20,7 @@ use std::fmt;
 
 use crate::time::{Duration, Unit};
 
-use super::ErrorControl;
+use super::{ErrorControl, WeightedErrorControl};
 use anise::frames::Frame;
 use serde::{Deserialize, Serialize};
 use typed_builder::TypedBuilder;
@@ -89,6 +89,16 @@ impl IntegratorOptions {
         )
     }
 
+    /// Initializes an adaptive step integrator with the weighted error control, whose error is normalized by the
+    /// per-component tolerances. The integrator tolerance is hence set to one.
+    pub fn with_weighted_error_ctrl(
+        min_step: Duration,
+        max_step: Duration,
+        weights: WeightedErrorControl,
+    ) -> Self {
+        Self::with_adaptive_step(min_step, max_step, 1.0, ErrorControl::Weighted(weights))
+    }
+
     /// `with_fixed_step` initializes an `PropOpts` such that the integrator is used with a fixed
     ///  step size.
     pub fn with_fixed_step(step: Duration) -> Self {
@@ -2,13 +2,16 @@ extern crate nyx_space as nyx;
 
 use anise::astro::Occultation;
 use anise::constants::celestial_objects::{JUPITER_BARYCENTER, SUN};
-use anise::constants::frames::SUN_J2000;
+use anise::constants::frames::{IAU_EARTH_FRAME, SUN_J2000};
 use nyx::cosmic::eclipse::EclipseLocator;
 use nyx::cosmic::Orbit;
 use nyx::dynamics::orbital::OrbitalDynamics;
 use nyx::dynamics::SpacecraftDynamics;
+use nyx::md::{EventArc, EventCombination, EventEvaluator};
+use nyx::od::prelude::{GroundStation, StochasticNoise};
 use nyx::propagators::{IntegratorOptions, Propagator};
 use nyx::time::{Epoch, Unit};
+use nyx::{Spacecraft, State};
 use std::sync::{mpsc, Arc};
 use std::thread;
 
@@ -122,3 +125,84 @@ fn geo_sun_earth_eclipses(almanac: Arc<Almanac>) {
 
     assert_eq!(cnt_changes, 14, "wrong number of eclipse state changes");
 }
+
+#[rstest]
+fn sunlit_visibility_arcs(almanac: Arc<Almanac>) {
+    let eme2k = almanac.frame_info(EARTH_J2000).unwrap();
+    let iau_earth = almanac.frame_info(IAU_EARTH_FRAME).unwrap();
+
+    let start_time = Epoch::from_gregorian_tai_at_midnight(2020, 1, 1);
+    let leo = Orbit::keplerian(7000.0, 0.01, 60.0, 0.0, 0.0, 0.0, start_time, eme2k);
+
+    let setup = Propagator::default(SpacecraftDynamics::new(OrbitalDynamics::two_body()));
+    let (_, traj) = setup
+        .with(leo.into(), almanac.clone())
+        .for_duration_with_traj(Unit::Day * 1)
+        .unwrap();
+
+    // Ground station elevation events must be computed in the body fixed frame of the station.
+    let traj = traj.to_frame(iau_earth, almanac.clone()).unwrap();
+
+    let dss65 = GroundStation::dss65_madrid(
+        10.0,
+        StochasticNoise::default_range_km(),
+        StochasticNoise::default_doppler_km_s(),
+        iau_earth,
+    );
+
+    let e_loc = EclipseLocator {
+        light_source: almanac.frame_info(SUN_J2000).unwrap(),
+        shadow_bodies: vec![eme2k],
+    };
+
+    let visible: Arc<dyn EventEvaluator<_>> = Arc::new(dss65);
+    // The penumbra event evaluates to one in full sunlight and zero in umbra.
+    let sunlit: Arc<dyn EventEvaluator<_>> = Arc::new(e_loc.to_penumbra_event());
+
+    let visible_arcs = traj
+        .find_combined_arcs(&visible.clone().into(), None, almanac.clone())
+        .unwrap();
+    assert!(!visible_arcs.is_empty(), "DSS-65 should see this LEO");
+
+    let sunlit_and_visible =
+        EventCombination::event(visible.clone()).and(EventCombination::event(sunlit.clone()));
+    println!("{sunlit_and_visible}");
+
+    let arcs = traj
+        .find_combined_arcs(&sunlit_and_visible, None, almanac.clone())
+        .unwrap();
+
+    let shadowed_and_visible =
+        EventCombination::event(visible.clone()).and(EventCombination::event(sunlit.clone()).not());
+    let shadowed_arcs = traj
+        .find_combined_arcs(&shadowed_and_visible, None, almanac.clone())
+        .unwrap();
+
+    // Visible arcs are split into sunlit and shadowed arcs, so their durations must match.
+    let total = |arcs: &[EventArc<Spacecraft>]| {
+        arcs.iter()
+            .map(|arc| arc.fall.state.epoch() - arc.rise.state.epoch())
+            .fold(Unit::Second * 0, |acc, dur| acc + dur)
+    };
+    let delta = total(&visible_arcs) - total(&arcs) - total(&shadowed_arcs);
+    assert!(delta.abs() < Unit::Second * 1, "{delta}");
+
+    for arc in &arcs {
+        println!("{arc}");
+        // Every arc is within a visibility arc
+        assert!(visible_arcs.iter().any(|vis| {
+            vis.rise.state.epoch() <= arc.rise.state.epoch()
+                && arc.fall.state.epoch() <= vis.fall.state.epoch()
+        }));
+        // And not in umbra at its mid-point
+        let mid = traj
+            .at(arc.rise.state.epoch() + (arc.fall.state.epoch() - arc.rise.state.epoch()) * 0.5)
+            .unwrap();
+        assert!(sunlit.eval(&mid, almanac.clone()).unwrap() > 0.0);
+    }
+
+    // Visibility or shadow covers at least the visibility arcs.
+    let either = EventCombination::event(visible.clone()).or(EventCombination::event(sunlit).not());
+    let either_arcs = traj.find_combined_arcs(&either, None, almanac).unwrap();
+    assert!(total(&either_arcs) >= total(&visible_arcs));
+}
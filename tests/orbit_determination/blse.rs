@@ -6,6 +6,8 @@ use anise::constants::frames::IAU_EARTH_FRAME;
 use nyx::cosmic::Orbit;
 use nyx::dynamics::orbital::OrbitalDynamics;
 use nyx::dynamics::SpacecraftDynamics;
+use nyx::io::ExportCfg;
+use nyx::linalg::SVector;
 use nyx::md::StateParameter;
 use nyx::od::blse::*;
 use nyx::od::prelude::*;
@@ -181,3 +183,428 @@ fn blse_robust_large_disp_cov_test(
     let kf_est: KfEstimate<Spacecraft> = blse_solution.into();
     println!("{kf_est}");
 }
+
+/// Tests that the consider parameters of the Batch least squares estimator increase its covariance.
+#[rstest]
+fn blse_consider_test(almanac: Arc<Almanac>) {
+    let _ = pretty_env_logger::try_init();
+
+    let iau_earth = almanac.frame_info(IAU_EARTH_FRAME).unwrap();
+    let eme2k = almanac.frame_info(EARTH_J2000).unwrap();
+    let dt = Epoch::from_gregorian_utc_hms(2020, 1, 1, 4, 0, 0);
+    let initial_state = Spacecraft::from(Orbit::keplerian(
+        22000.0, 0.01, 30.0, 80.0, 40.0, 170.0, dt, eme2k,
+    ));
+
+    let dss34_canberra = GroundStation::dss34_canberra(
+        0.0,
+        StochasticNoise::default_range_km(),
+        StochasticNoise::default_doppler_km_s(),
+        iau_earth,
+    );
+
+    let configs = BTreeMap::from([(
+        dss34_canberra.name.clone(),
+        TrkConfig::from_sample_rate(60.seconds()),
+    )]);
+
+    let mut devices = BTreeMap::new();
+    devices.insert("Canberra".to_string(), dss34_canberra);
+
+    let setup = Propagator::default(SpacecraftDynamics::new(OrbitalDynamics::two_body()));
+    let (_, traj) = setup
+        .with(initial_state, almanac.clone())
+        .for_duration_with_traj(2.hours())
+        .unwrap();
+
+    let mut arc_sim = TrackingArcSim::with_seed(devices.clone(), traj, configs, 0).unwrap();
+    arc_sim.build_schedule(almanac.clone()).unwrap();
+    let arc = arc_sim.generate_measurements(almanac.clone()).unwrap();
+
+    let consider = vec![
+        ConsiderParameter::grav_param(1.0),
+        ConsiderParameter::measurement_bias("Canberra".to_string(), MeasurementType::Range, 1e-3),
+    ];
+
+    let blse = BatchLeastSquares::builder()
+        .prop(setup)
+        .devices(devices)
+        .consider(consider.clone())
+        .almanac(almanac)
+        .build();
+
+    let blse_solution = blse.estimate(initial_state, &arc).unwrap();
+    println!("{blse_solution}");
+
+    assert_eq!(blse_solution.consider, consider);
+    let contribution = blse_solution.consider_covar_contribution().unwrap();
+    let consider_covar = blse_solution.consider_covar().unwrap();
+    for i in 0..6 {
+        assert!(
+            contribution[(i, i)] > 0.0,
+            "consider parameters do not contribute to the covariance @ [{i}, {i}]"
+        );
+        assert!(consider_covar[(i, i)] > blse_solution.covariance[(i, i)]);
+    }
+    assert!(format!("{blse_solution}").contains("Consider Covariance"));
+}
+
+/// Tests the a priori information and the residuals of the Batch least squares estimator.
+#[rstest]
+fn blse_a_priori_residuals_test(almanac: Arc<Almanac>) {
+    let _ = pretty_env_logger::try_init();
+
+    let iau_earth = almanac.frame_info(IAU_EARTH_FRAME).unwrap();
+    let eme2k = almanac.frame_info(EARTH_J2000).unwrap();
+    let dt = Epoch::from_gregorian_utc_hms(2020, 1, 1, 4, 0, 0);
+    let initial_state = Spacecraft::from(Orbit::keplerian(
+        22000.0, 0.01, 30.0, 80.0, 40.0, 170.0, dt, eme2k,
+    ));
+
+    let dss34_canberra = GroundStation::dss34_canberra(
+        0.0,
+        StochasticNoise::default_range_km(),
+        StochasticNoise::default_doppler_km_s(),
+        iau_earth,
+    );
+
+    let configs = BTreeMap::from([(
+        dss34_canberra.name.clone(),
+        TrkConfig::from_sample_rate(60.seconds()),
+    )]);
+
+    let mut devices = BTreeMap::new();
+    devices.insert("Canberra".to_string(), dss34_canberra);
+
+    let setup = Propagator::default(SpacecraftDynamics::new(OrbitalDynamics::two_body()));
+    let (_, traj) = setup
+        .with(initial_state, almanac.clone())
+        .for_duration_with_traj(2.hours())
+        .unwrap();
+
+    let mut arc_sim = TrackingArcSim::with_seed(devices.clone(), traj, configs, 0).unwrap();
+    arc_sim.build_schedule(almanac.clone()).unwrap();
+    let arc = arc_sim.generate_measurements(almanac.clone()).unwrap();
+
+    // Start from an a priori estimate which is off by one kilometer in X.
+    let mut a_priori_state = initial_state;
+    a_priori_state.orbit.radius_km.x += 1.0;
+    let a_priori = KfEstimate::from_diag(
+        a_priori_state,
+        SVector::<f64, 9>::from_iterator([1.0, 1.0, 1.0, 1e-3, 1e-3, 1e-3, 0.0, 0.0, 0.0]),
+    );
+
+    let blse = BatchLeastSquares::builder()
+        .prop(setup)
+        .devices(devices.clone())
+        .almanac(almanac)
+        .build();
+
+    let blse_solution = blse.estimate_with_a_priori(a_priori, &arc).unwrap();
+    println!("{blse_solution}");
+
+    assert!(blse_solution.converged);
+    assert_eq!(blse_solution.iterations.len(), blse_solution.num_iterations);
+    assert!(blse_solution.iterations.iter().all(|stats| stats.accepted));
+    assert_eq!(
+        blse_solution.iterations.last().unwrap().num_residuals,
+        blse_solution.residuals.len()
+    );
+    assert_eq!(blse_solution.residuals.len(), blse_solution.estimates.len());
+    // The measurements reduce the a priori uncertainty.
+    assert!(blse_solution.covariance[(0, 0)] < 1.0);
+
+    // The residuals go through the same statistics and exports as the Kalman filter.
+    let od_sol = blse_solution.to_od_solution(devices);
+    println!(
+        "RMS prefit: {:.3e}\tRMS postfit: {:.3e}",
+        od_sol.rms_prefit_residuals(),
+        od_sol.rms_postfit_residuals()
+    );
+    assert!(od_sol.rms_postfit_residuals() <= od_sol.rms_prefit_residuals());
+    assert_eq!(od_sol.estimates.len(), blse_solution.residuals.len());
+
+    od_sol
+        .to_parquet(
+            "./data/04_output/blse_a_priori_residuals.parquet",
+            ExportCfg::default(),
+        )
+        .unwrap();
+}
+
+/// Tests that the sigma editing and the robust weights of the Batch least squares estimator reject a bad pass.
+#[rstest]
+#[case(RobustWeighting::huber())]
+#[case(RobustWeighting::tukey())]
+fn blse_sigma_editing_test(#[case] robust_weighting: RobustWeighting, almanac: Arc<Almanac>) {
+    let _ = pretty_env_logger::try_init();
+
+    let iau_earth = almanac.frame_info(IAU_EARTH_FRAME).unwrap();
+    let eme2k = almanac.frame_info(EARTH_J2000).unwrap();
+    let dt = Epoch::from_gregorian_utc_hms(2020, 1, 1, 4, 0, 0);
+    let initial_state = Spacecraft::from(Orbit::keplerian(
+        22000.0, 0.01, 30.0, 80.0, 40.0, 170.0, dt, eme2k,
+    ));
+
+    let dss34_canberra = GroundStation::dss34_canberra(
+        0.0,
+        StochasticNoise::default_range_km(),
+        StochasticNoise::default_doppler_km_s(),
+        iau_earth,
+    );
+
+    let configs = BTreeMap::from([(
+        dss34_canberra.name.clone(),
+        TrkConfig::from_sample_rate(60.seconds()),
+    )]);
+
+    let mut devices = BTreeMap::new();
+    devices.insert("Canberra".to_string(), dss34_canberra);
+
+    let setup = Propagator::default(SpacecraftDynamics::new(OrbitalDynamics::two_body()));
+    let (_, traj) = setup
+        .with(initial_state, almanac.clone())
+        .for_duration_with_traj(2.hours())
+        .unwrap();
+
+    let mut arc_sim = TrackingArcSim::with_seed(devices.clone(), traj, configs, 0).unwrap();
+    arc_sim.build_schedule(almanac.clone()).unwrap();
+    let mut arc = arc_sim.generate_measurements(almanac.clone()).unwrap();
+
+    // Corrupt the range of a few measurements by 10 km.
+    let mut num_bad = 0;
+    for msr in arc.measurements.values_mut().skip(20).take(5) {
+        if let Some(range_km) = msr.data.get_mut(&MeasurementType::Range) {
+            *range_km += 10.0;
+            num_bad += 1;
+        }
+    }
+    assert!(num_bad > 0);
+
+    let blse = BatchLeastSquares::builder()
+        .prop(setup)
+        .devices(devices)
+        .resid_crit(ResidRejectCrit::default())
+        .robust_weighting(robust_weighting)
+        .almanac(almanac)
+        .build();
+
+    // Start from a guess which is off by one kilometer in X.
+    let mut initial_guess = initial_state;
+    initial_guess.orbit.radius_km.x += 1.0;
+
+    let blse_solution = blse.estimate(initial_guess, &arc).unwrap();
+    println!("{blse_solution}");
+
+    assert!(blse_solution.converged);
+    // Nothing is edited in the first iteration.
+    assert_eq!(blse_solution.iterations[0].num_edited, 0);
+
+    let edited = blse_solution.edited_residuals();
+    assert!(edited.len() >= num_bad);
+    for residual in edited {
+        assert!(residual.msr_types.contains(&MeasurementType::Range));
+    }
+
+    let err = (blse_solution.estimated_state.orbit - initial_state.orbit).unwrap();
+    println!("RMAG error: {:.3} m", err.rmag_km() * 1e3);
+    assert!(err.rmag_km() < 0.1, "bad pass was not edited out");
+}
+
+/// Tests the multi-arc Batch least squares estimator with a range bias common to two disjoint arcs.
+#[rstest]
+fn blse_multi_arc_test(almanac: Arc<Almanac>) {
+    let _ = pretty_env_logger::try_init();
+
+    let iau_earth = almanac.frame_info(IAU_EARTH_FRAME).unwrap();
+    let eme2k = almanac.frame_info(EARTH_J2000).unwrap();
+    let dt = Epoch::from_gregorian_utc_hms(2020, 1, 1, 4, 0, 0);
+    let initial_state = Spacecraft::from(Orbit::keplerian(
+        22000.0, 0.01, 30.0, 80.0, 40.0, 170.0, dt, eme2k,
+    ));
+
+    let dss34_canberra = GroundStation::dss34_canberra(
+        0.0,
+        StochasticNoise::default_range_km(),
+        StochasticNoise::default_doppler_km_s(),
+        iau_earth,
+    );
+
+    let configs = BTreeMap::from([(
+        dss34_canberra.name.clone(),
+        TrkConfig::from_sample_rate(60.seconds()),
+    )]);
+
+    let mut devices = BTreeMap::new();
+    devices.insert("Canberra".to_string(), dss34_canberra);
+
+    let setup = Propagator::default(SpacecraftDynamics::new(OrbitalDynamics::two_body()));
+
+    // Build two arcs of two hours, one day apart, whose ranges are all biased by five meters.
+    let range_bias_km = 5e-3;
+    let mut truth_states = Vec::new();
+    let mut arcs = Vec::new();
+    for (seed, offset) in [(0, 0.days()), (1, 1.days())] {
+        let arc_start = setup
+            .with(initial_state, almanac.clone())
+            .for_duration(offset)
+            .unwrap();
+        let (_, traj) = setup
+            .with(arc_start, almanac.clone())
+            .for_duration_with_traj(2.hours())
+            .unwrap();
+
+        let mut arc_sim =
+            TrackingArcSim::with_seed(devices.clone(), traj, configs.clone(), seed).unwrap();
+        arc_sim.build_schedule(almanac.clone()).unwrap();
+        let mut arc = arc_sim.generate_measurements(almanac.clone()).unwrap();
+        for msr in arc.measurements.values_mut() {
+            if let Some(range_km) = msr.data.get_mut(&MeasurementType::Range) {
+                *range_km += range_bias_km;
+            }
+        }
+
+        truth_states.push(arc_start);
+        arcs.push(arc);
+    }
+
+    // Start each arc from a guess which is off by one kilometer in X.
+    let guesses = truth_states
+        .iter()
+        .map(|state| {
+            let mut guess = *state;
+            guess.orbit.radius_km.x += 1.0;
+            guess
+        })
+        .collect::<Vec<_>>();
+
+    let global = vec![ConsiderParameter::measurement_bias(
+        "Canberra".to_string(),
+        MeasurementType::Range,
+        1e-2,
+    )];
+
+    let blse = BatchLeastSquares::builder()
+        .prop(setup)
+        .devices(devices)
+        .almanac(almanac)
+        .build();
+
+    assert!(matches!(
+        blse.estimate_multi_arc(&[], &global),
+        Err(ODError::NoTrackingArcs { .. })
+    ));
+
+    let multi_arc_sol = blse
+        .estimate_multi_arc(&[(guesses[0], &arcs[0]), (guesses[1], &arcs[1])], &global)
+        .unwrap();
+    println!("{multi_arc_sol}");
+
+    assert!(multi_arc_sol.converged);
+    assert_eq!(multi_arc_sol.arcs.len(), 2);
+    assert_eq!(multi_arc_sol.global, global);
+    assert_eq!(multi_arc_sol.cross_covar.len(), 2);
+
+    // The bias is recovered within three sigmas, and it reduces the a priori uncertainty.
+    let bias_sigma_km = multi_arc_sol.global_sigmas()[0];
+    assert!(bias_sigma_km < 1e-2);
+    let bias_err_km = (multi_arc_sol.global_corrections[0] - range_bias_km).abs();
+    println!(
+        "Range bias error: {:.3} m (σ = {:.3} m)",
+        bias_err_km * 1e3,
+        bias_sigma_km * 1e3
+    );
+    assert!(bias_err_km < 3.0 * bias_sigma_km);
+
+    for (arc_sol, truth) in multi_arc_sol.arcs.iter().zip(truth_states.iter()) {
+        assert_eq!(arc_sol.residuals.len(), arc_sol.estimates.len());
+        let err = (arc_sol.estimated_state.orbit - truth.orbit).unwrap();
+        println!("RMAG error: {:.3} m", err.rmag_km() * 1e3);
+        assert!(err.rmag_km() < 0.1);
+    }
+}
+
+/// Tests that the covariance of the Batch least squares estimator, mapped to the end of the arc, matches that of a Kalman filter.
+///
+/// Several propagation steps separate each measurement, so this fails if the STM of each step is compounded with the STM from
+/// the start of the batch instead of being reset at each step.
+#[rstest]
+fn blse_stm_matches_kalman_filter(almanac: Arc<Almanac>) {
+    let _ = pretty_env_logger::try_init();
+
+    let iau_earth = almanac.frame_info(IAU_EARTH_FRAME).unwrap();
+    let eme2k = almanac.frame_info(EARTH_J2000).unwrap();
+    let dt = Epoch::from_gregorian_utc_hms(2020, 1, 1, 4, 0, 0);
+    let initial_state = Spacecraft::from(Orbit::keplerian(
+        22000.0, 0.01, 30.0, 80.0, 40.0, 170.0, dt, eme2k,
+    ));
+
+    let dss34_canberra = GroundStation::dss34_canberra(
+        0.0,
+        StochasticNoise::default_range_km(),
+        StochasticNoise::default_doppler_km_s(),
+        iau_earth,
+    );
+
+    let configs = BTreeMap::from([(
+        dss34_canberra.name.clone(),
+        TrkConfig::from_sample_rate(2.minutes()),
+    )]);
+
+    let mut devices = BTreeMap::new();
+    devices.insert("Canberra".to_string(), dss34_canberra);
+
+    let setup = Propagator::default(SpacecraftDynamics::new(OrbitalDynamics::two_body()));
+    let (_, traj) = setup
+        .with(initial_state, almanac.clone())
+        .for_duration_with_traj(2.hours())
+        .unwrap();
+
+    let mut arc_sim = TrackingArcSim::with_seed(devices.clone(), traj, configs, 0).unwrap();
+    arc_sim.build_schedule(almanac.clone()).unwrap();
+    let arc = arc_sim.generate_measurements(almanac.clone()).unwrap();
+
+    let a_priori = KfEstimate::from_diag(
+        initial_state,
+        SVector::<f64, 9>::from_iterator([1.0, 1.0, 1.0, 1e-3, 1e-3, 1e-3, 0.0, 0.0, 0.0]),
+    );
+
+    // The batch propagates by at most 30 seconds, so there are four steps between each measurement.
+    let blse = BatchLeastSquares::builder()
+        .prop(setup.clone())
+        .devices(devices.clone())
+        .max_step(30.seconds())
+        .almanac(almanac.clone())
+        .build();
+
+    let blse_solution = blse.estimate_with_a_priori(a_priori, &arc).unwrap();
+    assert!(blse_solution.converged);
+
+    let odp = SpacecraftKalmanOD::new(
+        setup.clone(),
+        KalmanVariant::DeviationTracking,
+        None,
+        devices,
+        almanac.clone(),
+    );
+    let od_sol = odp.process_arc(a_priori, &arc).unwrap();
+    let kf_estimate = od_sol.estimates.last().unwrap();
+
+    // Map the batch covariance to the epoch of the last estimate of the filter.
+    let stm = setup
+        .with(blse_solution.estimated_state.with_stm(), almanac)
+        .until_epoch(kf_estimate.epoch())
+        .unwrap()
+        .stm()
+        .unwrap();
+    let mapped_covar = stm * blse_solution.covariance * stm.transpose();
+
+    for i in 0..6 {
+        let rel_err =
+            (mapped_covar[(i, i)] - kf_estimate.covar[(i, i)]).abs() / kf_estimate.covar[(i, i)];
+        assert!(
+            rel_err < 1e-2,
+            "batch covariance differs from the Kalman filter @ [{i}, {i}]"
+        );
+    }
+}
@@ -4,14 +4,14 @@ extern crate nyx_space as nyx;
 extern crate pretty_env_logger;
 
 use anise::constants::celestial_objects::{EARTH, SUN};
-use anise::constants::frames::{IAU_MOON_FRAME, MOON_J2000};
+use anise::constants::frames::{IAU_EARTH_FRAME, IAU_MOON_FRAME, MOON_J2000};
 use indexmap::{IndexMap, IndexSet};
 use nyx::cosmic::Orbit;
 use nyx::dynamics::orbital::OrbitalDynamics;
 use nyx::dynamics::SpacecraftDynamics;
 use nyx::linalg::Const;
 use nyx::md::prelude::*;
-use nyx::od::interlink::InterlinkTxSpacecraft;
+use nyx::od::interlink::{Crosslink, InterlinkTxSpacecraft, JointEstimate, JointODProcess};
 use nyx::od::prelude::*;
 use nyx::propagators::Propagator;
 use nyx::time::{Epoch, TimeUnits};
@@ -263,3 +263,184 @@ fn interlink_nrho_llo_cov_test(#[case] disperse: bool, almanac: Arc<Almanac>) {
         assert!(final_err.vmag_km_s() < 1e-6);
     }
 }
+
+/// Jointly estimate two spacecraft where only the leader is tracked from the ground, and the follower is only observed
+/// through the crosslink from the leader.
+#[rstest]
+fn interlink_joint_estimation(almanac: Arc<Almanac>) {
+    let _ = pretty_env_logger::try_init();
+
+    let eme2k = almanac.frame_info(EARTH_J2000).unwrap();
+    let iau_earth = almanac.frame_info(IAU_EARTH_FRAME).unwrap();
+
+    let epoch = Epoch::from_gregorian_utc_hms(2020, 1, 1, 4, 0, 0);
+    let leader = Spacecraft::from(Orbit::keplerian(
+        22000.0, 0.01, 30.0, 80.0, 40.0, 170.0, epoch, eme2k,
+    ));
+    let follower = Spacecraft::from(Orbit::keplerian(
+        22000.0, 0.01, 30.0, 80.0, 40.0, 168.0, epoch, eme2k,
+    ));
+
+    let setup = Propagator::default(SpacecraftDynamics::new(OrbitalDynamics::two_body()));
+    let prop_time = 6.hours();
+
+    let (_, mut leader_traj) = setup
+        .with(leader, almanac.clone())
+        .for_duration_with_traj(prop_time)
+        .unwrap();
+    leader_traj.name = Some("Leader".to_string());
+
+    let (_, follower_traj) = setup
+        .with(follower, almanac.clone())
+        .for_duration_with_traj(prop_time)
+        .unwrap();
+
+    /* == Ground tracking of the leader only == */
+    let dss65_madrid = GroundStation::dss65_madrid(
+        0.0,
+        StochasticNoise::default_range_km(),
+        StochasticNoise::default_doppler_km_s(),
+        iau_earth,
+    );
+    let dss34_canberra = GroundStation::dss34_canberra(
+        0.0,
+        StochasticNoise::default_range_km(),
+        StochasticNoise::default_doppler_km_s(),
+        iau_earth,
+    );
+
+    let mut ground_stations = BTreeMap::new();
+    let mut ground_configs = BTreeMap::new();
+    for station in [dss65_madrid, dss34_canberra] {
+        ground_configs.insert(
+            station.name.clone(),
+            TrkConfig::from_sample_rate(60.seconds()),
+        );
+        ground_stations.insert(station.name.clone(), station);
+    }
+
+    let mut ground_sim = TrackingArcSim::with_seed(
+        ground_stations.clone(),
+        leader_traj.clone(),
+        ground_configs,
+        0,
+    )
+    .unwrap();
+    ground_sim.build_schedule(almanac.clone()).unwrap();
+    let leader_arc = ground_sim.generate_measurements(almanac.clone()).unwrap();
+
+    /* == Crosslink from the leader to the follower == */
+    let mut measurement_types = IndexSet::new();
+    measurement_types.insert(MeasurementType::Range);
+    measurement_types.insert(MeasurementType::Doppler);
+
+    let mut stochastics = IndexMap::new();
+    stochastics.insert(MeasurementType::Range, StochasticNoise::default_range_km());
+    stochastics.insert(
+        MeasurementType::Doppler,
+        StochasticNoise::default_doppler_km_s(),
+    );
+
+    let interlink = InterlinkTxSpacecraft {
+        traj: leader_traj,
+        measurement_types,
+        integration_time: None,
+        timestamp_noise_s: None,
+        ab_corr: None,
+        stochastic_noises: Some(stochastics),
+    };
+
+    let mut link_configs = BTreeMap::new();
+    link_configs.insert(
+        "Leader".to_string(),
+        TrkConfig::builder()
+            .sampling(5.minutes())
+            .strands(vec![Strand {
+                start: epoch,
+                end: epoch + prop_time,
+            }])
+            .build(),
+    );
+
+    let crosslink = Crosslink::from_interlink(&interlink, "Follower".to_string());
+    let mut link_devices = BTreeMap::new();
+    link_devices.insert("Leader".to_string(), interlink);
+
+    let mut link_sim =
+        TrackingArcSim::with_seed(link_devices, follower_traj.clone(), link_configs, 1).unwrap();
+    let link_arc = link_sim.generate_measurements(almanac.clone()).unwrap();
+    println!("{link_arc}");
+
+    /* == Joint estimation == */
+    let initial_estimate_of = |truth: Spacecraft| {
+        let mut nominal = truth;
+        nominal.orbit.radius_km.x += 0.5;
+        nominal.orbit.radius_km.y -= 0.5;
+        KfEstimate::from_diag(
+            nominal,
+            nyx::linalg::SVector::<f64, 9>::from_iterator([
+                1.0, 1.0, 1.0, 1e-6, 1e-6, 1e-6, 0.0, 0.0, 0.0,
+            ]),
+        )
+    };
+
+    let initial_estimate = JointEstimate::new(vec![
+        ("Leader".to_string(), initial_estimate_of(leader)),
+        ("Follower".to_string(), initial_estimate_of(follower)),
+    ])
+    .unwrap();
+    println!("{initial_estimate}");
+
+    let odp = JointODProcess::builder()
+        .prop(setup)
+        .ground_stations(ground_stations.clone())
+        .crosslinks(BTreeMap::from([("Leader".to_string(), crosslink)]))
+        .resid_crit(ResidRejectCrit::default())
+        .almanac(almanac)
+        .build();
+
+    let joint_sol = odp
+        .process_arcs(
+            initial_estimate,
+            &BTreeMap::from([("Leader".to_string(), leader_arc)]),
+            &[link_arc],
+        )
+        .unwrap();
+
+    let final_estimate = joint_sol.estimates.last().unwrap();
+    println!("{final_estimate}");
+
+    // The crosslink correlates both vehicles.
+    let cross_covar = final_estimate.cross_covar("Leader", "Follower").unwrap();
+    assert!(cross_covar.fixed_view::<3, 3>(0, 0).norm() > 0.0);
+
+    // The follower is only observed through the crosslink, yet its error decreases.
+    let estimate = final_estimate.vehicle_estimate("Follower").unwrap();
+    let truth = follower_traj.at(estimate.epoch()).unwrap();
+    let err = truth
+        .orbit
+        .ric_difference(&estimate.orbital_state())
+        .unwrap();
+    println!("Follower RMAG error {:.3} m", err.rmag_km() * 1e3);
+    assert!(
+        err.rmag_km() < 0.5_f64.hypot(0.5),
+        "follower error did not decrease"
+    );
+    assert!(
+        estimate.within_3sigma(),
+        "follower should be within 3 sigma"
+    );
+
+    // Each vehicle has its own OD solution, with the residuals of the measurements which observe it.
+    let follower_sol = joint_sol.to_od_solution("Follower", ground_stations);
+    assert_eq!(follower_sol.estimates.len(), joint_sol.estimates.len());
+    assert!(follower_sol
+        .residuals
+        .iter()
+        .flatten()
+        .all(|residual| residual.tracker.as_deref() == Some("Leader")));
+    println!(
+        "Follower RMS postfit: {:.3e}",
+        follower_sol.rms_postfit_residuals()
+    );
+}
@@ -6,9 +6,10 @@ use anise::constants::frames::IAU_EARTH_FRAME;
 use nyx::cosmic::{Orbit, Spacecraft};
 use nyx::dynamics::orbital::OrbitalDynamics;
 use nyx::dynamics::spacecraft::{SolarPressure, SpacecraftDynamics};
+use nyx::io::Provenance;
 use nyx::linalg::{SMatrix, SVector};
 use nyx::md::trajectory::ExportCfg;
-use nyx::md::{Event, StateParameter};
+use nyx::md::{Event, StateParameter, Trajectory};
 use nyx::od::prelude::*;
 use nyx::propagators::{IntegratorOptions, Propagator};
 use nyx::time::{Epoch, TimeUnits, Unit};
@@ -473,6 +474,35 @@ fn od_val_sc_srp_estimation_cov_test(
     );
     assert!(od_sol_reloaded == od_sol, "womp womp");
 
+    // Export the estimates to OEM with the RIC covariance, and read the covariance back in the inertial frame.
+    let oem_path = od_sol
+        .to_oem_file(
+            "./data/04_output/od_srp_val.oem",
+            LocalFrame::RIC,
+            ExportCfg::default(),
+        )
+        .unwrap();
+    // The OEM records the provenance of the OD process.
+    assert_eq!(
+        Provenance::from_file(&oem_path).unwrap(),
+        od_sol.provenance.clone().unwrap()
+    );
+    let (oem_traj, oem_estimates) = Trajectory::from_oem_file_with_covar(oem_path, None).unwrap();
+    assert_eq!(oem_traj.states.len(), oem_estimates.len());
+    let est = od_sol.estimates.last().unwrap();
+    let oem_est = oem_estimates.last().unwrap();
+    assert_eq!(oem_est.epoch(), est.epoch());
+    for i in 0..6 {
+        for j in 0..6 {
+            let expected = est.covar[(i, j)];
+            assert!(
+                (oem_est.covar[(i, j)] - expected).abs()
+                    <= 1e-9 * (est.covar[(i, i)] * est.covar[(j, j)]).sqrt(),
+                "covariance mismatch at ({i}, {j})"
+            );
+        }
+    }
+
     println!(
         "Num residuals accepted: #{}",
         od_sol.accepted_residuals().len()
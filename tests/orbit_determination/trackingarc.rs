@@ -156,6 +156,52 @@ fn trk_simple(
 }
 
 /// Tests that inclusion epochs work
+#[rstest]
+fn trk_groundtrack_gis(
+    traj: Traj<Spacecraft>,
+    devices: BTreeMap<String, GroundStation>,
+    almanac: Arc<Almanac>,
+) {
+    let stations = devices.into_values().collect::<Vec<_>>();
+    let ground_track = traj
+        .to_ground_track(IAU_EARTH_FRAME, 1.minutes(), &stations, almanac)
+        .unwrap();
+
+    // The ground track wraps around the globe several times in three days, but no line crosses the antimeridian
+    assert!(ground_track.track.len() > 1);
+    for line in ground_track.track.iter().chain(
+        ground_track
+            .accesses
+            .iter()
+            .flat_map(|access| &access.track),
+    ) {
+        for pair in line.windows(2) {
+            assert!((pair[1].longitude_deg - pair[0].longitude_deg).abs() < 180.0);
+        }
+    }
+
+    assert_eq!(ground_track.stations.len(), stations.len());
+    assert!(!ground_track.accesses.is_empty());
+    for access in &ground_track.accesses {
+        assert!(access.end > access.start);
+        assert!(stations.iter().any(|gs| gs.name == access.station));
+    }
+
+    let path: PathBuf = [
+        env!("CARGO_MANIFEST_DIR"),
+        "data",
+        "04_output",
+        "tracking_truth_groundtrack.geojson",
+    ]
+    .iter()
+    .collect();
+
+    ground_track.to_geojson_file(&path).unwrap();
+    ground_track
+        .to_kml_file(path.with_extension("kml"))
+        .unwrap();
+}
+
 #[rstest]
 fn trkconfig_zero_inclusion(
     traj: Traj<Spacecraft>,
@@ -9,6 +9,7 @@ use nyx::dynamics::SpacecraftDynamics;
 use nyx::io::ConfigRepr;
 use nyx::io::{gravity::*, ExportCfg};
 use nyx::linalg::{SMatrix, SVector};
+use nyx::md::StateParameter;
 use nyx::od::prelude::*;
 use nyx::propagators::{IntegratorOptions, Propagator};
 use nyx::Spacecraft;
@@ -223,6 +224,567 @@ fn od_tb_val_ekf_fixed_step_perfect_stations(
     );
 }
 
+#[rstest]
+fn od_tb_val_ukf_fixed_step_perfect_stations(
+    almanac: Arc<Almanac>,
+    sim_devices: BTreeMap<String, GroundStation>,
+    proc_devices: BTreeMap<String, GroundStation>,
+) {
+    let _ = pretty_env_logger::try_init();
+
+    // Load the tracking configurations
+    let mut configs = BTreeMap::new();
+    let trkconfig_yaml: PathBuf = [
+        env!("CARGO_MANIFEST_DIR"),
+        "data",
+        "03_tests",
+        "config",
+        "trk_cfg_od_val.yaml",
+    ]
+    .iter()
+    .collect();
+
+    let cfg = TrkConfig::load(trkconfig_yaml).unwrap();
+
+    for name in sim_devices.keys() {
+        configs.insert(name.clone(), cfg.clone());
+    }
+
+    // Define the propagator information.
+    let prop_time = 6 * Unit::Hour;
+    let step_size = 10.0 * Unit::Second;
+    let opts = IntegratorOptions::with_fixed_step(step_size);
+
+    // Define state information.
+    let eme2k = almanac.frame_info(EARTH_J2000).unwrap();
+    let dt = Epoch::from_gregorian_tai_at_midnight(2020, 1, 1);
+    let initial_state = Orbit::keplerian(22000.0, 0.01, 30.0, 80.0, 40.0, 0.0, dt, eme2k);
+
+    let orbital_dyn = SpacecraftDynamics::new(OrbitalDynamics::two_body());
+    let setup = Propagator::new(orbital_dyn, IntegratorMethod::RungeKutta4, opts);
+
+    let mut prop = setup.with(initial_state.into(), almanac.clone());
+    let (final_truth, traj) = prop.for_duration_with_traj(prop_time).unwrap();
+
+    // Simulate tracking data
+    let mut arc_sim = TrackingArcSim::with_seed(sim_devices, traj, configs, 0).unwrap();
+    arc_sim.build_schedule(almanac.clone()).unwrap();
+
+    let arc = arc_sim.generate_measurements(almanac.clone()).unwrap();
+
+    let covar_radius_km = 1.0e-6;
+    let covar_velocity_km_s = 1.0e-6;
+    let init_covar = SMatrix::<f64, 9, 9>::from_diagonal(&SVector::<f64, 9>::from_iterator([
+        covar_radius_km,
+        covar_radius_km,
+        covar_radius_km,
+        covar_velocity_km_s,
+        covar_velocity_km_s,
+        covar_velocity_km_s,
+        0.0,
+        0.0,
+        0.0,
+    ]));
+
+    let initial_estimate = KfEstimate::from_covar(initial_state.into(), init_covar);
+
+    // The sigma points are propagated with the same propagator as the truth.
+    let odp = SpacecraftKalmanOD::new(
+        setup,
+        KalmanVariant::unscented(),
+        None,
+        proc_devices,
+        almanac,
+    );
+
+    let od_sol = odp.process_arc(initial_estimate, &arc).unwrap();
+
+    assert_eq!(
+        od_sol.estimates.len(),
+        od_sol.residuals.len(),
+        "each estimate should have a residual slot"
+    );
+    assert!(
+        !od_sol.accepted_residuals().is_empty(),
+        "no residuals accepted"
+    );
+
+    let est = &od_sol.estimates[od_sol.estimates.len() - 1];
+    println!("Final estimate:\n{est}");
+    for i in 0..6 {
+        assert!(
+            est.covar[(i, i)] >= 0.0,
+            "covar diagonal element negative @ [{i}, {i}]"
+        );
+        let init = if i < 3 {
+            covar_radius_km
+        } else {
+            covar_velocity_km_s
+        };
+        assert!(
+            est.covar[(i, i)] < init,
+            "covar did not decrease @ [{i}, {i}]"
+        );
+    }
+
+    let delta = (est.state().orbit - final_truth.orbit).unwrap();
+    println!(
+        "RMAG error = {:.3} m\tVMAG error = {:.3} mm/s",
+        delta.rmag_km() * 1e3,
+        delta.vmag_km_s() * 1e6
+    );
+
+    // Perfect stations and dynamics: the sigma points do not spread the estimate away from the truth.
+    assert!(delta.rmag_km() < 1e-6, "Position error should be nil");
+    assert!(delta.vmag_km_s() < 1e-9, "Velocity error should be nil");
+
+    // Streaming the same measurements leads to the same estimate.
+    let mut stream = odp
+        .start_stream(initial_estimate, arc.unique_types())
+        .unwrap();
+    for msr in arc.measurements.values() {
+        stream.ingest(msr).unwrap();
+    }
+    let stream_estimate = stream.current_estimate();
+    assert_eq!(stream_estimate.epoch(), est.epoch());
+    assert!((stream_estimate.state().orbit.radius_km - est.state().orbit.radius_km).norm() < 1e-9);
+    assert!((stream_estimate.covar - est.covar).norm() < 1e-12);
+}
+
+#[rstest]
+fn od_tb_ckf_consider(
+    almanac: Arc<Almanac>,
+    sim_devices: BTreeMap<String, GroundStation>,
+    proc_devices: BTreeMap<String, GroundStation>,
+) {
+    let _ = pretty_env_logger::try_init();
+
+    // Load the tracking configurations
+    let mut configs = BTreeMap::new();
+    let trkconfig_yaml: PathBuf = [
+        env!("CARGO_MANIFEST_DIR"),
+        "data",
+        "03_tests",
+        "config",
+        "trk_cfg_od_val.yaml",
+    ]
+    .iter()
+    .collect();
+
+    let cfg = TrkConfig::load(trkconfig_yaml).unwrap();
+
+    for name in sim_devices.keys() {
+        configs.insert(name.clone(), cfg.clone());
+    }
+
+    // Define the propagator information.
+    let prop_time = 6 * Unit::Hour;
+    let step_size = 10.0 * Unit::Second;
+    let opts = IntegratorOptions::with_fixed_step(step_size);
+
+    // Define state information.
+    let eme2k = almanac.frame_info(EARTH_J2000).unwrap();
+    let dt = Epoch::from_gregorian_tai_at_midnight(2020, 1, 1);
+    let initial_state = Orbit::keplerian(22000.0, 0.01, 30.0, 80.0, 40.0, 0.0, dt, eme2k);
+
+    let orbital_dyn = SpacecraftDynamics::new(OrbitalDynamics::two_body());
+    let setup = Propagator::new(orbital_dyn, IntegratorMethod::RungeKutta4, opts);
+
+    let mut prop = setup.with(initial_state.into(), almanac.clone());
+    let (_, traj) = prop.for_duration_with_traj(prop_time).unwrap();
+
+    // Simulate tracking data
+    let mut arc_sim = TrackingArcSim::with_seed(sim_devices, traj, configs, 0).unwrap();
+    arc_sim.build_schedule(almanac.clone()).unwrap();
+
+    let arc = arc_sim.generate_measurements(almanac.clone()).unwrap();
+
+    let initial_estimate = KfEstimate::from_diag(
+        initial_state.into(),
+        SVector::<f64, 9>::from_iterator([1e-3, 1e-3, 1e-3, 1e-6, 1e-6, 1e-6, 0.01, 0.0, 0.0]),
+    );
+
+    let mut odp = SpacecraftKalmanOD::new(
+        setup,
+        KalmanVariant::DeviationTracking,
+        None,
+        proc_devices,
+        almanac,
+    );
+    odp.consider = vec![
+        // Coefficient of reflectivity
+        ConsiderParameter::state_component(6, 0.1),
+        ConsiderParameter::grav_param(1.0),
+        ConsiderParameter::measurement_bias("Canberra".to_string(), MeasurementType::Range, 1e-3),
+        ConsiderParameter::tracker_location("Madrid".to_string(), StateParameter::Height, 1e-2)
+            .unwrap(),
+    ];
+
+    let od_sol = odp.process_arc(initial_estimate, &arc).unwrap();
+
+    assert_eq!(od_sol.consider, odp.consider);
+    assert_eq!(od_sol.estimates.len(), od_sol.consider_sensitivities.len());
+
+    let last = od_sol.estimates.len() - 1;
+    let est = &od_sol.estimates[last];
+    println!("Final estimate:\n{est}");
+
+    // The considered coefficient of reflectivity is not estimated.
+    assert_eq!(est.covar[(6, 6)], 0.0);
+
+    let contribution = od_sol.consider_covar_contribution(last).unwrap();
+    let consider_covar = od_sol.consider_covar(last).unwrap();
+    println!("Consider covariance:\n{consider_covar:.3e}");
+    for i in 0..6 {
+        assert!(
+            contribution[(i, i)] > 0.0,
+            "consider parameters do not contribute to the covariance @ [{i}, {i}]"
+        );
+        assert!(consider_covar[(i, i)] > est.covar[(i, i)]);
+    }
+    // The considered coefficient of reflectivity keeps its a priori uncertainty.
+    assert!((consider_covar[(6, 6)] - 0.1_f64.powi(2)).abs() < 1e-12);
+}
+
+#[rstest]
+fn od_tb_ckf_range_bias_estimation(
+    almanac: Arc<Almanac>,
+    sim_devices: BTreeMap<String, GroundStation>,
+    proc_devices: BTreeMap<String, GroundStation>,
+) {
+    let _ = pretty_env_logger::try_init();
+
+    // Load the tracking configurations
+    let mut configs = BTreeMap::new();
+    let trkconfig_yaml: PathBuf = [
+        env!("CARGO_MANIFEST_DIR"),
+        "data",
+        "03_tests",
+        "config",
+        "trk_cfg_od_val.yaml",
+    ]
+    .iter()
+    .collect();
+
+    let cfg = TrkConfig::load(trkconfig_yaml).unwrap();
+
+    for name in sim_devices.keys() {
+        configs.insert(name.clone(), cfg.clone());
+    }
+
+    // Define the propagator information.
+    let prop_time = 1 * Unit::Day;
+    let step_size = 10.0 * Unit::Second;
+    let opts = IntegratorOptions::with_fixed_step(step_size);
+
+    // Define state information.
+    let eme2k = almanac.frame_info(EARTH_J2000).unwrap();
+    let dt = Epoch::from_gregorian_tai_at_midnight(2020, 1, 1);
+    let initial_state = Orbit::keplerian(22000.0, 0.01, 30.0, 80.0, 40.0, 0.0, dt, eme2k);
+
+    let orbital_dyn = SpacecraftDynamics::new(OrbitalDynamics::two_body());
+    let setup = Propagator::new(orbital_dyn, IntegratorMethod::RungeKutta4, opts);
+
+    let mut prop = setup.with(initial_state.into(), almanac.clone());
+    let (_, traj) = prop.for_duration_with_traj(prop_time).unwrap();
+
+    // Simulate tracking data
+    let mut arc_sim = TrackingArcSim::with_seed(sim_devices, traj, configs, 0).unwrap();
+    arc_sim.build_schedule(almanac.clone()).unwrap();
+
+    let mut arc = arc_sim.generate_measurements(almanac.clone()).unwrap();
+
+    // Canberra ranging has a 25 meter bias.
+    let range_bias_km = 25e-3;
+    for msr in arc.measurements.values_mut() {
+        if msr.tracker == "Canberra" {
+            if let Some(range_km) = msr.data.get_mut(&MeasurementType::Range) {
+                *range_km += range_bias_km;
+            }
+        }
+    }
+
+    let initial_estimate = KfEstimate::from_diag(
+        initial_state.into(),
+        SVector::<f64, 9>::from_iterator([1e-3, 1e-3, 1e-3, 1e-6, 1e-6, 1e-6, 0.0, 0.0, 0.0]),
+    );
+
+    let mut odp = SpacecraftKalmanOD::new(
+        setup,
+        KalmanVariant::DeviationTracking,
+        None,
+        proc_devices,
+        almanac,
+    );
+    odp.biases = vec![
+        EstimatedBias::constant("Canberra".to_string(), MeasurementType::Range, 0.1),
+        EstimatedBias::gauss_markov(
+            "Madrid".to_string(),
+            MeasurementType::Range,
+            GaussMarkov::new(Unit::Hour * 6, 0.1).unwrap(),
+        ),
+    ];
+
+    let od_sol = odp.process_arc(initial_estimate, &arc).unwrap();
+
+    assert_eq!(od_sol.estimates.len(), od_sol.bias_estimates.len());
+
+    let canberra = od_sol.bias_history("Canberra", MeasurementType::Range);
+    assert!(!canberra.is_empty());
+    let (_, bias_km, sigma_km) = canberra.last().copied().unwrap();
+    println!(
+        "Canberra range bias: {:.3} m ± {:.3} m",
+        bias_km * 1e3,
+        sigma_km * 1e3
+    );
+    assert!(sigma_km < 0.1, "bias uncertainty did not decrease");
+    assert!(
+        (bias_km - range_bias_km).abs() < 3.0 * sigma_km,
+        "bias not estimated within 3 sigmas"
+    );
+
+    // Madrid has no bias.
+    let (_, bias_km, sigma_km) = od_sol
+        .bias_history("Madrid", MeasurementType::Range)
+        .last()
+        .copied()
+        .unwrap();
+    assert!(bias_km.abs() < 3.0 * sigma_km);
+    assert!(od_sol
+        .bias_history("Goldstone", MeasurementType::Range)
+        .is_empty());
+
+    od_sol
+        .to_parquet(
+            "./data/04_output/od_tb_range_bias.parquet",
+            ExportCfg::default(),
+        )
+        .unwrap();
+}
+
+#[allow(clippy::identity_op)]
+#[rstest]
+fn od_tb_ckf_time_tag_bias_estimation(
+    almanac: Arc<Almanac>,
+    mut sim_devices: BTreeMap<String, GroundStation>,
+    proc_devices: BTreeMap<String, GroundStation>,
+) {
+    let _ = pretty_env_logger::try_init();
+
+    // Load the tracking configurations
+    let mut configs = BTreeMap::new();
+    let trkconfig_yaml: PathBuf = [
+        env!("CARGO_MANIFEST_DIR"),
+        "data",
+        "03_tests",
+        "config",
+        "trk_cfg_od_val.yaml",
+    ]
+    .iter()
+    .collect();
+
+    let cfg = TrkConfig::load(trkconfig_yaml).unwrap();
+
+    for name in sim_devices.keys() {
+        configs.insert(name.clone(), cfg.clone());
+    }
+
+    // Madrid time tags its measurements 2 ms late.
+    let time_tag_bias_s = 2e-3;
+    let mut timestamp_bias = GaussMarkov::new(Unit::Day * 1, 1e-12).unwrap();
+    timestamp_bias.constant = Some(time_tag_bias_s);
+    sim_devices.get_mut("Madrid").unwrap().timestamp_noise_s = Some(StochasticNoise {
+        white_noise: None,
+        bias: Some(timestamp_bias),
+    });
+
+    // Define the propagator information.
+    let prop_time = 1 * Unit::Day;
+    let step_size = 10.0 * Unit::Second;
+    let opts = IntegratorOptions::with_fixed_step(step_size);
+
+    // Define state information.
+    let eme2k = almanac.frame_info(EARTH_J2000).unwrap();
+    let dt = Epoch::from_gregorian_tai_at_midnight(2020, 1, 1);
+    let initial_state = Orbit::keplerian(22000.0, 0.01, 30.0, 80.0, 40.0, 0.0, dt, eme2k);
+
+    let orbital_dyn = SpacecraftDynamics::new(OrbitalDynamics::two_body());
+    let setup = Propagator::new(orbital_dyn, IntegratorMethod::RungeKutta4, opts);
+
+    let mut prop = setup.with(initial_state.into(), almanac.clone());
+    let (_, traj) = prop.for_duration_with_traj(prop_time).unwrap();
+
+    // Simulate tracking data
+    let mut arc_sim = TrackingArcSim::with_seed(sim_devices, traj, configs, 0).unwrap();
+    arc_sim.build_schedule(almanac.clone()).unwrap();
+
+    let arc = arc_sim.generate_measurements(almanac.clone()).unwrap();
+
+    let initial_estimate = KfEstimate::from_diag(
+        initial_state.into(),
+        SVector::<f64, 9>::from_iterator([1e-3, 1e-3, 1e-3, 1e-6, 1e-6, 1e-6, 0.0, 0.0, 0.0]),
+    );
+
+    let mut odp = SpacecraftKalmanOD::new(
+        setup,
+        KalmanVariant::DeviationTracking,
+        None,
+        proc_devices,
+        almanac,
+    );
+    odp.biases = vec![
+        EstimatedBias::time_tag("Madrid".to_string(), BiasModel::Constant { sigma: 1e-2 }),
+        EstimatedBias::time_tag("Canberra".to_string(), BiasModel::Constant { sigma: 1e-2 }),
+    ];
+
+    let od_sol = odp.process_arc(initial_estimate, &arc).unwrap();
+
+    let (_, bias_s, sigma_s) = od_sol
+        .bias_history("Madrid", BiasedQuantity::TimeTag)
+        .last()
+        .copied()
+        .unwrap();
+    println!(
+        "Madrid time tag bias: {:.3} ms ± {:.3} ms",
+        bias_s * 1e3,
+        sigma_s * 1e3
+    );
+    assert!(sigma_s < 1e-3, "time tag uncertainty did not decrease");
+    assert!(
+        (bias_s - time_tag_bias_s).abs() < 3.0 * sigma_s,
+        "time tag bias not estimated within 3 sigmas"
+    );
+
+    // Canberra time tags are correct.
+    let (_, bias_s, sigma_s) = od_sol
+        .bias_history("Canberra", BiasedQuantity::TimeTag)
+        .last()
+        .copied()
+        .unwrap();
+    assert!(bias_s.abs() < 3.0 * sigma_s);
+}
+
+#[rstest]
+fn od_tb_ekf_dmc_unmodeled_harmonics(
+    almanac: Arc<Almanac>,
+    sim_devices: BTreeMap<String, GroundStation>,
+    proc_devices: BTreeMap<String, GroundStation>,
+) {
+    let _ = pretty_env_logger::try_init();
+
+    // Define the tracking configurations
+    let mut configs = BTreeMap::new();
+    let cfg = TrkConfig::from_sample_rate(1.minutes());
+    for name in sim_devices.keys() {
+        configs.insert(name.clone(), cfg.clone());
+    }
+
+    // Define the propagator information.
+    let prop_time = 1 * Unit::Day;
+    let opts = IntegratorOptions::with_fixed_step(10.0 * Unit::Second);
+
+    // Define state information.
+    let eme2k = almanac.frame_info(EARTH_J2000).unwrap();
+    let iau_earth = almanac.frame_info(IAU_EARTH_FRAME).unwrap();
+    let dt = Epoch::from_gregorian_tai_at_midnight(2020, 1, 1);
+    let initial_state = Orbit::keplerian(22000.0, 0.01, 30.0, 80.0, 40.0, 0.0, dt, eme2k);
+
+    // The truth includes the Earth harmonics, which the navigation dynamics do not model.
+    let earth_sph_harm =
+        HarmonicsMem::from_cof("data/01_planetary/JGM3.cof.gz", 20, 20, true).unwrap();
+    let harmonics = Harmonics::from_stor(iau_earth, earth_sph_harm);
+    let truth_setup = Propagator::new(
+        SpacecraftDynamics::new(OrbitalDynamics::from_model(harmonics)),
+        IntegratorMethod::RungeKutta4,
+        opts,
+    );
+    let (_, traj) = truth_setup
+        .with(initial_state.into(), almanac.clone())
+        .for_duration_with_traj(prop_time)
+        .unwrap();
+
+    // Simulate tracking data
+    let mut arc_sim = TrackingArcSim::with_seed(sim_devices, traj.clone(), configs, 0).unwrap();
+    arc_sim.build_schedule(almanac.clone()).unwrap();
+
+    let arc = arc_sim.generate_measurements(almanac.clone()).unwrap();
+
+    let initial_estimate = KfEstimate::from_diag(
+        initial_state.into(),
+        SVector::<f64, 9>::from_iterator([1e-3, 1e-3, 1e-3, 1e-6, 1e-6, 1e-6, 0.0, 0.0, 0.0]),
+    );
+
+    let setup = Propagator::new(
+        SpacecraftDynamics::new(OrbitalDynamics::two_body()),
+        IntegratorMethod::RungeKutta4,
+        opts,
+    );
+    let mut odp = SpacecraftKalmanOD::new(
+        setup,
+        KalmanVariant::ReferenceUpdate,
+        None,
+        proc_devices,
+        almanac.clone(),
+    );
+    let dmc_sigma_km_s2 = 1e-9;
+    odp.dmc = Some(DynamicModelCompensation::new(Unit::Hour * 2, dmc_sigma_km_s2).unwrap());
+
+    let od_sol = odp.process_arc(initial_estimate, &arc).unwrap();
+
+    assert_eq!(od_sol.estimates.len(), od_sol.dmc_estimates.len());
+    let dmc_history = od_sol.dmc_history();
+    assert_eq!(dmc_history.len(), od_sol.estimates.len());
+
+    // The tracking data reduces the uncertainty of the empirical accelerations.
+    let (_, last_dmc) = dmc_history.last().copied().unwrap();
+    println!(
+        "DMC acceleration (RIC): {:.3e} km/s^2 ± {:.3e} km/s^2",
+        last_dmc.accel_km_s2,
+        last_dmc.sigmas_km_s2()
+    );
+    for i in 0..3 {
+        assert!(last_dmc.sigmas_km_s2()[i] < dmc_sigma_km_s2);
+    }
+
+    let est = od_sol.estimates.last().unwrap();
+    let truth = traj.at(est.epoch()).unwrap();
+    let delta = (est.state().orbit - truth.orbit).unwrap();
+    println!(
+        "RMAG error = {:.3} m\tVMAG error = {:.3} mm/s",
+        delta.rmag_km() * 1e3,
+        delta.vmag_km_s() * 1e6
+    );
+    assert!(
+        delta.rmag_km() < 1.0,
+        "DMC should absorb the unmodeled harmonics"
+    );
+
+    od_sol
+        .to_parquet(
+            "./data/04_output/od_tb_ekf_dmc.parquet",
+            ExportCfg::default(),
+        )
+        .unwrap();
+
+    // The smoother keeps the filtered DMC estimates aligned with the smoothed estimates.
+    let smoothed_sol = od_sol.clone().smooth(almanac).unwrap();
+    assert_eq!(smoothed_sol.estimates.len(), od_sol.estimates.len());
+    assert_eq!(smoothed_sol.dmc_estimates.len(), od_sol.estimates.len());
+    assert_eq!(smoothed_sol.bias_estimates.len(), od_sol.estimates.len());
+    assert_eq!(
+        smoothed_sol.consider_sensitivities.len(),
+        od_sol.estimates.len()
+    );
+    assert_eq!(smoothed_sol.dmc_history(), dmc_history);
+
+    smoothed_sol
+        .to_parquet(
+            "./data/04_output/od_tb_ekf_dmc_smoothed.parquet",
+            ExportCfg::default(),
+        )
+        .unwrap();
+}
+
 #[allow(clippy::identity_op)]
 #[rstest]
 fn od_tb_val_with_arc(
@@ -1449,3 +2011,137 @@ fn od_tb_fixed_step_perfect_stations_several_snc_covar_map(
         "Velocity error should be zero"
     );
 }
+
+#[rstest]
+fn od_tb_ekf_stream_checkpoint(
+    almanac: Arc<Almanac>,
+    sim_devices: BTreeMap<String, GroundStation>,
+    proc_devices: BTreeMap<String, GroundStation>,
+) {
+    let _ = pretty_env_logger::try_init();
+
+    // Load the tracking configurations
+    let mut configs = BTreeMap::new();
+    let trkconfig_yaml: PathBuf = [
+        env!("CARGO_MANIFEST_DIR"),
+        "data",
+        "03_tests",
+        "config",
+        "trk_cfg_od_val.yaml",
+    ]
+    .iter()
+    .collect();
+
+    let cfg = TrkConfig::load(trkconfig_yaml).unwrap();
+
+    for name in sim_devices.keys() {
+        configs.insert(name.clone(), cfg.clone());
+    }
+
+    // Define the propagator information.
+    let prop_time = 1 * Unit::Day;
+    let step_size = 10.0 * Unit::Second;
+    let opts = IntegratorOptions::with_fixed_step(step_size);
+
+    // Define state information.
+    let eme2k = almanac.frame_info(EARTH_J2000).unwrap();
+    let dt = Epoch::from_gregorian_tai_at_midnight(2020, 1, 1);
+    let initial_state = Orbit::keplerian(22000.0, 0.01, 30.0, 80.0, 40.0, 0.0, dt, eme2k);
+
+    let orbital_dyn = SpacecraftDynamics::new(OrbitalDynamics::two_body());
+    let setup = Propagator::new(orbital_dyn, IntegratorMethod::RungeKutta4, opts);
+
+    let mut prop = setup.with(initial_state.into(), almanac.clone());
+    let (_, traj) = prop.for_duration_with_traj(prop_time).unwrap();
+
+    // Simulate tracking data
+    let mut arc_sim = TrackingArcSim::with_seed(sim_devices, traj, configs, 0).unwrap();
+    arc_sim.build_schedule(almanac.clone()).unwrap();
+
+    let mut arc = arc_sim.generate_measurements(almanac.clone()).unwrap();
+    // The ranges are ambiguous, and the stream must resolve them like the processing of the arc.
+    arc.set_moduli(MeasurementType::Range, 5000.0);
+    arc.apply_moduli();
+
+    let initial_estimate = KfEstimate::from_diag(
+        initial_state.into(),
+        SVector::<f64, 9>::from_iterator([1e-3, 1e-3, 1e-3, 1e-6, 1e-6, 1e-6, 0.0, 0.0, 0.0]),
+    );
+
+    let mut odp = SpacecraftKalmanOD::new(
+        setup,
+        KalmanVariant::ReferenceUpdate,
+        None,
+        proc_devices,
+        almanac,
+    );
+    odp.biases = vec![EstimatedBias::constant(
+        "Canberra".to_string(),
+        MeasurementType::Range,
+        0.1,
+    )];
+
+    let od_sol = odp.process_arc(initial_estimate, &arc).unwrap();
+    let batch_estimate = *od_sol.estimates.last().unwrap();
+
+    // Ingest the same measurements one at a time, and checkpoint halfway through.
+    let half = arc.measurements.len() / 2;
+    let mut stream = odp
+        .start_stream(initial_estimate, arc.unique_types())
+        .unwrap()
+        .with_moduli(arc.moduli.clone().unwrap());
+
+    for msr in arc.measurements.values().take(half) {
+        stream.ingest(msr).unwrap();
+    }
+
+    // Measurements cannot be ingested out of order.
+    assert!(stream
+        .ingest(arc.measurements.values().next().unwrap())
+        .is_err());
+
+    let checkpoint = stream.checkpoint();
+    let serialized = serde_yml::to_string(&checkpoint).unwrap();
+
+    let mut uninterrupted = stream;
+    for msr in arc.measurements.values().skip(half) {
+        uninterrupted.ingest(msr).unwrap();
+    }
+
+    let stream_estimate = uninterrupted.current_estimate();
+    assert_eq!(stream_estimate.epoch(), batch_estimate.epoch());
+    assert_eq!(
+        stream_estimate.state().orbit.radius_km,
+        batch_estimate.state().orbit.radius_km,
+        "streaming differs from processing the arc"
+    );
+    assert_eq!(
+        uninterrupted.solution().estimates.len(),
+        od_sol.estimates.len()
+    );
+
+    // Resume from the serialized checkpoint.
+    let checkpoint: ODStreamCheckpoint<Spacecraft> = serde_yml::from_str(&serialized).unwrap();
+    assert_eq!(checkpoint.moduli, arc.moduli);
+    let mut resumed = odp.resume_stream(checkpoint, arc.unique_types()).unwrap();
+    for msr in arc.measurements.values().skip(half) {
+        resumed.ingest(msr).unwrap();
+    }
+
+    let resumed_estimate = resumed.current_estimate();
+    let err_km =
+        (resumed_estimate.state().orbit.radius_km - stream_estimate.state().orbit.radius_km).norm();
+    println!("Resumed stream position difference: {:.3e} m", err_km * 1e3);
+    assert!(err_km < 1e-6, "resumed stream differs by {err_km} km");
+    assert!(
+        (resumed_estimate.covar - stream_estimate.covar).norm() < 1e-12,
+        "resumed stream covariance differs"
+    );
+
+    // Predict the estimate after the last measurement.
+    let end_epoch = stream_estimate.epoch() + 2 * Unit::Hour;
+    let predicted = resumed.predict_to(end_epoch).unwrap();
+    assert_eq!(predicted.epoch(), end_epoch);
+    assert!(predicted.predicted);
+    assert!(resumed.predict_to(dt).is_err());
+}
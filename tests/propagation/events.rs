@@ -182,3 +182,90 @@ fn event_tracker_true_anomaly(almanac: Arc<Almanac>) {
         });
     println!("[eclipses] {penumbra_event_loc} =>\n{pretty}");
 }
+
+#[rstest]
+fn relative_events(almanac: Arc<Almanac>) {
+    use nyx::md::prelude::*;
+    use nyx::md::{AngularSeparationEvent, EventEvaluator, LineOfSightEvent, RangeEvent};
+
+    let eme2k = almanac.frame_info(EARTH_J2000).unwrap();
+
+    let dt = Epoch::from_gregorian_tai_at_noon(2020, 1, 1);
+    // Two spacecraft on coplanar circular orbits of 7000 km and 7200 km, the inner one leading by 90 degrees at the start.
+    // They drift apart, so the Earth periodically blocks their link and their range varies.
+    let leader = Orbit::keplerian(7000.0, 0.0, 0.0, 0.0, 0.0, 90.0, dt, eme2k);
+    let follower = Orbit::keplerian(7200.0, 0.0, 0.0, 0.0, 0.0, 0.0, dt, eme2k);
+
+    let setup = Propagator::default(SpacecraftDynamics::new(OrbitalDynamics::two_body()));
+    let prop_time = 3 * follower.period().unwrap();
+    let (_, leader_traj) = setup
+        .with(leader.into(), almanac.clone())
+        .for_duration_with_traj(prop_time)
+        .unwrap();
+    let (_, follower_traj) = setup
+        .with(follower.into(), almanac.clone())
+        .for_duration_with_traj(prop_time)
+        .unwrap();
+
+    // Line of sight
+    let los_event = LineOfSightEvent::new(leader_traj.clone(), eme2k);
+    let los_arcs = follower_traj
+        .find_arcs(&los_event, None, almanac.clone())
+        .unwrap();
+    assert!(!los_arcs.is_empty());
+    for arc in &los_arcs {
+        println!("{arc}");
+        // The link is clear in the middle of the arc
+        let mid_epoch =
+            arc.rise.state.epoch() + (arc.fall.state.epoch() - arc.rise.state.epoch()) * 0.5;
+        let mid_state = follower_traj.at(mid_epoch).unwrap();
+        let leader_state = leader_traj.at(mid_epoch).unwrap();
+        assert!(!almanac
+            .line_of_sight_obstructed(mid_state.orbit, leader_state.orbit, eme2k, None)
+            .unwrap());
+        // And grazes the Earth at the rise and fall, unless these are the bounds of the trajectory
+        for bound in [&arc.rise.state, &arc.fall.state] {
+            if bound.epoch() != follower_traj.first().epoch()
+                && bound.epoch() != follower_traj.last().epoch()
+            {
+                assert!(los_event.eval(bound, almanac.clone()).unwrap().abs() < 1e-2);
+            }
+        }
+    }
+
+    // Range between both trajectories
+    let range_event = RangeEvent::new(leader_traj.clone(), 9000.0);
+    let range_events = follower_traj
+        .find(&range_event, None, almanac.clone())
+        .unwrap();
+    for event in &range_events {
+        let leader_state = leader_traj.at(event.state.epoch()).unwrap();
+        let range_km = (leader_state.orbit.radius_km - event.state.orbit.radius_km).norm();
+        assert!((range_km - 9000.0).abs() < range_event.value_precision);
+    }
+
+    // The same range event also stops a propagation
+    let (range_state, _) = setup
+        .with(follower.into(), almanac.clone())
+        .until_event(prop_time, &range_event)
+        .unwrap();
+    assert!(
+        range_event
+            .eval(&range_state, almanac.clone())
+            .unwrap()
+            .abs()
+            < 1e-3
+    );
+
+    // Sun-spacecraft-Earth angle
+    let sun = almanac.frame_info(SUN_J2000).unwrap();
+    let sep_event = AngularSeparationEvent::new(sun, eme2k, 90.0);
+    let sep_events = follower_traj
+        .find(&sep_event, None, almanac.clone())
+        .unwrap();
+    assert!(!sep_events.is_empty());
+    for event in &sep_events {
+        println!("{event}");
+        assert!(sep_event.eval(&event.state, almanac.clone()).unwrap().abs() < 1e-3);
+    }
+}
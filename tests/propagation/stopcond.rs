@@ -13,7 +13,7 @@ use nyx::cosmic::Orbit;
 use nyx::dynamics::guidance::{FiniteBurns, LocalFrame, Maneuver, Thruster};
 use nyx::dynamics::orbital::OrbitalDynamics;
 use nyx::dynamics::SpacecraftDynamics;
-use nyx::md::{Event, StateParameter};
+use nyx::md::{Event, StateParameter, StopCondition, StopConditions};
 use nyx::propagators::{IntegratorOptions, Propagator};
 use nyx::time::{Epoch, TimeUnits, Unit};
 use nyx::{Spacecraft, State};
@@ -218,6 +218,61 @@ fn stop_cond_nrho_apo(almanac: Arc<Almanac>) {
     }
 }
 
+#[rstest]
+fn stop_cond_compound(almanac: Arc<Almanac>) {
+    let eme2k = almanac.frame_info(EARTH_J2000).unwrap();
+
+    let start_dt = Epoch::from_mjd_tai(JD_J2000);
+    let state = Orbit::cartesian(
+        -2436.45, -2436.45, 6891.037, 5.088_611, -5.088_611, 0.01, start_dt, eme2k,
+    );
+
+    let period = state.period().unwrap();
+
+    let apo_event = Event::apoapsis();
+    // This orbit never reenters
+    let reentry_event = Event::new(StateParameter::Rmag, 6478.0);
+
+    let setup = Propagator::default(SpacecraftDynamics::new(OrbitalDynamics::two_body()));
+
+    // Stop at the third apoapsis or at reentry, whichever comes first
+    let conditions = StopConditions::any(vec![
+        StopCondition::new(Arc::new(apo_event)).nth(2),
+        StopCondition::new(Arc::new(reentry_event)),
+    ]);
+
+    let mut prop = setup.with(state.into(), almanac.clone());
+    let (stop, traj) = prop.until_stop_conditions(5 * period, &conditions).unwrap();
+    println!("{stop}");
+    assert_eq!(stop.condition, 0, "reentry should not have been found");
+
+    // Must match the nth event search
+    let (third_apo, _) = setup
+        .with(state.into(), almanac.clone())
+        .until_nth_event(5 * period, &apo_event, 2)
+        .unwrap();
+    assert!((stop.details.state.epoch() - third_apo.epoch()).abs() < 1.milliseconds());
+
+    // Requiring both conditions is impossible
+    let conditions = StopConditions::all(vec![
+        StopCondition::new(Arc::new(apo_event)),
+        StopCondition::new(Arc::new(reentry_event)),
+    ]);
+    assert!(conditions.find_in(&traj, None, almanac.clone()).is_err());
+
+    // The second event of periapsis or apoapsis is the first apoapsis since we start at periapsis
+    let conditions = StopConditions::nth(
+        vec![
+            StopCondition::new(Arc::new(Event::periapsis())),
+            StopCondition::new(Arc::new(apo_event)),
+        ],
+        1,
+    );
+    let stop = conditions.find_in(&traj, None, almanac).unwrap();
+    assert_eq!(stop.condition, 1);
+    assert!((180.0 - stop.details.state.orbit.ta_deg().unwrap()).abs() < 1e-3);
+}
+
 #[rstest]
 fn line_of_nodes(almanac: Arc<Almanac>) {
     let eme2k = almanac.frame_info(EARTH_J2000).unwrap();
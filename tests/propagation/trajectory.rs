@@ -7,7 +7,7 @@ use nyx::cosmic::eclipse::EclipseLocator;
 use nyx::cosmic::{GuidanceMode, Orbit, Spacecraft};
 use nyx::dynamics::guidance::{GuidanceLaw, Ruggiero, Thruster};
 use nyx::dynamics::{OrbitalDynamics, SpacecraftDynamics};
-use nyx::md::prelude::{ExportCfg, Objective};
+use nyx::md::prelude::{BspDataType, BspExportCfg, CompressionCfg, ExportCfg, Objective, Traj};
 use nyx::md::StateParameter;
 use nyx::propagators::*;
 use nyx::time::{Epoch, TimeSeries, Unit};
@@ -17,7 +17,7 @@ use std::path::PathBuf;
 use std::sync::mpsc::channel;
 use std::sync::Arc;
 
-use anise::prelude::Almanac;
+use anise::prelude::{Almanac, Frame};
 use rstest::*;
 
 #[fixture]
@@ -558,3 +558,131 @@ fn traj_ephem_backward(almanac: Arc<Almanac>) {
         "Maximum state in interpolation is too high!"
     );
 }
+
+#[rstest]
+fn traj_chebyshev_compression(almanac: Arc<Almanac>) {
+    let _ = pretty_env_logger::try_init();
+
+    let eme2k = almanac.frame_info(EARTH_J2000).unwrap();
+
+    let start_dt = Epoch::from_gregorian_utc_at_noon(2021, 1, 1);
+    let orbit = Orbit::keplerian(7000.0, 1e-3, 51.6, 20.0, 30.0, 40.0, start_dt, eme2k);
+
+    let setup = Propagator::default(SpacecraftDynamics::new(OrbitalDynamics::two_body()));
+    let (_, traj) = setup
+        .with(orbit.into(), almanac)
+        .for_duration_with_traj(3 * Unit::Day)
+        .unwrap();
+
+    let cfg = CompressionCfg::builder()
+        .interval(Unit::Hour * 2)
+        .tolerance_km(1e-5)
+        .tolerance_km_s(1e-8)
+        .build();
+    let compressed = traj.compress(cfg).unwrap();
+    println!("{traj}\n{compressed}");
+
+    // O(1) lookup requires fixed-interval segments
+    assert!(compressed
+        .segments
+        .windows(2)
+        .all(|pair| pair[1].start - pair[0].start == compressed.interval));
+
+    // Round trip against every stored state of the raw trajectory
+    let mut max_pos_err = 0.0_f64;
+    let mut max_vel_err = 0.0_f64;
+    for state in &traj.states {
+        let fitted = compressed.at(state.epoch()).unwrap();
+        assert_eq!(fitted.epoch(), state.epoch());
+        assert_eq!(fitted.orbit.frame, state.orbit.frame);
+        max_pos_err = max_pos_err.max((fitted.orbit.radius_km - state.orbit.radius_km).norm());
+        max_vel_err =
+            max_vel_err.max((fitted.orbit.velocity_km_s - state.orbit.velocity_km_s).norm());
+    }
+    println!("max errors: {max_pos_err:e} km\t{max_vel_err:e} km/s");
+    assert!(max_pos_err <= 1e-5);
+    assert!(max_vel_err <= 1e-8);
+
+    let decompressed = compressed.to_traj(Unit::Minute * 1).unwrap();
+    assert_eq!(decompressed.first().epoch(), traj.first().epoch());
+    assert_eq!(decompressed.last().epoch(), traj.last().epoch());
+}
+
+#[rstest]
+fn traj_bsp_export(almanac: Arc<Almanac>) {
+    let _ = pretty_env_logger::try_init();
+
+    let eme2k = almanac.frame_info(EARTH_J2000).unwrap();
+
+    let start_dt = Epoch::from_gregorian_utc_at_noon(2021, 1, 1);
+    let orbit = Orbit::keplerian(7000.0, 1e-3, 51.6, 20.0, 30.0, 40.0, start_dt, eme2k);
+
+    let setup = Propagator::default(SpacecraftDynamics::new(OrbitalDynamics::two_body()));
+    let (_, traj) = setup
+        .with(orbit.into(), almanac.clone())
+        .for_duration_with_traj(2 * Unit::Day)
+        .unwrap();
+
+    let sc_id = -10000001;
+    let sc_frame = Frame::from_ephem_j2000(sc_id);
+
+    for data_type in [BspDataType::Hermite, BspDataType::Lagrange] {
+        let path: PathBuf = [
+            env!("CARGO_MANIFEST_DIR"),
+            "data",
+            "04_output",
+            &format!("traj_{data_type:?}.bsp"),
+        ]
+        .iter()
+        .collect();
+
+        let cfg = BspExportCfg::builder()
+            .naif_id(sc_id)
+            .data_type(data_type)
+            .segment_duration(Unit::Hour * 12)
+            .build();
+        traj.to_bsp(&path, cfg).unwrap();
+
+        let loaded_almanac = Arc::new(
+            almanac
+                .as_ref()
+                .clone()
+                .load(path.to_str().unwrap())
+                .unwrap(),
+        );
+        assert_eq!(loaded_almanac.spk_summaries(sc_id).unwrap().len(), 4);
+
+        let reloaded = Traj::from_bsp(
+            sc_frame,
+            eme2k,
+            loaded_almanac,
+            Spacecraft::from(orbit),
+            Unit::Minute * 1,
+            None,
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(reloaded.first().epoch(), traj.first().epoch());
+        assert_eq!(reloaded.last().epoch(), traj.last().epoch());
+
+        // Stated tolerance of the export: one centimeter and one millimeter per second
+        for state in reloaded.states.iter().skip(1) {
+            let expected = traj.at(state.epoch()).unwrap();
+            let err_km = (state.orbit.radius_km - expected.orbit.radius_km).norm();
+            let err_km_s = (state.orbit.velocity_km_s - expected.orbit.velocity_km_s).norm();
+            assert!(
+                err_km < 1e-5,
+                "{data_type:?}: {err_km} km at {}",
+                state.epoch()
+            );
+            assert!(
+                err_km_s < 1e-6,
+                "{data_type:?}: {err_km_s} km/s at {}",
+                state.epoch()
+            );
+        }
+    }
+}